@@ -0,0 +1,193 @@
+#ifndef SJP_H
+#define SJP_H
+
+/* Generated by cbindgen from src/ffi.rs. Do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * An opaque JSON value.
+ */
+typedef struct SjpValue SjpValue;
+
+typedef enum SjpType {
+  SJP_TYPE_NULL,
+  SJP_TYPE_BOOL,
+  SJP_TYPE_NUMBER,
+  SJP_TYPE_STRING,
+  SJP_TYPE_ARRAY,
+  SJP_TYPE_OBJECT,
+} SjpType;
+
+/**
+ * An opaque iterator over an object's members.
+ */
+typedef struct SjpMembers SjpMembers;
+
+/**
+ * Why a parse failed. Zeroed fields mean no error.
+ */
+typedef struct SjpError {
+  /**
+   * The number of the error's code, such as 1 for `E001`, or 0 when the input was not
+   * UTF-8.
+   */
+  uint32_t code;
+  size_t line;
+  size_t column;
+  size_t offset;
+  /**
+   * The message, owned by this struct until `sjp_error_free`.
+   */
+  char *message;
+} SjpError;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Parses `len` bytes of UTF-8 at `input`. Returns null on failure, filling in `error`
+ * when it is not null; on success a non-null `error` is zeroed.
+ *
+ * # Safety
+ * `input` must point to `len` readable bytes, and `error` must be null or valid to write.
+ */
+SjpValue *sjp_parse(const char *input, size_t len, struct SjpError *error);
+
+/**
+ * Releases a value returned by `sjp_parse`. Null is ignored.
+ *
+ * # Safety
+ * `value` must be null or an owned handle from `sjp_parse` not yet freed.
+ */
+void sjp_free(SjpValue *value);
+
+/**
+ * Releases an error's message and zeroes it.
+ *
+ * # Safety
+ * `error` must be null or an error filled in by `sjp_parse`.
+ */
+void sjp_error_free(struct SjpError *error);
+
+/**
+ * Releases a string returned by `sjp_to_string`. Null is ignored.
+ *
+ * # Safety
+ * `s` must be null or a string from `sjp_to_string` not yet freed.
+ */
+void sjp_string_free(char *s);
+
+/**
+ * # Safety
+ * `value` must be a live value handle.
+ */
+enum SjpType sjp_type(const SjpValue *value);
+
+/**
+ * Writes a boolean to `out`, returning false if the value is not one.
+ *
+ * # Safety
+ * `value` must be a live value handle and `out` valid to write.
+ */
+bool sjp_get_bool(const SjpValue *value, bool *out);
+
+/**
+ * Writes a number to `out`, returning false if the value is not one.
+ *
+ * # Safety
+ * `value` must be a live value handle and `out` valid to write.
+ */
+bool sjp_get_number(const SjpValue *value, double *out);
+
+/**
+ * A string's UTF-8 bytes with its length written to `len`, or null if the value is not
+ * a string.
+ *
+ * # Safety
+ * `value` must be a live value handle and `len` valid to write.
+ */
+const char *sjp_get_string(const SjpValue *value, size_t *len);
+
+/**
+ * The number of elements or members, or 0 for other values.
+ *
+ * # Safety
+ * `value` must be a live value handle.
+ */
+size_t sjp_len(const SjpValue *value);
+
+/**
+ * An array element, or null when out of range or not an array.
+ *
+ * # Safety
+ * `value` must be a live value handle.
+ */
+const SjpValue *sjp_array_get(const SjpValue *value, size_t index);
+
+/**
+ * An object member's value by key, or null when missing or not an object.
+ *
+ * # Safety
+ * `value` must be a live value handle and `key` must point to `key_len` readable bytes.
+ */
+const SjpValue *sjp_object_get(const SjpValue *value, const char *key, size_t key_len);
+
+/**
+ * Starts iterating over an object's members, returning null if the value is not an
+ * object. Members come in an unspecified order. Release the iterator with
+ * `sjp_members_free` before the value it came from.
+ *
+ * # Safety
+ * `value` must be a live value handle.
+ */
+struct SjpMembers *sjp_members(const SjpValue *value);
+
+/**
+ * Advances to the next member, writing its key, the key's length and its value.
+ * Returns false, writing nothing, once every member has been visited.
+ *
+ * # Safety
+ * `members` must be a live iterator from `sjp_members`, and `key`, `key_len` and
+ * `value` valid to write.
+ */
+bool sjp_members_next(struct SjpMembers *members,
+                      const char **key,
+                      size_t *key_len,
+                      const SjpValue **value);
+
+/**
+ * Releases an iterator returned by `sjp_members`. Null is ignored.
+ *
+ * # Safety
+ * `members` must be null or an iterator from `sjp_members` not yet freed.
+ */
+void sjp_members_free(struct SjpMembers *members);
+
+/**
+ * The value at a JSON Pointer, or null when there is none.
+ *
+ * # Safety
+ * `value` must be a live value handle and `pointer` must point to `len` readable bytes.
+ */
+const SjpValue *sjp_pointer(const SjpValue *value, const char *pointer, size_t len);
+
+/**
+ * Serializes a value as a NUL-terminated string to release with `sjp_string_free`,
+ * compact or indented with two spaces and sorted keys.
+ *
+ * # Safety
+ * `value` must be a live value handle.
+ */
+char *sjp_to_string(const SjpValue *value, bool pretty);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* SJP_H */
@@ -0,0 +1,309 @@
+use alloc::{borrow::Cow, string::{String, ToString}, vec::Vec};
+
+use crate::j_item::{IntegerError, JItem};
+
+impl JItem {
+    /// The member named `key`, when this is an object.
+    pub fn get(&self, key: &str) -> Option<&JItem> {
+        let JItem::Object(hmap) = self else {
+            return None;
+        };
+        return hmap.get(key);
+    }
+
+    /// Like `get`, ignoring case, for sources that vary their key casing. An exact match
+    /// wins. Otherwise, when several keys differ from `key` only in case, the first of
+    /// them in sorted order is used, so the choice does not depend on hash order.
+    pub fn get_ci(&self, key: &str) -> Option<&JItem> {
+        let JItem::Object(hmap) = self else {
+            return None;
+        };
+        if let Some(value) = hmap.get(key) {
+            return Some(value);
+        }
+        let folded = |s: &str| s.chars().flat_map(char::to_lowercase).collect::<String>();
+        let wanted = folded(key);
+        return hmap.iter().filter(|(name, _)| folded(name) == wanted).min_by(|a, b| a.0.cmp(b.0)).map(|(_, value)| value);
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        let JItem::String(s) = self else {
+            return None;
+        };
+        return Some(s);
+    }
+
+    /// A number with no fractional part that fits in an `i64`. Raw number text is read
+    /// exactly, so integers beyond 2^53 keep their value.
+    pub fn as_i64(&self) -> Option<i64> {
+        if let JItem::RawNumber(text) = self
+            && let Ok(n) = text.parse()
+        {
+            return Some(n);
+        }
+        let x = self.number_value()?;
+        // -2^63 is exact as an f64, but 2^63 is one past the largest i64
+        if !(-9223372036854775808.0..9223372036854775808.0).contains(&x) || x as i64 as f64 != x {
+            return None;
+        }
+        return Some(x as i64);
+    }
+
+    /// Like `as_i64`, but saying why a number is not exactly an `i64`, for code such as
+    /// billing that must not act on a value that is only approximately an integer. Raw
+    /// number text is read exactly; a plain `Number` of 2^53 or more is `Rounded`.
+    pub fn as_i64_checked(&self) -> Result<i64, IntegerError> {
+        return self.checked_integer(i64::MIN.into(), i64::MAX.into()).map(|n| n as i64);
+    }
+
+    /// Like `as_i64_checked`, for `u64`.
+    pub fn as_u64_checked(&self) -> Result<u64, IntegerError> {
+        return self.checked_integer(0, u64::MAX.into()).map(|n| n as u64);
+    }
+
+    fn checked_integer(&self, min: i128, max: i128) -> Result<i128, IntegerError> {
+        let range = |n: i128| if (min..=max).contains(&n) { Ok(n) } else { Err(IntegerError::OutOfRange) };
+        if let JItem::RawNumber(text) = self
+            && let Some(exact) = exact_integer(text)
+        {
+            let (n, fraction) = exact?;
+            let n = range(n)?;
+            if fraction {
+                return Err(IntegerError::Truncated);
+            }
+            return Ok(n);
+        }
+        let x = self.number_value().ok_or(IntegerError::NotANumber)?;
+        if !x.is_finite() {
+            return Err(IntegerError::OutOfRange);
+        }
+        // the cast truncates toward zero, saturating far beyond the range of either type
+        let n = range(x as i128)?;
+        if n as f64 != x {
+            return Err(IntegerError::Truncated);
+        }
+        if n.unsigned_abs() >= 1 << 53 {
+            return Err(IntegerError::Rounded);
+        }
+        return Ok(n);
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        return self.number_value();
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        let JItem::Bool(b) = self else {
+            return None;
+        };
+        return Some(*b);
+    }
+
+    /// A number, read leniently: a number is itself, a string holding a number after
+    /// trimming whitespace (`"42"`, `" 1.5e3"`) is that number, and `true` and `false`
+    /// are 1 and 0. Strings that read as infinite or NaN, nulls and containers give
+    /// `None`.
+    pub fn coerce_f64(&self) -> Option<f64> {
+        return match self {
+            JItem::Number(_) | JItem::RawNumber(_) => self.number_value(),
+            JItem::String(s) => s.trim().parse::<f64>().ok().filter(|x| x.is_finite()),
+            JItem::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+            _ => None,
+        };
+    }
+
+    /// A boolean, read leniently: the numbers 1 and 0, and the strings `"true"`,
+    /// `"false"`, `"1"` and `"0"`, in any case and with whitespace trimmed, count as
+    /// booleans. Anything else gives `None`.
+    pub fn coerce_bool(&self) -> Option<bool> {
+        return match self {
+            JItem::Bool(b) => Some(*b),
+            JItem::Number(_) | JItem::RawNumber(_) => match self.number_value() {
+                Some(1.0) => Some(true),
+                Some(0.0) => Some(false),
+                _ => None,
+            },
+            JItem::String(s) => match s.trim() {
+                "1" => Some(true),
+                "0" => Some(false),
+                s if s.eq_ignore_ascii_case("true") => Some(true),
+                s if s.eq_ignore_ascii_case("false") => Some(false),
+                _ => None,
+            },
+            _ => None,
+        };
+    }
+
+    /// A string, read leniently: numbers and booleans are written as they would be in
+    /// JSON (`42`, `1.5`, `true`). Nulls and containers give `None`.
+    pub fn coerce_string(&self) -> Option<Cow<'_, str>> {
+        return match self {
+            JItem::String(s) => Some(Cow::Borrowed(s)),
+            JItem::RawNumber(text) => Some(Cow::Borrowed(text)),
+            JItem::Number(_) | JItem::Bool(_) => Some(Cow::Owned(self.to_string())),
+            _ => None,
+        };
+    }
+
+    /// The string member `key`, or `default` when it is missing or not a string.
+    pub fn get_str_or<'a>(&'a self, key: &str, default: &'a str) -> &'a str {
+        return self.get(key).and_then(JItem::as_str).unwrap_or(default);
+    }
+
+    /// The integer member `key`, or `default` when it is missing or not an integer that
+    /// fits in an `i64`.
+    pub fn get_i64_or(&self, key: &str, default: i64) -> i64 {
+        return self.get(key).and_then(JItem::as_i64).unwrap_or(default);
+    }
+
+    pub fn get_f64_or(&self, key: &str, default: f64) -> f64 {
+        return self.get(key).and_then(JItem::as_f64).unwrap_or(default);
+    }
+
+    pub fn get_bool_or(&self, key: &str, default: bool) -> bool {
+        return self.get(key).and_then(JItem::as_bool).unwrap_or(default);
+    }
+
+    /// Like `get_str_or`, for the value at a JSON Pointer.
+    pub fn pointer_str_or<'a>(&'a self, pointer: &str, default: &'a str) -> &'a str {
+        return self.pointer(pointer).and_then(JItem::as_str).unwrap_or(default);
+    }
+
+    pub fn pointer_i64_or(&self, pointer: &str, default: i64) -> i64 {
+        return self.pointer(pointer).and_then(JItem::as_i64).unwrap_or(default);
+    }
+
+    pub fn pointer_f64_or(&self, pointer: &str, default: f64) -> f64 {
+        return self.pointer(pointer).and_then(JItem::as_f64).unwrap_or(default);
+    }
+
+    pub fn pointer_bool_or(&self, pointer: &str, default: bool) -> bool {
+        return self.pointer(pointer).and_then(JItem::as_bool).unwrap_or(default);
+    }
+}
+
+/// Number text read exactly, as its integer part and whether a nonzero fraction follows
+/// it, or `None` if the text is not a JSON number. An integer part beyond `i128` is
+/// `OutOfRange`.
+fn exact_integer(text: &str) -> Option<Result<(i128, bool), IntegerError>> {
+    let (negative, text) = text.strip_prefix('-').map_or((false, text), |rest| (true, rest));
+    let (mantissa, exponent) = text.split_once(['e', 'E']).unwrap_or((text, "0"));
+    let (int, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits: Vec<u8> = int.chars().chain(fraction.chars()).map(|c| c.to_digit(10).map(|d| d as u8)).collect::<Option<_>>()?;
+    let exponent_digits = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+    if int.is_empty() || exponent_digits.is_empty() || !exponent_digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    if digits.iter().all(|d| *d == 0) {
+        return Some(Ok((0, false)));
+    }
+    // an exponent past the range of an i64 is far past the range of the result too
+    let exponent = exponent.parse::<i64>().unwrap_or(if exponent.starts_with('-') { i64::MIN } else { i64::MAX });
+    let point = (int.len() as i64).saturating_add(exponent);
+    let mut n: i128 = 0;
+    // some digit is nonzero, so this overflows within a few dozen steps past it
+    for i in 0..point.max(0) {
+        let digit = digits.get(i as usize).copied().unwrap_or(0);
+        let Some(next) = n.checked_mul(10).and_then(|n| n.checked_add(digit.into())) else {
+            return Some(Err(IntegerError::OutOfRange));
+        };
+        n = next;
+    }
+    let point = usize::try_from(point).unwrap_or(0).min(digits.len());
+    let fraction = digits[point..].iter().any(|d| *d != 0);
+    return Some(Ok((if negative { -n } else { n }, fraction)));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parse, parse_with, parser::ParseOptions};
+
+    #[test]
+    fn defaults_for_missing_or_mistyped_members() {
+        let config = parse(r#"{"name": "api", "port": 8080, "ratio": 0.5, "debug": true, "retries": "3", "server": {"host": "example.com", "workers": 4.0}}"#).unwrap();
+        assert_eq!(config.get_str_or("name", "anonymous"), "api");
+        assert_eq!(config.get_str_or("owner", "anonymous"), "anonymous");
+        assert_eq!(config.get_i64_or("port", 80), 8080);
+        assert_eq!(config.get_i64_or("retries", 1), 1);
+        assert_eq!(config.get_i64_or("ratio", 1), 1);
+        assert_eq!(config.get_f64_or("ratio", 1.0), 0.5);
+        assert_eq!(config.get_f64_or("port", 1.0), 8080.0);
+        assert!(config.get_bool_or("debug", false));
+        assert!(config.get_bool_or("verbose", true));
+        assert_eq!(config.pointer_str_or("/server/host", "localhost"), "example.com");
+        assert_eq!(config.pointer_str_or("/server/name", "localhost"), "localhost");
+        assert_eq!(config.pointer_i64_or("/server/workers", 1), 4);
+        assert_eq!(config.pointer_f64_or("/server/missing", 2.5), 2.5);
+        assert!(!config.pointer_bool_or("/debug/x", false));
+        assert_eq!(JItem::Array(vec![]).get_str_or("name", "none"), "none");
+    }
+
+    #[test]
+    fn case_insensitive_lookup() {
+        let item = parse(r#"{"UserId": 1, "userid": 2, "USERID": 3, "Straße": 4}"#).unwrap();
+        assert_eq!(item.get_ci("userid"), Some(&JItem::Number(2.0)));
+        assert_eq!(item.get_ci("UserID"), Some(&JItem::Number(3.0)));
+        assert_eq!(item.get_ci("STRASSE"), None);
+        assert_eq!(item.get_ci("straße"), Some(&JItem::Number(4.0)));
+        assert_eq!(item.get_ci("name"), None);
+        assert_eq!(JItem::Array(vec![]).get_ci("userid"), None);
+    }
+
+    #[test]
+    fn lenient_coercions() {
+        let Ok(JItem::Array(values)) = parse(r#"[42, " -1.5e3 ", "NaN", "0x10", true, null, "TRUE", "0", 1, 2, {}]"#) else {
+            panic!("should parse as an array");
+        };
+        let coerced: Vec<_> = values.iter().map(JItem::coerce_f64).collect();
+        assert_eq!(coerced, [Some(42.0), Some(-1500.0), None, None, Some(1.0), None, None, Some(0.0), Some(1.0), Some(2.0), None]);
+        let coerced: Vec<_> = values.iter().map(JItem::coerce_bool).collect();
+        assert_eq!(coerced, [None, None, None, None, Some(true), None, Some(true), Some(false), Some(true), None, None]);
+        assert_eq!(JItem::Number(42.0).coerce_string().as_deref(), Some("42"));
+        assert_eq!(JItem::Number(0.25).coerce_string().as_deref(), Some("0.25"));
+        assert_eq!(JItem::Bool(false).coerce_string().as_deref(), Some("false"));
+        assert_eq!(JItem::RawNumber("1.50".to_string()).coerce_string().as_deref(), Some("1.50"));
+        assert_eq!(JItem::Null.coerce_string(), None);
+    }
+
+    #[test]
+    fn as_i64_range() {
+        assert_eq!(JItem::Number(-9223372036854775808.0).as_i64(), Some(i64::MIN));
+        assert_eq!(JItem::Number(9223372036854775808.0).as_i64(), None);
+        assert_eq!(JItem::Number(f64::NAN).as_i64(), None);
+        let options = ParseOptions { preserve_number_text: true, ..ParseOptions::default() };
+        let big = parse_with("[9007199254740993, 1e2, 1.5]", &options).unwrap();
+        assert_eq!(big.pointer("/0").unwrap().as_i64(), Some(9007199254740993));
+        assert_eq!(big.pointer("/1").unwrap().as_i64(), Some(100));
+        assert_eq!(big.pointer("/2").unwrap().as_i64(), None);
+    }
+
+    #[test]
+    fn checked_integers() {
+        assert_eq!(JItem::Number(-42.0).as_i64_checked(), Ok(-42));
+        assert_eq!(JItem::Number(-42.0).as_u64_checked(), Err(IntegerError::OutOfRange));
+        assert_eq!(JItem::Number(2.5).as_i64_checked(), Err(IntegerError::Truncated));
+        assert_eq!(JItem::Number(-0.5).as_u64_checked(), Err(IntegerError::Truncated));
+        assert_eq!(JItem::Number(9007199254740991.0).as_i64_checked(), Ok((1 << 53) - 1));
+        assert_eq!(JItem::Number(9007199254740992.0).as_i64_checked(), Err(IntegerError::Rounded));
+        assert_eq!(JItem::Number(9223372036854775808.0).as_i64_checked(), Err(IntegerError::OutOfRange));
+        assert_eq!(JItem::Number(9223372036854775808.0).as_u64_checked(), Err(IntegerError::Rounded));
+        assert_eq!(JItem::Number(f64::INFINITY).as_u64_checked(), Err(IntegerError::OutOfRange));
+        assert_eq!(JItem::String("1".to_string()).as_i64_checked(), Err(IntegerError::NotANumber));
+
+        let options = ParseOptions { preserve_number_text: true, ..ParseOptions::default() };
+        let raw = parse_with("[18446744073709551615, 18446744073709551616, 9007199254740993.0, 1e2, 9007199254740993.5, 25e-1, 0.05e2, -0.5, 1e400, 1e-400, -0.0e99999999999999999999]", &options).unwrap();
+        assert_eq!(raw.pointer("/0").unwrap().as_u64_checked(), Ok(u64::MAX));
+        assert_eq!(raw.pointer("/0").unwrap().as_i64_checked(), Err(IntegerError::OutOfRange));
+        assert_eq!(raw.pointer("/1").unwrap().as_u64_checked(), Err(IntegerError::OutOfRange));
+        assert_eq!(raw.pointer("/2").unwrap().as_i64_checked(), Ok(9007199254740993));
+        assert_eq!(raw.pointer("/3").unwrap().as_i64_checked(), Ok(100));
+        assert_eq!(raw.pointer("/4").unwrap().as_i64_checked(), Err(IntegerError::Truncated));
+        assert_eq!(raw.pointer("/5").unwrap().as_i64_checked(), Err(IntegerError::Truncated));
+        assert_eq!(raw.pointer("/6").unwrap().as_u64_checked(), Ok(5));
+        assert_eq!(raw.pointer("/7").unwrap().as_u64_checked(), Err(IntegerError::Truncated));
+        assert_eq!(raw.pointer("/8").unwrap().as_i64_checked(), Err(IntegerError::OutOfRange));
+        assert_eq!(raw.pointer("/9").unwrap().as_i64_checked(), Err(IntegerError::Truncated));
+        assert_eq!(raw.pointer("/10").unwrap().as_i64_checked(), Ok(0));
+    }
+}
@@ -0,0 +1,168 @@
+//! Random `JItem` values for fuzzing and property tests. The `arbitrary` feature
+//! implements `arbitrary::Arbitrary`, and the `proptest` feature implements
+//! `proptest::arbitrary::Arbitrary` with `ArbitraryOptions` as its parameters. Numbers
+//! are always finite, since JSON has no way to write the others.
+
+/// Limits on the values generated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArbitraryOptions {
+    /// How many arrays and objects deep values may nest. 0 gives only scalars.
+    pub max_depth: usize,
+    /// The most elements, members, or characters in an array, object, or string.
+    pub max_len: usize,
+    pub alphabet: Alphabet,
+}
+
+impl Default for ArbitraryOptions {
+    fn default() -> Self {
+        return ArbitraryOptions { max_depth: 4, max_len: 8, alphabet: Alphabet::Any };
+    }
+}
+
+/// The characters strings and object keys are drawn from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Alphabet {
+    Any,
+    Ascii,
+    /// Only these characters. Must not be empty.
+    Chars(Vec<char>),
+}
+
+#[cfg(feature = "arbitrary")]
+mod fuzz {
+    use ::arbitrary::{Arbitrary, Result, Unstructured};
+
+    use super::{Alphabet, ArbitraryOptions};
+    use crate::j_item::JItem;
+
+    impl JItem {
+        /// Like `Arbitrary::arbitrary`, within `options`.
+        pub fn arbitrary_with(u: &mut Unstructured<'_>, options: &ArbitraryOptions) -> Result<JItem> {
+            return generate(u, options, options.max_depth);
+        }
+    }
+
+    impl<'a> Arbitrary<'a> for JItem {
+        fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+            return JItem::arbitrary_with(u, &ArbitraryOptions::default());
+        }
+    }
+
+    fn generate(u: &mut Unstructured<'_>, options: &ArbitraryOptions, depth: usize) -> Result<JItem> {
+        let kinds = if depth == 0 { 4 } else { 6 };
+        return Ok(match u.choose_index(kinds)? {
+            0 => JItem::Null,
+            1 => JItem::Bool(u.arbitrary()?),
+            2 => JItem::Number(Some(u.arbitrary::<f64>()?).filter(|x| x.is_finite()).unwrap_or(0.0)),
+            3 => JItem::String(string(u, options)?),
+            4 => JItem::Array((0..u.int_in_range(0..=options.max_len)?).map(|_| generate(u, options, depth - 1)).collect::<Result<_>>()?),
+            _ => JItem::Object((0..u.int_in_range(0..=options.max_len)?).map(|_| Ok((string(u, options)?, generate(u, options, depth - 1)?))).collect::<Result<_>>()?),
+        });
+    }
+
+    fn string(u: &mut Unstructured<'_>, options: &ArbitraryOptions) -> Result<String> {
+        return (0..u.int_in_range(0..=options.max_len)?).map(|_| match &options.alphabet {
+            Alphabet::Any => u.arbitrary::<char>(),
+            Alphabet::Ascii => Ok(char::from(u.int_in_range(0..=0x7f_u8)?)),
+            Alphabet::Chars(chars) => u.choose(chars).copied(),
+        }).collect();
+    }
+}
+
+#[cfg(feature = "proptest")]
+mod strategy {
+    use proptest::{
+        arbitrary::{Arbitrary, any},
+        collection::{hash_map, vec},
+        num::f64,
+        prop_oneof,
+        sample::select,
+        strategy::{BoxedStrategy, Just, Strategy},
+    };
+
+    use super::{Alphabet, ArbitraryOptions};
+    use crate::j_item::JItem;
+
+    impl Arbitrary for JItem {
+        type Parameters = ArbitraryOptions;
+        type Strategy = BoxedStrategy<JItem>;
+
+        fn arbitrary_with(options: ArbitraryOptions) -> Self::Strategy {
+            let chars = match options.alphabet {
+                Alphabet::Any => any::<char>().boxed(),
+                Alphabet::Ascii => proptest::char::range('\0', '\x7f').boxed(),
+                Alphabet::Chars(chars) => select(chars).boxed(),
+            };
+            let strings = vec(chars, 0..=options.max_len).prop_map(String::from_iter);
+            let leaf = prop_oneof![
+                Just(JItem::Null),
+                any::<bool>().prop_map(JItem::Bool),
+                (f64::POSITIVE | f64::NEGATIVE | f64::NORMAL | f64::SUBNORMAL | f64::ZERO).prop_map(JItem::Number),
+                strings.clone().prop_map(JItem::String),
+            ];
+            let max_len = options.max_len;
+            let depth = u32::try_from(options.max_depth).unwrap_or(u32::MAX);
+            return leaf.prop_recursive(depth, 256, max_len.max(1) as u32, move |inner| prop_oneof![
+                vec(inner.clone(), 0..=max_len).prop_map(JItem::Array),
+                hash_map(strings.clone(), inner, 0..=max_len).prop_map(|hmap| JItem::Object(hmap.into_iter().collect())),
+            ]).boxed();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::j_item::JItem;
+
+    fn depth(item: &JItem) -> usize {
+        return match item {
+            JItem::Array(elements) => 1 + elements.iter().map(depth).max().unwrap_or(0),
+            JItem::Object(hmap) => 1 + hmap.values().map(depth).max().unwrap_or(0),
+            _ => 0,
+        };
+    }
+
+    fn strings(item: &JItem, out: &mut Vec<String>) {
+        match item {
+            JItem::String(s) => out.push(s.clone()),
+            JItem::Array(elements) => elements.iter().for_each(|element| strings(element, out)),
+            JItem::Object(hmap) => hmap.iter().for_each(|(key, value)| {
+                out.push(key.clone());
+                strings(value, out);
+            }),
+            _ => {},
+        }
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn arbitrary_respects_options() {
+        let options = ArbitraryOptions { max_depth: 2, max_len: 5, alphabet: Alphabet::Chars(vec!['a', 'é']) };
+        let bytes: Vec<u8> = (0..4096_u32).map(|n| (n.wrapping_mul(2654435761) >> 13) as u8).collect();
+        let mut u = ::arbitrary::Unstructured::new(&bytes);
+        for _ in 0..50 {
+            let item = JItem::arbitrary_with(&mut u, &options).unwrap();
+            assert!(depth(&item) <= 2);
+            let mut found = vec![];
+            strings(&item, &mut found);
+            assert!(found.iter().all(|s| s.chars().count() <= 5 && s.chars().all(|c| c == 'a' || c == 'é')), "{:?}", found);
+        }
+    }
+
+    #[cfg(feature = "proptest")]
+    proptest::proptest! {
+        #[test]
+        fn serialized_values_parse_back(item in proptest::arbitrary::any::<JItem>()) {
+            proptest::prop_assert_eq!(crate::parse(&item.to_string()).unwrap(), item);
+        }
+
+        #[test]
+        fn proptest_respects_options(item in proptest::arbitrary::any_with::<JItem>(ArbitraryOptions { max_depth: 1, max_len: 3, alphabet: Alphabet::Ascii })) {
+            proptest::prop_assert!(depth(&item) <= 1);
+            let mut found = vec![];
+            strings(&item, &mut found);
+            proptest::prop_assert!(found.iter().all(|s| s.chars().count() <= 3 && s.is_ascii()));
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use std::io::{self, Write};
+
+use crate::{
+    format::{write_item, write_newline, FormatOptions},
+    j_item::JItem,
+};
+
+/// Writes a JSON array one element at a time, so large exports never have to be built
+/// as a single `JItem::Array`. The output matches `to_string_with` on the whole array.
+/// Call `finish` to write the closing `]`; dropping the writer leaves the array open.
+pub struct JsonArrayWriter<W: Write> {
+    writer: W,
+    layout: Layout,
+}
+
+impl<W: Write> JsonArrayWriter<W> {
+    /// Writes the opening `[`.
+    pub fn new(mut writer: W, options: FormatOptions) -> io::Result<Self> {
+        writer.write_all(b"[")?;
+        return Ok(JsonArrayWriter { writer, layout: Layout { options, count: 0 } });
+    }
+
+    pub fn push(&mut self, item: &JItem) -> io::Result<()> {
+        let text = self.layout.element(item);
+        return self.writer.write_all(text.as_bytes());
+    }
+
+    /// Converts `value` with its `Into<JItem>` impl and writes it.
+    pub fn push_value(&mut self, value: impl Into<JItem>) -> io::Result<()> {
+        return self.push(&value.into());
+    }
+
+    /// Elements written so far.
+    pub fn len(&self) -> usize {
+        return self.layout.count;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.layout.count == 0;
+    }
+
+    /// Writes the closing `]`, flushes and hands back the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let text = self.layout.end();
+        self.writer.write_all(text.as_bytes())?;
+        self.writer.flush()?;
+        return Ok(self.writer);
+    }
+}
+
+/// `JsonArrayWriter` for a tokio `AsyncWrite`.
+#[cfg(feature = "tokio")]
+pub struct AsyncJsonArrayWriter<W: tokio::io::AsyncWrite + Unpin> {
+    writer: W,
+    layout: Layout,
+}
+
+#[cfg(feature = "tokio")]
+impl<W: tokio::io::AsyncWrite + Unpin> AsyncJsonArrayWriter<W> {
+    /// Writes the opening `[`.
+    pub async fn new(mut writer: W, options: FormatOptions) -> io::Result<Self> {
+        use tokio::io::AsyncWriteExt;
+        writer.write_all(b"[").await?;
+        return Ok(AsyncJsonArrayWriter { writer, layout: Layout { options, count: 0 } });
+    }
+
+    pub async fn push(&mut self, item: &JItem) -> io::Result<()> {
+        use tokio::io::AsyncWriteExt;
+        let text = self.layout.element(item);
+        return self.writer.write_all(text.as_bytes()).await;
+    }
+
+    /// Converts `value` with its `Into<JItem>` impl and writes it.
+    pub async fn push_value(&mut self, value: impl Into<JItem>) -> io::Result<()> {
+        return self.push(&value.into()).await;
+    }
+
+    /// Elements written so far.
+    pub fn len(&self) -> usize {
+        return self.layout.count;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.layout.count == 0;
+    }
+
+    /// Writes the closing `]`, flushes and hands back the writer.
+    pub async fn finish(mut self) -> io::Result<W> {
+        use tokio::io::AsyncWriteExt;
+        let text = self.layout.end();
+        self.writer.write_all(text.as_bytes()).await?;
+        self.writer.flush().await?;
+        return Ok(self.writer);
+    }
+}
+
+/// The separators and indentation around each element, shared by both writers.
+struct Layout {
+    options: FormatOptions,
+    count: usize,
+}
+
+impl Layout {
+    fn element(&mut self, item: &JItem) -> String {
+        let mut out = String::new();
+        if self.count > 0 {
+            out.push(',');
+        }
+        write_newline(&self.options, 1, &mut out);
+        write_item(item, &self.options, 1, &mut out);
+        self.count += 1;
+        return out;
+    }
+
+    fn end(&self) -> String {
+        let mut out = String::new();
+        if self.count > 0 {
+            write_newline(&self.options, 0, &mut out);
+        }
+        out.push(']');
+        return out;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn matches_whole_array_output() {
+        let items = vec![parse(r#"{"b": [1, 2], "a": null}"#).unwrap(), JItem::String("x".to_string()), JItem::Array(vec![])];
+        for options in [FormatOptions::compact(), FormatOptions::pretty()] {
+            let options = FormatOptions { sort_keys: true, ..options };
+            let mut writer = JsonArrayWriter::new(vec![], options.clone()).unwrap();
+            for item in &items {
+                writer.push(item).unwrap();
+            }
+            assert_eq!(writer.len(), 3);
+            let out = String::from_utf8(writer.finish().unwrap()).unwrap();
+            assert_eq!(out, JItem::Array(items.clone()).to_string_with(&options));
+        }
+    }
+
+    #[test]
+    fn empty_and_converted_values() {
+        for options in [FormatOptions::compact(), FormatOptions::pretty()] {
+            let writer = JsonArrayWriter::new(vec![], options).unwrap();
+            assert!(writer.is_empty());
+            assert_eq!(writer.finish().unwrap(), b"[]");
+        }
+
+        let mut writer = JsonArrayWriter::new(vec![], FormatOptions::compact()).unwrap();
+        writer.push_value(true).unwrap();
+        writer.push_value(false).unwrap();
+        assert_eq!(writer.finish().unwrap(), b"[true,false]");
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn async_writer() {
+        use std::{future::Future, pin::pin, task::{Context, Poll, Waker}};
+
+        // writing to a Vec never waits, so polling once is enough
+        fn ready<T>(future: impl Future<Output = T>) -> T {
+            let Poll::Ready(output) = pin!(future).poll(&mut Context::from_waker(Waker::noop())) else {
+                panic!("future was not ready");
+            };
+            return output;
+        }
+
+        let options = FormatOptions::pretty();
+        let mut writer = ready(AsyncJsonArrayWriter::new(vec![], options.clone())).unwrap();
+        ready(writer.push(&JItem::Null)).unwrap();
+        ready(writer.push_value(true)).unwrap();
+        let out = ready(writer.finish()).unwrap();
+        assert_eq!(out, JItem::Array(vec![JItem::Null, JItem::Bool(true)]).to_string_with(&options).as_bytes());
+    }
+}
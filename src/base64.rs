@@ -0,0 +1,135 @@
+use crate::j_item::JItem;
+
+const STANDARD: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const URL_SAFE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// The base64 alphabets of RFC 4648. Both are written with `=` padding.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Alphabet {
+    #[default]
+    Standard,
+    /// Uses `-` and `_` in place of `+` and `/`, so values can sit in URLs and filenames.
+    UrlSafe,
+}
+
+impl JItem {
+    /// Stores binary data as a standard base64 string.
+    pub fn from_bytes_base64(bytes: &[u8]) -> JItem {
+        return JItem::from_bytes_base64_with(bytes, Alphabet::Standard);
+    }
+
+    pub fn from_bytes_base64_with(bytes: &[u8], alphabet: Alphabet) -> JItem {
+        return JItem::String(encode(bytes, alphabet));
+    }
+
+    /// Decodes a base64 string in either alphabet, with or without padding. Returns
+    /// `None` for other values and for strings that are not valid base64.
+    pub fn as_bytes_base64(&self) -> Option<Vec<u8>> {
+        let JItem::String(s) = self else {
+            return None;
+        };
+        return decode_lenient(s);
+    }
+}
+
+pub(crate) fn encode(data: &[u8], alphabet: Alphabet) -> String {
+    let table = match alphabet {
+        Alphabet::Standard => STANDARD,
+        Alphabet::UrlSafe => URL_SAFE,
+    };
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let group = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for n in 0..4 {
+            if n <= chunk.len() {
+                out.push(table[(group >> (18 - 6 * n) & 0x3f) as usize] as char);
+            }
+            else {
+                out.push('=');
+            }
+        }
+    }
+    return out;
+}
+
+/// Decodes base64 in either alphabet, with or without padding.
+pub(crate) fn decode_lenient(text: &str) -> Option<Vec<u8>> {
+    let padded = match text.len() % 4 {
+        0 => text.to_string(),
+        1 => return None,
+        n => format!("{}{}", text, "=".repeat(4 - n)),
+    };
+    return decode(&padded.replace('-', "+").replace('_', "/"));
+}
+
+pub(crate) fn decode(text: &str) -> Option<Vec<u8>> {
+    let text = text.as_bytes();
+    if !text.len().is_multiple_of(4) {
+        return None;
+    }
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    for (n, chunk) in text.chunks(4).enumerate() {
+        let is_last = n == text.len() / 4 - 1;
+        let padding = chunk.iter().rev().take_while(|c| **c == b'=').count();
+        if padding > 2 || (padding > 0 && !is_last) {
+            return None;
+        }
+        let mut group = 0u32;
+        for c in &chunk[..4 - padding] {
+            let value = STANDARD.iter().position(|a| a == c)?;
+            group = group << 6 | value as u32;
+        }
+        group <<= 6 * padding;
+        let bytes = group.to_be_bytes();
+        out.extend_from_slice(&bytes[1..4 - padding]);
+    }
+    return Some(out);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // test vectors from RFC 4648 section 10
+    #[test]
+    fn base64_rfc_vectors() {
+        let vectors = [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"), ("foob", "Zm9vYg=="), ("fooba", "Zm9vYmE="), ("foobar", "Zm9vYmFy")];
+        for (plain, encoded) in vectors {
+            assert_eq!(encode(plain.as_bytes(), Alphabet::Standard), encoded);
+            assert_eq!(decode(encoded), Some(plain.as_bytes().to_vec()));
+        }
+    }
+
+    #[test]
+    fn base64_decode_invalid() {
+        assert_eq!(decode("Zg="), None);
+        assert_eq!(decode("Zg=a"), None);
+        assert_eq!(decode("Z==="), None);
+        assert_eq!(decode("Zg==Zg=="), None);
+        assert_eq!(decode("Z!=="), None);
+    }
+
+    #[test]
+    fn base64_url_safe_alphabet() {
+        let bytes = [0xfb, 0xff, 0xbf];
+        assert_eq!(JItem::from_bytes_base64(&bytes), JItem::String("+/+/".to_string()));
+        assert_eq!(JItem::from_bytes_base64_with(&bytes, Alphabet::UrlSafe), JItem::String("-_-_".to_string()));
+    }
+
+    #[test]
+    fn as_bytes_base64_accepts_either_alphabet() {
+        assert_eq!(JItem::String("-_-_".to_string()).as_bytes_base64(), Some(vec![0xfb, 0xff, 0xbf]));
+        assert_eq!(JItem::String("+/+/".to_string()).as_bytes_base64(), Some(vec![0xfb, 0xff, 0xbf]));
+        assert_eq!(JItem::String("Zm8".to_string()).as_bytes_base64(), Some(b"fo".to_vec()));
+        assert_eq!(JItem::String("Zm9vY".to_string()).as_bytes_base64(), None);
+        assert_eq!(JItem::Number(1.0).as_bytes_base64(), None);
+    }
+
+    #[test]
+    fn base64_round_trip() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        assert_eq!(JItem::from_bytes_base64(&bytes).as_bytes_base64(), Some(bytes.clone()));
+        assert_eq!(JItem::from_bytes_base64_with(&bytes, Alphabet::UrlSafe).as_bytes_base64(), Some(bytes));
+    }
+}
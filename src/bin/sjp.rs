@@ -0,0 +1,145 @@
+//! `sjp`, a small command line tool over the library. Build it with `--features cli`.
+//!
+//! Every command reads JSON from the files it is given, or from stdin for `-` or when a
+//! file is left out, and writes JSON to stdout. Numbers are written back exactly as
+//! they were read, and object keys are sorted.
+
+#![allow(clippy::needless_return)]
+
+use std::{
+    io::{self, IsTerminal, Read},
+    process::ExitCode,
+};
+
+use simple_json_parser::{
+    format::FormatOptions,
+    j_item::JItem,
+    parse_with,
+    parser::ParseOptions,
+    patch::{RenderOptions, apply_patch, diff, render_diff},
+};
+
+const USAGE: &str = "usage: sjp <command> [args]
+
+commands:
+  validate [FILE...]         check that each input is valid JSON
+  pretty [--indent N] [FILE] write an input indented, two spaces by default
+  minify [FILE]              write an input on one line
+  get POINTER [FILE]         write the value at a JSON Pointer, such as /items/0
+  diff FROM TO               write a JSON Patch that turns FROM into TO
+  diff --unified FROM TO     write a line diff of FROM and TO, colored on a terminal
+  patch PATCH [FILE]         apply a JSON Patch to an input
+
+FILE may be - for stdin, which is also the default.";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some((command, args)) = args.split_first() else {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    };
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let result = match (command.as_str(), args.as_slice()) {
+        ("validate", files) => validate(files),
+        ("pretty", ["--indent", n, rest @ ..]) if rest.len() <= 1 => match n.parse() {
+            Ok(n) => pretty(rest.first().copied(), n),
+            Err(_) => Err(format!("invalid indent '{}'", n)),
+        },
+        ("pretty", [] | [_]) => pretty(args.first().copied(), 2),
+        ("minify", [] | [_]) => read_json(args.first().copied()).map(|item| write(&item, None)),
+        ("get", [pointer, rest @ ..]) if rest.len() <= 1 => get(pointer, rest.first().copied()),
+        ("diff", [from, to]) => diff_files(from, to),
+        ("diff", ["--unified", from, to]) => unified_diff(from, to),
+        ("patch", [patch, rest @ ..]) if rest.len() <= 1 => patch_file(patch, rest.first().copied()),
+        ("help" | "--help" | "-h", []) => {
+            println!("{}", USAGE);
+            Ok(true)
+        },
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        },
+    };
+    return match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("sjp: {}", e);
+            ExitCode::FAILURE
+        },
+    };
+}
+
+/// Reports every invalid input rather than stopping at the first.
+fn validate(files: &[&str]) -> Result<bool, String> {
+    let files = if files.is_empty() { &["-"][..] } else { files };
+    let mut valid = true;
+    for file in files {
+        let source = read(Some(file))?;
+        if let Err(e) = simple_json_parser::validate(&source) {
+            eprintln!("{}:\n{}", name(Some(file)), e.render(&source));
+            valid = false;
+        }
+    }
+    return Ok(valid);
+}
+
+fn pretty(file: Option<&str>, indent: usize) -> Result<bool, String> {
+    return Ok(write(&read_json(file)?, Some(indent)));
+}
+
+fn get(pointer: &str, file: Option<&str>) -> Result<bool, String> {
+    let item = read_json(file)?;
+    let Some(found) = item.pointer(pointer) else {
+        return Err(format!("nothing at '{}'", pointer));
+    };
+    return Ok(write(found, Some(2)));
+}
+
+fn diff_files(from: &str, to: &str) -> Result<bool, String> {
+    return Ok(write(&diff(&read_json(Some(from))?, &read_json(Some(to))?), Some(2)));
+}
+
+/// Succeeds only when the inputs are equal, like `diff(1)`.
+fn unified_diff(from: &str, to: &str) -> Result<bool, String> {
+    let options = RenderOptions { color: io::stdout().is_terminal(), ..RenderOptions::default() };
+    let rendered = render_diff(&read_json(Some(from))?, &read_json(Some(to))?, &options);
+    print!("{}", rendered);
+    return Ok(rendered.is_empty());
+}
+
+fn patch_file(patch: &str, file: Option<&str>) -> Result<bool, String> {
+    let patch = read_json(Some(patch))?;
+    let mut item = read_json(file)?;
+    apply_patch(&mut item, &patch)?;
+    return Ok(write(&item, Some(2)));
+}
+
+fn write(item: &JItem, indent: Option<usize>) -> bool {
+    let options = FormatOptions { indent: indent.map(|n| " ".repeat(n)), ..FormatOptions::pretty() };
+    println!("{}", item.to_string_with(&options));
+    return true;
+}
+
+fn name(file: Option<&str>) -> &str {
+    return match file {
+        None | Some("-") => "<stdin>",
+        Some(file) => file,
+    };
+}
+
+fn read(file: Option<&str>) -> Result<String, String> {
+    let mut source = String::new();
+    let result = match file {
+        None | Some("-") => io::stdin().read_to_string(&mut source).map(|_| ()),
+        Some(file) => std::fs::read_to_string(file).map(|read| source = read),
+    };
+    result.map_err(|e| format!("{}: {}", name(file), e))?;
+    return Ok(source);
+}
+
+fn read_json(file: Option<&str>) -> Result<JItem, String> {
+    let source = read(file)?;
+    let options = ParseOptions { preserve_number_text: true, ..ParseOptions::default() };
+    return parse_with(&source, &options).map_err(|e| format!("{}:\n{}", name(file), e.render(&source)));
+}
@@ -0,0 +1,466 @@
+use std::collections::HashMap;
+
+use crate::{base64, j_item::JItem};
+
+/// Encodes an object as a BSON document. Single-member objects in Extended JSON form,
+/// such as `{"$oid": "..."}` or `{"$date": {"$numberLong": "..."}}`, are written as the
+/// BSON type they describe. Whole numbers become int32 or int64 where they fit.
+pub fn to_bson(item: &JItem) -> Result<Vec<u8>, String> {
+    let JItem::Object(hmap) = item else {
+        return Err("bson documents must be objects at the top level".to_string());
+    };
+    let mut members: Vec<_> = hmap.iter().collect();
+    members.sort_by_key(|(key, _)| *key);
+    let mut out = vec![];
+    write_document(members.into_iter().map(|(key, value)| (key.to_string(), value)), &mut out)?;
+    return Ok(out);
+}
+
+/// Decodes one BSON document. Types without a JSON equivalent come back in canonical
+/// Extended JSON form, and int64 values beyond 2^53 are kept exact as `$numberLong`.
+/// Documents and arrays nested more than 128 deep are an error.
+pub fn from_bson(bytes: &[u8]) -> Result<JItem, String> {
+    let mut reader = Reader { bytes, pos: 0, depth: 0 };
+    let item = reader.read_document(false)?;
+    if reader.pos != bytes.len() {
+        return Err(format!("invalid bson: {} trailing bytes", bytes.len() - reader.pos));
+    }
+    return Ok(item);
+}
+
+/// Decodes back-to-back documents, the layout `mongodump` writes to `.bson` files.
+pub fn from_bson_documents(bytes: &[u8]) -> Result<Vec<JItem>, String> {
+    let mut reader = Reader { bytes, pos: 0, depth: 0 };
+    let mut documents = vec![];
+    while reader.pos < bytes.len() {
+        documents.push(reader.read_document(false)?);
+    }
+    return Ok(documents);
+}
+
+const MAX_SAFE_INTEGER: f64 = 9007199254740992.0;
+
+/// How deeply documents and arrays may nest in decoded input, counting the outermost
+/// document, so that untrusted bytes cannot exhaust the stack.
+const MAX_DEPTH: usize = 128;
+
+fn write_cstring(s: &str, out: &mut Vec<u8>) -> Result<(), String> {
+    if s.contains('\0') {
+        return Err(format!("bson keys and patterns cannot contain NUL: '{}'", s.escape_default()));
+    }
+    out.extend_from_slice(s.as_bytes());
+    out.push(0);
+    return Ok(());
+}
+
+fn write_string(s: &str, out: &mut Vec<u8>) {
+    out.extend_from_slice(&(s.len() as i32 + 1).to_le_bytes());
+    out.extend_from_slice(s.as_bytes());
+    out.push(0);
+}
+
+fn write_document<'a>(members: impl Iterator<Item = (String, &'a JItem)>, out: &mut Vec<u8>) -> Result<(), String> {
+    let start = out.len();
+    out.extend_from_slice(&[0; 4]);
+    for (key, value) in members {
+        let type_at = out.len();
+        out.push(0);
+        write_cstring(&key, out)?;
+        out[type_at] = write_value(value, out)?;
+    }
+    out.push(0);
+    let len = (out.len() - start) as i32;
+    out[start..start + 4].copy_from_slice(&len.to_le_bytes());
+    return Ok(());
+}
+
+// writes the value and returns its element type
+fn write_value(item: &JItem, out: &mut Vec<u8>) -> Result<u8, String> {
+    return match item {
+        JItem::RawNumber(_) => write_value(&JItem::Number(item.number_value().unwrap()), out),
+        JItem::Object(hmap) => {
+            if let Some(written) = write_extended(hmap, out) {
+                return written;
+            }
+            let mut members: Vec<_> = hmap.iter().collect();
+            members.sort_by_key(|(key, _)| *key);
+            write_document(members.into_iter().map(|(key, value)| (key.to_string(), value)), out)?;
+            Ok(0x03)
+        },
+        JItem::Array(elements) => {
+            write_document(elements.iter().enumerate().map(|(n, element)| (n.to_string(), element)), out)?;
+            Ok(0x04)
+        },
+        JItem::String(s) => {
+            write_string(s, out);
+            Ok(0x02)
+        },
+        JItem::Number(x) if x.fract() == 0.0 && !(*x == 0.0 && x.is_sign_negative()) && x.abs() <= i32::MAX as f64 => {
+            out.extend_from_slice(&(*x as i32).to_le_bytes());
+            Ok(0x10)
+        },
+        JItem::Number(x) if x.fract() == 0.0 && x.abs() <= MAX_SAFE_INTEGER => {
+            out.extend_from_slice(&(*x as i64).to_le_bytes());
+            Ok(0x12)
+        },
+        JItem::Number(x) => {
+            out.extend_from_slice(&x.to_le_bytes());
+            Ok(0x01)
+        },
+        JItem::Bool(b) => {
+            out.push(*b as u8);
+            Ok(0x08)
+        },
+        JItem::Null => Ok(0x0a),
+    };
+}
+
+fn member<'a>(item: &'a JItem, key: &str) -> Option<&'a JItem> {
+    let JItem::Object(hmap) = item else {
+        return None;
+    };
+    return hmap.get(key);
+}
+
+fn as_str(item: Option<&JItem>) -> Option<&str> {
+    let Some(JItem::String(s)) = item else {
+        return None;
+    };
+    return Some(s);
+}
+
+fn as_number(item: Option<&JItem>) -> Option<f64> {
+    let Some(JItem::Number(x)) = item else {
+        return None;
+    };
+    return Some(*x);
+}
+
+// writes a single-member Extended JSON wrapper, or returns None for ordinary objects
+fn write_extended(hmap: &HashMap<String, JItem>, out: &mut Vec<u8>) -> Option<Result<u8, String>> {
+    if hmap.len() != 1 {
+        return None;
+    }
+    let (key, value) = hmap.iter().next()?;
+    let invalid = || Err(format!("invalid extended json value for '{}': {}", key, value));
+    let result = match key.as_str() {
+        "$oid" => match as_str(Some(value)).filter(|s| s.len() == 24 && s.bytes().all(|b| b.is_ascii_hexdigit())) {
+            Some(hex) => {
+                // every byte is a hex digit, so each pair is a char boundary and decodes
+                out.extend((0..24).step_by(2).map(|n| u8::from_str_radix(&hex[n..n + 2], 16).unwrap()));
+                Ok(0x07)
+            },
+            None => invalid(),
+        },
+        "$date" => {
+            let millis = match value {
+                JItem::Number(x) if x.fract() == 0.0 => Some(*x as i64),
+                _ => as_str(member(value, "$numberLong")).and_then(|s| s.parse().ok()),
+            };
+            match millis {
+                Some(millis) => {
+                    out.extend_from_slice(&i64::to_le_bytes(millis));
+                    Ok(0x09)
+                },
+                None => invalid(),
+            }
+        },
+        "$binary" => {
+            let data = as_str(member(value, "base64")).and_then(base64::decode);
+            let subtype = as_str(member(value, "subType")).and_then(|s| u8::from_str_radix(s, 16).ok());
+            match (data, subtype) {
+                (Some(data), Some(subtype)) => {
+                    out.extend_from_slice(&(data.len() as i32).to_le_bytes());
+                    out.push(subtype);
+                    out.extend_from_slice(&data);
+                    Ok(0x05)
+                },
+                _ => invalid(),
+            }
+        },
+        "$numberLong" => match as_str(Some(value)).and_then(|s| s.parse::<i64>().ok()) {
+            Some(n) => {
+                out.extend_from_slice(&n.to_le_bytes());
+                Ok(0x12)
+            },
+            None => invalid(),
+        },
+        "$numberInt" => match as_str(Some(value)).and_then(|s| s.parse::<i32>().ok()) {
+            Some(n) => {
+                out.extend_from_slice(&n.to_le_bytes());
+                Ok(0x10)
+            },
+            None => invalid(),
+        },
+        "$numberDouble" => {
+            let x = match as_str(Some(value)) {
+                Some("Infinity") => Some(f64::INFINITY),
+                Some("-Infinity") => Some(f64::NEG_INFINITY),
+                Some("NaN") => Some(f64::NAN),
+                other => other.and_then(|s| s.parse().ok()),
+            };
+            match x {
+                Some(x) => {
+                    out.extend_from_slice(&x.to_le_bytes());
+                    Ok(0x01)
+                },
+                None => invalid(),
+            }
+        },
+        "$timestamp" => match (as_number(member(value, "t")), as_number(member(value, "i"))) {
+            (Some(t), Some(i)) => {
+                out.extend_from_slice(&(i as u32).to_le_bytes());
+                out.extend_from_slice(&(t as u32).to_le_bytes());
+                Ok(0x11)
+            },
+            _ => invalid(),
+        },
+        "$regularExpression" => match (as_str(member(value, "pattern")), as_str(member(value, "options"))) {
+            (Some(pattern), Some(options)) => write_cstring(pattern, out).and(write_cstring(options, out)).map(|_| 0x0b),
+            _ => invalid(),
+        },
+        "$code" => match as_str(Some(value)) {
+            Some(code) => {
+                write_string(code, out);
+                Ok(0x0d)
+            },
+            None => invalid(),
+        },
+        "$minKey" => Ok(0xff),
+        "$maxKey" => Ok(0x7f),
+        _ => return None,
+    };
+    return Some(result);
+}
+
+fn wrap(key: &str, value: JItem) -> JItem {
+    let mut hmap = HashMap::new();
+    hmap.insert(key.to_string(), value);
+    return JItem::Object(hmap);
+}
+
+fn string(s: impl Into<String>) -> JItem {
+    return JItem::String(s.into());
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    /// Documents and arrays open around the current position.
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let Some(slice) = self.bytes.get(self.pos..self.pos + N) else {
+            return Err("invalid bson: unexpected end of input".to_string());
+        };
+        self.pos += N;
+        return Ok(slice.try_into().unwrap());
+    }
+
+    fn take_slice(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let Some(slice) = self.pos.checked_add(len).and_then(|end| self.bytes.get(self.pos..end)) else {
+            return Err("invalid bson: unexpected end of input".to_string());
+        };
+        self.pos += len;
+        return Ok(slice);
+    }
+
+    fn read_len(&mut self) -> Result<usize, String> {
+        let len = i32::from_le_bytes(self.take()?);
+        return usize::try_from(len).map_err(|_| format!("invalid bson: negative length {}", len));
+    }
+
+    fn read_cstring(&mut self) -> Result<String, String> {
+        let Some(len) = self.bytes[self.pos..].iter().position(|b| *b == 0) else {
+            return Err("invalid bson: unterminated cstring".to_string());
+        };
+        let s = self.take_slice(len)?;
+        self.pos += 1;
+        return String::from_utf8(s.to_vec()).map_err(|_| "invalid bson: cstring is not valid utf-8".to_string());
+    }
+
+    fn read_string(&mut self) -> Result<String, String> {
+        let len = self.read_len()?;
+        let bytes = self.take_slice(len)?;
+        let Some((0, s)) = bytes.split_last() else {
+            return Err("invalid bson: string is not NUL terminated".to_string());
+        };
+        return String::from_utf8(s.to_vec()).map_err(|_| "invalid bson: string is not valid utf-8".to_string());
+    }
+
+    fn read_document(&mut self, is_array: bool) -> Result<JItem, String> {
+        if self.depth == MAX_DEPTH {
+            return Err(format!("invalid bson: nested more than {} deep", MAX_DEPTH));
+        }
+        self.depth += 1;
+        let start = self.pos;
+        let len = self.read_len()?;
+        let mut hmap = HashMap::new();
+        let mut elements = vec![];
+        loop {
+            let [element_type] = self.take()?;
+            if element_type == 0 {
+                break;
+            }
+            let key = self.read_cstring()?;
+            let value = self.read_value(element_type, &key)?;
+            if is_array {
+                elements.push(value);
+            }
+            else if hmap.insert(key.clone(), value).is_some() {
+                return Err(format!("invalid bson: duplicate key '{}'", key));
+            }
+        }
+        if self.pos - start != len {
+            return Err(format!("invalid bson: document length {} does not match its content", len));
+        }
+        self.depth -= 1;
+        return Ok(if is_array { JItem::Array(elements) } else { JItem::Object(hmap) });
+    }
+
+    fn read_value(&mut self, element_type: u8, key: &str) -> Result<JItem, String> {
+        return match element_type {
+            0x01 => Ok(JItem::Number(f64::from_le_bytes(self.take()?))),
+            0x02 | 0x0e => Ok(JItem::String(self.read_string()?)),
+            0x03 => self.read_document(false),
+            0x04 => self.read_document(true),
+            0x05 => {
+                let len = self.read_len()?;
+                let [subtype] = self.take()?;
+                let data = self.take_slice(len)?;
+                let mut binary = HashMap::new();
+                binary.insert("base64".to_string(), string(base64::encode(data, base64::Alphabet::Standard)));
+                binary.insert("subType".to_string(), string(format!("{:02x}", subtype)));
+                Ok(wrap("$binary", JItem::Object(binary)))
+            },
+            0x06 | 0x0a => Ok(JItem::Null),
+            0x07 => {
+                let bytes: [u8; 12] = self.take()?;
+                Ok(wrap("$oid", string(bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>())))
+            },
+            0x08 => match self.take()? {
+                [0] => Ok(JItem::Bool(false)),
+                [1] => Ok(JItem::Bool(true)),
+                [b] => Err(format!("invalid bson: boolean byte {}", b)),
+            },
+            0x09 => {
+                let millis = i64::from_le_bytes(self.take()?);
+                Ok(wrap("$date", wrap("$numberLong", string(millis.to_string()))))
+            },
+            0x0b => {
+                let mut regex = HashMap::new();
+                regex.insert("pattern".to_string(), string(self.read_cstring()?));
+                regex.insert("options".to_string(), string(self.read_cstring()?));
+                Ok(wrap("$regularExpression", JItem::Object(regex)))
+            },
+            0x0d => Ok(wrap("$code", string(self.read_string()?))),
+            0x10 => Ok(JItem::Number(i32::from_le_bytes(self.take()?) as f64)),
+            0x11 => {
+                let increment = u32::from_le_bytes(self.take()?);
+                let seconds = u32::from_le_bytes(self.take()?);
+                let mut timestamp = HashMap::new();
+                timestamp.insert("t".to_string(), JItem::Number(seconds as f64));
+                timestamp.insert("i".to_string(), JItem::Number(increment as f64));
+                Ok(wrap("$timestamp", JItem::Object(timestamp)))
+            },
+            0x12 => {
+                let n = i64::from_le_bytes(self.take()?);
+                // compared as integers, since the cast would round 2^53 + 1 down to 2^53
+                if n.unsigned_abs() <= 1 << 53 {
+                    Ok(JItem::Number(n as f64))
+                }
+                else {
+                    Ok(wrap("$numberLong", string(n.to_string())))
+                }
+            },
+            0xff => Ok(wrap("$minKey", JItem::Number(1.0))),
+            0x7f => Ok(wrap("$maxKey", JItem::Number(1.0))),
+            _ => Err(format!("invalid bson: unsupported element type 0x{:02x} for '{}'", element_type, key)),
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn to_bson_spec_example() {
+        // {"hello": "world"} from bsonspec.org
+        let bytes = to_bson(&parse(r#"{"hello": "world"}"#).unwrap()).unwrap();
+        assert_eq!(bytes, b"\x16\x00\x00\x00\x02hello\x00\x06\x00\x00\x00world\x00\x00");
+    }
+
+    #[test]
+    fn bson_round_trip() {
+        let item = parse(r#"{"name": "x", "n": 5, "big": 5000000000, "ratio": 0.5, "ok": true, "none": null, "tags": ["a", {"b": []}]}"#).unwrap();
+        assert_eq!(from_bson(&to_bson(&item).unwrap()), Ok(item));
+    }
+
+    #[test]
+    fn bson_extended_json_round_trip() {
+        let item = parse(r#"{
+            "_id": {"$oid": "507f1f77bcf86cd799439011"},
+            "at": {"$date": {"$numberLong": "1356351330000"}},
+            "blob": {"$binary": {"base64": "AQID", "subType": "00"}},
+            "huge": {"$numberLong": "9223372036854775807"},
+            "ts": {"$timestamp": {"t": 42, "i": 1}},
+            "re": {"$regularExpression": {"pattern": "^a", "options": "i"}},
+            "low": {"$minKey": 1}
+        }"#).unwrap();
+        assert_eq!(from_bson(&to_bson(&item).unwrap()), Ok(item));
+    }
+
+    #[test]
+    fn to_bson_number_types() {
+        let bytes = to_bson(&parse(r#"{"a": {"$numberInt": "7"}, "b": {"$numberDouble": "Infinity"}}"#).unwrap()).unwrap();
+        let item = from_bson(&bytes).unwrap();
+        assert_eq!(item.pointer("/a"), Some(&JItem::Number(7.0)));
+        assert_eq!(item.pointer("/b"), Some(&JItem::Number(f64::INFINITY)));
+    }
+
+    #[test]
+    fn from_bson_int64_beyond_2_53() {
+        let bytes = to_bson(&parse(r#"{"a": {"$numberLong": "9007199254740992"}, "b": {"$numberLong": "9007199254740993"}, "c": {"$numberLong": "-9007199254740993"}}"#).unwrap()).unwrap();
+        let item = from_bson(&bytes).unwrap();
+        assert_eq!(item.pointer("/a"), Some(&JItem::Number(9007199254740992.0)));
+        assert_eq!(item.pointer("/b"), parse(r#"{"$numberLong": "9007199254740993"}"#).ok().as_ref());
+        assert_eq!(item.pointer("/c"), parse(r#"{"$numberLong": "-9007199254740993"}"#).ok().as_ref());
+    }
+
+    #[test]
+    fn from_bson_documents_concatenated() {
+        let mut bytes = to_bson(&parse(r#"{"a": 1}"#).unwrap()).unwrap();
+        bytes.extend(to_bson(&parse(r#"{"b": 2}"#).unwrap()).unwrap());
+        assert_eq!(from_bson_documents(&bytes), Ok(vec![parse(r#"{"a": 1}"#).unwrap(), parse(r#"{"b": 2}"#).unwrap()]));
+    }
+
+    #[test]
+    fn from_bson_depth_limit() {
+        // a document holding arrays nested so that `depth` documents are open at the deepest
+        let nested = |depth: usize| {
+            let mut value = JItem::Null;
+            for _ in 1..depth {
+                value = JItem::Array(vec![value]);
+            }
+            return to_bson(&JItem::Object(HashMap::from([("a".to_string(), value)]))).unwrap();
+        };
+        assert!(from_bson(&nested(MAX_DEPTH)).is_ok());
+        assert_eq!(from_bson(&nested(MAX_DEPTH + 1)), Err("invalid bson: nested more than 128 deep".to_string()));
+        assert!(from_bson_documents(&nested(MAX_DEPTH + 1)).is_err());
+    }
+
+    #[test]
+    fn bson_errors() {
+        assert!(to_bson(&parse("[1]").unwrap()).is_err());
+        assert!(to_bson(&parse(r#"{"_id": {"$oid": "zz"}}"#).unwrap()).is_err());
+        assert!(to_bson(&parse(r#"{"_id": {"$oid": "€€€€€€€€"}}"#).unwrap()).is_err());
+        assert!(to_bson(&parse(r#"{"_id": {"$oid": "+07f1f77bcf86cd799439011"}}"#).unwrap()).is_err());
+        assert!(from_bson(b"\x05\x00\x00\x00").is_err());
+        assert!(from_bson(b"\x06\x00\x00\x00\x00\x00").is_err());
+        assert!(from_bson(b"\x0c\x00\x00\x00\x13a\x00\x00\x00\x00\x00").is_err());
+    }
+}
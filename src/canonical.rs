@@ -0,0 +1,87 @@
+use alloc::{string::{String, ToString}, vec::Vec};
+
+use crate::{j_item::{escape_string, format_f64, JItem}, sha256::sha256};
+
+impl JItem {
+    /// Serializes without whitespace and with object keys sorted bytewise, so equal values
+    /// always produce the same text. `-0` is written as `0`, and non-finite numbers,
+    /// which JSON cannot represent, as `null`.
+    pub fn to_canonical_string(&self) -> String {
+        let mut canonical = String::new();
+        write_canonical(self, &mut canonical);
+        return canonical;
+    }
+
+    /// SHA-256 digest of `to_canonical_string`, usable as a content address that ignores
+    /// key order and formatting of the source text.
+    pub fn canonical_hash(&self) -> [u8; 32] {
+        return sha256(self.to_canonical_string().as_bytes());
+    }
+}
+
+fn write_canonical(item: &JItem, out: &mut String) {
+    match item {
+        JItem::Object(hmap) => {
+            let mut keys: Vec<_> = hmap.keys().collect();
+            keys.sort();
+            out.push('{');
+            for (n, key) in keys.into_iter().enumerate() {
+                if n > 0 {
+                    out.push(',');
+                }
+                out.push('"');
+                out.push_str(&escape_string(key));
+                out.push_str("\":");
+                write_canonical(&hmap[key], out);
+            }
+            out.push('}');
+        },
+        JItem::Array(elements) => {
+            out.push('[');
+            for (n, element) in elements.iter().enumerate() {
+                if n > 0 {
+                    out.push(',');
+                }
+                write_canonical(element, out);
+            }
+            out.push(']');
+        },
+        JItem::RawNumber(_) => write_canonical(&JItem::Number(item.number_value().unwrap()), out),
+        JItem::Number(x) if !x.is_finite() => out.push_str("null"),
+        // adding 0.0 turns -0.0 into 0.0
+        JItem::Number(x) => out.push_str(&format_f64(x + 0.0)),
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn canonical_string_sorts_keys() {
+        let item = parse(r#"{ "b": [1, {"z": null, "y": true}], "a": "x" }"#).unwrap();
+        assert_eq!(item.to_canonical_string(), r#"{"a":"x","b":[1,{"y":true,"z":null}]}"#);
+    }
+
+    #[test]
+    fn canonical_string_numbers() {
+        let item = JItem::Array(vec![JItem::Number(-0.), JItem::Number(1.5), JItem::Number(f64::NAN), JItem::Number(1e300), JItem::Number(1.25e-7)]);
+        assert_eq!(item.to_canonical_string(), "[0,1.5,null,1e300,1.25e-7]");
+    }
+
+    #[test]
+    fn canonical_hash_ignores_formatting_and_order() {
+        let a = parse(r#"{"a": 1, "b": [true, false]}"#).unwrap();
+        let b = parse("{\n  \"b\": [ true , false ],\n  \"a\": 1.0\n}").unwrap();
+        assert_eq!(a.canonical_hash(), b.canonical_hash());
+    }
+
+    #[test]
+    fn canonical_hash_differs_for_different_values() {
+        let a = parse(r#"{"a": 1}"#).unwrap();
+        let b = parse(r#"{"a": "1"}"#).unwrap();
+        assert_ne!(a.canonical_hash(), b.canonical_hash());
+    }
+}
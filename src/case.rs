@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+
+use crate::j_item::JItem;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Case {
+    /// `fooBarBaz`
+    CamelCase,
+    /// `foo_bar_baz`
+    SnakeCase,
+    /// `foo-bar-baz`
+    KebabCase,
+    /// `FooBarBaz`
+    PascalCase,
+}
+
+impl Case {
+    /// Converts `s` to this case. Words are split on `_`, `-` and spaces, on lower to
+    /// upper case transitions, and before the last capital of an acronym (`HTTPServer`
+    /// becomes `http` and `server`). Digits stay attached to the preceding word. Leading
+    /// `_` and `-` are kept as they are, so `_id` stays apart from `id`.
+    pub fn convert(&self, s: &str) -> String {
+        let rest = s.trim_start_matches(['_', '-']);
+        let prefix = &s[..s.len() - rest.len()];
+        let words = split_words(rest);
+        let converted: String = match self {
+            Case::SnakeCase => words.join("_"),
+            Case::KebabCase => words.join("-"),
+            Case::CamelCase => {
+                let mut converted = String::new();
+                for (n, word) in words.iter().enumerate() {
+                    if n == 0 {
+                        converted.push_str(word);
+                    }
+                    else {
+                        converted.push_str(&capitalize(word));
+                    }
+                }
+                converted
+            },
+            Case::PascalCase => words.iter().map(|w| capitalize(w)).collect(),
+        };
+        return format!("{}{}", prefix, converted);
+    }
+}
+
+fn split_words(s: &str) -> Vec<String> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut words = vec![];
+    let mut word = String::new();
+    for (n, c) in chars.iter().enumerate() {
+        if matches!(c, '_' | '-' | ' ') {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+        if c.is_uppercase() && !word.is_empty() {
+            let prev = chars[n - 1];
+            let next_is_lower = chars.get(n + 1).is_some_and(|next| next.is_lowercase());
+            if prev.is_lowercase() || prev.is_ascii_digit() || (prev.is_uppercase() && next_is_lower) {
+                words.push(std::mem::take(&mut word));
+            }
+        }
+        word.extend(c.to_lowercase());
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    return words;
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    let Some(first) = chars.next() else {
+        return String::new();
+    };
+    return first.to_uppercase().chain(chars).collect();
+}
+
+impl JItem {
+    /// Recursively renames every object key to `case`. If two keys of one object convert
+    /// to the same name, the member whose original key sorts last is kept.
+    pub fn rename_keys(&mut self, case: Case) {
+        match self {
+            JItem::Object(hmap) => {
+                let mut members: Vec<_> = hmap.drain().collect();
+                members.sort_by(|(a, _), (b, _)| a.cmp(b));
+                let mut renamed = HashMap::with_capacity(members.len());
+                for (key, mut value) in members {
+                    value.rename_keys(case);
+                    renamed.insert(case.convert(&key), value);
+                }
+                *hmap = renamed;
+            },
+            JItem::Array(elements) => {
+                for element in elements {
+                    element.rename_keys(case);
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn convert_camel_to_snake() {
+        assert_eq!(Case::SnakeCase.convert("userIdNumber"), "user_id_number");
+    }
+
+    #[test]
+    fn convert_snake_to_camel() {
+        assert_eq!(Case::CamelCase.convert("user_id_number"), "userIdNumber");
+    }
+
+    #[test]
+    fn convert_kebab_to_pascal() {
+        assert_eq!(Case::PascalCase.convert("user-id-number"), "UserIdNumber");
+    }
+
+    #[test]
+    fn convert_pascal_to_kebab() {
+        assert_eq!(Case::KebabCase.convert("UserIdNumber"), "user-id-number");
+    }
+
+    #[test]
+    fn convert_acronyms_and_digits() {
+        assert_eq!(Case::SnakeCase.convert("HTTPServerV2Url"), "http_server_v2_url");
+    }
+
+    #[test]
+    fn convert_already_converted() {
+        assert_eq!(Case::SnakeCase.convert("already_snake"), "already_snake");
+    }
+
+    #[test]
+    fn convert_keeps_leading_separators() {
+        assert_eq!(Case::CamelCase.convert("__private_field"), "__privateField");
+        assert_eq!(Case::PascalCase.convert("-flag-name"), "-FlagName");
+        let mut item = parse(r#"{"_id": 1, "id": 2}"#).unwrap();
+        item.rename_keys(Case::SnakeCase);
+        assert_eq!(item, parse(r#"{"_id": 1, "id": 2}"#).unwrap());
+    }
+
+    #[test]
+    fn rename_keys_recursive() {
+        let mut item = parse(r#"{"userName": "a", "homeAddress": {"streetName": "b"}, "pastJobs": [{"jobTitle": "c"}]}"#).unwrap();
+        item.rename_keys(Case::SnakeCase);
+        let expected = parse(r#"{"user_name": "a", "home_address": {"street_name": "b"}, "past_jobs": [{"job_title": "c"}]}"#).unwrap();
+        assert_eq!(item, expected);
+    }
+
+    #[test]
+    fn rename_keys_leaves_values_alone() {
+        let mut item = parse(r#"{"some_key": "some_value"}"#).unwrap();
+        item.rename_keys(Case::CamelCase);
+        assert_eq!(item, parse(r#"{"someKey": "some_value"}"#).unwrap());
+    }
+
+    #[test]
+    fn rename_keys_collision_keeps_last_sorted() {
+        let mut item = parse(r#"{"foo_bar": 1, "fooBar": 2}"#).unwrap();
+        item.rename_keys(Case::SnakeCase);
+        assert_eq!(item, parse(r#"{"foo_bar": 1}"#).unwrap());
+    }
+}
@@ -0,0 +1,377 @@
+use std::collections::HashMap;
+
+use crate::j_item::JItem;
+
+/// How deeply arrays, maps and tags may nest in decoded input, so that untrusted bytes
+/// cannot exhaust the stack.
+const MAX_DEPTH: usize = 128;
+
+/// How byte strings, which have no JSON equivalent, are decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BytesPolicy {
+    Reject,
+    /// A lowercase hex string.
+    Hex,
+    /// An array of numbers, one per byte.
+    Array,
+}
+
+/// How tagged values, such as dates or bignums, are decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagPolicy {
+    Reject,
+    /// Drops the tag and keeps the tagged value.
+    Strip,
+    /// Keeps the tag as `{"tag": number, "value": value}`.
+    Wrap,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CborOptions {
+    pub bytes: BytesPolicy,
+    pub tags: TagPolicy,
+}
+
+impl Default for CborOptions {
+    fn default() -> Self {
+        return CborOptions { bytes: BytesPolicy::Hex, tags: TagPolicy::Strip };
+    }
+}
+
+/// Encodes `item` as CBOR. Whole numbers are written as integers and everything else
+/// as a float64. Map keys are written in sorted order.
+pub fn to_cbor(item: &JItem) -> Vec<u8> {
+    let mut out = vec![];
+    write_item(item, &mut out);
+    return out;
+}
+
+pub fn from_cbor(bytes: &[u8]) -> Result<JItem, String> {
+    return from_cbor_with(bytes, &CborOptions::default());
+}
+
+/// Decodes a single CBOR value. Integer and text map keys become strings, as COSE uses
+/// integer labels; other key types are errors. `undefined` decodes as null. Arrays, maps
+/// and tags nested more than 128 deep are an error.
+pub fn from_cbor_with(bytes: &[u8], options: &CborOptions) -> Result<JItem, String> {
+    let mut reader = Reader { bytes, pos: 0, options, depth: 0 };
+    let item = reader.read_item()?;
+    if reader.pos != bytes.len() {
+        return Err(format!("invalid cbor: {} trailing bytes", bytes.len() - reader.pos));
+    }
+    return Ok(item);
+}
+
+fn write_head(major: u8, argument: u64, out: &mut Vec<u8>) {
+    let major = major << 5;
+    match argument {
+        0..=23 => out.push(major | argument as u8),
+        24..=0xff => out.extend_from_slice(&[major | 24, argument as u8]),
+        0x100..=0xffff => {
+            out.push(major | 25);
+            out.extend_from_slice(&(argument as u16).to_be_bytes());
+        },
+        0x1_0000..=0xffff_ffff => {
+            out.push(major | 26);
+            out.extend_from_slice(&(argument as u32).to_be_bytes());
+        },
+        _ => {
+            out.push(major | 27);
+            out.extend_from_slice(&argument.to_be_bytes());
+        },
+    }
+}
+
+fn write_text(s: &str, out: &mut Vec<u8>) {
+    write_head(3, s.len() as u64, out);
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn write_item(item: &JItem, out: &mut Vec<u8>) {
+    match item {
+        JItem::RawNumber(_) => write_item(&JItem::Number(item.number_value().unwrap()), out),
+        JItem::Object(hmap) => {
+            write_head(5, hmap.len() as u64, out);
+            let mut members: Vec<_> = hmap.iter().collect();
+            members.sort_by_key(|(key, _)| *key);
+            for (key, value) in members {
+                write_text(key, out);
+                write_item(value, out);
+            }
+        },
+        JItem::String(s) => write_text(s, out),
+        JItem::Array(elements) => {
+            write_head(4, elements.len() as u64, out);
+            for element in elements {
+                write_item(element, out);
+            }
+        },
+        JItem::Number(x) if x.fract() == 0.0 && *x >= 0.0 && *x < u64::MAX as f64 && !(*x == 0.0 && x.is_sign_negative()) => {
+            write_head(0, *x as u64, out);
+        },
+        JItem::Number(x) if x.fract() == 0.0 && *x < 0.0 && *x >= -(u64::MAX as f64) => write_head(1, (-1.0 - x) as u64, out),
+        JItem::Number(x) => {
+            out.push(0xfb);
+            out.extend_from_slice(&x.to_be_bytes());
+        },
+        JItem::Bool(b) => out.push(if *b { 0xf5 } else { 0xf4 }),
+        JItem::Null => out.push(0xf6),
+    }
+}
+
+fn half_to_f64(half: u16) -> f64 {
+    let exponent = (half >> 10) & 0x1f;
+    let mantissa = (half & 0x3ff) as f64;
+    let magnitude = match exponent {
+        0 => mantissa * 2f64.powi(-24),
+        31 if mantissa == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        _ => (mantissa + 1024.0) * 2f64.powi(exponent as i32 - 25),
+    };
+    return if half & 0x8000 != 0 { -magnitude } else { magnitude };
+}
+
+const BREAK: u8 = 0xff;
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    options: &'a CborOptions,
+    /// Arrays, maps and tags open around the current position.
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take_slice(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let Some(slice) = self.pos.checked_add(len).and_then(|end| self.bytes.get(self.pos..end)) else {
+            return Err("invalid cbor: unexpected end of input".to_string());
+        };
+        self.pos += len;
+        return Ok(slice);
+    }
+
+    fn peek_break(&mut self) -> Result<bool, String> {
+        let Some(byte) = self.bytes.get(self.pos) else {
+            return Err("invalid cbor: unexpected end of input".to_string());
+        };
+        if *byte == BREAK {
+            self.pos += 1;
+            return Ok(true);
+        }
+        return Ok(false);
+    }
+
+    // reads an initial byte, returning the major type and its argument, or None for indefinite lengths
+    fn read_head(&mut self) -> Result<(u8, u8, Option<u64>), String> {
+        let initial = self.take_slice(1)?[0];
+        let info = initial & 0x1f;
+        let argument = match info {
+            0..=23 => Some(info as u64),
+            24 => Some(self.take_slice(1)?[0] as u64),
+            25 => Some(u16::from_be_bytes(self.take_slice(2)?.try_into().unwrap()) as u64),
+            26 => Some(u32::from_be_bytes(self.take_slice(4)?.try_into().unwrap()) as u64),
+            27 => Some(u64::from_be_bytes(self.take_slice(8)?.try_into().unwrap())),
+            31 => None,
+            _ => return Err(format!("invalid cbor: reserved additional information {}", info)),
+        };
+        return Ok((initial >> 5, info, argument));
+    }
+
+    // reads the content of a byte or text string, joining indefinite-length chunks
+    fn read_string_bytes(&mut self, major: u8, len: Option<u64>) -> Result<Vec<u8>, String> {
+        let Some(len) = len else {
+            let mut joined = vec![];
+            while !self.peek_break()? {
+                let (chunk_major, _, chunk_len) = self.read_head()?;
+                if chunk_major != major || chunk_len.is_none() {
+                    return Err("invalid cbor: malformed indefinite-length string".to_string());
+                }
+                joined.extend(self.read_string_bytes(major, chunk_len)?);
+            }
+            return Ok(joined);
+        };
+        let len = usize::try_from(len).map_err(|_| "invalid cbor: string too long".to_string())?;
+        return Ok(self.take_slice(len)?.to_vec());
+    }
+
+    fn read_key(&mut self) -> Result<String, String> {
+        return match self.read_item()? {
+            JItem::String(s) => Ok(s),
+            JItem::Number(x) if x.fract() == 0.0 => Ok(x.to_string()),
+            _ => Err("invalid cbor: map keys must be text or integers".to_string()),
+        };
+    }
+
+    fn read_item(&mut self) -> Result<JItem, String> {
+        let (major, info, argument) = self.read_head()?;
+        let nests = matches!(major, 4..=6);
+        if nests {
+            if self.depth == MAX_DEPTH {
+                return Err(format!("invalid cbor: nested more than {} deep", MAX_DEPTH));
+            }
+            self.depth += 1;
+        }
+        let item = match (major, argument) {
+            (0, Some(n)) => Ok(JItem::Number(n as f64)),
+            (1, Some(n)) => Ok(JItem::Number(-1.0 - n as f64)),
+            (2, len) => {
+                let bytes = self.read_string_bytes(2, len)?;
+                match self.options.bytes {
+                    BytesPolicy::Reject => Err("invalid cbor: byte strings are not allowed".to_string()),
+                    BytesPolicy::Hex => Ok(JItem::String(bytes.iter().map(|b| format!("{:02x}", b)).collect())),
+                    BytesPolicy::Array => Ok(JItem::Array(bytes.into_iter().map(|b| JItem::Number(b as f64)).collect())),
+                }
+            },
+            (3, len) => {
+                let bytes = self.read_string_bytes(3, len)?;
+                String::from_utf8(bytes).map(JItem::String).map_err(|_| "invalid cbor: text is not valid utf-8".to_string())
+            },
+            (4, len) => {
+                let mut elements = vec![];
+                match len {
+                    Some(len) => {
+                        for _ in 0..len {
+                            elements.push(self.read_item()?);
+                        }
+                    },
+                    None => {
+                        while !self.peek_break()? {
+                            elements.push(self.read_item()?);
+                        }
+                    },
+                }
+                Ok(JItem::Array(elements))
+            },
+            (5, len) => {
+                let mut hmap = HashMap::new();
+                let mut remaining = len;
+                loop {
+                    match &mut remaining {
+                        Some(0) => break,
+                        Some(n) => *n -= 1,
+                        None if self.peek_break()? => break,
+                        None => {},
+                    }
+                    let key = self.read_key()?;
+                    let value = self.read_item()?;
+                    if hmap.contains_key(&key) {
+                        return Err(format!("invalid cbor: duplicate key '{}'", key));
+                    }
+                    hmap.insert(key, value);
+                }
+                Ok(JItem::Object(hmap))
+            },
+            (6, Some(tag)) => {
+                let value = self.read_item()?;
+                match self.options.tags {
+                    TagPolicy::Reject => Err(format!("invalid cbor: tag {} is not allowed", tag)),
+                    TagPolicy::Strip => Ok(value),
+                    TagPolicy::Wrap => {
+                        let mut hmap = HashMap::new();
+                        hmap.insert("tag".to_string(), JItem::Number(tag as f64));
+                        hmap.insert("value".to_string(), value);
+                        Ok(JItem::Object(hmap))
+                    },
+                }
+            },
+            (7, argument) => match (info, argument) {
+                (20, _) => Ok(JItem::Bool(false)),
+                (21, _) => Ok(JItem::Bool(true)),
+                (22, _) | (23, _) => Ok(JItem::Null),
+                (25, Some(bits)) => Ok(JItem::Number(half_to_f64(bits as u16))),
+                (26, Some(bits)) => Ok(JItem::Number(f32::from_bits(bits as u32) as f64)),
+                (27, Some(bits)) => Ok(JItem::Number(f64::from_bits(bits))),
+                (31, _) => Err("invalid cbor: unexpected break".to_string()),
+                _ => Err(format!("invalid cbor: unsupported simple value {}", argument.unwrap_or(info as u64))),
+            },
+            _ => Err(format!("invalid cbor: indefinite length is not allowed for major type {}", major)),
+        };
+        if nests {
+            self.depth -= 1;
+        }
+        return item;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    fn hex(s: &str) -> Vec<u8> {
+        return (0..s.len()).step_by(2).map(|n| u8::from_str_radix(&s[n..n + 2], 16).unwrap()).collect();
+    }
+
+    #[test]
+    fn to_cbor_encodings() {
+        assert_eq!(to_cbor(&JItem::Number(0.0)), hex("00"));
+        assert_eq!(to_cbor(&JItem::Number(24.0)), hex("1818"));
+        assert_eq!(to_cbor(&JItem::Number(1000000.0)), hex("1a000f4240"));
+        assert_eq!(to_cbor(&JItem::Number(-1000.0)), hex("3903e7"));
+        assert_eq!(to_cbor(&JItem::Number(1.1)), hex("fb3ff199999999999a"));
+        assert_eq!(to_cbor(&JItem::Null), hex("f6"));
+        assert_eq!(to_cbor(&parse(r#"{"b": [2, 3], "a": 1}"#).unwrap()), hex("a26161016162820203"));
+    }
+
+    // examples from RFC 8949 appendix A
+    #[test]
+    fn from_cbor_rfc_examples() {
+        assert_eq!(from_cbor(&hex("3bffffffffffffffff")), Ok(JItem::Number(-18446744073709551616.0)));
+        assert_eq!(from_cbor(&hex("f93c00")), Ok(JItem::Number(1.0)));
+        assert_eq!(from_cbor(&hex("f90001")), Ok(JItem::Number(5.960464477539063e-8)));
+        assert_eq!(from_cbor(&hex("fa47c35000")), Ok(JItem::Number(100000.0)));
+        assert_eq!(from_cbor(&hex("f97c00")), Ok(JItem::Number(f64::INFINITY)));
+        assert_eq!(from_cbor(&hex("f7")), Ok(JItem::Null));
+        assert_eq!(from_cbor(&hex("6449455446")), Ok(JItem::String("IETF".to_string())));
+        assert_eq!(from_cbor(&hex("7f657374726561646d696e67ff")), Ok(JItem::String("streaming".to_string())));
+        assert_eq!(from_cbor(&hex("9f018202039f0405ffff")), Ok(parse("[1, [2, 3], [4, 5]]").unwrap()));
+        assert_eq!(from_cbor(&hex("bf61610161629f0203ffff")), Ok(parse(r#"{"a": 1, "b": [2, 3]}"#).unwrap()));
+        assert_eq!(from_cbor(&hex("a201020304")), Ok(parse(r#"{"1": 2, "3": 4}"#).unwrap()));
+    }
+
+    #[test]
+    fn cbor_round_trip() {
+        let item = parse(r#"{"name": "x", "values": [0, -1, -500, 70000, 2.5, true, null, "y"], "nested": {"empty": []}}"#).unwrap();
+        assert_eq!(from_cbor(&to_cbor(&item)), Ok(item));
+    }
+
+    #[test]
+    fn from_cbor_byte_string_policy() {
+        let bytes = hex("4401020304");
+        assert_eq!(from_cbor(&bytes), Ok(JItem::String("01020304".to_string())));
+        let array = CborOptions { bytes: BytesPolicy::Array, ..CborOptions::default() };
+        assert_eq!(from_cbor_with(&bytes, &array), Ok(parse("[1, 2, 3, 4]").unwrap()));
+        let reject = CborOptions { bytes: BytesPolicy::Reject, ..CborOptions::default() };
+        assert!(from_cbor_with(&bytes, &reject).is_err());
+    }
+
+    #[test]
+    fn from_cbor_tag_policy() {
+        let date = hex("c074323031332d30332d32315432303a30343a30305a");
+        assert_eq!(from_cbor(&date), Ok(JItem::String("2013-03-21T20:04:00Z".to_string())));
+        let wrap = CborOptions { tags: TagPolicy::Wrap, ..CborOptions::default() };
+        assert_eq!(from_cbor_with(&date, &wrap), Ok(parse(r#"{"tag": 0, "value": "2013-03-21T20:04:00Z"}"#).unwrap()));
+        let reject = CborOptions { tags: TagPolicy::Reject, ..CborOptions::default() };
+        assert!(from_cbor_with(&date, &reject).is_err());
+    }
+
+    #[test]
+    fn from_cbor_errors() {
+        assert!(from_cbor(&[]).is_err());
+        assert!(from_cbor(&hex("8201")).is_err());
+        assert!(from_cbor(&hex("0101")).is_err());
+        assert!(from_cbor(&hex("ff")).is_err());
+        assert!(from_cbor(&hex("1c")).is_err());
+        assert!(from_cbor(&hex("a1f400")).is_err());
+        assert!(from_cbor(&hex("1f")).is_err());
+    }
+
+    #[test]
+    fn from_cbor_depth_limit() {
+        let nested = |head: u8, depth: usize| [vec![head; depth], vec![0xf6]].concat();
+        assert!(from_cbor(&nested(0x81, MAX_DEPTH)).is_ok());
+        assert_eq!(from_cbor(&nested(0x81, MAX_DEPTH + 1)), Err("invalid cbor: nested more than 128 deep".to_string()));
+        assert!(from_cbor(&nested(0x9f, 100_000)).is_err());
+        assert!(from_cbor(&nested(0xc6, 100_000)).is_err());
+    }
+}
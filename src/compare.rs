@@ -0,0 +1,189 @@
+use alloc::{string::String, vec, vec::Vec};
+
+use crate::j_item::JItem;
+
+/// How `JItem::approx_eq_with` and `patch::diff_with` compare values.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompareOptions {
+    /// Numbers match if they differ by at most this much.
+    pub epsilon: f64,
+    pub array_order: ArrayOrder,
+}
+
+/// How the elements of two arrays are paired up.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ArrayOrder {
+    /// Each element is paired with the one at the same index.
+    #[default]
+    Ordered,
+    /// Order does not matter: arrays are compared as multisets, pairing elements so that
+    /// as many as possible match.
+    Unordered,
+    /// Order does not matter, and object elements with this member are paired by its
+    /// value, such as records by their `"id"`. Paired objects are then compared member by
+    /// member. Elements without the member are paired as in `Unordered`.
+    KeyedBy(String),
+}
+
+impl JItem {
+    /// Structural equality where two numbers match if they differ by at most `epsilon`.
+    /// Objects are compared by key regardless of member order; NaN matches NaN.
+    pub fn approx_eq(&self, other: &JItem, epsilon: f64) -> bool {
+        return self.approx_eq_with(other, &CompareOptions { epsilon, array_order: ArrayOrder::Ordered });
+    }
+
+    /// Like `approx_eq`, with arrays compared as `options.array_order` says.
+    pub fn approx_eq_with(&self, other: &JItem, options: &CompareOptions) -> bool {
+        return match (self, other) {
+            (JItem::Number(_) | JItem::RawNumber(_), JItem::Number(_) | JItem::RawNumber(_)) => {
+                numbers_approx_eq(self.number_value().unwrap(), other.number_value().unwrap(), options.epsilon)
+            },
+            (JItem::Array(a), JItem::Array(b)) => {
+                a.len() == b.len() && pair_elements(a, b, options).iter().enumerate().all(|(n, pair)| pair.is_some_and(|m| a[n].approx_eq_with(&b[m], options)))
+            },
+            (JItem::Object(a), JItem::Object(b)) => {
+                a.len() == b.len() && a.iter().all(|(key, x)| b.get(key).is_some_and(|y| x.approx_eq_with(y, options)))
+            },
+            _ => self == other,
+        };
+    }
+}
+
+/// For each element of `a`, the index of the element of `b` it is paired with, if any.
+/// No element of `b` is paired twice.
+pub(crate) fn pair_elements(a: &[JItem], b: &[JItem], options: &CompareOptions) -> Vec<Option<usize>> {
+    let key = match &options.array_order {
+        ArrayOrder::Ordered => return (0..a.len()).map(|n| (n < b.len()).then_some(n)).collect(),
+        ArrayOrder::Unordered => None,
+        ArrayOrder::KeyedBy(key) => Some(key.as_str()),
+    };
+    let key_of = |item: &'_ JItem| match (item, key) {
+        (JItem::Object(hmap), Some(key)) => hmap.get(key).cloned(),
+        _ => None,
+    };
+    let candidates: Vec<Vec<usize>> = a.iter().map(|x| {
+        let value = key_of(x);
+        return (0..b.len()).filter(|&m| match (&value, key_of(&b[m])) {
+            (Some(value), Some(other)) => other.approx_eq_with(value, options),
+            (None, None) => x.approx_eq_with(&b[m], options),
+            _ => false,
+        }).collect();
+    }).collect();
+    // greedy pairing can strand an element whose only match was taken by an earlier one
+    // with other choices, so pairs are moved along augmenting paths as needed
+    let mut owner: Vec<Option<usize>> = vec![None; b.len()];
+    for n in 0..a.len() {
+        augment(n, &candidates, &mut owner);
+    }
+    let mut pairs = vec![None; a.len()];
+    for (m, n) in owner.iter().enumerate() {
+        if let Some(n) = n {
+            pairs[*n] = Some(m);
+        }
+    }
+    return pairs;
+}
+
+/// Pairs `start` with an element of `b`, moving earlier pairs to other candidates if that
+/// frees one up. Returns false, changing nothing, if no such rearrangement exists.
+fn augment(start: usize, candidates: &[Vec<usize>], owner: &mut [Option<usize>]) -> bool {
+    let mut visited = vec![false; owner.len()];
+    // the rows on the current path, each with how many of its candidates were tried,
+    // and the columns that lead from each row to the next
+    let mut rows = vec![(start, 0)];
+    let mut columns: Vec<usize> = vec![];
+    while let Some((row, tried)) = rows.last_mut() {
+        let Some(skipped) = candidates[*row][*tried..].iter().position(|&m| !visited[m]) else {
+            rows.pop();
+            columns.pop();
+            continue;
+        };
+        let m = candidates[*row][*tried + skipped];
+        *tried += skipped + 1;
+        visited[m] = true;
+        columns.push(m);
+        match owner[m] {
+            Some(next) => rows.push((next, 0)),
+            None => {
+                for (&(row, _), &m) in rows.iter().zip(&columns) {
+                    owner[m] = Some(row);
+                }
+                return true;
+            },
+        }
+    }
+    return false;
+}
+
+fn numbers_approx_eq(a: f64, b: f64, epsilon: f64) -> bool {
+    if a == b || (a.is_nan() && b.is_nan()) {
+        // also covers equal infinities, whose difference would be NaN
+        return true;
+    }
+    return (a - b).abs() <= epsilon;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn approx_eq_within_epsilon() {
+        let a = parse(r#"{"x": 0.30000000000000004, "y": [1.0000001]}"#).unwrap();
+        let b = parse(r#"{"x": 0.3, "y": [1]}"#).unwrap();
+        assert!(a.approx_eq(&b, 1e-6));
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn approx_eq_outside_epsilon() {
+        assert!(!JItem::Number(1.).approx_eq(&JItem::Number(1.1), 0.01));
+    }
+
+    #[test]
+    fn approx_eq_infinities() {
+        assert!(JItem::Number(f64::INFINITY).approx_eq(&JItem::Number(f64::INFINITY), 0.1));
+        assert!(!JItem::Number(f64::INFINITY).approx_eq(&JItem::Number(f64::NEG_INFINITY), 0.1));
+    }
+
+    #[test]
+    fn approx_eq_structure_mismatch() {
+        let a = parse(r#"{"x": [1, 2]}"#).unwrap();
+        let b = parse(r#"{"x": [1, 2, 3]}"#).unwrap();
+        let c = parse(r#"{"y": [1, 2]}"#).unwrap();
+        assert!(!a.approx_eq(&b, 1.));
+        assert!(!a.approx_eq(&c, 1.));
+    }
+
+    #[test]
+    fn approx_eq_non_numbers_exact() {
+        assert!(!JItem::String("a".to_string()).approx_eq(&JItem::String("b".to_string()), 100.));
+        assert!(!JItem::Number(0.).approx_eq(&JItem::Null, 100.));
+    }
+
+    #[test]
+    fn approx_eq_unordered_arrays() {
+        let options = CompareOptions { epsilon: 0.01, array_order: ArrayOrder::Unordered };
+        let a = parse(r#"[1, [2, 3], 1, {"a": [4, 5]}]"#).unwrap();
+        assert!(a.approx_eq_with(&parse(r#"[{"a": [5, 4]}, 1.001, [3, 2], 1]"#).unwrap(), &options));
+        assert!(!a.approx_eq_with(&parse(r#"[{"a": [5, 4]}, [3, 2], 1, 2]"#).unwrap(), &options));
+        assert!(!a.approx_eq(&parse(r#"[1, 1, [2, 3], {"a": [4, 5]}]"#).unwrap(), 0.01));
+
+        // 1.05 matches both 1.0 and 1.1, but only 1.1 matches 1.15
+        let options = CompareOptions { epsilon: 0.06, array_order: ArrayOrder::Unordered };
+        let (a, b) = (parse("[1.0, 1.1]").unwrap(), parse("[1.05, 1.15]").unwrap());
+        assert!(a.approx_eq_with(&b, &options));
+        assert!(b.approx_eq_with(&a, &options));
+        let (a, b) = (parse("[1.1, 1.0]").unwrap(), parse("[1.15, 1.05]").unwrap());
+        assert!(a.approx_eq_with(&b, &options) && b.approx_eq_with(&a, &options));
+    }
+
+    #[test]
+    fn approx_eq_keyed_arrays() {
+        let options = CompareOptions { epsilon: 0.0, array_order: ArrayOrder::KeyedBy("id".to_string()) };
+        let a = parse(r#"[{"id": 1, "v": "x"}, {"id": 2, "v": "y"}, "loose"]"#).unwrap();
+        assert!(a.approx_eq_with(&parse(r#"["loose", {"v": "y", "id": 2}, {"id": 1, "v": "x"}]"#).unwrap(), &options));
+        assert!(!a.approx_eq_with(&parse(r#"["loose", {"id": 2, "v": "x"}, {"id": 1, "v": "y"}]"#).unwrap(), &options));
+    }
+}
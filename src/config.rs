@@ -0,0 +1,177 @@
+//! Layered configuration: several sources, such as a defaults file, a per-environment
+//! file and environment variables, parsed and merged in order into one document.
+//!
+//! Merging works key by key. Where both the value so far and the next source have an
+//! object, their members are merged the same way; anything else from the next source,
+//! arrays included, replaces what was there. Every value a source sets is recorded with
+//! the source's name, so `Config::source_of` can say which source a setting came from.
+//! The merge and the record are those of `provenance::Tracked`.
+
+use std::{collections::BTreeMap, path::PathBuf};
+
+use crate::{
+    file::parse_file,
+    j_item::JItem,
+    parse,
+    path::{parse_path, PathSegment},
+    provenance::{Origin, Tracked},
+    HashMap,
+};
+
+/// One layer of configuration. Later sources override earlier ones.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    /// A JSON file, named by its path.
+    File(PathBuf),
+    /// JSON text, such as an embedded default.
+    Text { name: String, text: String },
+    /// Environment variables named `prefix` and then a path with `__` between keys, such
+    /// as `APP_SERVER__PORT` for `server.port` with the prefix `APP_`. Keys are
+    /// lowercased, and a key of only digits indexes an array. Values are read as JSON
+    /// where they parse as it, and as strings otherwise, so `8080` is a number but
+    /// `localhost` a string. Each variable is named `$` and its name.
+    Env { prefix: String },
+    /// Values for paths like `servers[0].port`, as `JItem::set_path` takes them, such as
+    /// command line overrides.
+    Overrides { name: String, values: Vec<(String, JItem)> },
+}
+
+/// The merged configuration, and which source set each value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    tracked: Tracked,
+}
+
+impl Config {
+    pub fn value(&self) -> &JItem {
+        return self.tracked.value();
+    }
+
+    pub fn into_value(self) -> JItem {
+        return self.tracked.into_value();
+    }
+
+    /// The merged document with the origin of each value, which also says where in a
+    /// file source the value was.
+    pub fn tracked(&self) -> &Tracked {
+        return &self.tracked;
+    }
+
+    /// The name of the source that set the value at `pointer`, or the container it is
+    /// in, if there is a value there.
+    pub fn source_of(&self, pointer: &str) -> Option<&str> {
+        return self.tracked.source_of(pointer);
+    }
+
+    /// Every recorded setting, as a JSON Pointer and the name of its source, in pointer
+    /// order. Values inside one a source set as a whole are not listed separately.
+    pub fn provenance(&self) -> impl Iterator<Item = (String, &str)> {
+        return self.tracked.origins().into_iter().map(|(pointer, origin)| (pointer, origin.source.as_str()));
+    }
+
+    /// Sets `value` at `segments` on behalf of `source`, forgetting who set anything it
+    /// replaces.
+    fn set(&mut self, segments: &[PathSegment], value: JItem, source: &str) -> Result<(), String> {
+        return self.tracked.set_segments(segments, value, Origin { source: source.to_string(), pointer: String::new() });
+    }
+}
+
+/// Parses each source in turn and merges it over those before. File and text sources
+/// must hold an object.
+pub fn load(sources: &[Source]) -> Result<Config, String> {
+    let mut config = Config { tracked: Tracked::unsourced(JItem::Object(HashMap::new())) };
+    for source in sources {
+        match source {
+            Source::File(path) => {
+                let name = path.display().to_string();
+                let layer = parse_file(path).map_err(|e| format!("failed to load config '{}': {}", name, e))?;
+                merge_layer(&mut config, layer, &name)?;
+            },
+            Source::Text { name, text } => {
+                let layer = parse(text).map_err(|e| format!("failed to load config '{}': {}", name, e))?;
+                merge_layer(&mut config, layer, name)?;
+            },
+            Source::Env { prefix } => apply_vars(&mut config, prefix, std::env::vars())?,
+            Source::Overrides { name, values } => {
+                for (path, value) in values {
+                    config.set(&parse_path(path, ".")?, value.clone(), name).map_err(|e| format!("failed to apply '{}' from '{}': {}", path, name, e))?;
+                }
+            },
+        }
+    }
+    return Ok(config);
+}
+
+fn merge_layer(config: &mut Config, layer: JItem, name: &str) -> Result<(), String> {
+    if !matches!(layer, JItem::Object(_)) {
+        return Err(format!("config '{}' is not an object", name));
+    }
+    config.tracked.merge(layer, name);
+    return Ok(());
+}
+
+fn apply_vars(config: &mut Config, prefix: &str, vars: impl IntoIterator<Item = (String, String)>) -> Result<(), String> {
+    // sorted so that a variable setting a container comes before those setting inside it
+    let vars: BTreeMap<String, String> = vars.into_iter().filter(|(name, _)| name.starts_with(prefix) && name.len() > prefix.len()).collect();
+    for (name, text) in vars {
+        let segments: Vec<PathSegment> = name[prefix.len()..]
+            .split("__")
+            .map(|key| match key.parse() {
+                Ok(index) => PathSegment::Index(index),
+                Err(_) => PathSegment::Key(key.to_lowercase()),
+            })
+            .collect();
+        let value = parse(&text).unwrap_or(JItem::String(text));
+        config.set(&segments, value, &format!("${}", name)).map_err(|e| format!("failed to apply ${}: {}", name, e))?;
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn text(name: &str, text: &str) -> Source {
+        return Source::Text { name: name.to_string(), text: text.to_string() };
+    }
+
+    #[test]
+    fn layered_sources() {
+        let sources = [
+            text("defaults", r#"{"server": {"host": "localhost", "port": 80, "tls": {"on": false}}, "hosts": ["a", "b"], "debug": false}"#),
+            text("prod", r#"{"server": {"host": "example.com", "tls": null}, "hosts": ["c"]}"#),
+            Source::Overrides { name: "cli".to_string(), values: vec![("server.tls.on".to_string(), JItem::Bool(true)), ("hosts[0]".to_string(), JItem::String("d".to_string()))] },
+        ];
+        let config = load(&sources).unwrap();
+        assert_eq!(config.value(), &parse(r#"{"server": {"host": "example.com", "port": 80, "tls": {"on": true}}, "hosts": ["d"], "debug": false}"#).unwrap());
+        assert_eq!(config.source_of("/server/host"), Some("prod"));
+        assert_eq!(config.source_of("/server/port"), Some("defaults"));
+        assert_eq!(config.source_of("/server/tls/on"), Some("cli"));
+        assert_eq!(config.source_of("/hosts/0"), Some("cli"));
+        assert_eq!(config.source_of("/hosts"), Some("prod"));
+        assert_eq!(config.source_of("/missing"), None);
+        assert_eq!(config.provenance().count(), 7);
+        assert_eq!(config.tracked().origin_of("/server/tls/on"), Some(Origin { source: "cli".to_string(), pointer: String::new() }));
+        assert_eq!(config.tracked().origin_of("/server/port"), Some(Origin { source: "defaults".to_string(), pointer: "/server/port".to_string() }));
+    }
+
+    #[test]
+    fn environment_variables() {
+        let mut config = load(&[text("defaults", r#"{"server": {"port": 80, "name": "api"}, "workers": [{"n": 1}]}"#)]).unwrap();
+        let vars = [("APP_SERVER__PORT", "8080"), ("APP_SERVER__NAME", "web"), ("APP_WORKERS__0__N", "[2]"), ("OTHER", "1"), ("APP_", "x")];
+        apply_vars(&mut config, "APP_", vars.map(|(name, value)| (name.to_string(), value.to_string()))).unwrap();
+        assert_eq!(config.value(), &parse(r#"{"server": {"port": 8080, "name": "web"}, "workers": [{"n": [2]}]}"#).unwrap());
+        assert_eq!(config.source_of("/server/port"), Some("$APP_SERVER__PORT"));
+        assert_eq!(config.source_of("/workers/0/n/0"), Some("$APP_WORKERS__0__N"));
+        assert_eq!(apply_vars(&mut config, "APP_", [("APP_SERVER__PORT__X".to_string(), "1".to_string())]), Err("failed to apply $APP_SERVER__PORT__X: cannot set key 'x' on a non-object value".to_string()));
+        let huge = [("APP_X__18446744073709551615".to_string(), "1".to_string())];
+        assert_eq!(apply_vars(&mut config, "APP_", huge), Err("failed to apply $APP_X__18446744073709551615: index 18446744073709551615 is too far past the end of an array of length 0".to_string()));
+    }
+
+    #[test]
+    fn bad_sources() {
+        assert_eq!(load(&[text("list", "[1]")]), Err("config 'list' is not an object".to_string()));
+        assert!(load(&[text("broken", "{")]).unwrap_err().starts_with("failed to load config 'broken': "));
+        assert!(load(&[Source::File("/definitely/not/here.json".into())]).unwrap_err().starts_with("failed to load config '/definitely/not/here.json': "));
+    }
+}
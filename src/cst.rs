@@ -0,0 +1,665 @@
+use std::{collections::HashSet, fmt, ops::Range};
+
+use crate::{
+    j_item::{JItem, escape_string},
+    pointer::{array_index, parse_pointer},
+};
+
+/// A parsed document that keeps its exact source text, so whitespace, comments, key
+/// order and number spelling all survive. Edits splice new text over just the affected
+/// range, leaving the rest of the file byte for byte as it was.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CstDocument {
+    text: String,
+    root: CstNode,
+    lenient: bool,
+}
+
+/// A value in the source, with the byte range its text occupies.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CstNode {
+    pub span: Range<usize>,
+    pub kind: CstKind,
+}
+
+/// Strings and numbers hold their decoded values; the exact source text is available
+/// through `CstDocument::source_of`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CstKind {
+    Object(Vec<CstMember>),
+    Array(Vec<CstNode>),
+    String(String),
+    Number(f64),
+    Bool(bool),
+    Null,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CstMember {
+    pub key: String,
+    pub key_span: Range<usize>,
+    pub value: CstNode,
+}
+
+impl CstNode {
+    pub fn to_item(&self) -> JItem {
+        return match &self.kind {
+            CstKind::Object(members) => {
+                JItem::Object(members.iter().map(|member| (member.key.to_string(), member.value.to_item())).collect())
+            },
+            CstKind::Array(elements) => JItem::Array(elements.iter().map(CstNode::to_item).collect()),
+            CstKind::String(s) => JItem::String(s.to_string()),
+            CstKind::Number(x) => JItem::Number(*x),
+            CstKind::Bool(b) => JItem::Bool(*b),
+            CstKind::Null => JItem::Null,
+        };
+    }
+
+    fn children(&self) -> Vec<&CstNode> {
+        return match &self.kind {
+            CstKind::Object(members) => members.iter().map(|member| &member.value).collect(),
+            CstKind::Array(elements) => elements.iter().collect(),
+            _ => vec![],
+        };
+    }
+
+    fn descendant_mut(&mut self, path: &[usize]) -> &mut CstNode {
+        let Some((first, rest)) = path.split_first() else {
+            return self;
+        };
+        let child = match &mut self.kind {
+            CstKind::Object(members) => &mut members[*first].value,
+            CstKind::Array(elements) => &mut elements[*first],
+            _ => unreachable!("paths only pass through containers"),
+        };
+        return child.descendant_mut(rest);
+    }
+
+    // moves every position at or after `from` by `delta` bytes
+    fn shift(&mut self, from: usize, delta: isize) {
+        let moved = |position: &mut usize| {
+            if *position >= from {
+                *position = position.wrapping_add_signed(delta);
+            }
+        };
+        moved(&mut self.span.start);
+        moved(&mut self.span.end);
+        match &mut self.kind {
+            CstKind::Object(members) => {
+                for member in members {
+                    moved(&mut member.key_span.start);
+                    moved(&mut member.key_span.end);
+                    member.value.shift(from, delta);
+                }
+            },
+            CstKind::Array(elements) => {
+                for element in elements {
+                    element.shift(from, delta);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    // the entries of a container, each as (start of key or value, end of value)
+    fn entry_spans(&self) -> Vec<Range<usize>> {
+        return match &self.kind {
+            CstKind::Object(members) => members.iter().map(|member| member.key_span.start..member.value.span.end).collect(),
+            CstKind::Array(elements) => elements.iter().map(|element| element.span.clone()).collect(),
+            _ => vec![],
+        };
+    }
+}
+
+impl CstDocument {
+    /// Parses strict JSON.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        return CstDocument::parse_with(text, false);
+    }
+
+    /// Parses JSON that may also contain `//` and `/* */` comments and trailing commas,
+    /// as commonly found in hand-maintained configuration files.
+    pub fn parse_lenient(text: &str) -> Result<Self, String> {
+        return CstDocument::parse_with(text, true);
+    }
+
+    fn parse_with(text: &str, lenient: bool) -> Result<Self, String> {
+        let root = parse_root(text, lenient)?;
+        return Ok(CstDocument { text: text.to_string(), root, lenient });
+    }
+
+    pub fn text(&self) -> &str {
+        return &self.text;
+    }
+
+    pub fn root(&self) -> &CstNode {
+        return &self.root;
+    }
+
+    pub fn to_item(&self) -> JItem {
+        return self.root.to_item();
+    }
+
+    /// The exact source text of `node`, such as `1.50` for a number.
+    pub fn source_of(&self, node: &CstNode) -> &str {
+        return &self.text[node.span.clone()];
+    }
+
+    /// Looks up the node referenced by an RFC 6901 JSON Pointer.
+    pub fn node(&self, pointer: &str) -> Option<&CstNode> {
+        return resolve_node(&self.root, &parse_pointer(pointer).ok()?);
+    }
+
+    /// Replaces the text of the value at `pointer` with `value` written compactly.
+    pub fn replace(&mut self, pointer: &str, value: &JItem) -> Result<(), String> {
+        let Some(node) = self.node(pointer) else {
+            return Err(format!("json pointer '{}' does not exist in the document", pointer));
+        };
+        let span = node.span.clone();
+        return self.splice(span, &value.to_string());
+    }
+
+    /// Adds a member to the object at `pointer`, after its last member and with the
+    /// same indentation. Fails if the key already exists.
+    pub fn insert(&mut self, pointer: &str, key: &str, value: &JItem) -> Result<(), String> {
+        let Some(node) = self.node(pointer) else {
+            return Err(format!("json pointer '{}' does not exist in the document", pointer));
+        };
+        let CstKind::Object(members) = &node.kind else {
+            return Err(format!("json pointer '{}' does not refer to an object", pointer));
+        };
+        if members.iter().any(|member| member.key == key) {
+            return Err(format!("key '{}' already exists at json pointer '{}'", key, pointer));
+        }
+        let entry = format!("\"{}\": {}", escape_string(key), value);
+        return self.append_entry(node.clone(), &entry);
+    }
+
+    /// Appends an element to the array at `pointer`, matching the existing layout.
+    pub fn push(&mut self, pointer: &str, value: &JItem) -> Result<(), String> {
+        let Some(node) = self.node(pointer) else {
+            return Err(format!("json pointer '{}' does not exist in the document", pointer));
+        };
+        let CstKind::Array(_) = &node.kind else {
+            return Err(format!("json pointer '{}' does not refer to an array", pointer));
+        };
+        return self.append_entry(node.clone(), &value.to_string());
+    }
+
+    /// Removes the member or element at `pointer` along with its separating comma.
+    pub fn remove(&mut self, pointer: &str) -> Result<(), String> {
+        let mut tokens = parse_pointer(pointer)?;
+        let Some(last) = tokens.pop() else {
+            return Err("the root of a document cannot be removed".to_string());
+        };
+        let parent = resolve_node(&self.root, &tokens);
+        let index = match parent.map(|parent| &parent.kind) {
+            Some(CstKind::Object(members)) => members.iter().position(|member| member.key == last),
+            Some(CstKind::Array(elements)) => array_index(&last).filter(|index| *index < elements.len()),
+            _ => None,
+        };
+        let (Some(parent), Some(index)) = (parent, index) else {
+            return Err(format!("json pointer '{}' does not exist in the document", pointer));
+        };
+
+        let entries = parent.entry_spans();
+        let range = if index + 1 < entries.len() {
+            entries[index].start..entries[index + 1].start
+        }
+        else if index > 0 {
+            entries[index - 1].end..entries[index].end
+        }
+        else {
+            let end = entries[index].end;
+            entries[index].start..self.trailing_comma(end).map_or(end, |comma| comma + 1)
+        };
+        return self.splice(range, "");
+    }
+
+    fn append_entry(&mut self, container: CstNode, entry: &str) -> Result<(), String> {
+        let entries = container.entry_spans();
+        let Some(last) = entries.last() else {
+            return self.splice(container.span.start + 1..container.span.start + 1, entry);
+        };
+        let line_start = self.text[..last.start].rfind('\n').map_or(0, |newline| newline + 1);
+        let indent = &self.text[line_start..last.start];
+        let separator = if indent.chars().all(char::is_whitespace) && line_start > 0 {
+            format!("\n{}", indent)
+        }
+        else {
+            " ".to_string()
+        };
+        return match self.trailing_comma(last.end) {
+            Some(comma) => self.splice(comma + 1..comma + 1, &format!("{}{},", separator, entry)),
+            None => self.splice(last.end..last.end, &format!(",{}{}", separator, entry)),
+        };
+    }
+
+    // the position of a comma directly following `end`, ignoring whitespace and comments
+    fn trailing_comma(&self, end: usize) -> Option<usize> {
+        let mut scanner = Scanner { text: &self.text, pos: end, lenient: self.lenient };
+        scanner.skip_trivia().ok()?;
+        return scanner.rest().starts_with(',').then_some(scanner.pos);
+    }
+
+    fn splice(&mut self, range: Range<usize>, replacement: &str) -> Result<(), String> {
+        return self.edit(range, replacement);
+    }
+
+    /// Replaces the text in `range` with `replacement` and updates the tree, as an
+    /// editor would on each keystroke. Only the smallest value enclosing the edit is
+    /// parsed again; positions after it are shifted. If the edit leaves invalid JSON an
+    /// error is returned and the document is unchanged.
+    pub fn edit(&mut self, range: Range<usize>, replacement: &str) -> Result<(), String> {
+        if range.start > range.end || !self.text.is_char_boundary(range.start) || !self.text.is_char_boundary(range.end) {
+            return Err(format!("edit range {:?} is not valid for a text of {} bytes", range, self.text.len()));
+        }
+        let mut text = self.text.clone();
+        text.replace_range(range.clone(), replacement);
+        let delta = replacement.len() as isize - range.len() as isize;
+
+        let contains = |node: &CstNode| node.span.start <= range.start && range.end <= node.span.end;
+        if !contains(&self.root) {
+            self.root = parse_root(&text, self.lenient)?;
+            self.text = text;
+            return Ok(());
+        }
+        // indices leading from the root to the innermost value enclosing the edit
+        let mut path = vec![];
+        let mut node = &self.root;
+        while let Some((n, child)) = node.children().into_iter().enumerate().find(|(_, child)| contains(child)) {
+            path.push(n);
+            node = child;
+        }
+
+        loop {
+            let old = self.root.descendant_mut(&path);
+            let mut scanner = Scanner { text: &text, pos: old.span.start, lenient: self.lenient };
+            let reparsed = scanner.parse_value();
+            if let Ok(reparsed) = reparsed
+                && scanner.pos == old.span.end.wrapping_add_signed(delta)
+            {
+                self.root.shift(range.end, delta);
+                *self.root.descendant_mut(&path) = reparsed;
+                self.text = text;
+                return Ok(());
+            }
+            if path.pop().is_none() {
+                break;
+            }
+        }
+        self.root = parse_root(&text, self.lenient)?;
+        self.text = text;
+        return Ok(());
+    }
+}
+
+impl fmt::Display for CstDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "{}", self.text);
+    }
+}
+
+fn resolve_node<'a>(root: &'a CstNode, tokens: &[String]) -> Option<&'a CstNode> {
+    let mut current = root;
+    for token in tokens {
+        current = match &current.kind {
+            CstKind::Object(members) => &members.iter().find(|member| member.key == *token)?.value,
+            CstKind::Array(elements) => elements.get(array_index(token)?)?,
+            _ => return None,
+        };
+    }
+    return Some(current);
+}
+
+fn parse_root(text: &str, lenient: bool) -> Result<CstNode, String> {
+    let mut scanner = Scanner { text, pos: 0, lenient };
+    scanner.skip_trivia()?;
+    let root = scanner.parse_value()?;
+    scanner.skip_trivia()?;
+    if !scanner.rest().is_empty() {
+        return Err(scanner.error("unexpected text after the document"));
+    }
+    return Ok(root);
+}
+
+struct Scanner<'a> {
+    text: &'a str,
+    pos: usize,
+    lenient: bool,
+}
+
+impl<'a> Scanner<'a> {
+    fn rest(&self) -> &'a str {
+        return &self.text[self.pos..];
+    }
+
+    fn error(&self, message: &str) -> String {
+        return format!("invalid json at byte {}: {}", self.pos, message);
+    }
+
+    fn skip_trivia(&mut self) -> Result<(), String> {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start_matches([' ', '\t', '\n', '\r']);
+            self.pos += rest.len() - trimmed.len();
+            if !self.lenient {
+                return Ok(());
+            }
+            if trimmed.starts_with("//") {
+                self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+            }
+            else if let Some(comment) = trimmed.strip_prefix("/*") {
+                let Some(end) = comment.find("*/") else {
+                    return Err(self.error("unterminated comment"));
+                };
+                self.pos += 2 + end + 2;
+            }
+            else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if !self.rest().starts_with(c) {
+            return Err(self.error(&format!("expected '{}'", c)));
+        }
+        self.pos += 1;
+        return Ok(());
+    }
+
+    fn parse_value(&mut self) -> Result<CstNode, String> {
+        let start = self.pos;
+        let kind = match self.rest().chars().next() {
+            Some('{') => self.parse_object()?,
+            Some('[') => self.parse_array()?,
+            Some('"') => CstKind::String(self.parse_string()?),
+            Some('-' | '0'..='9') => self.parse_number()?,
+            Some(_) if self.rest().starts_with("true") => self.keyword("true", CstKind::Bool(true)),
+            Some(_) if self.rest().starts_with("false") => self.keyword("false", CstKind::Bool(false)),
+            Some(_) if self.rest().starts_with("null") => self.keyword("null", CstKind::Null),
+            Some(_) => return Err(self.error("expected a value")),
+            None => return Err(self.error("unexpected end of input")),
+        };
+        return Ok(CstNode { span: start..self.pos, kind });
+    }
+
+    fn keyword(&mut self, keyword: &str, kind: CstKind) -> CstKind {
+        self.pos += keyword.len();
+        return kind;
+    }
+
+    // parses comma-separated entries up to `close`, allowing a trailing comma when lenient
+    fn parse_entries<F>(&mut self, close: char, mut parse_entry: F) -> Result<(), String>
+    where
+        F: FnMut(&mut Self) -> Result<(), String>,
+    {
+        self.pos += 1;
+        self.skip_trivia()?;
+        if self.rest().starts_with(close) {
+            self.pos += 1;
+            return Ok(());
+        }
+        loop {
+            parse_entry(self)?;
+            self.skip_trivia()?;
+            if self.rest().starts_with(close) {
+                self.pos += 1;
+                return Ok(());
+            }
+            self.expect(',')?;
+            self.skip_trivia()?;
+            if self.lenient && self.rest().starts_with(close) {
+                self.pos += 1;
+                return Ok(());
+            }
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<CstKind, String> {
+        let mut members: Vec<CstMember> = vec![];
+        let mut seen = HashSet::new();
+        self.parse_entries('}', |scanner| {
+            let key_start = scanner.pos;
+            if !scanner.rest().starts_with('"') {
+                return Err(scanner.error("expected a string key"));
+            }
+            let key = scanner.parse_string()?;
+            if !seen.insert(key.to_string()) {
+                return Err(format!("duplicate key found in jobject: '{}'", key));
+            }
+            let key_span = key_start..scanner.pos;
+            scanner.skip_trivia()?;
+            scanner.expect(':')?;
+            scanner.skip_trivia()?;
+            let value = scanner.parse_value()?;
+            members.push(CstMember { key, key_span, value });
+            return Ok(());
+        })?;
+        return Ok(CstKind::Object(members));
+    }
+
+    fn parse_array(&mut self) -> Result<CstKind, String> {
+        let mut elements = vec![];
+        self.parse_entries(']', |scanner| {
+            elements.push(scanner.parse_value()?);
+            return Ok(());
+        })?;
+        return Ok(CstKind::Array(elements));
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let mut decoded = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((n, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += n + 1;
+                    return Ok(decoded);
+                },
+                '\\' => {
+                    let escaped = match chars.next() {
+                        Some((_, '"')) => '"',
+                        Some((_, '\\')) => '\\',
+                        Some((_, '/')) => '/',
+                        Some((_, 'b')) => '\u{8}',
+                        Some((_, 'f')) => '\u{c}',
+                        Some((_, 'n')) => '\n',
+                        Some((_, 'r')) => '\r',
+                        Some((_, 't')) => '\t',
+                        Some((_, 'u')) => {
+                            let high = hex_escape(&mut chars).ok_or_else(|| self.error("invalid \\u escape"))?;
+                            let code = if (0xd800..0xdc00).contains(&high) {
+                                let low = match (chars.next(), chars.next()) {
+                                    (Some((_, '\\')), Some((_, 'u'))) => hex_escape(&mut chars),
+                                    _ => None,
+                                };
+                                match low {
+                                    Some(low) if (0xdc00..0xe000).contains(&low) => 0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00),
+                                    _ => return Err(self.error("unpaired surrogate in \\u escape")),
+                                }
+                            }
+                            else {
+                                high
+                            };
+                            char::from_u32(code).ok_or_else(|| self.error("unpaired surrogate in \\u escape"))?
+                        },
+                        _ => return Err(self.error("invalid escape in string")),
+                    };
+                    decoded.push(escaped);
+                },
+                c if (c as u32) < 0x20 => return Err(self.error("unescaped control character in string")),
+                c => decoded.push(c),
+            }
+        }
+        return Err(self.error("unterminated string"));
+    }
+
+    fn parse_number(&mut self) -> Result<CstKind, String> {
+        let bytes = self.rest().as_bytes();
+        let digits = |from: usize| bytes[from..].iter().take_while(|b| b.is_ascii_digit()).count();
+        let mut end = usize::from(bytes[0] == b'-');
+        let integer = digits(end);
+        if integer == 0 || (integer > 1 && bytes[end] == b'0') {
+            return Err(self.error("invalid number"));
+        }
+        end += integer;
+        if bytes.get(end) == Some(&b'.') {
+            let fraction = digits(end + 1);
+            if fraction == 0 {
+                return Err(self.error("invalid number"));
+            }
+            end += 1 + fraction;
+        }
+        if matches!(bytes.get(end), Some(b'e' | b'E')) {
+            end += 1;
+            if matches!(bytes.get(end), Some(b'+' | b'-')) {
+                end += 1;
+            }
+            let exponent = digits(end);
+            if exponent == 0 {
+                return Err(self.error("invalid number"));
+            }
+            end += exponent;
+        }
+        let x = self.rest()[..end].parse().map_err(|_| self.error("invalid number"))?;
+        self.pos += end;
+        return Ok(CstKind::Number(x));
+    }
+}
+
+fn hex_escape(chars: &mut std::str::CharIndices) -> Option<u32> {
+    let mut code = 0;
+    for _ in 0..4 {
+        code = code * 16 + chars.next()?.1.to_digit(16)?;
+    }
+    return Some(code);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    const CONFIG: &str = r#"{
+    "name": "service",
+    "ratio": 1.50,
+    "limits": {"max": 1e3},
+    "hosts": [
+        "a",
+        "b"
+    ]
+}
+"#;
+
+    #[test]
+    fn cst_keeps_source_text() {
+        let document = CstDocument::parse(CONFIG).unwrap();
+        assert_eq!(document.to_string(), CONFIG);
+        assert_eq!(document.source_of(document.node("/ratio").unwrap()), "1.50");
+        assert_eq!(document.source_of(document.node("/limits/max").unwrap()), "1e3");
+        assert_eq!(document.to_item(), parse(r#"{"name": "service", "ratio": 1.5, "limits": {"max": 1000}, "hosts": ["a", "b"]}"#).unwrap());
+    }
+
+    #[test]
+    fn cst_decodes_escapes() {
+        let document = CstDocument::parse(r#"["a\"b\\c\né😀"]"#).unwrap();
+        assert_eq!(document.to_item(), JItem::Array(vec![JItem::String("a\"b\\c\né😀".to_string())]));
+    }
+
+    #[test]
+    fn cst_replace_only_touches_the_value() {
+        let mut document = CstDocument::parse(CONFIG).unwrap();
+        document.replace("/limits/max", &JItem::Number(5.0)).unwrap();
+        assert_eq!(document.text(), CONFIG.replace("1e3", "5"));
+        assert_eq!(document.source_of(document.node("/ratio").unwrap()), "1.50");
+    }
+
+    #[test]
+    fn cst_insert_and_push_follow_indentation() {
+        let mut document = CstDocument::parse(CONFIG).unwrap();
+        document.insert("", "debug", &JItem::Bool(false)).unwrap();
+        document.push("/hosts", &JItem::String("c".to_string())).unwrap();
+        document.insert("/limits", "min", &JItem::Number(0.0)).unwrap();
+        let expected = CONFIG
+            .replace("\"b\"\n", "\"b\",\n        \"c\"\n")
+            .replace("]\n}", "],\n    \"debug\": false\n}")
+            .replace("1e3}", "1e3, \"min\": 0}");
+        assert_eq!(document.text(), expected);
+        assert!(document.insert("", "name", &JItem::Null).is_err());
+    }
+
+    #[test]
+    fn cst_insert_into_empty_containers() {
+        let mut document = CstDocument::parse(r#"{"a": {}, "b": []}"#).unwrap();
+        document.insert("/a", "x", &JItem::Number(1.0)).unwrap();
+        document.push("/b", &JItem::Null).unwrap();
+        assert_eq!(document.text(), r#"{"a": {"x": 1}, "b": [null]}"#);
+    }
+
+    #[test]
+    fn cst_remove_entries() {
+        let mut document = CstDocument::parse(CONFIG).unwrap();
+        document.remove("/hosts/0").unwrap();
+        document.remove("/hosts/0").unwrap();
+        document.remove("/ratio").unwrap();
+        document.remove("/hosts").unwrap();
+        assert_eq!(document.text(), "{\n    \"name\": \"service\",\n    \"limits\": {\"max\": 1e3}\n}\n");
+        assert!(document.remove("/missing").is_err());
+        assert!(document.remove("").is_err());
+    }
+
+    #[test]
+    fn cst_lenient_comments_and_trailing_commas() {
+        let input = "{\n    // the service name\n    \"name\": \"x\", /* inline */\n    \"tags\": [1, 2,],\n}";
+        assert!(CstDocument::parse(input).is_err());
+        let mut document = CstDocument::parse_lenient(input).unwrap();
+        assert_eq!(document.to_item(), parse(r#"{"name": "x", "tags": [1, 2]}"#).unwrap());
+        document.insert("", "on", &JItem::Bool(true)).unwrap();
+        document.remove("/tags").unwrap();
+        assert_eq!(document.text(), "{\n    // the service name\n    \"name\": \"x\", /* inline */\n    \"on\": true,\n}");
+    }
+
+    fn assert_incremental(input: &str, range: Range<usize>, replacement: &str) {
+        let mut document = CstDocument::parse_lenient(input).unwrap();
+        let mut expected = input.to_string();
+        expected.replace_range(range.clone(), replacement);
+        document.edit(range, replacement).unwrap();
+        assert_eq!(document, CstDocument::parse_lenient(&expected).unwrap());
+    }
+
+    #[test]
+    fn cst_edit_matches_full_parse() {
+        let number = CONFIG.find("1e3").unwrap();
+        assert_incremental(CONFIG, number..number + 3, "[1, {\"x\": 2}]");
+        let host = CONFIG.find("\"a\"").unwrap();
+        assert_incremental(CONFIG, host + 1..host + 1, "abc");
+        assert_incremental(CONFIG, host + 3..host + 3, ", \"z\"");
+        let key = CONFIG.find("\"ratio\"").unwrap();
+        assert_incremental(CONFIG, key + 1..key + 6, "scale");
+        assert_incremental(CONFIG, 0..0, "\n\n");
+        assert_incremental(CONFIG, CONFIG.len()..CONFIG.len(), "// done\n");
+    }
+
+    #[test]
+    fn cst_edit_rejects_invalid_results() {
+        let mut document = CstDocument::parse(CONFIG).unwrap();
+        let key = CONFIG.find("\"ratio\"").unwrap();
+        assert!(document.edit(key + 1..key + 6, "name").is_err());
+        assert!(document.edit(0..1, "[").is_err());
+        assert!(document.edit(CONFIG.len()..CONFIG.len() + 1, "").is_err());
+        assert_eq!(document.text(), CONFIG);
+    }
+
+    #[test]
+    fn cst_rejects_invalid_json() {
+        assert!(CstDocument::parse("[01]").is_err());
+        assert!(CstDocument::parse("[1.]").is_err());
+        assert!(CstDocument::parse(r#"{"a": 1, "a": 2}"#).is_err());
+        assert!(CstDocument::parse("[\"\u{1}\"]").is_err());
+        assert!(CstDocument::parse(r#"["\ud800"]"#).is_err());
+        assert!(CstDocument::parse("[1] x").is_err());
+    }
+}
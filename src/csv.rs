@@ -0,0 +1,408 @@
+use std::{collections::{BTreeSet, HashMap}, io::{Read, Write}, ops::Range};
+
+use crate::{error::{ErrorKind, JsonError}, flatten::{flatten, unflatten}, format::FormatOptions, j_item::JItem};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvOptions {
+    pub delimiter: char,
+    /// Spread nested objects and arrays over columns named like `a.b[0]`. When false
+    /// they are written as compact JSON text in a single column.
+    pub flatten_nested: bool,
+    /// Field text for a column a row has no value for. Nulls are always written empty.
+    /// On import, fields with exactly this text are left out of the row's object.
+    pub missing: String,
+    /// On import, give each column a type from all of its fields: when every field other
+    /// than `null` reads as a JSON number, or every one as `true` or `false`, the column's
+    /// fields become those values and its `null`s nulls. Other columns stay strings.
+    pub infer_types: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        return CsvOptions { delimiter: ',', flatten_nested: true, missing: String::new(), infer_types: false };
+    }
+}
+
+/// Converts an array of objects to CSV. The header is the sorted union of every row's
+/// columns and lines end with `\n`. Anything else fails with
+/// `ErrorKind::Unrepresentable`.
+pub fn to_csv(item: &JItem) -> Result<String, JsonError> {
+    return to_csv_with(item, &CsvOptions::default());
+}
+
+pub fn to_csv_with(item: &JItem, options: &CsvOptions) -> Result<String, JsonError> {
+    let mut out = vec![];
+    write_csv(&mut out, item, options)?;
+    return Ok(String::from_utf8(out).expect("csv is written from strings"));
+}
+
+/// Writes the CSV form of `item` to `writer`. See `to_csv`. Failures to write are
+/// reported as `ErrorKind::Io`.
+pub fn write_csv<W: Write>(mut writer: W, item: &JItem, options: &CsvOptions) -> Result<(), JsonError> {
+    let unrepresentable = |message: String| JsonError::new(ErrorKind::Unrepresentable(message), "", 0..0);
+    let JItem::Array(rows) = item else {
+        return Err(unrepresentable("csv export requires an array of objects".to_string()));
+    };
+
+    let mut row_fields = Vec::with_capacity(rows.len());
+    for (n, row) in rows.iter().enumerate() {
+        let JItem::Object(hmap) = row else {
+            return Err(unrepresentable(format!("csv export requires an array of objects, but row {} is not an object", n)));
+        };
+        row_fields.push(row_to_fields(hmap, options));
+    }
+
+    let header: BTreeSet<&String> = row_fields.iter().flat_map(|fields| fields.keys()).collect();
+    let header_line: Vec<_> = header.iter().map(|column| quote_field(column, options.delimiter)).collect();
+    write_line(&mut writer, &header_line, options.delimiter)?;
+
+    for fields in &row_fields {
+        let line: Vec<_> = header
+            .iter()
+            .map(|column| match fields.get(*column) {
+                Some(value) => quote_field(value, options.delimiter),
+                None => quote_field(&options.missing, options.delimiter),
+            })
+            .collect();
+        write_line(&mut writer, &line, options.delimiter)?;
+    }
+    return Ok(());
+}
+
+fn row_to_fields(hmap: &HashMap<String, JItem>, options: &CsvOptions) -> HashMap<String, String> {
+    if options.flatten_nested {
+        let row = JItem::Object(hmap.clone());
+        return flatten(&row, ".").into_iter().map(|(column, value)| (column, field_text(&value))).collect();
+    }
+    return hmap.iter().map(|(column, value)| (column.to_string(), field_text(value))).collect();
+}
+
+fn field_text(value: &JItem) -> String {
+    return match value {
+        JItem::String(s) => s.to_string(),
+        JItem::Null => String::new(),
+        JItem::Object(_) | JItem::Array(_) => value.to_string_with(&FormatOptions { sort_keys: true, ..FormatOptions::compact() }),
+        scalar => scalar.to_string(),
+    };
+}
+
+fn quote_field(field: &str, delimiter: char) -> String {
+    if !field.contains([delimiter, '"', '\n', '\r']) {
+        return field.to_string();
+    }
+    return format!("\"{}\"", field.replace('"', "\"\""));
+}
+
+fn write_line<W: Write>(writer: &mut W, fields: &[String], delimiter: char) -> std::io::Result<()> {
+    let line = fields.join(delimiter.encode_utf8(&mut [0; 4]));
+    return writeln!(writer, "{}", line);
+}
+
+/// Reads CSV with a header row into an array with one object per record. When
+/// `flatten_nested` is set, column names like `a.b[0]` are expanded back into nested
+/// values, reversing `to_csv`. Malformed input fails with `ErrorKind::InvalidCsv`, and
+/// failures to read with `ErrorKind::Io`.
+pub fn from_csv<R: Read>(mut reader: R, options: &CsvOptions) -> Result<JItem, JsonError> {
+    let mut input = String::new();
+    reader.read_to_string(&mut input)?;
+    return from_csv_str(&input, options);
+}
+
+pub fn from_csv_str(input: &str, options: &CsvOptions) -> Result<JItem, JsonError> {
+    let invalid = |message: String, span: Range<usize>| JsonError::new(ErrorKind::InvalidCsv(message), input, span);
+    let mut records = parse_records(input, options.delimiter)?;
+    if records.is_empty() {
+        return Ok(JItem::Array(vec![]));
+    }
+    let (_, header) = records.remove(0);
+    for (n, (span, record)) in records.iter().enumerate() {
+        if record.len() > header.len() {
+            return Err(invalid(format!("csv record {} has {} fields but the header has {}", n + 1, record.len(), header.len()), span.clone()));
+        }
+    }
+    let types: Vec<FieldType> = (0..header.len()).map(|column| {
+        if !options.infer_types {
+            return FieldType::String;
+        }
+        let fields = records.iter().filter_map(|(_, record)| record.get(column)).filter(|field| **field != options.missing);
+        return column_type(fields);
+    }).collect();
+
+    let mut rows = vec![];
+    for (n, (span, record)) in records.into_iter().enumerate() {
+        let mut fields = HashMap::new();
+        for ((column, field), field_type) in header.iter().zip(record).zip(&types) {
+            if field == options.missing {
+                continue;
+            }
+            fields.insert(column.to_string(), field_type.value(field));
+        }
+        if options.flatten_nested {
+            rows.push(unflatten(&fields, ".").map_err(|e| invalid(format!("csv record {}: {}", n + 1, e), span))?);
+        }
+        else {
+            rows.push(JItem::Object(fields));
+        }
+    }
+    return Ok(JItem::Array(rows));
+}
+
+/// The span of a record's line and its unquoted fields.
+type Record = (Range<usize>, Vec<String>);
+
+// splits RFC 4180 text into records. blank lines are skipped.
+fn parse_records(input: &str, delimiter: char) -> Result<Vec<Record>, JsonError> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut field = String::new();
+    let mut start = 0;
+    // where the open quoted field began
+    let mut quote = None;
+    let mut chars = input.char_indices().peekable();
+    while let Some((n, c)) = chars.next() {
+        let next = chars.peek().map(|&(_, next)| next);
+        if quote.is_some() {
+            match c {
+                '"' if next == Some('"') => {
+                    chars.next();
+                    field.push('"');
+                },
+                '"' => quote = None,
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() => quote = Some(n),
+            '\r' if next == Some('\n') => {},
+            '\n' | '\r' => {
+                record.push(std::mem::take(&mut field));
+                if record.len() > 1 || !record[0].is_empty() {
+                    let end = start + input[start..n].trim_end_matches('\r').len();
+                    records.push((start..end, std::mem::take(&mut record)));
+                }
+                else {
+                    record.clear();
+                }
+                start = n + 1;
+            },
+            c if c == delimiter => record.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if let Some(quote) = quote {
+        return Err(JsonError::new(ErrorKind::InvalidCsv("unterminated quoted csv field".to_string()), input, quote..input.len()));
+    }
+    record.push(field);
+    if record.len() > 1 || !record[0].is_empty() {
+        records.push((start..input.len(), record));
+    }
+    return Ok(records);
+}
+
+/// What the fields of a column are read as on import.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FieldType {
+    String,
+    Number,
+    Bool,
+    /// Every field is `null`.
+    Null,
+}
+
+impl FieldType {
+    fn value(self, field: String) -> JItem {
+        return match (self, field.as_str()) {
+            (FieldType::String, _) => JItem::String(field),
+            (_, "null") => JItem::Null,
+            (FieldType::Number, number) => JItem::Number(number.parse().unwrap_or(f64::NAN)),
+            (_, boolean) => JItem::Bool(boolean == "true"),
+        };
+    }
+}
+
+fn column_type<'a>(fields: impl Iterator<Item = &'a String>) -> FieldType {
+    let mut column = FieldType::Null;
+    for field in fields {
+        let field_type = match field.as_str() {
+            "null" => continue,
+            "true" | "false" => FieldType::Bool,
+            number if is_json_number(number) => FieldType::Number,
+            _ => return FieldType::String,
+        };
+        if column != FieldType::Null && column != field_type {
+            return FieldType::String;
+        }
+        column = field_type;
+    }
+    return column;
+}
+
+// the JSON number grammar: -?(0|[1-9][0-9]*)(\.[0-9]+)?([eE][+-]?[0-9]+)?
+fn is_json_number(s: &str) -> bool {
+    let bytes = s.as_bytes();
+    let mut i = 0;
+    let digits_from = |mut i: usize| {
+        while i < bytes.len() && bytes[i].is_ascii_digit() {
+            i += 1;
+        }
+        i
+    };
+    if bytes.get(i) == Some(&b'-') {
+        i += 1;
+    }
+    match bytes.get(i) {
+        Some(b'0') => i += 1,
+        Some(b'1'..=b'9') => i = digits_from(i),
+        _ => return false,
+    }
+    if bytes.get(i) == Some(&b'.') {
+        let end = digits_from(i + 1);
+        if end == i + 1 {
+            return false;
+        }
+        i = end;
+    }
+    if matches!(bytes.get(i), Some(b'e' | b'E')) {
+        i += 1;
+        if matches!(bytes.get(i), Some(b'+' | b'-')) {
+            i += 1;
+        }
+        let end = digits_from(i);
+        if end == i {
+            return false;
+        }
+        i = end;
+    }
+    return i == bytes.len();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn to_csv_unified_header() {
+        let item = parse(r#"[{"name": "a", "age": 3}, {"name": "b", "email": "b@x"}]"#).unwrap();
+        assert_eq!(to_csv(&item), Ok("age,email,name\n3,,a\n,b@x,b\n".to_string()));
+    }
+
+    #[test]
+    fn to_csv_flattens_nested() {
+        let item = parse(r#"[{"id": 1, "tags": ["x", "y"], "geo": {"lat": 1.5}, "note": null}]"#).unwrap();
+        assert_eq!(to_csv(&item), Ok("geo.lat,id,note,tags[0],tags[1]\n1.5,1,,x,y\n".to_string()));
+    }
+
+    #[test]
+    fn to_csv_nested_as_json() {
+        let item = parse(r#"[{"id": 1, "geo": {"lat": 1.5, "lon": 2}}]"#).unwrap();
+        let options = CsvOptions { flatten_nested: false, ..CsvOptions::default() };
+        assert_eq!(to_csv_with(&item, &options), Ok("geo,id\n\"{\"\"lat\"\":1.5,\"\"lon\"\":2}\",1\n".to_string()));
+    }
+
+    #[test]
+    fn to_csv_quotes_fields() {
+        let mut row = HashMap::new();
+        row.insert("a".to_string(), JItem::String("x,y".to_string()));
+        row.insert("b".to_string(), JItem::String("line\nbreak".to_string()));
+        row.insert("c".to_string(), JItem::String("say \"hi\"".to_string()));
+        let item = JItem::Array(vec![JItem::Object(row)]);
+        assert_eq!(to_csv(&item), Ok("a,b,c\n\"x,y\",\"line\nbreak\",\"say \"\"hi\"\"\"\n".to_string()));
+    }
+
+    #[test]
+    fn to_csv_missing_and_delimiter() {
+        let item = parse(r#"[{"a": 1}, {"b": true}]"#).unwrap();
+        let options = CsvOptions { delimiter: ';', missing: "NA".to_string(), ..CsvOptions::default() };
+        assert_eq!(to_csv_with(&item, &options), Ok("a;b\n1;NA\nNA;true\n".to_string()));
+    }
+
+    #[test]
+    fn to_csv_rejects_non_objects() {
+        assert_eq!(to_csv(&parse("{}").unwrap()).unwrap_err().code(), "E023");
+        assert_eq!(
+            to_csv(&parse(r#"[{}, 1]"#).unwrap()).unwrap_err().kind(),
+            &ErrorKind::Unrepresentable("csv export requires an array of objects, but row 1 is not an object".to_string())
+        );
+    }
+
+    #[test]
+    fn write_csv_to_writer() {
+        let item = parse(r#"[{"a": 1}]"#).unwrap();
+        let mut out = vec![];
+        write_csv(&mut out, &item, &CsvOptions::default()).unwrap();
+        assert_eq!(out, b"a\n1\n");
+        let error = write_csv(&mut [0; 3][..], &item, &CsvOptions::default()).unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::Io { kind: std::io::ErrorKind::WriteZero, .. }));
+    }
+
+    #[test]
+    fn from_csv_strings() {
+        let item = from_csv("name,age\na,3\nb,\n".as_bytes(), &CsvOptions::default()).unwrap();
+        assert_eq!(item, parse(r#"[{"name": "a", "age": "3"}, {"name": "b"}]"#).unwrap());
+    }
+
+    #[test]
+    fn from_csv_infers_types() {
+        let options = CsvOptions { infer_types: true, ..CsvOptions::default() };
+        let item = from_csv_str("a,b,c,d,e\n-1.5e2,true,null,007,x\n", &options).unwrap();
+        assert_eq!(item, parse(r#"[{"a": -150, "b": true, "c": null, "d": "007", "e": "x"}]"#).unwrap());
+    }
+
+    #[test]
+    fn from_csv_infers_types_per_column() {
+        let options = CsvOptions { infer_types: true, ..CsvOptions::default() };
+        let item = from_csv_str("id,flag,code,none\n1,true,1,null\nnull,false,x,\n2.5,,true,null\n", &options).unwrap();
+        let expected = parse(r#"[
+            {"id": 1, "flag": true, "code": "1", "none": null},
+            {"id": null, "flag": false, "code": "x"},
+            {"id": 2.5, "code": "true", "none": null}
+        ]"#).unwrap();
+        assert_eq!(item, expected);
+    }
+
+    #[test]
+    fn from_csv_quoted_fields() {
+        let input = "a,b\r\n\"x,y\",\"line\nbreak \"\"quoted\"\"\"\r\n";
+        let item = from_csv_str(input, &CsvOptions::default()).unwrap();
+        let JItem::Array(rows) = item else {
+            panic!("expected an array");
+        };
+        assert_eq!(rows[0].pointer("/a"), Some(&JItem::String("x,y".to_string())));
+        assert_eq!(rows[0].pointer("/b"), Some(&JItem::String("line\nbreak \"quoted\"".to_string())));
+    }
+
+    #[test]
+    fn from_csv_unflattens_columns() {
+        let options = CsvOptions { infer_types: true, ..CsvOptions::default() };
+        let item = from_csv_str("geo.lat,id,tags[0],tags[1]\n1.5,1,x,y\n", &options).unwrap();
+        assert_eq!(item, parse(r#"[{"id": 1, "tags": ["x", "y"], "geo": {"lat": 1.5}}]"#).unwrap());
+    }
+
+    #[test]
+    fn from_csv_round_trip() {
+        let item = parse(r#"[{"id": 1, "name": "a,b", "nested": {"ok": false}}, {"id": 2, "name": "c"}]"#).unwrap();
+        let options = CsvOptions { infer_types: true, ..CsvOptions::default() };
+        let csv = to_csv_with(&item, &options).unwrap();
+        assert_eq!(from_csv_str(&csv, &options), Ok(item));
+    }
+
+    #[test]
+    fn from_csv_errors() {
+        let error = from_csv_str("a\n1,2\r\n", &CsvOptions::default()).unwrap_err();
+        assert_eq!((error.code(), error.span(), error.to_string().as_str()), ("E025", 2..5, "csv record 1 has 2 fields but the header has 1 at line 2, column 1"));
+        assert_eq!(from_csv_str("a\n\"open\n", &CsvOptions::default()).unwrap_err().span(), 2..8);
+        assert_eq!(
+            from_csv_str("a[18446744073709551615]\n1\n", &CsvOptions::default()).unwrap_err().kind(),
+            &ErrorKind::InvalidCsv("csv record 1: conflicting flattened key 'a[18446744073709551615]': index 18446744073709551615 is too far past the end of an array of length 0".to_string())
+        );
+        let invalid_utf8: &[u8] = b"a\n\xff\n";
+        assert!(matches!(from_csv(invalid_utf8, &CsvOptions::default()).unwrap_err().kind(), ErrorKind::Io { .. }));
+    }
+
+    #[test]
+    fn from_csv_empty_input() {
+        assert_eq!(from_csv_str("", &CsvOptions::default()), Ok(JItem::Array(vec![])));
+    }
+}
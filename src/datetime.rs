@@ -0,0 +1,141 @@
+use chrono::{DateTime, FixedOffset, NaiveDate, SecondsFormat, TimeZone};
+
+use crate::j_item::JItem;
+
+/// The unit of an epoch timestamp stored as a number.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EpochUnit {
+    #[default]
+    Seconds,
+    Milliseconds,
+    Microseconds,
+}
+
+impl EpochUnit {
+    fn per_second(self) -> f64 {
+        return match self {
+            EpochUnit::Seconds => 1.0,
+            EpochUnit::Milliseconds => 1e3,
+            EpochUnit::Microseconds => 1e6,
+        };
+    }
+}
+
+impl JItem {
+    /// Reads an RFC 3339 string, or a number of seconds since the Unix epoch.
+    pub fn as_datetime(&self) -> Option<DateTime<FixedOffset>> {
+        return self.as_datetime_with(EpochUnit::Seconds);
+    }
+
+    /// Reads an RFC 3339 string, or a number counted in `unit` since the Unix epoch.
+    /// Epoch numbers come back in UTC.
+    pub fn as_datetime_with(&self, unit: EpochUnit) -> Option<DateTime<FixedOffset>> {
+        return match self {
+            JItem::String(s) => DateTime::parse_from_rfc3339(s).ok(),
+            JItem::Number(_) | JItem::RawNumber(_) => {
+                let x = self.number_value().unwrap();
+                if !x.is_finite() {
+                    return None;
+                }
+                let micros = (x / unit.per_second() * 1e6).round();
+                if micros.abs() >= i64::MAX as f64 {
+                    return None;
+                }
+                DateTime::from_timestamp_micros(micros as i64).map(|utc| utc.fixed_offset())
+            },
+            _ => None,
+        };
+    }
+
+    /// Reads a `YYYY-MM-DD` string, or the calendar date of an RFC 3339 string in its
+    /// own offset.
+    pub fn as_date(&self) -> Option<NaiveDate> {
+        let JItem::String(s) = self else {
+            return None;
+        };
+        if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            return Some(date);
+        }
+        return DateTime::parse_from_rfc3339(s).ok().map(|datetime| datetime.date_naive());
+    }
+
+    /// Writes a datetime as an epoch number in `unit`, the counterpart of `as_datetime_with`.
+    pub fn from_epoch<Tz: TimeZone>(datetime: &DateTime<Tz>, unit: EpochUnit) -> JItem {
+        let micros = datetime.timestamp_micros() as f64;
+        return JItem::Number(micros / 1e6 * unit.per_second());
+    }
+}
+
+/// Writes an RFC 3339 string, keeping the datetime's offset and only as many fractional
+/// digits as needed.
+impl<Tz: TimeZone> From<DateTime<Tz>> for JItem
+where
+    Tz::Offset: std::fmt::Display,
+{
+    fn from(datetime: DateTime<Tz>) -> Self {
+        return JItem::String(datetime.to_rfc3339_opts(SecondsFormat::AutoSi, true));
+    }
+}
+
+/// Writes a `YYYY-MM-DD` string.
+impl From<NaiveDate> for JItem {
+    fn from(date: NaiveDate) -> Self {
+        return JItem::String(date.format("%Y-%m-%d").to_string());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Utc;
+
+    use super::*;
+
+    fn string(s: &str) -> JItem {
+        return JItem::String(s.to_string());
+    }
+
+    fn utc(datetime: DateTime<FixedOffset>) -> DateTime<Utc> {
+        return datetime.with_timezone(&Utc);
+    }
+
+    #[test]
+    fn as_datetime_rfc3339() {
+        let datetime = string("2024-02-29T12:30:00+02:00").as_datetime().unwrap();
+        assert_eq!(datetime.timestamp(), 1709202600);
+        assert_eq!(datetime.offset().local_minus_utc(), 7200);
+        assert_eq!(string("2024-02-30T12:30:00Z").as_datetime(), None);
+        assert_eq!(string("yesterday").as_datetime(), None);
+        assert_eq!(JItem::Null.as_datetime(), None);
+    }
+
+    #[test]
+    fn as_datetime_epoch_units() {
+        let expected = Utc.with_ymd_and_hms(2001, 9, 9, 1, 46, 40).unwrap();
+        assert_eq!(JItem::Number(1e9).as_datetime(), Some(expected.fixed_offset()));
+        assert_eq!(JItem::Number(1e12).as_datetime_with(EpochUnit::Milliseconds), Some(expected.fixed_offset()));
+        assert_eq!(JItem::Number(1e15).as_datetime_with(EpochUnit::Microseconds), Some(expected.fixed_offset()));
+        assert_eq!(JItem::Number(f64::NAN).as_datetime(), None);
+    }
+
+    #[test]
+    fn as_date_strings() {
+        let expected = NaiveDate::from_ymd_opt(2024, 2, 29);
+        assert_eq!(string("2024-02-29").as_date(), expected);
+        assert_eq!(string("2024-02-29T23:30:00-05:00").as_date(), expected);
+        assert_eq!(string("2023-02-29").as_date(), None);
+    }
+
+    #[test]
+    fn datetime_constructors_round_trip() {
+        let datetime = DateTime::parse_from_rfc3339("2024-02-29T12:30:00.250+02:00").unwrap();
+        let item = JItem::from(datetime);
+        assert_eq!(item, string("2024-02-29T12:30:00.250+02:00"));
+        assert_eq!(item.as_datetime(), Some(datetime));
+        assert_eq!(JItem::from(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()), string("2024-01-01T00:00:00Z"));
+
+        let epoch = JItem::from_epoch(&datetime, EpochUnit::Milliseconds);
+        assert_eq!(epoch, JItem::Number(1709202600250.0));
+        assert_eq!(epoch.as_datetime_with(EpochUnit::Milliseconds).map(utc), Some(utc(datetime)));
+        assert_eq!(JItem::from(NaiveDate::from_ymd_opt(2024, 2, 29).unwrap()), string("2024-02-29"));
+    }
+}
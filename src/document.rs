@@ -0,0 +1,193 @@
+use std::ops::Range;
+
+use crate::{
+    cst::{CstDocument, CstKind, CstNode},
+    path::PathSegment,
+};
+
+/// A zero-based line and column. Columns count UTF-16 code units, as the Language
+/// Server Protocol does by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// The innermost value at an offset, with the path leading to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeAt<'a> {
+    pub node: &'a CstNode,
+    pub path: Vec<PathSegment>,
+    /// Whether the offset is on the member's key rather than its value. `node` is still
+    /// the member's value.
+    pub on_key: bool,
+}
+
+/// A CST document together with a line index, for editor and language server
+/// features that work in terms of cursor positions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextDocument {
+    cst: CstDocument,
+    line_starts: Vec<usize>,
+}
+
+impl TextDocument {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        return Ok(TextDocument::from(CstDocument::parse(text)?));
+    }
+
+    pub fn parse_lenient(text: &str) -> Result<Self, String> {
+        return Ok(TextDocument::from(CstDocument::parse_lenient(text)?));
+    }
+
+    pub fn cst(&self) -> &CstDocument {
+        return &self.cst;
+    }
+
+    pub fn text(&self) -> &str {
+        return self.cst.text();
+    }
+
+    /// Applies a text edit. See `CstDocument::edit`.
+    pub fn edit(&mut self, range: Range<usize>, replacement: &str) -> Result<(), String> {
+        self.cst.edit(range, replacement)?;
+        self.line_starts = line_starts(self.cst.text());
+        return Ok(());
+    }
+
+    /// The position of a byte offset, which may be the end of the text.
+    pub fn position_of(&self, offset: usize) -> Option<Position> {
+        let text = self.text();
+        if offset > text.len() || !text.is_char_boundary(offset) {
+            return None;
+        }
+        let line = self.line_starts.partition_point(|start| *start <= offset) - 1;
+        let column = text[self.line_starts[line]..offset].encode_utf16().count();
+        return Some(Position { line, column });
+    }
+
+    /// The byte offset of a position. Columns past the end of a line are rejected, as are
+    /// columns that split a surrogate pair.
+    pub fn offset_of(&self, position: Position) -> Option<usize> {
+        let text = self.text();
+        let start = *self.line_starts.get(position.line)?;
+        let end = self.line_starts.get(position.line + 1).map_or(text.len(), |next| next - 1);
+        let mut units = 0;
+        for (n, c) in text[start..end].char_indices() {
+            if units == position.column {
+                return Some(start + n);
+            }
+            units += c.len_utf16();
+        }
+        return (units == position.column).then_some(end);
+    }
+
+    /// The innermost value whose text contains `offset`, counting its end as inside so
+    /// that a cursor just after a value still finds it.
+    pub fn node_at(&self, offset: usize) -> Option<NodeAt<'_>> {
+        let contains = |span: &Range<usize>| span.start <= offset && offset <= span.end;
+        let mut node = self.cst.root();
+        if !contains(&node.span) {
+            return None;
+        }
+        let mut path = vec![];
+        loop {
+            let next = match &node.kind {
+                CstKind::Object(members) => members.iter().find_map(|member| {
+                    if contains(&member.key_span) {
+                        return Some((PathSegment::Key(member.key.to_string()), &member.value, true));
+                    }
+                    return contains(&member.value.span).then(|| (PathSegment::Key(member.key.to_string()), &member.value, false));
+                }),
+                CstKind::Array(elements) => {
+                    elements.iter().enumerate().find(|(_, element)| contains(&element.span)).map(|(n, element)| (PathSegment::Index(n), element, false))
+                },
+                _ => None,
+            };
+            let Some((segment, child, on_key)) = next else {
+                return Some(NodeAt { node, path, on_key: false });
+            };
+            path.push(segment);
+            if on_key {
+                return Some(NodeAt { node: child, path, on_key: true });
+            }
+            node = child;
+        }
+    }
+
+    /// The path to the innermost value at `offset`, empty for the root or outside it.
+    pub fn path_at(&self, offset: usize) -> Vec<PathSegment> {
+        return self.node_at(offset).map(|found| found.path).unwrap_or_default();
+    }
+}
+
+impl From<CstDocument> for TextDocument {
+    fn from(cst: CstDocument) -> Self {
+        let line_starts = line_starts(cst.text());
+        return TextDocument { cst, line_starts };
+    }
+}
+
+fn line_starts(text: &str) -> Vec<usize> {
+    let mut starts = vec![0];
+    starts.extend(text.match_indices('\n').map(|(n, _)| n + 1));
+    return starts;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const INPUT: &str = "{\n  \"name\": \"é😀x\",\n  \"items\": [1, {\"id\": 2}]\n}";
+
+    fn key(key: &str) -> PathSegment {
+        return PathSegment::Key(key.to_string());
+    }
+
+    #[test]
+    fn position_offset_round_trip() {
+        let document = TextDocument::parse(INPUT).unwrap();
+        let x = INPUT.find('x').unwrap();
+        assert_eq!(document.position_of(x), Some(Position { line: 1, column: 14 }));
+        assert_eq!(document.offset_of(Position { line: 1, column: 14 }), Some(x));
+        assert_eq!(document.position_of(0), Some(Position { line: 0, column: 0 }));
+        assert_eq!(document.position_of(INPUT.len()), Some(Position { line: 3, column: 1 }));
+        assert_eq!(document.offset_of(Position { line: 3, column: 1 }), Some(INPUT.len()));
+    }
+
+    #[test]
+    fn position_rejects_invalid() {
+        let document = TextDocument::parse(INPUT).unwrap();
+        let emoji = INPUT.find('😀').unwrap();
+        assert_eq!(document.position_of(emoji + 1), None);
+        assert_eq!(document.position_of(INPUT.len() + 1), None);
+        assert_eq!(document.offset_of(Position { line: 1, column: 13 }), None);
+        assert_eq!(document.offset_of(Position { line: 0, column: 2 }), None);
+        assert_eq!(document.offset_of(Position { line: 4, column: 0 }), None);
+    }
+
+    #[test]
+    fn node_at_finds_innermost_value() {
+        let document = TextDocument::parse(INPUT).unwrap();
+        let found = document.node_at(INPUT.find("2}").unwrap()).unwrap();
+        assert_eq!(found.path, vec![key("items"), PathSegment::Index(1), key("id")]);
+        assert_eq!(document.cst().source_of(found.node), "2");
+        assert!(!found.on_key);
+
+        let on_key = document.node_at(INPUT.find("name").unwrap()).unwrap();
+        assert_eq!(on_key.path, vec![key("name")]);
+        assert!(on_key.on_key);
+
+        assert_eq!(document.path_at(INPUT.find('[').unwrap()), vec![key("items")]);
+        assert_eq!(document.path_at(1), vec![]);
+    }
+
+    #[test]
+    fn edit_updates_line_index() {
+        let mut document = TextDocument::parse(INPUT).unwrap();
+        document.edit(1..1, "\n").unwrap();
+        let x = document.text().find('x').unwrap();
+        assert_eq!(document.position_of(x), Some(Position { line: 2, column: 14 }));
+        assert_eq!(document.path_at(x), vec![key("name")]);
+    }
+}
@@ -0,0 +1,223 @@
+//! Finding repetition in a document: keys given more than once in an object, which a
+//! parse with `DuplicateKeys::KeepFirst` or `KeepLast` lets through, and equal values
+//! stored at several places, which `SharedJItem::compact` would store once.
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::ops::Range;
+
+use crate::{
+    error::JsonError,
+    j_item::{escape_string, sorted_members, JItem},
+    parser::{parse_source, DuplicateKeys, Node, ParseOptions},
+    pointer::JsonPointer,
+    HashMap,
+};
+
+/// The tokens of the path to every repeated key within a value, relative to it, in the
+/// order the repeats appear.
+struct KeyRepeats(Vec<Vec<String>>);
+
+/// An object's keys so far, with how many times each has been seen, and the repeats
+/// found in it.
+#[derive(Default)]
+struct SeenKeys {
+    counts: HashMap<String, usize>,
+    repeats: Vec<Vec<String>>,
+}
+
+impl Node for KeyRepeats {
+    type Members = SeenKeys;
+
+    fn scalar(_: JItem, _: Range<usize>) -> Self {
+        return KeyRepeats(vec![]);
+    }
+
+    fn array(elements: Vec<Self>, _: Range<usize>) -> Self {
+        let repeats = elements.into_iter().enumerate().flat_map(|(index, element)| element.0.into_iter().map(move |mut path| {
+            path.insert(0, index.to_string());
+            return path;
+        }));
+        return KeyRepeats(repeats.collect());
+    }
+
+    fn object(members: Self::Members, _: Range<usize>) -> Self {
+        return KeyRepeats(members.repeats);
+    }
+
+    fn contains_key(members: &Self::Members, key: &str) -> bool {
+        return members.counts.contains_key(key);
+    }
+
+    fn insert(members: &mut Self::Members, key: String, _: Range<usize>, value: Self) {
+        let seen = members.counts.entry(key.clone()).or_insert(0);
+        *seen += 1;
+        if *seen == 2 {
+            members.repeats.push(vec![key.clone()]);
+        }
+        for mut path in value.0 {
+            path.insert(0, key.clone());
+            members.repeats.push(path);
+        }
+    }
+}
+
+/// The pointer to every key that is given more than once in the same object of `s`,
+/// each listed once, in the order the repeats appear. Syntax is checked as `parse`
+/// checks it.
+pub fn find_duplicate_keys(s: &str) -> Result<Vec<JsonPointer>, JsonError> {
+    return find_duplicate_keys_with(s, &ParseOptions::default());
+}
+
+/// Like `find_duplicate_keys`, accepting the syntax `parse_with` accepts with `options`,
+/// such as comments. Keys a `KeyFilter` drops are not counted, and the setting of
+/// `duplicate_keys` is ignored.
+pub fn find_duplicate_keys_with(s: &str, options: &ParseOptions) -> Result<Vec<JsonPointer>, JsonError> {
+    let options = ParseOptions { duplicate_keys: DuplicateKeys::KeepLast, ..options.clone() };
+    let (found, _) = parse_source::<KeyRepeats>(s, &options, &mut vec![], &mut String::new())?;
+    return Ok(found.0.into_iter().map(JsonPointer::from_tokens).collect());
+}
+
+impl JItem {
+    /// Groups of pointers to values that are equal, as `canonical_hash` compares them,
+    /// for values whose canonical text is at least `min_size` bytes long. A group is
+    /// left out when its values only repeat because the values holding them do. Groups
+    /// are in the order their first values appear, visiting object members in sorted
+    /// key order, and pointers within a group are in that order too.
+    pub fn find_duplicate_values(&self, min_size: usize) -> Vec<Vec<JsonPointer>> {
+        let mut nodes = vec![];
+        collect(self, None, String::new(), &mut HashMap::new(), &mut nodes);
+
+        let mut groups: Vec<Vec<usize>> = vec![];
+        let mut group_of = vec![None; nodes.len()];
+        let mut by_class: HashMap<usize, usize> = HashMap::new();
+        for (n, node) in nodes.iter().enumerate() {
+            if node.size < min_size {
+                continue;
+            }
+            let group = *by_class.entry(node.class).or_insert_with(|| {
+                groups.push(vec![]);
+                groups.len() - 1
+            });
+            groups[group].push(n);
+            group_of[n] = Some(group);
+        }
+
+        return groups.iter().filter(|members| {
+            if members.len() < 2 {
+                return false;
+            }
+            let parent_groups: Vec<_> = members.iter().map(|n| nodes[*n].parent.and_then(|parent| group_of[parent])).collect();
+            return !(parent_groups[0].is_some() && parent_groups.iter().all(|group| *group == parent_groups[0]));
+        }).map(|members| members.iter().map(|n| pointer(&nodes, *n)).collect()).collect();
+    }
+}
+
+/// A value in the tree: the token leading to it from its parent, and its class, which
+/// it shares with exactly the values that have the same canonical text, `size` bytes
+/// long.
+struct ValueNode {
+    token: String,
+    parent: Option<usize>,
+    class: usize,
+    size: usize,
+}
+
+/// Adds `item` and everything in it to `nodes`, each value before the values in it,
+/// returning where `item` went. Only scalars are written out as canonical text. An
+/// array or object is told apart by its children's classes, so the work per value
+/// stays in proportion to its own members rather than to everything below it.
+fn collect(item: &JItem, parent: Option<usize>, token: String, classes: &mut HashMap<String, usize>, nodes: &mut Vec<ValueNode>) -> usize {
+    let n = nodes.len();
+    nodes.push(ValueNode { token, parent, class: 0, size: 0 });
+    // containers start with a bracket, which no scalar's canonical text does
+    let (key, size) = match item {
+        JItem::Object(hmap) => {
+            let mut key = String::from("{");
+            let mut size = 2 + hmap.len().saturating_sub(1);
+            for (name, value) in sorted_members(hmap) {
+                let escaped = escape_string(name);
+                let child = collect(value, Some(n), name.clone(), classes, nodes);
+                size += escaped.len() + 3 + nodes[child].size;
+                key.push_str(&format!("\"{}\":{},", escaped, nodes[child].class));
+            }
+            (key, size)
+        },
+        JItem::Array(elements) => {
+            let mut key = String::from("[");
+            let mut size = 2 + elements.len().saturating_sub(1);
+            for (index, element) in elements.iter().enumerate() {
+                let child = collect(element, Some(n), index.to_string(), classes, nodes);
+                size += nodes[child].size;
+                key.push_str(&format!("{},", nodes[child].class));
+            }
+            (key, size)
+        },
+        scalar => {
+            let text = scalar.to_canonical_string();
+            let size = text.len();
+            (text, size)
+        },
+    };
+    let next = classes.len();
+    nodes[n].class = *classes.entry(key).or_insert(next);
+    nodes[n].size = size;
+    return n;
+}
+
+/// The pointer to the `n`th node, found by following its parents back to the root.
+fn pointer(nodes: &[ValueNode], mut n: usize) -> JsonPointer {
+    let mut tokens = vec![];
+    while let Some(parent) = nodes[n].parent {
+        tokens.push(nodes[n].token.as_str());
+        n = parent;
+    }
+    return JsonPointer::from_tokens(tokens.into_iter().rev());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    fn pointers(found: &[JsonPointer]) -> Vec<String> {
+        return found.iter().map(|pointer| pointer.to_string()).collect();
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        let found = find_duplicate_keys(r#"{"a": 1, "b": [{"x": 1, "x": 2, "x": 3}, {"y/z": 1, "y/z": 2}], "a": 2}"#).unwrap();
+        assert_eq!(pointers(&found), ["/b/0/x", "/b/1/y~1z", "/a"]);
+        assert_eq!(find_duplicate_keys(r#"[{"a": 1}, {"a": 2}]"#), Ok(vec![]));
+        assert_eq!(find_duplicate_keys(r#"{"a": 1, "a"}"#).unwrap_err().code(), "E008");
+
+        let found = find_duplicate_keys(r#"{"a": {"z": 1, "z": 2}, "a": [{"z": 1, "z": 2}]}"#).unwrap();
+        assert_eq!(pointers(&found), ["/a/z", "/a", "/a/0/z"]);
+
+        let input = "// settings\n{\"a\": 1, \"b\": {\"c\": 1, \"c\": 2,},}";
+        assert!(find_duplicate_keys(input).is_err());
+        let options = ParseOptions { strictness: crate::parser::Strictness::Lenient, ..ParseOptions::default() };
+        assert_eq!(pointers(&find_duplicate_keys_with(input, &options).unwrap()), ["/b/c"]);
+    }
+
+    #[test]
+    fn duplicate_values() {
+        let item = parse(r#"{"rows": [{"meta": {"v": 1, "tags": ["x"]}, "id": 1}, {"meta": {"v": 1, "tags": ["x"]}, "id": 2}], "tag": "x", "other": "x"}"#).unwrap();
+        let groups: Vec<Vec<String>> = item.find_duplicate_values(3).iter().map(|group| pointers(group)).collect();
+        assert_eq!(groups, [vec!["/other", "/rows/0/meta/tags/0", "/rows/1/meta/tags/0", "/tag"], vec!["/rows/0/meta", "/rows/1/meta"]]);
+        // the ids are too short to be counted, and each meta's members only repeat with it
+        assert_eq!(item.find_duplicate_values(10).len(), 1);
+        assert!(parse("[1, 2, 3]").unwrap().find_duplicate_values(0).is_empty());
+
+        // sizes and equality follow canonical text, escaping and key order included
+        let item = parse(r#"{"a": [{"q\"": [1.0, "\u00e9"], "p": null}], "b": {"p": null, "q\"": [1, "é"]}, "c": {"p": null, "q": [1, "é"]}}"#).unwrap();
+        let size = item.pointer("/b").unwrap().to_canonical_string().len();
+        let groups: Vec<Vec<String>> = item.find_duplicate_values(size).iter().map(|group| pointers(group)).collect();
+        assert_eq!(groups, [vec!["/a/0", "/b"]]);
+        assert!(item.find_duplicate_values(size + 1).is_empty());
+    }
+}
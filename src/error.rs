@@ -0,0 +1,493 @@
+use alloc::{format, string::{String, ToString}, vec::Vec};
+use core::{error::Error, fmt, num::ParseFloatError, ops::Range};
+
+use crate::lexer::Token;
+
+/// What went wrong while lexing or parsing.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    UnexpectedCharacter(char),
+    UnterminatedString,
+    UnknownKeyword(String),
+    InvalidNumber { text: String, source: ParseFloatError },
+    /// A token that cannot start a value.
+    UnexpectedToken(Token),
+    ExpectedKey(Token),
+    DuplicateKey(String),
+    /// A specific token was required; `found` is `None` at the end of the input.
+    Expected { expected: Token, found: Option<Token> },
+    UnexpectedEof,
+    UnterminatedArray,
+    UnterminatedObject,
+    TrailingTokens,
+    InvalidEscape(String),
+    /// A number `Strictness::Strict` rejects, such as `01` or `1.`.
+    NonConformingNumber(String),
+    /// An unescaped control character in a string under `Strictness::Strict`.
+    ControlCharacter(char),
+    UnterminatedComment,
+    InvalidUtf8,
+    /// Reading the input failed. Only the file functions report this, and they place it
+    /// at the start of the input.
+    #[cfg(feature = "std")]
+    Io { kind: std::io::ErrorKind, message: String },
+    /// `ParseOptions::deadline` passed before parsing finished.
+    Timeout,
+    /// A `ParseOptions::hooks` function refused a value or key, giving this reason.
+    Rejected(String),
+    /// An object has more members than `ParseOptions::max_object_members` allows.
+    TooManyMembers(usize),
+    /// An array has more elements than `ParseOptions::max_array_elements` allows.
+    TooManyElements(usize),
+    /// A value that an output format cannot hold, as when exporting anything but an array
+    /// of objects to CSV. Exports report it at the start, as there is no input to point at.
+    Unrepresentable(String),
+    /// Events that do not form exactly one value, given to `events_to_item`. Reported at
+    /// the start, as the events may not come from any input.
+    InvalidEvents(String),
+    /// Input that `csv::from_csv` cannot read, reported at the record or quote at fault.
+    InvalidCsv(String),
+}
+
+impl ErrorKind {
+    /// A stable identifier for this kind of error. Codes are never renumbered or reused,
+    /// so they can key documentation, translations, or suppression lists.
+    pub fn code(&self) -> &'static str {
+        return match self {
+            ErrorKind::UnterminatedString => "E001",
+            ErrorKind::UnexpectedCharacter(_) => "E002",
+            ErrorKind::UnknownKeyword(_) => "E003",
+            ErrorKind::InvalidNumber { .. } => "E004",
+            ErrorKind::UnexpectedToken(_) => "E005",
+            ErrorKind::ExpectedKey(_) => "E006",
+            ErrorKind::DuplicateKey(_) => "E007",
+            ErrorKind::Expected { .. } => "E008",
+            ErrorKind::UnexpectedEof => "E009",
+            ErrorKind::UnterminatedArray => "E010",
+            ErrorKind::UnterminatedObject => "E011",
+            ErrorKind::TrailingTokens => "E012",
+            ErrorKind::InvalidEscape(_) => "E013",
+            ErrorKind::NonConformingNumber(_) => "E014",
+            ErrorKind::ControlCharacter(_) => "E015",
+            ErrorKind::UnterminatedComment => "E016",
+            ErrorKind::InvalidUtf8 => "E017",
+            #[cfg(feature = "std")]
+            ErrorKind::Io { .. } => "E018",
+            ErrorKind::Timeout => "E019",
+            ErrorKind::Rejected(_) => "E020",
+            ErrorKind::TooManyMembers(_) => "E021",
+            ErrorKind::TooManyElements(_) => "E022",
+            ErrorKind::Unrepresentable(_) => "E023",
+            ErrorKind::InvalidEvents(_) => "E024",
+            ErrorKind::InvalidCsv(_) => "E025",
+        };
+    }
+
+    /// Replacement text that would likely fix the input, where one can be guessed. For an
+    /// unknown keyword this is the keyword it is closest to, as `true` for `Ture` or
+    /// `null` for `None`, or otherwise the word quoted as a string.
+    pub fn suggestion(&self) -> Option<String> {
+        return match self {
+            ErrorKind::UnknownKeyword(word) => Some(keyword_suggestion(word)),
+            _ => None,
+        };
+    }
+
+    /// A few words to print under the offending text.
+    pub fn label(&self) -> &'static str {
+        return match self {
+            ErrorKind::UnexpectedCharacter(_) => "unexpected character",
+            ErrorKind::UnterminatedString => "string is never closed",
+            ErrorKind::UnknownKeyword(_) => "unknown keyword",
+            ErrorKind::InvalidNumber { .. } => "invalid number",
+            ErrorKind::UnexpectedToken(_) => "expected a value",
+            ErrorKind::ExpectedKey(_) => "expected a string key",
+            ErrorKind::DuplicateKey(_) => "key already used in this object",
+            ErrorKind::Expected { found: Some(_), .. } => "unexpected token",
+            ErrorKind::Expected { found: None, .. } | ErrorKind::UnexpectedEof => "unexpected end of input",
+            ErrorKind::UnterminatedArray => "array is never closed",
+            ErrorKind::UnterminatedObject => "object is never closed",
+            ErrorKind::TrailingTokens => "unexpected content after the value",
+            ErrorKind::InvalidEscape(_) => "invalid escape",
+            ErrorKind::NonConformingNumber(_) => "not an RFC 8259 number",
+            ErrorKind::ControlCharacter(_) => "control character must be escaped",
+            ErrorKind::UnterminatedComment => "comment is never closed",
+            ErrorKind::InvalidUtf8 => "invalid UTF-8",
+            #[cfg(feature = "std")]
+            ErrorKind::Io { .. } => "could not read input",
+            ErrorKind::Timeout => "gave up here",
+            ErrorKind::Rejected(_) => "rejected",
+            ErrorKind::TooManyMembers(_) => "one member too many",
+            ErrorKind::TooManyElements(_) => "one element too many",
+            ErrorKind::Unrepresentable(_) => "cannot be written",
+            ErrorKind::InvalidEvents(_) => "events do not form a value",
+            ErrorKind::InvalidCsv(_) => "invalid csv",
+        };
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            ErrorKind::UnexpectedCharacter(c) => write!(f, "Unknown symbol '{}'", c),
+            ErrorKind::UnterminatedString => write!(f, "unterminated string literal. reached EOF."),
+            ErrorKind::UnknownKeyword(keyword) => match keyword_suggestion(keyword) {
+                quoted if quoted.starts_with('"') => write!(f, "unknown keyword '{}', did you mean the string {}?", keyword, quoted),
+                suggestion => write!(f, "unknown keyword '{}', did you mean '{}'?", keyword, suggestion),
+            },
+            ErrorKind::InvalidNumber { text, .. } => write!(f, "invalid number literal '{}'.", text),
+            ErrorKind::UnexpectedToken(token) => write!(f, "Unexpected '{:?}' during parse.", token),
+            ErrorKind::ExpectedKey(token) => write!(f, "expected string key for jobject but got {:?}", token),
+            ErrorKind::DuplicateKey(key) => write!(f, "duplicate key found in jobject: '{}'", key),
+            ErrorKind::Expected { expected, found: Some(found) } => write!(f, "Unexpected token during parse. Expected {:?} but got {:?}", expected, found),
+            ErrorKind::Expected { expected, found: None } => write!(f, "Unexpected EOF during parse. Expected {:?} but got EOF.", expected),
+            ErrorKind::UnexpectedEof => write!(f, "tried to parse JItem, but got EOF."),
+            ErrorKind::UnterminatedArray => write!(f, "unexpected EOF during parse of array."),
+            ErrorKind::UnterminatedObject => write!(f, "unexpected EOF during parse of object."),
+            ErrorKind::TrailingTokens => write!(f, "Parsing finished with tokens left."),
+            ErrorKind::InvalidEscape(escape) => write!(f, "invalid escape sequence '{}'", escape),
+            ErrorKind::NonConformingNumber(text) => write!(f, "number literal '{}' is not valid RFC 8259 JSON", text),
+            ErrorKind::ControlCharacter(c) => write!(f, "unescaped control character {:?} in string", c),
+            ErrorKind::UnterminatedComment => write!(f, "unterminated block comment. reached EOF."),
+            ErrorKind::InvalidUtf8 => write!(f, "input is not valid UTF-8"),
+            #[cfg(feature = "std")]
+            ErrorKind::Io { message, .. } => write!(f, "{}", message),
+            ErrorKind::Timeout => write!(f, "parsing did not finish before the deadline"),
+            ErrorKind::Rejected(reason) => write!(f, "rejected: {}", reason),
+            ErrorKind::TooManyMembers(limit) => write!(f, "object has more than {} members", limit),
+            ErrorKind::TooManyElements(limit) => write!(f, "array has more than {} elements", limit),
+            ErrorKind::Unrepresentable(message) => write!(f, "{}", message),
+            ErrorKind::InvalidEvents(message) => write!(f, "{}", message),
+            ErrorKind::InvalidCsv(message) => write!(f, "{}", message),
+        };
+    }
+}
+
+fn keyword_suggestion(word: &str) -> String {
+    let lower = word.to_ascii_lowercase();
+    if matches!(lower.as_str(), "none" | "nil" | "undefined") {
+        return "null".to_string();
+    }
+    let closest = ["true", "false", "null"].into_iter().map(|keyword| (edit_distance(&lower, keyword), keyword)).min();
+    return match closest {
+        // one edit for four letter keywords, two for `false`
+        Some((distance, keyword)) if distance * 2 < keyword.len() => keyword.to_string(),
+        _ => format!("\"{}\"", word),
+    };
+}
+
+/// Edits to turn `a` into `b`, counting a swap of neighbouring characters as one.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    // distance[n][m] is the distance between the first n characters of a and m of b
+    let mut distance = alloc::vec![alloc::vec![0; b.len() + 1]; a.len() + 1];
+    for (n, row) in distance.iter_mut().enumerate() {
+        row[0] = n;
+    }
+    distance[0] = (0..=b.len()).collect();
+    for n in 1..=a.len() {
+        for m in 1..=b.len() {
+            let substitute = distance[n - 1][m - 1] + usize::from(a[n - 1] != b[m - 1]);
+            let mut best = substitute.min(distance[n - 1][m] + 1).min(distance[n][m - 1] + 1);
+            if n > 1 && m > 1 && a[n - 1] == b[m - 2] && a[n - 2] == b[m - 1] {
+                best = best.min(distance[n - 2][m - 2] + 1);
+            }
+            distance[n][m] = best;
+        }
+    }
+    return distance[a.len()][b.len()];
+}
+
+/// An error from `parse`, with where in the input it happened. Lines and columns
+/// count from 1, and columns count characters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonError {
+    kind: ErrorKind,
+    span: Range<usize>,
+    line: usize,
+    column: usize,
+}
+
+impl JsonError {
+    pub(crate) fn new(kind: ErrorKind, source: &str, span: Range<usize>) -> Self {
+        let (line, column) = line_column(source, span.start);
+        return JsonError { kind, span, line, column };
+    }
+
+    /// Reads `bytes` as UTF-8, pointing an error at the first invalid byte.
+    pub(crate) fn check_utf8(bytes: &[u8]) -> Result<&str, JsonError> {
+        return core::str::from_utf8(bytes).map_err(|e| {
+            let valid = e.valid_up_to();
+            let source = core::str::from_utf8(&bytes[..valid]).unwrap();
+            JsonError::new(ErrorKind::InvalidUtf8, source, valid..valid + e.error_len().unwrap_or(bytes.len() - valid))
+        });
+    }
+
+    /// Moves an error found in one line of a larger input, starting at byte `start` and
+    /// with zero-based index `line`, to its place in that input.
+    pub(crate) fn within_line(mut self, start: usize, line: usize) -> Self {
+        self.span = self.span.start + start..self.span.end + start;
+        self.line = line + 1;
+        return self;
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        return &self.kind;
+    }
+
+    /// See `ErrorKind::code`.
+    pub fn code(&self) -> &'static str {
+        return self.kind.code();
+    }
+
+    /// The byte range of the offending text. It is empty at the end of the input.
+    pub fn span(&self) -> Range<usize> {
+        return self.span.clone();
+    }
+
+    pub fn offset(&self) -> usize {
+        return self.span.start;
+    }
+
+    pub fn line(&self) -> usize {
+        return self.line;
+    }
+
+    pub fn column(&self) -> usize {
+        return self.column;
+    }
+
+    /// Renders the error against the `source` it came from: the message, the offending
+    /// line, and a caret underline with a short label.
+    ///
+    /// ```text
+    /// error[E002]: Unknown symbol '@'
+    ///  --> line 2, column 8
+    ///   |
+    /// 2 |   "a": @
+    ///   |        ^ unexpected character
+    /// ```
+    pub fn render(&self, source: &str) -> String {
+        let line_start = source[..self.span.start].rfind('\n').map_or(0, |n| n + 1);
+        let line_end = source[self.span.start..].find('\n').map_or(source.len(), |n| self.span.start + n);
+        let text = source[line_start..line_end].trim_end_matches('\r');
+        // keep tabs so the caret lines up however the terminal renders them
+        let padding: String = source[line_start..self.span.start].chars().map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+        let underlined = source[self.span.start..self.span.end.clamp(self.span.start, line_end)].chars().count();
+        let number = self.line.to_string();
+        let gutter = " ".repeat(number.len());
+        return format!(
+            "error[{}]: {}\n{}--> line {}, column {}\n{} |\n{} | {}\n{} | {}{} {}\n",
+            self.code(), self.kind, gutter, self.line, self.column, gutter, number, text, gutter, padding, "^".repeat(underlined.max(1)), self.kind.label()
+        );
+    }
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "{} at line {}, column {}", self.kind, self.line, self.column);
+    }
+}
+
+/// The source of an invalid number is the `ParseFloatError` from reading its text.
+impl Error for JsonError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        return match &self.kind {
+            ErrorKind::InvalidNumber { source, .. } => Some(source),
+            _ => None,
+        };
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for JsonError {
+    fn from(error: std::io::Error) -> Self {
+        return JsonError::new(ErrorKind::Io { kind: error.kind(), message: error.to_string() }, "", 0..0);
+    }
+}
+
+impl From<JsonError> for String {
+    fn from(error: JsonError) -> Self {
+        return error.to_string();
+    }
+}
+
+/// A recoverable oddity that a parse fixed up rather than failing on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WarningKind {
+    /// A repeated object key, resolved by `ParseOptions::duplicate_keys`.
+    DuplicateKey(String),
+    /// A number literal that no `f64` represents exactly.
+    LostPrecision(String),
+    /// A `\u` escape of an unpaired surrogate, replaced with U+FFFD.
+    LoneSurrogate,
+}
+
+impl WarningKind {
+    /// A stable identifier, like `ErrorKind::code`.
+    pub fn code(&self) -> &'static str {
+        return match self {
+            WarningKind::DuplicateKey(_) => "W001",
+            WarningKind::LostPrecision(_) => "W002",
+            WarningKind::LoneSurrogate => "W003",
+        };
+    }
+}
+
+impl fmt::Display for WarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            WarningKind::DuplicateKey(key) => write!(f, "duplicate key '{}'", key),
+            WarningKind::LostPrecision(text) => write!(f, "number '{}' lost precision", text),
+            WarningKind::LoneSurrogate => write!(f, "unpaired surrogate replaced with U+FFFD"),
+        };
+    }
+}
+
+/// A warning from `parse_with_warnings`, positioned like `JsonError`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    kind: WarningKind,
+    span: Range<usize>,
+    line: usize,
+    column: usize,
+}
+
+impl Warning {
+    pub(crate) fn new(kind: WarningKind, source: &str, span: Range<usize>) -> Self {
+        let (line, column) = line_column(source, span.start);
+        return Warning { kind, span, line, column };
+    }
+
+    pub fn kind(&self) -> &WarningKind {
+        return &self.kind;
+    }
+
+    pub fn code(&self) -> &'static str {
+        return self.kind.code();
+    }
+
+    pub fn span(&self) -> Range<usize> {
+        return self.span.clone();
+    }
+
+    pub fn line(&self) -> usize {
+        return self.line;
+    }
+
+    pub fn column(&self) -> usize {
+        return self.column;
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "{} at line {}, column {}", self.kind, self.line, self.column);
+    }
+}
+
+/// The 1-based line and character column of a byte offset.
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |n| n + 1);
+    return (line, before[line_start..].chars().count() + 1);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn error_positions() {
+        let source = "{\n  \"é\": @\n}";
+        let error = parse(source).unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::UnexpectedCharacter('@'));
+        assert_eq!((error.line(), error.column(), error.offset()), (2, 8, 10));
+        assert_eq!(error.to_string(), "Unknown symbol '@' at line 2, column 8");
+        assert_eq!(error.code(), "E002");
+    }
+
+    #[test]
+    fn error_source_chain() {
+        fn assert_send_sync<T: Send + Sync + 'static>() {}
+        assert_send_sync::<JsonError>();
+
+        let error = parse("[1e]").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::InvalidNumber { text: "1e".to_string(), source: "1e".parse::<f64>().unwrap_err() });
+        let source = error.source().unwrap();
+        assert!(source.is::<ParseFloatError>());
+        assert_eq!(source.to_string(), "invalid float literal");
+        assert!(parse("[1,").unwrap_err().source().is_none());
+
+        let boxed: Box<dyn Error + Send + Sync> = Box::new(error);
+        assert_eq!(boxed.to_string(), "invalid number literal '1e'. at line 1, column 2");
+    }
+
+    #[test]
+    fn error_codes() {
+        let code = |source: &str| parse(source).unwrap_err().code();
+        assert_eq!(code("\"abc"), "E001");
+        assert_eq!(code("1.2.3"), "E004");
+        assert_eq!(code("]"), "E005");
+        assert_eq!(code("{1: 2}"), "E006");
+        assert_eq!(code("{\"a\": 1, \"a\": 2}"), "E007");
+        assert_eq!(code("{\"a\" 1}"), "E008");
+        assert_eq!(code(""), "E009");
+        assert_eq!(code("{\"a\": 1,"), "E011");
+        assert_eq!(code("1 2"), "E012");
+    }
+
+    #[test]
+    fn invalid_utf8_position() {
+        let error = JsonError::check_utf8(b"[\"ok\",\n \"\xc3\"]").unwrap_err();
+        assert_eq!((error.code(), error.span(), error.line(), error.column()), ("E017", 9..10, 2, 3));
+        assert_eq!(JsonError::check_utf8(b"[\"\xf0\x9f"), Err(JsonError::new(ErrorKind::InvalidUtf8, "[\"", 2..4)));
+    }
+
+    #[test]
+    fn unknown_keyword_suggestions() {
+        let suggestion = |word: &str| ErrorKind::UnknownKeyword(word.to_string()).suggestion().unwrap();
+        for (word, expected) in [
+            ("Ture", "true"),
+            ("TRUE", "true"),
+            ("tru", "true"),
+            ("flase", "false"),
+            ("fals", "false"),
+            ("Fales", "false"),
+            ("NULL", "null"),
+            ("nul", "null"),
+            ("None", "null"),
+            ("nil", "null"),
+            ("undefined", "null"),
+            ("to", "\"to\""),
+            ("hello", "\"hello\""),
+            ("f", "\"f\""),
+        ] {
+            assert_eq!(suggestion(word), expected, "{}", word);
+        }
+        assert_eq!(ErrorKind::UnknownKeyword("Ture".to_string()).to_string(), "unknown keyword 'Ture', did you mean 'true'?");
+        assert_eq!(ErrorKind::UnknownKeyword("hi".to_string()).to_string(), "unknown keyword 'hi', did you mean the string \"hi\"?");
+        assert_eq!(ErrorKind::UnexpectedEof.suggestion(), None);
+    }
+
+    #[test]
+    fn render_caret_under_span() {
+        let source = "{\n  \"a\": tru,\n}";
+        let error = parse(source).unwrap_err();
+        assert_eq!(error.render(source), "error[E003]: unknown keyword 'tru', did you mean 'true'?\n --> line 2, column 8\n  |\n2 |   \"a\": tru,\n  |        ^^^ unknown keyword\n");
+    }
+
+    #[test]
+    fn render_at_end_of_input() {
+        let source = "[1, 2";
+        let error = parse(source).unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::UnterminatedArray);
+        assert_eq!(error.render(source), "error[E010]: unexpected EOF during parse of array.\n --> line 1, column 1\n  |\n1 | [1, 2\n  | ^ array is never closed\n");
+
+        let source = "{\"a\":\t\"b";
+        let error = parse(source).unwrap_err();
+        assert_eq!(error.render(source), "error[E001]: unterminated string literal. reached EOF.\n --> line 1, column 7\n  |\n1 | {\"a\":\t\"b\n  |      \t^^ string is never closed\n");
+    }
+}
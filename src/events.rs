@@ -0,0 +1,382 @@
+//! A pull parser, for reading documents as a stream of events rather than a tree.
+//!
+//! Events follow the same rules as `validate`: syntax is checked as `parse` checks it
+//! with the default options, but duplicate keys are not. Tokens borrow from the input,
+//! so nothing is decoded or allocated unless asked for, and only the open brackets are
+//! remembered, so nesting depth is not limited by the call stack.
+
+use alloc::{string::{String, ToString}, vec, vec::Vec};
+use core::ops::Range;
+
+use crate::{
+    error::{ErrorKind, JsonError},
+    j_item::JItem,
+    lexer::{self, lex},
+    tokenizer::{Token, TokenKind as Kind, Tokenizer},
+    HashMap,
+};
+
+#[derive(Clone, Copy)]
+enum State {
+    Start,
+    Value,
+    AfterValue,
+    ArrayStart,
+    ObjectStart,
+    Done,
+}
+
+/// What `Events` reads, in document order. Brackets carry their own spans.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event<'a> {
+    StartObject(Range<usize>),
+    EndObject(Range<usize>),
+    StartArray(Range<usize>),
+    EndArray(Range<usize>),
+    /// An object key, just before its value.
+    Key(Token<'a>),
+    /// A string, number, boolean or null.
+    Scalar(Token<'a>),
+}
+
+/// The events of a document, in order. Nothing more is returned after an error.
+pub struct Events<'a> {
+    scanner: Scanner<'a>,
+    open: Vec<(Kind, Range<usize>)>,
+    token: Option<Token<'a>>,
+    state: State,
+    /// An event read ahead by `skip_value` that was not part of a value.
+    peeked: Option<Event<'a>>,
+}
+
+impl<'a> Events<'a> {
+    pub fn new(s: &'a str) -> Self {
+        return Events { scanner: Scanner { s, tokens: Tokenizer::new(s), last_end: 0 }, open: vec![], token: None, state: State::Start, peeked: None };
+    }
+
+    /// Reads past the value that comes next, without returning its events, and returns
+    /// its span. Call it where a value may start: at the beginning, after a `Key`, or
+    /// inside an array. Returns `None`, leaving the next event in place, when no value
+    /// comes next, as at the end of an array. The value is checked as fully as any
+    /// other, but only a count of open brackets is kept while skipping it.
+    pub fn skip_value(&mut self) -> Result<Option<Range<usize>>, JsonError> {
+        let start = match self.next().transpose()? {
+            Some(Event::Scalar(token)) => return Ok(Some(token.span)),
+            Some(Event::StartObject(span) | Event::StartArray(span)) => span.start,
+            other => {
+                self.peeked = other;
+                return Ok(None);
+            },
+        };
+        let mut depth = 1;
+        loop {
+            match self.next().transpose()? {
+                Some(Event::StartObject(_) | Event::StartArray(_)) => depth += 1,
+                Some(Event::EndObject(span) | Event::EndArray(span)) => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(Some(start..span.end));
+                    }
+                },
+                Some(Event::Key(_) | Event::Scalar(_)) => {},
+                // every bracket is closed before the events end
+                None => return Ok(Some(start..self.scanner.last_end)),
+            }
+        }
+    }
+
+    fn step(&mut self) -> Result<Option<Event<'a>>, JsonError> {
+        let scanner = &mut self.scanner;
+        loop {
+            let (state, event) = match self.state {
+                State::Done => return Ok(None),
+                State::Start => {
+                    self.token = scanner.next()?;
+                    (State::Value, None)
+                },
+                State::Value => match self.token.take() {
+                    None => return Err(scanner.error(ErrorKind::UnexpectedEof, scanner.eof())),
+                    Some(Token { kind: kind @ (Kind::LBrace | Kind::LSquareBracket), span, .. }) => {
+                        self.open.push((kind, span.clone()));
+                        self.token = scanner.next()?;
+                        match kind {
+                            Kind::LBrace => (State::ObjectStart, Some(Event::StartObject(span))),
+                            _ => (State::ArrayStart, Some(Event::StartArray(span))),
+                        }
+                    },
+                    Some(token @ Token { kind: Kind::String | Kind::Number | Kind::True | Kind::False | Kind::Null, .. }) => (State::AfterValue, Some(Event::Scalar(token))),
+                    Some(token) => return Err(scanner.error(ErrorKind::UnexpectedToken(scanner.token(&token.span)), token.span)),
+                },
+                State::ArrayStart => match &self.token {
+                    Some(Token { kind: Kind::RSquareBracket, span, .. }) => {
+                        self.open.pop();
+                        (State::AfterValue, Some(Event::EndArray(span.clone())))
+                    },
+                    None => return Err(scanner.error(ErrorKind::UnterminatedArray, self.open.pop().unwrap().1)),
+                    Some(_) => (State::Value, None),
+                },
+                State::ObjectStart => match self.token.take() {
+                    Some(Token { kind: Kind::RBrace, span, .. }) => {
+                        self.open.pop();
+                        (State::AfterValue, Some(Event::EndObject(span)))
+                    },
+                    None => return Err(scanner.error(ErrorKind::UnterminatedObject, self.open.pop().unwrap().1)),
+                    Some(key @ Token { kind: Kind::String, .. }) => {
+                        scanner.expect(Kind::Colon, lexer::Token::Colon)?;
+                        self.token = scanner.next()?;
+                        (State::Value, Some(Event::Key(key)))
+                    },
+                    Some(token) => return Err(scanner.error(ErrorKind::ExpectedKey(scanner.token(&token.span)), token.span)),
+                },
+                State::AfterValue => {
+                    self.token = scanner.next()?;
+                    let Some((container, opener)) = self.open.last().cloned() else {
+                        let Some(token) = &self.token else {
+                            self.state = State::Done;
+                            return Ok(None);
+                        };
+                        let start = token.span.start;
+                        // read to the end as the parser does: the error covers everything left, and a
+                        // lex error further on is reported instead
+                        while scanner.next()?.is_some() {}
+                        return Err(scanner.error(ErrorKind::TrailingTokens, start..scanner.last_end));
+                    };
+                    let (close, unterminated, start) = match container {
+                        Kind::LBrace => (Kind::RBrace, ErrorKind::UnterminatedObject, State::ObjectStart),
+                        _ => (Kind::RSquareBracket, ErrorKind::UnterminatedArray, State::ArrayStart),
+                    };
+                    match self.token.take() {
+                        Some(token) if token.kind == close => {
+                            self.open.pop();
+                            let event = match close {
+                                Kind::RBrace => Event::EndObject(token.span),
+                                _ => Event::EndArray(token.span),
+                            };
+                            (State::AfterValue, Some(event))
+                        },
+                        None => return Err(scanner.error(unterminated, opener)),
+                        Some(Token { kind: Kind::Comma, .. }) => {
+                            self.token = scanner.next()?;
+                            (start, None)
+                        },
+                        Some(token) => {
+                            let found = Some(scanner.token(&token.span));
+                            return Err(scanner.error(ErrorKind::Expected { expected: lexer::Token::Comma, found }, token.span));
+                        },
+                    }
+                },
+            };
+            self.state = state;
+            if event.is_some() {
+                return Ok(event);
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for Events<'a> {
+    type Item = Result<Event<'a>, JsonError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(event) = self.peeked.take() {
+            return Some(Ok(event));
+        }
+        let event = self.step().transpose();
+        if let Some(Err(_)) = event {
+            self.state = State::Done;
+        }
+        return event;
+    }
+}
+
+/// Builds the value that `events` describe, so code written against events can also
+/// produce a tree. An error from `events` is returned as it is. A repeated key keeps its
+/// last value. Events that do not form exactly one value, which `Events` never gives,
+/// are an `ErrorKind::InvalidEvents`.
+pub fn events_to_item<'a>(events: impl IntoIterator<Item = Result<Event<'a>, JsonError>>) -> Result<JItem, JsonError> {
+    enum Open {
+        Array(Vec<JItem>),
+        Object(HashMap<String, JItem>, Option<String>),
+    }
+    let invalid = |message: &str| JsonError::new(ErrorKind::InvalidEvents(message.to_string()), "", 0..0);
+    let mut open: Vec<Open> = vec![];
+    for event in events {
+        let value = match event? {
+            Event::StartArray(_) => {
+                open.push(Open::Array(vec![]));
+                continue;
+            },
+            Event::StartObject(_) => {
+                open.push(Open::Object(HashMap::new(), None));
+                continue;
+            },
+            Event::Key(token) => {
+                let Some(Open::Object(_, key)) = open.last_mut() else {
+                    return Err(invalid("a key outside an object"));
+                };
+                let Some(name) = token.string_value() else {
+                    return Err(invalid("a key that is not a string"));
+                };
+                *key = Some(name.into_owned());
+                continue;
+            },
+            Event::EndArray(_) => match open.pop() {
+                Some(Open::Array(elements)) => JItem::Array(elements),
+                _ => return Err(invalid("the end of an array that was not started")),
+            },
+            Event::EndObject(_) => match open.pop() {
+                Some(Open::Object(hmap, _)) => JItem::Object(hmap),
+                _ => return Err(invalid("the end of an object that was not started")),
+            },
+            Event::Scalar(token) => scalar_item(&token),
+        };
+        match open.last_mut() {
+            None => return Ok(value),
+            Some(Open::Array(elements)) => elements.push(value),
+            Some(Open::Object(hmap, key)) => {
+                let Some(key) = key.take() else {
+                    return Err(invalid("a value in an object without a key"));
+                };
+                hmap.insert(key, value);
+            },
+        }
+    }
+    return Err(invalid("the events ended before the value did"));
+}
+
+fn scalar_item(token: &Token) -> JItem {
+    return match token.kind {
+        Kind::String => JItem::String(token.string_value().unwrap().into_owned()),
+        Kind::Number => JItem::Number(token.number_value().unwrap()),
+        Kind::True => JItem::Bool(true),
+        Kind::False => JItem::Bool(false),
+        _ => JItem::Null,
+    };
+}
+
+/// Calls `f` with the events of `item`, the same ones `Events` reads from its compact
+/// serialization, so code written against events can also take a tree. Spans point
+/// into that serialization rather than any original source.
+pub fn item_to_events(item: &JItem, mut f: impl FnMut(Event<'_>)) {
+    let text = item.to_string();
+    for event in Events::new(&text) {
+        f(event.expect("serialized items always parse"));
+    }
+}
+
+struct Scanner<'a> {
+    s: &'a str,
+    tokens: Tokenizer<'a>,
+    last_end: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn error(&self, kind: ErrorKind, span: Range<usize>) -> JsonError {
+        return JsonError::new(kind, self.s, span);
+    }
+
+    fn eof(&self) -> Range<usize> {
+        return self.last_end..self.last_end;
+    }
+
+    /// The full token at `span`, for error messages.
+    fn token(&self, span: &Range<usize>) -> lexer::Token {
+        return lex(&self.s[span.clone()]).unwrap().remove(0);
+    }
+
+    fn expect(&mut self, kind: Kind, expected: lexer::Token) -> Result<(), JsonError> {
+        return match self.next()? {
+            Some(next) if next.kind == kind => Ok(()),
+            Some(next) => Err(self.error(ErrorKind::Expected { expected, found: Some(self.token(&next.span)) }, next.span)),
+            None => Err(self.error(ErrorKind::Expected { expected, found: None }, self.eof())),
+        };
+    }
+
+    fn next(&mut self) -> Result<Option<Token<'a>>, JsonError> {
+        let Some(token) = self.tokens.next().transpose()? else {
+            return Ok(None);
+        };
+        self.last_end = token.span.end;
+        return Ok(Some(token));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn events_in_order() {
+        let input = r#"{"a": [1, {}], "b": null,}"#;
+        let events: Vec<String> = Events::new(input).map(|event| match event.unwrap() {
+            Event::StartObject(span) | Event::StartArray(span) | Event::EndObject(span) | Event::EndArray(span) => input[span].to_string(),
+            Event::Key(token) => format!("key {}", token.as_str()),
+            Event::Scalar(token) => token.as_str().to_string(),
+        }).collect();
+        assert_eq!(events, ["{", "key \"a\"", "[", "1", "{", "}", "]", "key \"b\"", "null", "}"]);
+
+        let mut events = Events::new("[1, 2 3]");
+        assert_eq!(events.by_ref().filter(Result::is_ok).count(), 3);
+        assert_eq!(events.next(), None);
+    }
+
+    #[test]
+    fn events_and_trees() {
+        let input = r#"{"a": [1, {"b": "x\ny"}], "c": null, "d": [true, false, -2.5e3]}"#;
+        let item = events_to_item(Events::new(input)).unwrap();
+        assert_eq!(item, crate::parse(input).unwrap());
+        assert_eq!(events_to_item(Events::new(r#"{"a": [1 2]}"#)).unwrap_err().code(), "E008");
+        assert_eq!(events_to_item([Ok(Event::EndArray(0..1))]).unwrap_err().code(), "E024");
+        assert_eq!(events_to_item(Events::new("[1, 2]").take(2)).unwrap_err().code(), "E024");
+
+        let mut keys = vec![];
+        item_to_events(&item, |event| {
+            if let Event::Key(token) = event {
+                keys.push(token.string_value().unwrap().to_string());
+            }
+        });
+        keys.sort();
+        assert_eq!(keys, ["a", "b", "c", "d"]);
+
+        // the same event handler, given a stream and a tree
+        let describe = |event: Event<'_>, out: &mut Vec<String>| match event {
+            Event::Scalar(token) => out.push(token.as_str().to_string()),
+            Event::Key(_) => {},
+            _ => out.push("|".to_string()),
+        };
+        let (mut streamed, mut from_tree) = (vec![], vec![]);
+        Events::new(r#"[1, ["a", {"k": null}]]"#).for_each(|event| describe(event.unwrap(), &mut streamed));
+        item_to_events(&crate::parse(r#"[1, ["a", {"k": null}]]"#).unwrap(), |event| describe(event, &mut from_tree));
+        assert_eq!(streamed, ["|", "1", "|", "\"a\"", "|", "null", "|", "|", "|"]);
+        assert_eq!(streamed, from_tree);
+    }
+
+    #[test]
+    fn skip_values() {
+        let input = r#"{"skip": {"a": [1, {"b": [[]]}]}, "keep": 2, "list": ["x", [3]]}"#;
+        let mut events = Events::new(input);
+        assert_eq!(events.skip_value(), Ok(Some(0..input.len())));
+        assert_eq!(events.next(), None);
+
+        let mut events = Events::new(input);
+        assert!(matches!(events.next(), Some(Ok(Event::StartObject(_)))));
+        assert!(matches!(events.next(), Some(Ok(Event::Key(key))) if key.as_str() == "\"skip\""));
+        let span = events.skip_value().unwrap().unwrap();
+        assert_eq!(&input[span], r#"{"a": [1, {"b": [[]]}]}"#);
+        assert!(matches!(events.next(), Some(Ok(Event::Key(key))) if key.as_str() == "\"keep\""));
+        assert!(matches!(events.next(), Some(Ok(Event::Scalar(token))) if token.as_str() == "2"));
+        events.next().unwrap().unwrap();
+        assert!(matches!(events.next(), Some(Ok(Event::StartArray(_)))));
+        assert_eq!(events.skip_value().unwrap().map(|span| &input[span]), Some("\"x\""));
+        assert_eq!(events.skip_value().unwrap().map(|span| &input[span]), Some("[3]"));
+        assert_eq!(events.skip_value(), Ok(None));
+        assert!(matches!(events.next(), Some(Ok(Event::EndArray(_)))));
+        assert!(matches!(events.next(), Some(Ok(Event::EndObject(_)))));
+        assert_eq!(events.skip_value(), Ok(None));
+        assert_eq!(events.next(), None);
+
+        let mut events = Events::new(r#"[{"a": [1 2]}]"#);
+        events.next();
+        assert_eq!(events.skip_value().unwrap_err().span(), 10..11);
+    }
+}
@@ -0,0 +1,365 @@
+//! A C ABI for embedding the parser in C and C++ programs. The matching header is
+//! `include/sjp.h`, generated from this module with `cbindgen --config cbindgen.toml`.
+//! Build a library to link against with
+//! `cargo rustc --release --features ffi --crate-type staticlib` (or `cdylib`).
+//!
+//! A parse returns an owned `SjpValue` handle to release with `sjp_free`. Values reached
+//! through it, such as array elements, are borrowed and live as long as that handle.
+//! Strings handed out by getters are borrowed UTF-8 with an explicit length and no NUL
+//! terminator; strings the caller owns are NUL-terminated and released with
+//! `sjp_string_free`.
+
+use std::{
+    collections::hash_map,
+    ffi::{CString, c_char},
+    ptr, slice,
+};
+
+use crate::{format::FormatOptions, j_item::JItem};
+
+/// An opaque JSON value.
+#[repr(transparent)]
+pub struct SjpValue(JItem);
+
+/// An opaque iterator over an object's members.
+// the lifetime is really the object's, which C has to keep alive until the iterator is freed
+pub struct SjpMembers(hash_map::Iter<'static, String, JItem>);
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SjpType {
+    Null,
+    Bool,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+/// Why a parse failed. Zeroed fields mean no error.
+#[repr(C)]
+pub struct SjpError {
+    /// The number of the error's code, such as 1 for `E001`, or 0 when the input was not
+    /// UTF-8.
+    pub code: u32,
+    pub line: usize,
+    pub column: usize,
+    pub offset: usize,
+    /// The message, owned by this struct until `sjp_error_free`.
+    pub message: *mut c_char,
+}
+
+/// Parses `len` bytes of UTF-8 at `input`. Returns null on failure, filling in `error`
+/// when it is not null; on success a non-null `error` is zeroed.
+///
+/// # Safety
+/// `input` must point to `len` readable bytes, and `error` must be null or valid to write.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sjp_parse(input: *const c_char, len: usize, error: *mut SjpError) -> *mut SjpValue {
+    let bytes = unsafe { bytes(input, len) };
+    let result = match std::str::from_utf8(bytes) {
+        Ok(input) => crate::parse(input).map_err(|e| (e.code()[1..].parse().unwrap(), e.line(), e.column(), e.offset(), e.to_string())),
+        Err(e) => Err((0, 0, 0, e.valid_up_to(), "input is not valid UTF-8".to_string())),
+    };
+    return match result {
+        Ok(item) => {
+            if !error.is_null() {
+                unsafe { error.write(SjpError { code: 0, line: 0, column: 0, offset: 0, message: ptr::null_mut() }) };
+            }
+            Box::into_raw(Box::new(SjpValue(item)))
+        },
+        Err((code, line, column, offset, message)) => {
+            if !error.is_null() {
+                let message = CString::new(message).unwrap_or_default().into_raw();
+                unsafe { error.write(SjpError { code, line, column, offset, message }) };
+            }
+            ptr::null_mut()
+        },
+    };
+}
+
+/// Releases a value returned by `sjp_parse`. Null is ignored.
+///
+/// # Safety
+/// `value` must be null or an owned handle from `sjp_parse` not yet freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sjp_free(value: *mut SjpValue) {
+    if !value.is_null() {
+        drop(unsafe { Box::from_raw(value) });
+    }
+}
+
+/// Releases an error's message and zeroes it.
+///
+/// # Safety
+/// `error` must be null or an error filled in by `sjp_parse`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sjp_error_free(error: *mut SjpError) {
+    let Some(error) = (unsafe { error.as_mut() }) else {
+        return;
+    };
+    unsafe { sjp_string_free(error.message) };
+    *error = SjpError { code: 0, line: 0, column: 0, offset: 0, message: ptr::null_mut() };
+}
+
+/// Releases a string returned by `sjp_to_string`. Null is ignored.
+///
+/// # Safety
+/// `s` must be null or a string from `sjp_to_string` not yet freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sjp_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(unsafe { CString::from_raw(s) });
+    }
+}
+
+/// # Safety
+/// `value` must be a live value handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sjp_type(value: *const SjpValue) -> SjpType {
+    return match unsafe { item(value) } {
+        JItem::Null => SjpType::Null,
+        JItem::Bool(_) => SjpType::Bool,
+        JItem::Number(_) | JItem::RawNumber(_) => SjpType::Number,
+        JItem::String(_) => SjpType::String,
+        JItem::Array(_) => SjpType::Array,
+        JItem::Object(_) => SjpType::Object,
+    };
+}
+
+/// Writes a boolean to `out`, returning false if the value is not one.
+///
+/// # Safety
+/// `value` must be a live value handle and `out` valid to write.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sjp_get_bool(value: *const SjpValue, out: *mut bool) -> bool {
+    let JItem::Bool(b) = (unsafe { item(value) }) else {
+        return false;
+    };
+    unsafe { out.write(*b) };
+    return true;
+}
+
+/// Writes a number to `out`, returning false if the value is not one.
+///
+/// # Safety
+/// `value` must be a live value handle and `out` valid to write.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sjp_get_number(value: *const SjpValue, out: *mut f64) -> bool {
+    let Some(x) = unsafe { item(value) }.number_value() else {
+        return false;
+    };
+    unsafe { out.write(x) };
+    return true;
+}
+
+/// A string's UTF-8 bytes with its length written to `len`, or null if the value is not
+/// a string.
+///
+/// # Safety
+/// `value` must be a live value handle and `len` valid to write.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sjp_get_string(value: *const SjpValue, len: *mut usize) -> *const c_char {
+    let JItem::String(s) = (unsafe { item(value) }) else {
+        return ptr::null();
+    };
+    unsafe { len.write(s.len()) };
+    return s.as_ptr().cast();
+}
+
+/// The number of elements or members, or 0 for other values.
+///
+/// # Safety
+/// `value` must be a live value handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sjp_len(value: *const SjpValue) -> usize {
+    return match unsafe { item(value) } {
+        JItem::Array(elements) => elements.len(),
+        JItem::Object(hmap) => hmap.len(),
+        _ => 0,
+    };
+}
+
+/// An array element, or null when out of range or not an array.
+///
+/// # Safety
+/// `value` must be a live value handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sjp_array_get(value: *const SjpValue, index: usize) -> *const SjpValue {
+    let JItem::Array(elements) = (unsafe { item(value) }) else {
+        return ptr::null();
+    };
+    return handle(elements.get(index));
+}
+
+/// An object member's value by key, or null when missing or not an object.
+///
+/// # Safety
+/// `value` must be a live value handle and `key` must point to `key_len` readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sjp_object_get(value: *const SjpValue, key: *const c_char, key_len: usize) -> *const SjpValue {
+    let (JItem::Object(hmap), Ok(key)) = (unsafe { item(value) }, std::str::from_utf8(unsafe { bytes(key, key_len) })) else {
+        return ptr::null();
+    };
+    return handle(hmap.get(key));
+}
+
+/// Starts iterating over an object's members, returning null if the value is not an
+/// object. Members come in an unspecified order. Release the iterator with
+/// `sjp_members_free` before the value it came from.
+///
+/// # Safety
+/// `value` must be a live value handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sjp_members(value: *const SjpValue) -> *mut SjpMembers {
+    let JItem::Object(hmap) = (unsafe { item(value) }) else {
+        return ptr::null_mut();
+    };
+    return Box::into_raw(Box::new(SjpMembers(hmap.iter())));
+}
+
+/// Advances to the next member, writing its key, the key's length and its value.
+/// Returns false, writing nothing, once every member has been visited.
+///
+/// # Safety
+/// `members` must be a live iterator from `sjp_members`, and `key`, `key_len` and
+/// `value` valid to write.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sjp_members_next(members: *mut SjpMembers, key: *mut *const c_char, key_len: *mut usize, value: *mut *const SjpValue) -> bool {
+    let Some((k, v)) = unsafe { &mut *members }.0.next() else {
+        return false;
+    };
+    unsafe {
+        key.write(k.as_ptr().cast());
+        key_len.write(k.len());
+        value.write(handle(Some(v)));
+    }
+    return true;
+}
+
+/// Releases an iterator returned by `sjp_members`. Null is ignored.
+///
+/// # Safety
+/// `members` must be null or an iterator from `sjp_members` not yet freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sjp_members_free(members: *mut SjpMembers) {
+    if !members.is_null() {
+        drop(unsafe { Box::from_raw(members) });
+    }
+}
+
+/// The value at a JSON Pointer, or null when there is none.
+///
+/// # Safety
+/// `value` must be a live value handle and `pointer` must point to `len` readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sjp_pointer(value: *const SjpValue, pointer: *const c_char, len: usize) -> *const SjpValue {
+    let Ok(pointer) = std::str::from_utf8(unsafe { bytes(pointer, len) }) else {
+        return ptr::null();
+    };
+    return handle(unsafe { item(value) }.pointer(pointer));
+}
+
+/// Serializes a value as a NUL-terminated string to release with `sjp_string_free`,
+/// compact or indented with two spaces and sorted keys.
+///
+/// # Safety
+/// `value` must be a live value handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sjp_to_string(value: *const SjpValue, pretty: bool) -> *mut c_char {
+    let options = match pretty {
+        true => FormatOptions::pretty(),
+        false => FormatOptions::compact(),
+    };
+    // serialized JSON escapes every control character, so it never holds a NUL
+    return CString::new(unsafe { item(value) }.to_string_with(&options)).unwrap().into_raw();
+}
+
+unsafe fn item<'a>(value: *const SjpValue) -> &'a JItem {
+    return unsafe { &(*value).0 };
+}
+
+fn handle(item: Option<&JItem>) -> *const SjpValue {
+    return item.map_or(ptr::null(), |item| ptr::from_ref(item).cast());
+}
+
+unsafe fn bytes<'a>(data: *const c_char, len: usize) -> &'a [u8] {
+    if len == 0 {
+        return &[];
+    }
+    return unsafe { slice::from_raw_parts(data.cast(), len) };
+}
+
+#[cfg(test)]
+mod test {
+    use std::ffi::CStr;
+
+    use super::*;
+
+    fn parse(input: &str) -> (*mut SjpValue, SjpError) {
+        let mut error = SjpError { code: 0, line: 0, column: 0, offset: 0, message: ptr::null_mut() };
+        let value = unsafe { sjp_parse(input.as_ptr().cast(), input.len(), &mut error) };
+        return (value, error);
+    }
+
+    unsafe fn text<'a>(data: *const c_char, len: usize) -> &'a str {
+        return std::str::from_utf8(unsafe { bytes(data, len) }).unwrap();
+    }
+
+    #[test]
+    fn ffi_parse_and_read() {
+        let (value, _) = parse(r#"{"name": "sjp", "tags": [true, 2.5], "nested": {"x": null}}"#);
+        unsafe {
+            assert_eq!(sjp_type(value), SjpType::Object);
+            assert_eq!(sjp_len(value), 3);
+
+            let mut len = 0;
+            let name = sjp_object_get(value, "name".as_ptr().cast(), 4);
+            assert_eq!(text(sjp_get_string(name, &mut len), len), "sjp");
+
+            let tags = sjp_pointer(value, "/tags".as_ptr().cast(), 5);
+            let (mut b, mut x) = (false, 0.0);
+            assert!(sjp_get_bool(sjp_array_get(tags, 0), &mut b) && b);
+            assert!(sjp_get_number(sjp_array_get(tags, 1), &mut x) && x == 2.5);
+            assert!(!sjp_get_number(sjp_array_get(tags, 0), &mut x));
+            assert!(sjp_array_get(tags, 2).is_null());
+
+            assert!(sjp_members(tags).is_null());
+            let members = sjp_members(value);
+            let (mut key, mut member) = (ptr::null(), ptr::null());
+            let mut keys = Vec::new();
+            while sjp_members_next(members, &mut key, &mut len, &mut member) {
+                keys.push((text(key, len), sjp_type(member)));
+            }
+            sjp_members_free(members);
+            keys.sort_by_key(|(key, _)| *key);
+            assert_eq!(keys, [("name", SjpType::String), ("nested", SjpType::Object), ("tags", SjpType::Array)]);
+
+            let json = sjp_to_string(sjp_pointer(value, "/nested".as_ptr().cast(), 7), false);
+            assert_eq!(CStr::from_ptr(json).to_str(), Ok(r#"{"x":null}"#));
+            sjp_string_free(json);
+            sjp_free(value);
+        }
+    }
+
+    #[test]
+    fn ffi_parse_errors() {
+        let (value, mut error) = parse("[1,\n  tru]");
+        assert!(value.is_null());
+        assert_eq!((error.code, error.line, error.column, error.offset), (3, 2, 3, 6));
+        assert_eq!(unsafe { CStr::from_ptr(error.message) }.to_str(), Ok("unknown keyword 'tru', did you mean 'true'? at line 2, column 3"));
+        unsafe { sjp_error_free(&mut error) };
+        assert!(error.message.is_null());
+
+        let mut error = SjpError { code: 9, line: 1, column: 1, offset: 4, message: ptr::null_mut() };
+        let value = unsafe { sjp_parse(b"[]".as_ptr().cast(), 2, &mut error) };
+        assert!(!value.is_null());
+        assert_eq!((error.code, error.line, error.column, error.offset), (0, 0, 0, 0));
+        unsafe { sjp_free(value) };
+
+        let mut error = SjpError { code: 9, line: 0, column: 0, offset: 0, message: ptr::null_mut() };
+        assert!(unsafe { sjp_parse(b"[\xff]".as_ptr().cast(), 3, &mut error) }.is_null());
+        assert_eq!((error.code, error.offset), (0, 1));
+        unsafe { sjp_error_free(&mut error) };
+    }
+}
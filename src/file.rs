@@ -0,0 +1,256 @@
+//! Reading and writing JSON files.
+//!
+//! With the `gzip` or `zstd` features, the parse functions here also accept input in
+//! that format, recognised by its magic bytes, and decompress it first. Neither can be
+//! mistaken for JSON, which never starts with those bytes. Decompressed input larger
+//! than `MAX_DECOMPRESSED_BYTES` fails with `ErrorKind::Io`, so a small file cannot
+//! expand to fill memory.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, Read, Write},
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::{error::JsonError, format::FormatOptions, j_item::JItem, parse_with, parser::ParseOptions};
+
+/// Reads and parses a file. A leading UTF-8 byte order mark is skipped, and error
+/// positions count from just after it.
+pub fn parse_file(path: impl AsRef<Path>) -> Result<JItem, JsonError> {
+    return parse_file_with(path, &ParseOptions::default());
+}
+
+/// Like `parse_file`, with options as `parse_with` takes them.
+pub fn parse_file_with(path: impl AsRef<Path>, options: &ParseOptions) -> Result<JItem, JsonError> {
+    return parse_bytes(&fs::read(path)?, options);
+}
+
+/// Reads `reader` to the end and parses what it read, as `parse_file` does.
+pub fn parse_from_reader(mut reader: impl Read) -> Result<JItem, JsonError> {
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes)?;
+    return parse_bytes(&bytes, &ParseOptions::default());
+}
+
+/// Parses a file by memory-mapping it, so even very large inputs are never copied into
+/// a `String`. The parsed tree still owns its strings. A byte order mark is skipped as
+/// in `parse_file`.
+///
+/// Compressed input is still decompressed into memory first, as in `parse_file`, so
+/// only plain JSON avoids the copy.
+///
+/// The file must not be changed by anything else while it is being parsed; the result
+/// of doing so is undefined, as with any memory map.
+#[cfg(feature = "mmap")]
+pub fn parse_file_mmap(path: impl AsRef<Path>) -> Result<JItem, JsonError> {
+    let file = File::open(path)?;
+    // SAFETY: the map is only read, and only for the length of this call. Outside
+    // changes to the file are ruled out by the documented contract above.
+    let map = unsafe { memmap2::Mmap::map(&file) }?;
+    return parse_bytes(&map, &ParseOptions::default());
+}
+
+/// The most bytes that gzip or zstd input may decompress to.
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+pub const MAX_DECOMPRESSED_BYTES: usize = 1 << 30;
+
+fn parse_bytes(bytes: &[u8], options: &ParseOptions) -> Result<JItem, JsonError> {
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    let decompressed = decompress(bytes, MAX_DECOMPRESSED_BYTES)?;
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    let bytes = decompressed.as_deref().unwrap_or(bytes);
+    let bytes = bytes.strip_prefix(b"\xef\xbb\xbf").unwrap_or(bytes);
+    return parse_with(JsonError::check_utf8(bytes)?, options);
+}
+
+/// The decompressed contents of gzip or zstd input, or `None` for anything else.
+/// Concatenated gzip members or zstd frames are decompressed one after another, failing
+/// once they add up to more than `limit` bytes.
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+fn decompress(bytes: &[u8], limit: usize) -> std::io::Result<Option<Vec<u8>>> {
+    #[cfg(feature = "gzip")]
+    if bytes.starts_with(&[0x1f, 0x8b]) {
+        let mut out = vec![];
+        read_limited(flate2::read::MultiGzDecoder::new(bytes), &mut out, limit)?;
+        return Ok(Some(out));
+    }
+    #[cfg(feature = "zstd")]
+    if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        let mut out = vec![];
+        let mut input = bytes;
+        while !input.is_empty() {
+            read_limited(ruzstd::decoding::StreamingDecoder::new(&mut input).map_err(std::io::Error::other)?, &mut out, limit)?;
+        }
+        return Ok(Some(out));
+    }
+    return Ok(None);
+}
+
+/// Appends what `reader` holds to `out`, failing if that takes `out` past `limit` bytes.
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+fn read_limited(reader: impl Read, out: &mut Vec<u8>, limit: usize) -> std::io::Result<()> {
+    let room = limit.saturating_sub(out.len()) as u64;
+    reader.take(room + 1).read_to_end(out)?;
+    if out.len() > limit {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("decompressed input is larger than {} bytes", limit)));
+    }
+    return Ok(());
+}
+
+impl JItem {
+    /// Writes the item to `path`, followed by a newline. The text goes to a temporary
+    /// file in the same directory that then replaces `path`, so a crash or full disk never
+    /// leaves `path` half written. Concurrent writes to the same path each use their own
+    /// temporary file, and the last rename wins.
+    pub fn write_file(&self, path: impl AsRef<Path>, options: &FormatOptions) -> std::io::Result<()> {
+        static WRITES: AtomicU64 = AtomicU64::new(0);
+
+        let path = path.as_ref();
+        let Some(name) = path.file_name() else {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("'{}' is not a file path", path.display())));
+        };
+        let mut temp_name = name.to_os_string();
+        temp_name.push(format!(".{}.{}.tmp", std::process::id(), WRITES.fetch_add(1, Ordering::Relaxed)));
+        let temp = path.with_file_name(temp_name);
+        let written = (|| {
+            let mut out = BufWriter::new(OpenOptions::new().write(true).create_new(true).open(&temp)?);
+            out.write_all(self.to_string_with(options).as_bytes())?;
+            out.write_all(b"\n")?;
+            out.into_inner()?.sync_all()?;
+            return fs::rename(&temp, path);
+        })();
+        if written.is_err() {
+            let _ = fs::remove_file(&temp);
+            return written;
+        }
+        // the rename itself only survives a crash once the directory holding it is synced
+        #[cfg(unix)]
+        {
+            let parent = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+            File::open(parent)?.sync_all()?;
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::ErrorKind;
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("sjp-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        return dir;
+    }
+
+    #[test]
+    fn parse_files() {
+        let dir = temp_dir("parse");
+        fs::write(dir.join("bom.json"), b"\xef\xbb\xbf{\"a\": [1, \"b\"]}").unwrap();
+        let item = parse_file(dir.join("bom.json")).unwrap();
+        assert_eq!(item.pointer("/a/1"), Some(&JItem::String("b".to_string())));
+
+        fs::write(dir.join("bad.json"), b"\xef\xbb\xbf[1,\n tru]").unwrap();
+        let error = parse_file(dir.join("bad.json")).unwrap_err();
+        assert_eq!((error.code(), error.line(), error.column()), ("E003", 2, 2));
+        fs::write(dir.join("latin1.json"), b"[\"caf\xe9\"]").unwrap();
+        let error = parse_file(dir.join("latin1.json")).unwrap_err();
+        assert_eq!((error.code(), error.span()), ("E017", 5..6));
+        let error = parse_file(dir.join("missing.json")).unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::Io { kind: std::io::ErrorKind::NotFound, .. }));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn parse_mmapped_files() {
+        let dir = temp_dir("mmap");
+        fs::write(dir.join("ok.json"), b"\xef\xbb\xbf{\"a\": [1, \"b\"]}").unwrap();
+        let item = parse_file_mmap(dir.join("ok.json")).unwrap();
+        assert_eq!(item.pointer("/a/1"), Some(&JItem::String("b".to_string())));
+
+        fs::write(dir.join("empty.json"), b"").unwrap();
+        assert_eq!(parse_file_mmap(dir.join("empty.json")).unwrap_err().code(), "E009");
+        let error = parse_file_mmap(dir.join("missing.json")).unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::Io { kind: std::io::ErrorKind::NotFound, .. }));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn parse_readers() {
+        let item = parse_from_reader(&b"\xef\xbb\xbf[1, {\"a\": null}]"[..]).unwrap();
+        assert_eq!(item.pointer("/1/a"), Some(&JItem::Null));
+        assert_eq!(parse_from_reader(&b"[1,"[..]).unwrap_err().code(), "E010");
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn parse_gzip() {
+        use flate2::{Compression, write::GzEncoder};
+
+        let mut compressed = vec![];
+        for part in ["[1, ", "\"two\"]"] {
+            let mut encoder = GzEncoder::new(vec![], Compression::default());
+            encoder.write_all(part.as_bytes()).unwrap();
+            compressed.extend(encoder.finish().unwrap());
+        }
+        assert_eq!(parse_from_reader(&compressed[..]).unwrap(), crate::parse(r#"[1, "two"]"#).unwrap());
+        let error = parse_from_reader(&compressed[..12]).unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::Io { .. }));
+
+        assert_eq!(decompress(&compressed, 10).unwrap().unwrap(), b"[1, \"two\"]");
+        let error = decompress(&compressed, 9).unwrap_err();
+        assert_eq!((error.kind(), error.to_string()), (std::io::ErrorKind::InvalidData, "decompressed input is larger than 9 bytes".to_string()));
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn parse_zstd() {
+        use ruzstd::encoding::{CompressionLevel, compress_to_vec};
+
+        let mut compressed = compress_to_vec(&b"{\"a\": "[..], CompressionLevel::Fastest);
+        compressed.extend(compress_to_vec(&b"[true]}"[..], CompressionLevel::Fastest));
+        let dir = temp_dir("zstd");
+        fs::write(dir.join("in.json.zst"), &compressed).unwrap();
+        assert_eq!(parse_file(dir.join("in.json.zst")).unwrap(), crate::parse(r#"{"a": [true]}"#).unwrap());
+        fs::remove_dir_all(dir).unwrap();
+        let error = parse_from_reader(&compressed[..6]).unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::Io { .. }));
+
+        assert_eq!(decompress(&compressed, 13).unwrap().unwrap(), b"{\"a\": [true]}");
+        assert!(decompress(&compressed, 12).is_err());
+        assert!(decompress(&compressed, 5).is_err());
+    }
+
+    #[test]
+    fn write_file_replaces_atomically() {
+        let dir = temp_dir("write");
+        let path = dir.join("out.json");
+        fs::write(&path, "old").unwrap();
+        let item = crate::parse(r#"{"b": 1, "a": [true]}"#).unwrap();
+        item.write_file(&path, &FormatOptions::pretty()).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{\n  \"a\": [\n    true\n  ],\n  \"b\": 1\n}\n");
+        assert_eq!(parse_file(&path).unwrap(), item);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        assert!(item.write_file(dir.join("missing").join("out.json"), &FormatOptions::compact()).is_err());
+        assert!(item.write_file("/", &FormatOptions::compact()).is_err());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        let writers: Vec<_> = (0..8).map(|n| {
+            let path = path.clone();
+            return std::thread::spawn(move || JItem::Number(n as f64).write_file(path, &FormatOptions::compact()));
+        }).collect();
+        for writer in writers {
+            writer.join().unwrap().unwrap();
+        }
+        assert!(matches!(parse_file(&path).unwrap(), JItem::Number(_)));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
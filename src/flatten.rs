@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+
+use crate::{j_item::JItem, path::{format_path, insert_at, parse_path, PathSegment}};
+
+/// Flattens `item` into a map from paths like `a.b[0].c` to leaf values, using `sep`
+/// between object keys. Empty objects and arrays are kept as leaves so that
+/// `unflatten` can restore them.
+pub fn flatten(item: &JItem, sep: &str) -> HashMap<String, JItem> {
+    let mut flattened = HashMap::new();
+    let mut segments = vec![];
+    flatten_into(item, sep, &mut segments, &mut flattened);
+    return flattened;
+}
+
+fn flatten_into(item: &JItem, sep: &str, segments: &mut Vec<PathSegment>, flattened: &mut HashMap<String, JItem>) {
+    match item {
+        JItem::Object(hmap) if !hmap.is_empty() => {
+            for (key, value) in hmap {
+                segments.push(PathSegment::Key(key.to_string()));
+                flatten_into(value, sep, segments, flattened);
+                segments.pop();
+            }
+        },
+        JItem::Array(elements) if !elements.is_empty() => {
+            for (index, value) in elements.iter().enumerate() {
+                segments.push(PathSegment::Index(index));
+                flatten_into(value, sep, segments, flattened);
+                segments.pop();
+            }
+        },
+        _ => {
+            flattened.insert(format_path(segments, sep), item.clone());
+        },
+    }
+}
+
+/// Rebuilds a tree from the output of `flatten`. Array gaps are filled with nulls, but
+/// an index more than 1024 past the last one set is an error.
+pub fn unflatten(flattened: &HashMap<String, JItem>, sep: &str) -> Result<JItem, String> {
+    let mut keys: Vec<_> = flattened.keys().collect();
+    keys.sort();
+    let mut paths = vec![];
+    for key in keys {
+        paths.push((parse_path(key, sep)?, key));
+    }
+    // indices in numeric order, so that `[10]` does not come before `[2]` and leave a gap
+    paths.sort();
+
+    let mut root = JItem::Null;
+    for (segments, key) in paths {
+        if key.is_empty() && flattened.len() > 1 {
+            return Err("root value conflicts with other flattened keys".to_string());
+        }
+        insert_at(&mut root, &segments, flattened[key].clone())
+            .map_err(|e| format!("conflicting flattened key '{}': {}", key, e))?;
+    }
+    return Ok(root);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn flatten_nested() {
+        let item = parse(r#"{"a": {"b": [{"c": 1}, true]}, "d": null}"#).unwrap();
+        let flattened = flatten(&item, ".");
+        let mut expected = HashMap::new();
+        expected.insert("a.b[0].c".to_string(), JItem::Number(1.));
+        expected.insert("a.b[1]".to_string(), JItem::Bool(true));
+        expected.insert("d".to_string(), JItem::Null);
+        assert_eq!(flattened, expected);
+    }
+
+    #[test]
+    fn flatten_scalar_root() {
+        let flattened = flatten(&JItem::Number(3.), ".");
+        assert_eq!(flattened.get(""), Some(&JItem::Number(3.)));
+    }
+
+    #[test]
+    fn flatten_custom_separator() {
+        let item = parse(r#"{"a": {"b": 1}}"#).unwrap();
+        let flattened = flatten(&item, "/");
+        assert_eq!(flattened.get("a/b"), Some(&JItem::Number(1.)));
+    }
+
+    #[test]
+    fn flatten_keeps_empty_containers() {
+        let item = parse(r#"{"a": {}, "b": []}"#).unwrap();
+        let flattened = flatten(&item, ".");
+        assert_eq!(flattened.get("a"), Some(&JItem::Object(HashMap::new())));
+        assert_eq!(flattened.get("b"), Some(&JItem::Array(vec![])));
+    }
+
+    #[test]
+    fn unflatten_round_trip() {
+        let item = parse(r#"{"a": {"b.c": [1, [2, {}], []]}, "d": "e", "f": [null, false]}"#).unwrap();
+        let round_tripped = unflatten(&flatten(&item, "."), ".");
+        assert_eq!(round_tripped, Ok(item));
+    }
+
+    #[test]
+    fn unflatten_round_trip_empty_keys() {
+        for input in [r#"{"": 1}"#, r#"{"": [1, {"": []}]}"#, r#"{"": {"": null, "a": {}}}"#, r#"{"a": {"": 2}}"#] {
+            let item = parse(input).unwrap();
+            assert_eq!(unflatten(&flatten(&item, "."), "."), Ok(item));
+        }
+    }
+
+    #[test]
+    fn unflatten_root_array() {
+        let mut flattened = HashMap::new();
+        flattened.insert("[1].a".to_string(), JItem::Bool(true));
+        let expected = parse(r#"[null, {"a": true}]"#).unwrap();
+        assert_eq!(unflatten(&flattened, "."), Ok(expected));
+    }
+
+    #[test]
+    fn unflatten_indices() {
+        let item = JItem::Array((0..12).map(|n| JItem::Number(n as f64)).collect());
+        assert_eq!(unflatten(&flatten(&item, "."), "."), Ok(item));
+        let mut flattened = HashMap::new();
+        flattened.insert("a[18446744073709551615]".to_string(), JItem::Null);
+        assert_eq!(
+            unflatten(&flattened, "."),
+            Err("conflicting flattened key 'a[18446744073709551615]': index 18446744073709551615 is too far past the end of an array of length 0".to_string())
+        );
+        flattened.clear();
+        flattened.insert("[1024]".to_string(), JItem::Null);
+        assert!(matches!(unflatten(&flattened, "."), Ok(JItem::Array(elements)) if elements.len() == 1025));
+    }
+
+    #[test]
+    fn unflatten_conflict() {
+        let mut flattened = HashMap::new();
+        flattened.insert("a".to_string(), JItem::Bool(true));
+        flattened.insert("a.b".to_string(), JItem::Bool(false));
+        assert_eq!(
+            unflatten(&flattened, "."),
+            Err("conflicting flattened key 'a.b': cannot set key 'b' on a non-object value".to_string())
+        );
+    }
+}
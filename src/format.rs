@@ -0,0 +1,526 @@
+use alloc::{format, string::{String, ToString}, vec::Vec};
+
+use crate::j_item::{escape_string, escape_string_with, format_f64, JItem};
+
+/// Controls how `JItem::to_string_with` lays out its output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Text used for one level of indentation. `None` writes everything on one line.
+    pub indent: Option<String>,
+    /// Write object members sorted by key rather than in map order.
+    pub sort_keys: bool,
+    /// Which characters in strings and keys are written as `\u` escapes.
+    pub escape: Escape,
+    /// How `Number` values are written. `RawNumber` text is written as it is when
+    /// it is a JSON number.
+    pub number: NumberFormat,
+    /// With an indent, write arrays and objects that fit these limits on one line, as
+    /// `[1, 2]` or `{"a": 1}`, and expand only the larger ones.
+    pub collapse: Option<Collapse>,
+}
+
+/// Limits for `FormatOptions::collapse`. An array or object is kept on one line when
+/// it, and each one nested in it, has at most `max_items` entries, and its one-line form
+/// takes at most `max_chars` characters, not counting the indentation and key before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Collapse {
+    pub max_chars: usize,
+    pub max_items: usize,
+}
+
+/// Output form for `Number` values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NumberFormat {
+    /// The fewest digits that parse back to the same value.
+    #[default]
+    Shortest,
+    /// Rounded to at most this many significant digits, then written shortest, so
+    /// `0.30000000000000004` becomes `0.3` with 15 digits. Zero counts as one.
+    Significant(usize),
+    /// Exactly this many digits after the decimal point, as in `12.50`.
+    Fixed(usize),
+}
+
+/// How much of a string is escaped on output. Quotes, backslashes and control characters
+/// are always escaped, since the output would not be JSON otherwise.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Escape {
+    /// Nothing beyond what JSON requires.
+    #[default]
+    Minimal,
+    /// Every non-ASCII character, for transports that are not 8-bit clean.
+    Ascii,
+    /// `<`, `>`, `&`, U+2028 and U+2029 too, so the output can be embedded in a
+    /// `<script>` tag or evaluated as JavaScript.
+    Html,
+    /// Also every character the function returns true for.
+    Custom(fn(char) -> bool),
+}
+
+impl PartialEq for Escape {
+    fn eq(&self, other: &Self) -> bool {
+        return match (self, other) {
+            (Escape::Custom(a), Escape::Custom(b)) => core::ptr::fn_addr_eq(*a, *b),
+            _ => core::mem::discriminant(self) == core::mem::discriminant(other),
+        };
+    }
+}
+
+impl Eq for Escape {}
+
+impl Escape {
+    pub(crate) fn escapes(&self, c: char) -> bool {
+        return match self {
+            Escape::Minimal => false,
+            Escape::Ascii => !c.is_ascii(),
+            Escape::Html => matches!(c, '<' | '>' | '&' | '\u{2028}' | '\u{2029}'),
+            Escape::Custom(escapes) => escapes(c),
+        };
+    }
+}
+
+impl FormatOptions {
+    /// Single line, no whitespace, members in map order. Same layout as `Display`.
+    pub fn compact() -> Self {
+        return FormatOptions { indent: None, sort_keys: false, escape: Escape::Minimal, number: NumberFormat::Shortest, collapse: None };
+    }
+
+    /// Two space indentation with sorted keys.
+    pub fn pretty() -> Self {
+        return FormatOptions { indent: Some("  ".to_string()), sort_keys: true, escape: Escape::Minimal, number: NumberFormat::Shortest, collapse: None };
+    }
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        return FormatOptions::compact();
+    }
+}
+
+/// Named layouts whose output is fixed: a value is written the same way by every
+/// version of this crate, so files kept in one of them do not churn on upgrade. All of
+/// them sort object keys and write numbers in their shortest form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// One line, no whitespace.
+    Compact,
+    /// Two space indentation, `": "` after keys, empty containers as `[]` and `{}`.
+    Pretty2,
+    /// As `Pretty2`, with four spaces.
+    Pretty4,
+    /// As `Pretty2`, with one tab.
+    PrettyTabs,
+    /// The form of `JItem::to_canonical_string`.
+    Canonical,
+}
+
+impl Preset {
+    /// The options giving this layout, which are spelled out here rather than taken from
+    /// `FormatOptions::pretty` so that they never change. `None` for `Canonical`, which
+    /// is written by its own serializer.
+    fn options(self) -> Option<FormatOptions> {
+        let indent = match self {
+            Preset::Compact => None,
+            Preset::Pretty2 => Some("  ".to_string()),
+            Preset::Pretty4 => Some("    ".to_string()),
+            Preset::PrettyTabs => Some("\t".to_string()),
+            Preset::Canonical => return None,
+        };
+        return Some(FormatOptions { indent, sort_keys: true, escape: Escape::Minimal, number: NumberFormat::Shortest, collapse: None });
+    }
+}
+
+/// Limits for `JItem::to_truncated_string`. `None` leaves that part unlimited.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TruncateOptions {
+    /// Characters kept from each string value.
+    pub max_string_chars: Option<usize>,
+    /// Elements kept from each array, and members from each object.
+    pub max_items: Option<usize>,
+    /// Bytes of output after which no further elements or members are started.
+    pub max_output_bytes: Option<usize>,
+}
+
+impl Default for TruncateOptions {
+    fn default() -> Self {
+        return TruncateOptions { max_string_chars: Some(256), max_items: Some(32), max_output_bytes: Some(4096) };
+    }
+}
+
+impl JItem {
+    pub fn to_string_with(&self, options: &FormatOptions) -> String {
+        let stage = crate::trace::Stage::enter("serialize");
+        let mut out = String::new();
+        write_item(self, options, 0, &mut out);
+        stage.finish(out.len(), None);
+        return out;
+    }
+
+    pub fn to_pretty_string(&self) -> String {
+        return self.to_string_with(&FormatOptions::pretty());
+    }
+
+    pub fn to_string_preset(&self, preset: Preset) -> String {
+        return match preset.options() {
+            Some(options) => self.to_string_with(&options),
+            None => self.to_canonical_string(),
+        };
+    }
+
+    /// Compact JSON of bounded size, for logging payloads. Cut strings end with
+    /// `…(+N chars)`, cut arrays end with a `"…(+N items)"` element, and cut objects end
+    /// with a `"…": "+N members"` member; keys are sorted so the same members are kept
+    /// each time, except that a `…` key of the input's own comes last and is cut first. Once the output reaches `max_output_bytes`, every open array and object
+    /// is closed off this way, so the output may run over by the last value written plus
+    /// one marker per level of nesting. The result always parses.
+    pub fn to_truncated_string(&self, options: &TruncateOptions) -> String {
+        let mut out = String::new();
+        write_truncated(self, options, &mut out);
+        return out;
+    }
+}
+
+fn write_truncated(item: &JItem, options: &TruncateOptions, out: &mut String) {
+    let full = |out: &String| options.max_output_bytes.is_some_and(|max| out.len() >= max);
+    match item {
+        JItem::Object(hmap) => {
+            let mut members: Vec<_> = hmap.iter().collect();
+            // a real "…" member goes last, so it is only written when no marker follows it
+            members.sort_by_key(|(key, _)| (key.as_str() == "…", *key));
+            out.push('{');
+            let mut written = 0;
+            for (key, value) in &members {
+                if options.max_items.is_some_and(|max| written >= max) || full(out) {
+                    break;
+                }
+                if written > 0 {
+                    out.push(',');
+                }
+                out.push('"');
+                out.push_str(&escape_string(key));
+                out.push_str("\":");
+                write_truncated(value, options, out);
+                written += 1;
+            }
+            if written < members.len() {
+                if written > 0 {
+                    out.push(',');
+                }
+                out.push_str(&format!("\"…\":\"+{} members\"", members.len() - written));
+            }
+            out.push('}');
+        },
+        JItem::Array(elements) => {
+            out.push('[');
+            let mut written = 0;
+            for element in elements {
+                if options.max_items.is_some_and(|max| written >= max) || full(out) {
+                    break;
+                }
+                if written > 0 {
+                    out.push(',');
+                }
+                write_truncated(element, options, out);
+                written += 1;
+            }
+            if written < elements.len() {
+                if written > 0 {
+                    out.push(',');
+                }
+                out.push_str(&format!("\"…(+{} items)\"", elements.len() - written));
+            }
+            out.push(']');
+        },
+        JItem::String(s) => {
+            let len = s.chars().count();
+            match options.max_string_chars {
+                Some(max) if len > max => {
+                    let kept: String = s.chars().take(max).collect();
+                    out.push_str(&format!("\"{}…(+{} chars)\"", escape_string(&kept), len - max));
+                },
+                _ => out.push_str(&format!("\"{}\"", escape_string(s))),
+            }
+        },
+        JItem::Number(x) if !x.is_finite() => out.push_str("null"),
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+pub(crate) fn write_item(item: &JItem, options: &FormatOptions, depth: usize, out: &mut String) {
+    if options.indent.is_some()
+        && let Some(collapse) = options.collapse
+        && matches!(item, JItem::Object(_) | JItem::Array(_))
+    {
+        let start = out.len();
+        if write_inline(item, options, collapse, start + collapse.max_chars, out) {
+            return;
+        }
+        out.truncate(start);
+    }
+    match item {
+        JItem::Object(hmap) => {
+            let members = sorted_members(hmap, options);
+            write_container('{', '}', members.len(), options, depth, out, |n, out| {
+                let (key, value) = members[n];
+                out.push('"');
+                out.push_str(&escape_string_with(key, &options.escape));
+                out.push('"');
+                out.push(':');
+                if options.indent.is_some() {
+                    out.push(' ');
+                }
+                write_item(value, options, depth + 1, out);
+            });
+        },
+        JItem::Array(elements) => {
+            write_container('[', ']', elements.len(), options, depth, out, |n, out| {
+                write_item(&elements[n], options, depth + 1, out);
+            });
+        },
+        JItem::String(s) => {
+            out.push('"');
+            out.push_str(&escape_string_with(s, &options.escape));
+            out.push('"');
+        },
+        // JSON has no representation for NaN or infinities
+        JItem::Number(x) if !x.is_finite() => out.push_str("null"),
+        JItem::Number(x) => out.push_str(&format_number(*x, options.number)),
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+fn sorted_members<'a>(hmap: &'a crate::HashMap<String, JItem>, options: &FormatOptions) -> Vec<(&'a String, &'a JItem)> {
+    let mut members: Vec<_> = hmap.iter().collect();
+    if options.sort_keys {
+        members.sort_by_key(|(key, _)| *key);
+    }
+    return members;
+}
+
+/// Writes `item` on one line with a space after each separator, giving up and returning
+/// false as soon as a container is too large or the output passes `end` bytes.
+fn write_inline(item: &JItem, options: &FormatOptions, collapse: Collapse, end: usize, out: &mut String) -> bool {
+    match item {
+        JItem::Object(hmap) => {
+            if hmap.len() > collapse.max_items {
+                return false;
+            }
+            out.push('{');
+            for (n, (key, value)) in sorted_members(hmap, options).into_iter().enumerate() {
+                if n > 0 {
+                    out.push_str(", ");
+                }
+                out.push('"');
+                out.push_str(&escape_string_with(key, &options.escape));
+                out.push_str("\": ");
+                if !write_inline(value, options, collapse, end, out) {
+                    return false;
+                }
+            }
+            out.push('}');
+        },
+        JItem::Array(elements) => {
+            if elements.len() > collapse.max_items {
+                return false;
+            }
+            out.push('[');
+            for (n, element) in elements.iter().enumerate() {
+                if n > 0 {
+                    out.push_str(", ");
+                }
+                if !write_inline(element, options, collapse, end, out) {
+                    return false;
+                }
+            }
+            out.push(']');
+        },
+        scalar => write_item(scalar, options, 0, out),
+    }
+    // `end` is in bytes, so count characters only when the bytes run over
+    return out.len() <= end || out[end - collapse.max_chars..].chars().count() <= collapse.max_chars;
+}
+
+fn format_number(x: f64, number: NumberFormat) -> String {
+    return match number {
+        NumberFormat::Shortest => format_f64(x),
+        NumberFormat::Significant(digits) => {
+            // scientific notation rounds to significant digits rather than decimal places
+            let rounded = format!("{:.*e}", digits.max(1) - 1, x);
+            format_f64(rounded.parse().unwrap_or(x))
+        },
+        NumberFormat::Fixed(places) => format!("{:.*}", places, x),
+    };
+}
+
+fn write_container<F>(open: char, close: char, len: usize, options: &FormatOptions, depth: usize, out: &mut String, mut write_entry: F)
+where
+    F: FnMut(usize, &mut String),
+{
+    out.push(open);
+    if len == 0 {
+        out.push(close);
+        return;
+    }
+    for n in 0..len {
+        if n > 0 {
+            out.push(',');
+        }
+        write_newline(options, depth + 1, out);
+        write_entry(n, out);
+    }
+    write_newline(options, depth, out);
+    out.push(close);
+}
+
+pub(crate) fn write_newline(options: &FormatOptions, depth: usize, out: &mut String) {
+    let Some(indent) = &options.indent else {
+        return;
+    };
+    out.push('\n');
+    for _ in 0..depth {
+        out.push_str(indent);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn format_compact() {
+        let item = parse(r#"[1, {"a": [true, null]}, "x"]"#).unwrap();
+        assert_eq!(item.to_string_with(&FormatOptions::compact()), r#"[1,{"a":[true,null]},"x"]"#);
+    }
+
+    #[test]
+    fn format_pretty() {
+        let item = parse(r#"{"b": [1, 2], "a": {"c": "d"}, "e": [], "f": {}}"#).unwrap();
+        let expected = r#"{
+  "a": {
+    "c": "d"
+  },
+  "b": [
+    1,
+    2
+  ],
+  "e": [],
+  "f": {}
+}"#;
+        assert_eq!(item.to_pretty_string(), expected);
+    }
+
+    #[test]
+    fn format_collapsed() {
+        let item = parse(r#"{"point": {"x": 1, "y": 2}, "tags": ["a", "b", "c"], "empty": [], "rows": [[1, 2], [3, 4, 5, 6], {"name": "a long enough name to expand"}]}"#).unwrap();
+        let options = FormatOptions { collapse: Some(Collapse { max_chars: 20, max_items: 3 }), ..FormatOptions::pretty() };
+        let expected = r#"{
+  "empty": [],
+  "point": {"x": 1, "y": 2},
+  "rows": [
+    [1, 2],
+    [
+      3,
+      4,
+      5,
+      6
+    ],
+    {
+      "name": "a long enough name to expand"
+    }
+  ],
+  "tags": ["a", "b", "c"]
+}"#;
+        assert_eq!(item.to_string_with(&options), expected);
+        assert_eq!(parse(r#"["é", "é"]"#).unwrap().to_string_with(&FormatOptions { collapse: Some(Collapse { max_chars: 10, max_items: 2 }), ..FormatOptions::pretty() }), r#"["é", "é"]"#);
+        assert_eq!(item.to_string_with(&FormatOptions { collapse: options.collapse, ..FormatOptions::compact() }), item.to_string_with(&FormatOptions::compact()));
+    }
+
+    // these outputs are promised not to change; do not update them to match new code
+    #[test]
+    fn format_presets() {
+        let item = parse(r#"{"b": [1.50, -0.0, {}], "a": "é\u0001", "c": 1e3}"#).unwrap();
+        assert_eq!(item.to_string_preset(Preset::Compact), "{\"a\":\"é\\u0001\",\"b\":[1.5,-0,{}],\"c\":1000}");
+        assert_eq!(item.to_string_preset(Preset::Pretty2), "{\n  \"a\": \"é\\u0001\",\n  \"b\": [\n    1.5,\n    -0,\n    {}\n  ],\n  \"c\": 1000\n}");
+        assert_eq!(item.to_string_preset(Preset::Pretty4), "{\n    \"a\": \"é\\u0001\",\n    \"b\": [\n        1.5,\n        -0,\n        {}\n    ],\n    \"c\": 1000\n}");
+        assert_eq!(item.to_string_preset(Preset::PrettyTabs), "{\n\t\"a\": \"é\\u0001\",\n\t\"b\": [\n\t\t1.5,\n\t\t-0,\n\t\t{}\n\t],\n\t\"c\": 1000\n}");
+        assert_eq!(item.to_string_preset(Preset::Canonical), "{\"a\":\"é\\u0001\",\"b\":[1.5,0,{}],\"c\":1000}");
+    }
+
+    #[test]
+    fn format_custom_indent() {
+        let item = parse(r#"[[1]]"#).unwrap();
+        let options = FormatOptions { indent: Some("\t".to_string()), ..FormatOptions::compact() };
+        assert_eq!(item.to_string_with(&options), "[\n\t[\n\t\t1\n\t]\n]");
+    }
+
+    #[test]
+    fn format_escape_policies() {
+        let item = parse(r#"{"<k>": "a</script>&\u2028é😀\n"}"#).unwrap();
+        let with = |escape| item.to_string_with(&FormatOptions { escape, ..FormatOptions::compact() });
+        assert_eq!(with(Escape::Minimal), "{\"<k>\":\"a</script>&\u{2028}é😀\\n\"}");
+        assert_eq!(with(Escape::Ascii), r#"{"<k>":"a</script>&\u2028\u00e9\ud83d\ude00\n"}"#);
+        assert_eq!(with(Escape::Html), r#"{"\u003ck\u003e":"a\u003c/script\u003e\u0026\u2028é😀\n"}"#);
+        assert_eq!(with(Escape::Custom(|c| c == 'a')), "{\"<k>\":\"\\u0061</script>&\u{2028}é😀\\n\"}");
+        for escape in [Escape::Ascii, Escape::Html] {
+            assert_eq!(parse(&with(escape)).unwrap(), item);
+        }
+    }
+
+    #[test]
+    fn format_number_precision() {
+        let item = JItem::Array(vec![JItem::Number(0.1 + 0.2), JItem::Number(12.75), JItem::Number(-1234.5678), JItem::Number(0.0), JItem::RawNumber("1.50".to_string())]);
+        let with = |number| item.to_string_with(&FormatOptions { number, ..FormatOptions::compact() });
+        assert_eq!(with(NumberFormat::Shortest), "[0.30000000000000004,12.75,-1234.5678,0,1.50]");
+        assert_eq!(with(NumberFormat::Significant(15)), "[0.3,12.75,-1234.5678,0,1.50]");
+        assert_eq!(with(NumberFormat::Significant(2)), "[0.3,13,-1200,0,1.50]");
+        assert_eq!(with(NumberFormat::Significant(0)), "[0.3,10,-1000,0,1.50]");
+        assert_eq!(with(NumberFormat::Fixed(2)), "[0.30,12.75,-1234.57,0.00,1.50]");
+        assert_eq!(with(NumberFormat::Fixed(0)), "[0,13,-1235,0,1.50]");
+
+        let injected = JItem::Array(vec![JItem::RawNumber("1, \"x\": 2".to_string())]);
+        assert_eq!(injected.to_string_with(&FormatOptions::pretty()), "[\n  null\n]");
+    }
+
+    #[test]
+    fn format_non_finite_as_null() {
+        let item = JItem::Array(vec![JItem::Number(f64::NAN), JItem::Number(f64::INFINITY)]);
+        assert_eq!(item.to_string_with(&FormatOptions::compact()), "[null,null]");
+    }
+
+    #[test]
+    fn format_pretty_scalar() {
+        assert_eq!(JItem::String("a\"b".to_string()).to_pretty_string(), r#""a\"b""#);
+    }
+
+    #[test]
+    fn truncate_strings_and_items() {
+        let item = parse(r#"{"b": "abcdefgh", "a": [1, 2, 3, 4, 5], "c": {"x": 1, "y": 2, "z": 3}, "d": "é\"é\"é"}"#).unwrap();
+        let options = TruncateOptions { max_string_chars: Some(3), max_items: Some(4), max_output_bytes: None };
+        let expected = r#"{"a":[1,2,3,4,"…(+1 items)"],"b":"abc…(+5 chars)","c":{"x":1,"y":2,"z":3},"d":"é\"é…(+2 chars)"}"#;
+        assert_eq!(item.to_truncated_string(&options), expected);
+        assert!(parse(expected).is_ok());
+
+        let options = TruncateOptions { max_items: Some(2), ..options };
+        assert_eq!(parse(r#"{"c": {"x": 1, "y": 2, "z": 3}}"#).unwrap().to_truncated_string(&options), r#"{"c":{"x":1,"y":2,"…":"+1 members"}}"#);
+
+        let item = parse(r#"{"…": 1, "中": 2, "中2": 3}"#).unwrap();
+        let truncated = item.to_truncated_string(&TruncateOptions { max_items: Some(1), ..options.clone() });
+        assert_eq!(truncated, r#"{"中":2,"…":"+2 members"}"#);
+        assert!(parse(&truncated).is_ok());
+        assert_eq!(item.to_truncated_string(&TruncateOptions { max_items: Some(3), ..options }), r#"{"中":2,"中2":3,"…":1}"#);
+    }
+
+    #[test]
+    fn truncate_total_output() {
+        let item = parse(&format!("[{}]", (0..10_000).map(|n| format!("{{\"n\": [{}, \"{}\"]}}", n, "x".repeat(50))).collect::<Vec<_>>().join(","))).unwrap();
+        let options = TruncateOptions { max_string_chars: None, max_items: None, max_output_bytes: Some(1000) };
+        let truncated = item.to_truncated_string(&options);
+        assert!(truncated.len() < 1100, "{}", truncated.len());
+        assert!(truncated.ends_with(r#""…(+9984 items)"]"#), "{}", truncated);
+        assert!(parse(&truncated).is_ok());
+
+        assert_eq!(parse("[[1, 2], 3]").unwrap().to_truncated_string(&TruncateOptions { max_output_bytes: Some(3), ..TruncateOptions::default() }), r#"[[1,"…(+1 items)"],"…(+1 items)"]"#);
+        assert_eq!(parse("[]").unwrap().to_truncated_string(&TruncateOptions { max_output_bytes: Some(0), ..TruncateOptions::default() }), "[]");
+    }
+}
@@ -0,0 +1,836 @@
+use std::ops::RangeInclusive;
+
+use crate::{
+    format::FormatOptions,
+    j_item::JItem,
+    schema::{self, SchemaRegistry},
+    HashMap,
+};
+
+/// A small seeded random number generator (SplitMix64). The same seed always gives the
+/// same documents, so a failing load test can be replayed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        return Rng { state: seed };
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        return z ^ (z >> 31);
+    }
+
+    /// A number in `0..n`. `n` must not be 0.
+    pub fn below(&mut self, n: u64) -> u64 {
+        return ((self.next_u64() as u128 * n as u128) >> 64) as u64;
+    }
+
+    pub fn in_range(&mut self, range: RangeInclusive<usize>) -> usize {
+        let (start, end) = range.into_inner();
+        if end <= start {
+            return start;
+        }
+        return start + self.below((end - start) as u64 + 1) as usize;
+    }
+
+    /// A number in `0.0..1.0`.
+    pub fn unit(&mut self) -> f64 {
+        return (self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64;
+    }
+}
+
+/// How often each kind of value is picked, relative to the others. A kind with weight
+/// 0 never appears; if every kind allowed at some point has weight 0, `null` is used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TypeWeights {
+    pub null: u32,
+    pub bool: u32,
+    pub number: u32,
+    pub string: u32,
+    pub array: u32,
+    pub object: u32,
+}
+
+/// How long generated strings are, in characters.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LengthDistribution {
+    Fixed(usize),
+    Uniform(RangeInclusive<usize>),
+    /// Mostly short with a long tail, averaging `mean`.
+    Geometric { mean: f64 },
+}
+
+/// The shape of generated documents.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    /// How many arrays and objects deep values may nest. Past it only scalars are picked.
+    pub max_depth: usize,
+    /// How many elements an array, or members an object, has. Members whose generated
+    /// keys collide are merged, so objects may come out smaller.
+    pub branching: RangeInclusive<usize>,
+    pub weights: TypeWeights,
+    pub string_length: LengthDistribution,
+    pub key_length: LengthDistribution,
+    /// The characters strings and keys are made of. Must not be empty.
+    pub alphabet: Vec<char>,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        return Profile {
+            max_depth: 4,
+            branching: 0..=6,
+            weights: TypeWeights { null: 1, bool: 2, number: 4, string: 4, array: 2, object: 2 },
+            string_length: LengthDistribution::Geometric { mean: 8.0 },
+            key_length: LengthDistribution::Uniform(3..=10),
+            alphabet: ('a'..='z').collect(),
+        };
+    }
+}
+
+/// A random document shaped by `profile`.
+pub fn generate(profile: &Profile, rng: &mut Rng) -> JItem {
+    return generate_at(profile, rng, profile.max_depth);
+}
+
+/// A random document shaped by `profile`, serialized with `options`.
+pub fn generate_text(profile: &Profile, rng: &mut Rng, options: &FormatOptions) -> String {
+    return generate(profile, rng).to_string_with(options);
+}
+
+fn generate_at(profile: &Profile, rng: &mut Rng, depth: usize) -> JItem {
+    let w = &profile.weights;
+    let (array, object) = if depth == 0 { (0, 0) } else { (w.array, w.object) };
+    let weights = [w.null, w.bool, w.number, w.string, array, object];
+    let total: u64 = weights.iter().map(|weight| *weight as u64).sum();
+    if total == 0 {
+        return JItem::Null;
+    }
+    let mut pick = rng.below(total);
+    let kind = weights.iter().position(|weight| {
+        let found = pick < *weight as u64;
+        pick = pick.saturating_sub(*weight as u64);
+        return found;
+    }).unwrap();
+    return match kind {
+        0 => JItem::Null,
+        1 => JItem::Bool(rng.below(2) == 1),
+        2 => JItem::Number(number(rng)),
+        3 => JItem::String(string(&profile.string_length, profile, rng)),
+        4 => JItem::Array((0..rng.in_range(profile.branching.clone())).map(|_| generate_at(profile, rng, depth - 1)).collect()),
+        _ => {
+            let mut hmap = HashMap::new();
+            for _ in 0..rng.in_range(profile.branching.clone()) {
+                hmap.insert(string(&profile.key_length, profile, rng), generate_at(profile, rng, depth - 1));
+            }
+            JItem::Object(hmap)
+        },
+    };
+}
+
+/// An integer or a number with two decimal places, below a million either way.
+fn number(rng: &mut Rng) -> f64 {
+    let magnitude = rng.below(100_000_000) as f64 / 100.0;
+    let x = if rng.below(2) == 0 { magnitude.trunc() } else { magnitude };
+    return if rng.below(2) == 0 { x } else { -x };
+}
+
+fn string(length: &LengthDistribution, profile: &Profile, rng: &mut Rng) -> String {
+    let len = match length {
+        LengthDistribution::Fixed(len) => *len,
+        LengthDistribution::Uniform(range) => rng.in_range(range.clone()),
+        LengthDistribution::Geometric { mean } => {
+            let mut len = 0;
+            while rng.unit() < mean / (mean + 1.0) {
+                len += 1;
+            }
+            len
+        },
+    };
+    return (0..len).map(|_| profile.alphabet[rng.below(profile.alphabet.len() as u64) as usize]).collect();
+}
+
+/// A random document that satisfies a JSON Schema, for contract tests. This covers
+/// `type`, `const`, `enum`, `allOf`, `anyOf`, `oneOf`, object `properties` and
+/// `required`, array `items`, `prefixItems`, `minItems`, `maxItems` and
+/// `uniqueItems`, numeric bounds and `multipleOf`, and string lengths, common `format`s
+/// and `pattern`s. A `pattern` is generated from directly and takes precedence over the
+/// string lengths; it may use literals, groups, alternation, character classes that are
+/// not negated, `.`, `\d`, `\w`, `\s`, and the usual quantifiers. Optional properties
+/// are each included half the time. `oneOf` branches are not checked against each other,
+/// and anything else that is not understood is an error rather than a document that
+/// might not match. `$ref`s may only point within the schema; see
+/// `generate_matching_with` for schemas split across documents.
+pub fn generate_matching(schema: &JItem, rng: &mut Rng) -> Result<JItem, String> {
+    return generate_matching_with(schema, &mut SchemaRegistry::new(), rng);
+}
+
+/// Like `generate_matching`, resolving `$ref`s through `registry`. The schema itself is
+/// registered under `""`, and under its `$id` if it has one. Keywords beside a `$ref`
+/// are combined with the schema it points to, as `allOf` would. Following more than
+/// 32 nested `$ref`s is an error, which keeps circular schemas from recursing forever.
+pub fn generate_matching_with(schema: &JItem, registry: &mut SchemaRegistry, rng: &mut Rng) -> Result<JItem, String> {
+    registry.register("", schema.clone());
+    return matching(schema, &mut Scope { registry, base: String::new(), depth: 0 }, rng);
+}
+
+const MAX_REF_DEPTH: usize = 32;
+
+/// Where `$ref`s are resolved from while generating.
+struct Scope<'a> {
+    registry: &'a mut SchemaRegistry,
+    /// The URI relative references are resolved against.
+    base: String,
+    /// The number of `$ref`s being followed.
+    depth: usize,
+}
+
+fn matching(schema: &JItem, scope: &mut Scope, rng: &mut Rng) -> Result<JItem, String> {
+    let members = match schema {
+        JItem::Bool(true) => return Ok(generate(&Profile { max_depth: 2, ..Profile::default() }, rng)),
+        JItem::Bool(false) => return Err("schema 'false' matches nothing".to_string()),
+        JItem::Object(members) => members,
+        _ => return Err(format!("schema must be an object or boolean, not {}", schema)),
+    };
+    let outer = scope.base.clone();
+    if let Some(JItem::String(id)) = members.get("$id") {
+        scope.base = schema::join(&outer, id);
+    }
+    let result = match members.contains_key("$ref") {
+        true if scope.depth == MAX_REF_DEPTH => Err(format!("more than {} nested '$ref's; the schema may be circular", MAX_REF_DEPTH)),
+        true => match dereference(members, scope) {
+            Ok(members) => {
+                scope.depth += 1;
+                let result = matching(&JItem::Object(members), scope, rng);
+                scope.depth -= 1;
+                result
+            },
+            Err(e) => Err(e),
+        },
+        false => matching_members(members, scope, rng),
+    };
+    scope.base = outer;
+    return result;
+}
+
+/// Follows `$ref` until reaching a schema without one, combining it with the keywords
+/// found beside each `$ref` on the way. The result's `$id` is set to the absolute URI
+/// of where it was found, so references inside it still resolve once it is merged into
+/// another schema.
+fn dereference(members: &HashMap<String, JItem>, scope: &mut Scope) -> Result<HashMap<String, JItem>, String> {
+    let mut members = members.clone();
+    let mut base = scope.base.clone();
+    for _ in 0..MAX_REF_DEPTH {
+        let Some(reference) = members.remove("$ref") else {
+            return Ok(members);
+        };
+        let JItem::String(reference) = reference else {
+            return Err("'$ref' must be a string".to_string());
+        };
+        let (uri, target) = scope.registry.resolve(&base, &reference)?;
+        let mut target = match target {
+            JItem::Object(target) => target,
+            JItem::Bool(true) => HashMap::new(),
+            JItem::Bool(false) => return Err(format!("'$ref' '{}' points to schema 'false', which matches nothing", reference)),
+            _ => return Err(format!("'$ref' '{}' does not point to a schema", reference)),
+        };
+        base = match target.get("$id") {
+            Some(JItem::String(id)) => schema::join(&uri, id),
+            _ => uri,
+        };
+        target.insert("$id".to_string(), JItem::String(base.clone()));
+        members.remove("$id");
+        merge_into(&mut target, &members);
+        members = target;
+    }
+    return Err(format!("more than {} '$ref's in a row; the schema may be circular", MAX_REF_DEPTH));
+}
+
+fn matching_members(members: &HashMap<String, JItem>, scope: &mut Scope, rng: &mut Rng) -> Result<JItem, String> {
+    if let Some(JItem::Array(all)) = members.get("allOf") {
+        let mut merged = without(members, "allOf");
+        for sub in all {
+            let JItem::Object(sub) = sub else {
+                return Err("'allOf' entries must be objects".to_string());
+            };
+            merge_into(&mut merged, &dereference(sub, scope)?);
+        }
+        return matching(&JItem::Object(merged), scope, rng);
+    }
+    for keyword in ["anyOf", "oneOf"] {
+        if let Some(branches) = members.get(keyword) {
+            let JItem::Array(branches) = branches else {
+                return Err(format!("'{}' must be an array", keyword));
+            };
+            if branches.is_empty() {
+                return Err(format!("'{}' must not be empty", keyword));
+            }
+            let mut merged = without(members, keyword);
+            match &branches[rng.below(branches.len() as u64) as usize] {
+                JItem::Object(branch) => merge_into(&mut merged, &dereference(branch, scope)?),
+                JItem::Bool(true) => {},
+                _ => return Err(format!("'{}' entries must be objects", keyword)),
+            }
+            return matching(&JItem::Object(merged), scope, rng);
+        }
+    }
+    if let Some(value) = members.get("const") {
+        return Ok(value.clone());
+    }
+    if let Some(values) = members.get("enum") {
+        return match values {
+            JItem::Array(values) if !values.is_empty() => Ok(values[rng.below(values.len() as u64) as usize].clone()),
+            _ => Err("'enum' must be a non-empty array".to_string()),
+        };
+    }
+    let kind = match members.get("type") {
+        Some(JItem::String(kind)) => kind.as_str(),
+        Some(JItem::Array(kinds)) if !kinds.is_empty() => match &kinds[rng.below(kinds.len() as u64) as usize] {
+            JItem::String(kind) => kind.as_str(),
+            other => return Err(format!("invalid type {}", other)),
+        },
+        Some(other) => return Err(format!("invalid type {}", other)),
+        None => infer_type(members),
+    };
+    return match kind {
+        "null" => Ok(JItem::Null),
+        "boolean" => Ok(JItem::Bool(rng.below(2) == 1)),
+        "integer" | "number" => matching_number(members, kind == "integer", rng),
+        "string" => matching_string(members, rng).map(JItem::String),
+        "array" => matching_array(members, scope, rng),
+        "object" => matching_object(members, scope, rng),
+        "any" => matching(&JItem::Bool(true), scope, rng),
+        other => Err(format!("unknown type '{}'", other)),
+    };
+}
+
+/// The members other than `keyword`. `$id` is left out too, as it has already been
+/// applied to the base URI.
+fn without(members: &HashMap<String, JItem>, keyword: &str) -> HashMap<String, JItem> {
+    return members.iter().filter(|(key, _)| *key != keyword && *key != "$id").map(|(key, value)| (key.clone(), value.clone())).collect();
+}
+
+/// Adds a subschema's keywords, combining `properties` and `required` rather than
+/// replacing them.
+fn merge_into(merged: &mut HashMap<String, JItem>, sub: &HashMap<String, JItem>) {
+    for (key, value) in sub {
+        match (key.as_str(), merged.get_mut(key), value) {
+            ("properties", Some(JItem::Object(existing)), JItem::Object(more)) => existing.extend(more.iter().map(|(k, v)| (k.clone(), v.clone()))),
+            ("required", Some(JItem::Array(existing)), JItem::Array(more)) => existing.extend(more.iter().cloned()),
+            _ => {
+                merged.insert(key.clone(), value.clone());
+            },
+        }
+    }
+}
+
+fn infer_type(members: &HashMap<String, JItem>) -> &'static str {
+    let has = |keywords: &[&str]| keywords.iter().any(|keyword| members.contains_key(*keyword));
+    if has(&["properties", "required", "additionalProperties"]) {
+        return "object";
+    }
+    if has(&["items", "prefixItems", "minItems", "maxItems", "uniqueItems"]) {
+        return "array";
+    }
+    if has(&["minLength", "maxLength", "pattern", "format"]) {
+        return "string";
+    }
+    if has(&["minimum", "maximum", "exclusiveMinimum", "exclusiveMaximum", "multipleOf"]) {
+        return "number";
+    }
+    return "any";
+}
+
+fn number_member(members: &HashMap<String, JItem>, keyword: &str) -> Result<Option<f64>, String> {
+    return match members.get(keyword) {
+        None => Ok(None),
+        Some(value) => value.number_value().map(Some).ok_or_else(|| format!("'{}' must be a number", keyword)),
+    };
+}
+
+fn count_member(members: &HashMap<String, JItem>, keyword: &str) -> Result<Option<usize>, String> {
+    return match number_member(members, keyword)? {
+        Some(n) if n >= 0.0 && n.fract() == 0.0 => Ok(Some(n as usize)),
+        Some(_) => Err(format!("'{}' must be a non-negative integer", keyword)),
+        None => Ok(None),
+    };
+}
+
+fn matching_number(members: &HashMap<String, JItem>, integer: bool, rng: &mut Rng) -> Result<JItem, String> {
+    let exclusive_min = number_member(members, "exclusiveMinimum")?;
+    let exclusive_max = number_member(members, "exclusiveMaximum")?;
+    let mut min = number_member(members, "minimum")?;
+    let mut max = number_member(members, "maximum")?;
+    // multiples of `step` are picked, so exclusive bounds move in by at most one step
+    let step = match number_member(members, "multipleOf")? {
+        Some(step) if step > 0.0 => step,
+        Some(_) => return Err("'multipleOf' must be greater than 0".to_string()),
+        None if integer => 1.0,
+        None => 0.01,
+    };
+    if let Some(bound) = exclusive_min {
+        min = Some(min.map_or(bound, |min| min.max(bound)));
+    }
+    if let Some(bound) = exclusive_max {
+        max = Some(max.map_or(bound, |max| max.min(bound)));
+    }
+    let (min, max) = match (min, max) {
+        (Some(min), Some(max)) => (min, max),
+        (Some(min), None) => (min, min + 1000.0),
+        (None, Some(max)) => (max - 1000.0, max),
+        (None, None) => (-1000.0, 1000.0),
+    };
+    let mut low = (min / step).ceil();
+    let mut high = (max / step).floor();
+    if exclusive_min.is_some_and(|bound| low * step <= bound) {
+        low += 1.0;
+    }
+    if exclusive_max.is_some_and(|bound| high * step >= bound) {
+        high -= 1.0;
+    }
+    if integer && step.fract() != 0.0 {
+        return Err(format!("'multipleOf' {} is not supported for integers", step));
+    }
+    if low > high {
+        return Err(format!("no {} lies between {} and {}", if integer { "integer" } else { "number" }, min, max));
+    }
+    let k = low + rng.below((high - low) as u64 + 1) as f64;
+    // rounding keeps steps like 0.1 from printing as 0.30000000000000004
+    let x = k * step;
+    let x = if step.fract() == 0.0 { x } else { (x * 1e9).round() / 1e9 };
+    return Ok(JItem::Number(x));
+}
+
+fn matching_string(members: &HashMap<String, JItem>, rng: &mut Rng) -> Result<String, String> {
+    if let Some(pattern) = members.get("pattern") {
+        let JItem::String(pattern) = pattern else {
+            return Err("'pattern' must be a string".to_string());
+        };
+        let mut out = String::new();
+        Pattern::parse(pattern)?.generate(rng, &mut out);
+        return Ok(out);
+    }
+    let word = |rng: &mut Rng, len: usize| -> String { (0..len).map(|_| (b'a' + rng.below(26) as u8) as char).collect() };
+    let digits = |rng: &mut Rng, n: u64, width: usize| format!("{:0width$}", rng.below(n), width = width);
+    if let Some(JItem::String(format)) = members.get("format") {
+        let formatted = match format.as_str() {
+            "date" => Some(format!("20{}-{:02}-{:02}", digits(rng, 100, 2), 1 + rng.below(12), 1 + rng.below(28))),
+            "time" => Some(format!("{:02}:{:02}:{:02}Z", rng.below(24), rng.below(60), rng.below(60))),
+            "date-time" => Some(format!("20{}-{:02}-{:02}T{:02}:{:02}:{:02}Z", digits(rng, 100, 2), 1 + rng.below(12), 1 + rng.below(28), rng.below(24), rng.below(60), rng.below(60))),
+            "email" => Some(format!("{}@example.com", word(rng, 8))),
+            "hostname" => Some(format!("{}.example.com", word(rng, 8))),
+            "uri" => Some(format!("https://example.com/{}", word(rng, 8))),
+            "ipv4" => Some(format!("{}.{}.{}.{}", rng.below(256), rng.below(256), rng.below(256), rng.below(256))),
+            "uuid" => {
+                let hex = format!("{:016x}{:016x}", rng.next_u64(), rng.next_u64());
+                Some(format!("{}-{}-4{}-{}{}-{}", &hex[..8], &hex[8..12], &hex[13..16], ["8", "9", "a", "b"][rng.below(4) as usize], &hex[17..20], &hex[20..32]))
+            },
+            _ => None,
+        };
+        if let Some(formatted) = formatted {
+            return Ok(formatted);
+        }
+    }
+    let min = count_member(members, "minLength")?.unwrap_or(0);
+    let max = count_member(members, "maxLength")?.unwrap_or(min + 10);
+    if min > max {
+        return Err(format!("minLength {} is greater than maxLength {}", min, max));
+    }
+    let len = rng.in_range(min..=max);
+    return Ok(word(rng, len));
+}
+
+fn matching_array(members: &HashMap<String, JItem>, scope: &mut Scope, rng: &mut Rng) -> Result<JItem, String> {
+    let prefix = match members.get("prefixItems") {
+        Some(JItem::Array(prefix)) => prefix.as_slice(),
+        Some(_) => return Err("'prefixItems' must be an array".to_string()),
+        None => &[],
+    };
+    let items = members.get("items");
+    let min = count_member(members, "minItems")?.unwrap_or(0).max(prefix.len());
+    let max = match (count_member(members, "maxItems")?, items) {
+        (Some(max), _) => max,
+        (None, Some(JItem::Bool(false))) => prefix.len(),
+        (None, _) => min.max(prefix.len()) + 4,
+    };
+    if min > max {
+        return Err(format!("minItems {} is more than the {} items allowed", min, max));
+    }
+    let unique = matches!(members.get("uniqueItems"), Some(JItem::Bool(true)));
+    let len = rng.in_range(min..=max);
+    let mut elements: Vec<JItem> = vec![];
+    let mut attempts = 0;
+    while elements.len() < len {
+        let schema = prefix.get(elements.len()).or(items).unwrap_or(&JItem::Bool(true));
+        let element = matching(schema, scope, rng)?;
+        if unique && elements.contains(&element) {
+            attempts += 1;
+            if attempts > 100 {
+                return Err("could not generate enough unique items".to_string());
+            }
+            continue;
+        }
+        elements.push(element);
+    }
+    return Ok(JItem::Array(elements));
+}
+
+fn matching_object(members: &HashMap<String, JItem>, scope: &mut Scope, rng: &mut Rng) -> Result<JItem, String> {
+    let empty = HashMap::new();
+    let properties = match members.get("properties") {
+        Some(JItem::Object(properties)) => properties,
+        Some(_) => return Err("'properties' must be an object".to_string()),
+        None => &empty,
+    };
+    let required: Vec<&str> = match members.get("required") {
+        Some(JItem::Array(required)) => required.iter().map(|key| match key {
+            JItem::String(key) => Ok(key.as_str()),
+            _ => Err("'required' must list strings".to_string()),
+        }).collect::<Result<_, _>>()?,
+        Some(_) => return Err("'required' must be an array".to_string()),
+        None => vec![],
+    };
+    let mut keys: Vec<&String> = properties.keys().collect();
+    keys.sort();
+    let mut hmap = HashMap::new();
+    for key in keys {
+        if required.contains(&key.as_str()) || rng.below(2) == 0 {
+            hmap.insert(key.clone(), matching(&properties[key], scope, rng)?);
+        }
+    }
+    for key in required {
+        if !hmap.contains_key(key) {
+            hmap.insert(key.to_string(), matching(members.get("additionalProperties").unwrap_or(&JItem::Bool(true)), scope, rng)?);
+        }
+    }
+    return Ok(JItem::Object(hmap));
+}
+
+/// A regular expression, read only as far as needed to produce strings it matches.
+enum Pattern {
+    Literal(char),
+    /// Inclusive character ranges.
+    Class(Vec<(char, char)>),
+    Sequence(Vec<Pattern>),
+    Alternation(Vec<Pattern>),
+    Repeat(Box<Pattern>, usize, usize),
+}
+
+impl Pattern {
+    fn parse(pattern: &str) -> Result<Pattern, String> {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut pos = 0;
+        let parsed = parse_alternation(&chars, &mut pos).map_err(|e| format!("unsupported pattern '{}': {}", pattern, e))?;
+        if pos < chars.len() {
+            return Err(format!("unsupported pattern '{}': unmatched ')'", pattern));
+        }
+        return Ok(parsed);
+    }
+
+    fn generate(&self, rng: &mut Rng, out: &mut String) {
+        match self {
+            Pattern::Literal(c) => out.push(*c),
+            Pattern::Class(ranges) => {
+                let (low, high) = ranges[rng.below(ranges.len() as u64) as usize];
+                out.push(char::from_u32(rng.in_range(low as usize..=high as usize) as u32).unwrap_or(low));
+            },
+            Pattern::Sequence(parts) => parts.iter().for_each(|part| part.generate(rng, out)),
+            Pattern::Alternation(branches) => branches[rng.below(branches.len() as u64) as usize].generate(rng, out),
+            Pattern::Repeat(inner, min, max) => {
+                for _ in 0..rng.in_range(*min..=*max) {
+                    inner.generate(rng, out);
+                }
+            },
+        }
+    }
+}
+
+fn parse_alternation(chars: &[char], pos: &mut usize) -> Result<Pattern, String> {
+    let mut branches = vec![parse_sequence(chars, pos)?];
+    while chars.get(*pos) == Some(&'|') {
+        *pos += 1;
+        branches.push(parse_sequence(chars, pos)?);
+    }
+    return Ok(if branches.len() == 1 { branches.pop().unwrap() } else { Pattern::Alternation(branches) });
+}
+
+fn parse_sequence(chars: &[char], pos: &mut usize) -> Result<Pattern, String> {
+    let mut parts = vec![];
+    while let Some(c) = chars.get(*pos).copied() {
+        if c == '|' || c == ')' {
+            break;
+        }
+        *pos += 1;
+        let atom = match c {
+            // a generated string is matched whole, so anchors need no text
+            '^' | '$' => continue,
+            '(' => {
+                if chars[*pos..].starts_with(&['?', ':']) {
+                    *pos += 2;
+                }
+                let inner = parse_alternation(chars, pos)?;
+                if chars.get(*pos) != Some(&')') {
+                    return Err("unclosed group".to_string());
+                }
+                *pos += 1;
+                inner
+            },
+            '[' => parse_class(chars, pos)?,
+            '.' => Pattern::Class(vec![(' ', '~')]),
+            '\\' => escape(chars, pos)?,
+            '*' | '+' | '?' | '{' => return Err(format!("nothing to repeat before '{}'", c)),
+            c => Pattern::Literal(c),
+        };
+        parts.push(quantified(atom, chars, pos)?);
+    }
+    return Ok(Pattern::Sequence(parts));
+}
+
+fn escape(chars: &[char], pos: &mut usize) -> Result<Pattern, String> {
+    let Some(c) = chars.get(*pos).copied() else {
+        return Err("trailing '\\'".to_string());
+    };
+    *pos += 1;
+    return Ok(match c {
+        'd' => Pattern::Class(vec![('0', '9')]),
+        'w' => Pattern::Class(vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')]),
+        's' => Pattern::Literal(' '),
+        'D' | 'W' | 'S' | 'b' | 'B' => return Err(format!("'\\{}' is not supported", c)),
+        'n' => Pattern::Literal('\n'),
+        't' => Pattern::Literal('\t'),
+        c if c.is_ascii_alphanumeric() => return Err(format!("'\\{}' is not supported", c)),
+        c => Pattern::Literal(c),
+    });
+}
+
+fn parse_class(chars: &[char], pos: &mut usize) -> Result<Pattern, String> {
+    if chars.get(*pos) == Some(&'^') {
+        return Err("negated classes are not supported".to_string());
+    }
+    let mut ranges = vec![];
+    loop {
+        let Some(c) = chars.get(*pos).copied() else {
+            return Err("unclosed '['".to_string());
+        };
+        *pos += 1;
+        match c {
+            ']' if !ranges.is_empty() => return Ok(Pattern::Class(ranges)),
+            '\\' => match escape(chars, pos)? {
+                Pattern::Literal(c) => ranges.push((c, c)),
+                Pattern::Class(more) => ranges.extend(more),
+                _ => unreachable!(),
+            },
+            c if chars.get(*pos) == Some(&'-') && chars.get(*pos + 1).is_some_and(|end| *end != ']') => {
+                let end = chars[*pos + 1];
+                *pos += 2;
+                if end < c {
+                    return Err(format!("range '{}-{}' is backwards", c, end));
+                }
+                ranges.push((c, end));
+            },
+            c => ranges.push((c, c)),
+        }
+    }
+}
+
+/// Applies any quantifier after `atom`. Open-ended ones repeat at most 3 more times
+/// than their minimum.
+fn quantified(atom: Pattern, chars: &[char], pos: &mut usize) -> Result<Pattern, String> {
+    let (min, max) = match chars.get(*pos) {
+        Some('*') => (0, 3),
+        Some('+') => (1, 4),
+        Some('?') => (0, 1),
+        Some('{') => {
+            let Some(close) = chars[*pos..].iter().position(|c| *c == '}') else {
+                return Err("unclosed '{'".to_string());
+            };
+            let body: String = chars[*pos + 1..*pos + close].iter().collect();
+            let number = |s: &str| s.trim().parse::<usize>().map_err(|_| format!("invalid repetition '{{{}}}'", body));
+            let bounds = match body.split_once(',') {
+                None => (number(&body)?, number(&body)?),
+                Some((min, "")) => (number(min)?, number(min)? + 3),
+                Some((min, max)) => (number(min)?, number(max)?),
+            };
+            *pos += close;
+            bounds
+        },
+        _ => return Ok(atom),
+    };
+    *pos += 1;
+    if chars.get(*pos) == Some(&'?') {
+        *pos += 1;
+    }
+    if min > max {
+        return Err(format!("repetition {{{},{}}} is backwards", min, max));
+    }
+    return Ok(Pattern::Repeat(Box::new(atom), min, max));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn generate_is_reproducible() {
+        let profile = Profile::default();
+        let first: Vec<JItem> = (0..20).map(|_| generate(&profile, &mut Rng::new(7))).collect();
+        assert!(first.iter().all(|item| *item == first[0]));
+        let mut rng = Rng::new(7);
+        let text = generate_text(&profile, &mut rng, &FormatOptions::pretty());
+        assert_eq!(parse(&text).unwrap(), first[0]);
+        assert_ne!(generate(&profile, &mut rng), first[0]);
+    }
+
+    #[test]
+    fn generate_follows_profile() {
+        let profile = Profile {
+            max_depth: 2,
+            branching: 2..=3,
+            weights: TypeWeights { null: 0, bool: 0, number: 0, string: 1, array: 1, object: 0 },
+            string_length: LengthDistribution::Fixed(4),
+            key_length: LengthDistribution::Fixed(1),
+            alphabet: vec!['x', 'y'],
+        };
+        let mut rng = Rng::new(1);
+        for _ in 0..100 {
+            let item = generate(&profile, &mut rng);
+            let stats = item.stats();
+            assert!(stats.max_depth <= 2);
+            assert_eq!((stats.objects, stats.numbers, stats.booleans, stats.nulls), (0, 0, 0, 0));
+            assert_eq!(stats.string_bytes, stats.strings * 4);
+            if let JItem::Array(elements) = &item {
+                assert!((2..=3).contains(&elements.len()));
+            }
+        }
+    }
+
+    #[test]
+    fn generate_without_weights() {
+        let profile = Profile { weights: TypeWeights { null: 0, bool: 0, number: 0, string: 0, array: 0, object: 5 }, max_depth: 0, ..Profile::default() };
+        assert_eq!(generate(&profile, &mut Rng::new(3)), JItem::Null);
+    }
+
+    #[test]
+    fn rng_ranges() {
+        let mut rng = Rng::new(42);
+        for _ in 0..1000 {
+            assert!(rng.below(3) < 3);
+            assert!((5..=9).contains(&rng.in_range(5..=9)));
+            assert!((0.0..1.0).contains(&rng.unit()));
+        }
+        assert_eq!(rng.in_range(4..=4), 4);
+    }
+
+    #[test]
+    fn generate_matching_schema() {
+        let schema = parse(r#"{
+            "type": "object",
+            "required": ["id", "name", "tags", "kind"],
+            "properties": {
+                "id": {"type": "integer", "minimum": 10, "exclusiveMaximum": 20},
+                "price": {"type": "number", "minimum": 0, "maximum": 1, "multipleOf": 0.25},
+                "name": {"type": "string", "minLength": 2, "maxLength": 4},
+                "code": {"pattern": "^[A-C]{3}-\\d{2,4}(x|yz)?$"},
+                "email": {"type": "string", "format": "email"},
+                "tags": {"type": "array", "items": {"enum": ["a", "b", "c"]}, "minItems": 1, "maxItems": 3, "uniqueItems": true},
+                "kind": {"oneOf": [{"const": "fixed"}, {"type": "null"}]},
+                "nested": {"allOf": [{"properties": {"x": {"type": "boolean"}}, "required": ["x"]}, {"required": ["y"], "properties": {"y": {"const": 1}}}]}
+            }
+        }"#).unwrap();
+        let mut rng = Rng::new(5);
+        for _ in 0..200 {
+            let item = generate_matching(&schema, &mut rng).unwrap();
+            let id = item.pointer("/id").unwrap().number_value().unwrap();
+            assert!((10.0..20.0).contains(&id) && id.fract() == 0.0);
+            if let Some(price) = item.pointer("/price") {
+                assert!([0.0, 0.25, 0.5, 0.75, 1.0].contains(&price.number_value().unwrap()));
+            }
+            let JItem::String(name) = item.pointer("/name").unwrap() else { panic!() };
+            assert!((2..=4).contains(&name.len()));
+            if let Some(JItem::String(code)) = item.pointer("/code") {
+                let (letters, rest) = code.split_once('-').unwrap();
+                let digits = rest.trim_end_matches(['x', 'y', 'z']);
+                assert!(letters.len() == 3 && letters.chars().all(|c| ('A'..='C').contains(&c)), "{}", code);
+                assert!((2..=4).contains(&digits.len()) && digits.chars().all(|c| c.is_ascii_digit()), "{}", code);
+                assert!(["", "x", "yz"].contains(&&rest[digits.len()..]), "{}", code);
+            }
+            if let Some(JItem::String(email)) = item.pointer("/email") {
+                assert!(email.ends_with("@example.com"));
+            }
+            let JItem::Array(tags) = item.pointer("/tags").unwrap() else { panic!() };
+            assert!((1..=3).contains(&tags.len()));
+            assert!(tags.iter().enumerate().all(|(n, tag)| !tags[..n].contains(tag)));
+            assert!(matches!(item.pointer("/kind").unwrap(), JItem::Null | JItem::String(_)));
+            if let Some(nested) = item.pointer("/nested") {
+                assert!(matches!(nested.pointer("/x"), Some(JItem::Bool(_))));
+                assert_eq!(nested.pointer("/y"), Some(&JItem::Number(1.0)));
+            }
+        }
+    }
+
+    #[test]
+    fn generate_matching_follows_refs() {
+        let schema = parse(r##"{
+            "$id": "https://example.com/schemas/order.json",
+            "type": "object",
+            "required": ["customer", "total", "line"],
+            "properties": {
+                "customer": {"$ref": "customer.json"},
+                "total": {"$ref": "common/money.json#/$defs/amount", "maximum": 5},
+                "line": {"allOf": [{"$ref": "#/$defs/line"}, {"$ref": "#described"}]}
+            },
+            "$defs": {
+                "line": {"type": "object", "required": ["next"], "properties": {"next": {"$ref": "#/$defs/link"}}},
+                "link": {"anyOf": [{"type": "null"}, {"$ref": "#/$defs/line"}]},
+                "described": {"$anchor": "described", "required": ["note"], "properties": {"note": {"const": "n"}}}
+            }
+        }"##).unwrap();
+        let mut registry = SchemaRegistry::new().with_loader(|uri| {
+            let text = match uri {
+                "https://example.com/schemas/customer.json" => r#"{"type": "object", "required": ["id"], "properties": {"id": {"$ref": "common/money.json#/$defs/id"}}}"#,
+                "https://example.com/schemas/common/money.json" => r##"{"$defs": {"amount": {"type": "integer", "minimum": 1}, "id": {"$ref": "#/$defs/amount"}}}"##,
+                _ => return Err("not found".to_string()),
+            };
+            return parse(text).map_err(|e| e.to_string());
+        });
+        let mut rng = Rng::new(9);
+        for _ in 0..100 {
+            let item = generate_matching_with(&schema, &mut registry, &mut rng).unwrap();
+            assert!(item.pointer("/customer/id").unwrap().number_value().unwrap() >= 1.0);
+            assert!((1.0..=5.0).contains(&item.pointer("/total").unwrap().number_value().unwrap()));
+            assert_eq!(item.pointer("/line/note"), Some(&JItem::String("n".to_string())));
+            let mut line = item.pointer("/line").unwrap();
+            while let Some(next @ JItem::Object(_)) = line.pointer("/next") {
+                line = next;
+            }
+            assert_eq!(line.pointer("/next"), Some(&JItem::Null));
+        }
+        assert!(registry.get("https://example.com/schemas/customer.json").is_some());
+    }
+
+    #[test]
+    fn generate_matching_errors() {
+        let mut rng = Rng::new(0);
+        for (schema, error) in [
+            (r#"false"#, "schema 'false' matches nothing"),
+            (r##"{"$ref": "#/defs/a"}"##, "'#/defs/a' does not point to anything in ''"),
+            (r##"{"$ref": "#"}"##, "more than 32 '$ref's in a row; the schema may be circular"),
+            (r#"{"$ref": "other.json"}"#, "no schema is registered for 'other.json'"),
+            (r#"{"type": "integer", "minimum": 1.2, "maximum": 1.8}"#, "no integer lies between 1.2 and 1.8"),
+            (r#"{"type": "string", "minLength": 3, "maxLength": 2}"#, "minLength 3 is greater than maxLength 2"),
+            (r#"{"pattern": "[^a]"}"#, "unsupported pattern '[^a]': negated classes are not supported"),
+            (r#"{"enum": []}"#, "'enum' must be a non-empty array"),
+            (r#"{"items": {"enum": [1]}, "minItems": 2, "uniqueItems": true}"#, "could not generate enough unique items"),
+        ] {
+            assert_eq!(generate_matching(&parse(schema).unwrap(), &mut rng), Err(error.to_string()), "{}", schema);
+        }
+    }
+}
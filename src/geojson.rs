@@ -0,0 +1,378 @@
+//! Typed GeoJSON (RFC 7946) on top of `JItem`. Read with `TryFrom<&JItem>`, which checks
+//! the members each object requires, and write back with `From<...> for JItem`.
+
+use std::collections::HashMap;
+
+use crate::j_item::JItem;
+
+/// Longitude, latitude and optionally altitude, in that order.
+pub type Position = Vec<f64>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Geometry {
+    Point(Position),
+    MultiPoint(Vec<Position>),
+    /// At least two positions.
+    LineString(Vec<Position>),
+    MultiLineString(Vec<Vec<Position>>),
+    /// Linear rings, the exterior first. Each ring is closed and has at least four
+    /// positions.
+    Polygon(Vec<Vec<Position>>),
+    MultiPolygon(Vec<Vec<Vec<Position>>>),
+    GeometryCollection(Vec<Geometry>),
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Feature {
+    /// A string or number, when present.
+    pub id: Option<JItem>,
+    /// `None` is written as `"geometry": null`, an unlocated feature.
+    pub geometry: Option<Geometry>,
+    /// `None` is written as `"properties": null`.
+    pub properties: Option<HashMap<String, JItem>>,
+    pub bbox: Option<Vec<f64>>,
+    /// Members the specification does not define, kept so they survive a round trip.
+    pub foreign_members: HashMap<String, JItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FeatureCollection {
+    pub features: Vec<Feature>,
+    pub bbox: Option<Vec<f64>>,
+    pub foreign_members: HashMap<String, JItem>,
+}
+
+/// Any GeoJSON object, told apart by its `type` member.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GeoJson {
+    Geometry(Geometry),
+    Feature(Feature),
+    FeatureCollection(FeatureCollection),
+}
+
+impl Geometry {
+    pub fn type_name(&self) -> &'static str {
+        return match self {
+            Geometry::Point(_) => "Point",
+            Geometry::MultiPoint(_) => "MultiPoint",
+            Geometry::LineString(_) => "LineString",
+            Geometry::MultiLineString(_) => "MultiLineString",
+            Geometry::Polygon(_) => "Polygon",
+            Geometry::MultiPolygon(_) => "MultiPolygon",
+            Geometry::GeometryCollection(_) => "GeometryCollection",
+        };
+    }
+}
+
+impl TryFrom<&JItem> for GeoJson {
+    type Error = String;
+
+    fn try_from(item: &JItem) -> Result<Self, String> {
+        return match type_member(item)? {
+            "Feature" => Feature::try_from(item).map(GeoJson::Feature),
+            "FeatureCollection" => FeatureCollection::try_from(item).map(GeoJson::FeatureCollection),
+            _ => Geometry::try_from(item).map(GeoJson::Geometry),
+        };
+    }
+}
+
+impl TryFrom<&JItem> for Geometry {
+    type Error = String;
+
+    fn try_from(item: &JItem) -> Result<Self, String> {
+        let kind = type_member(item)?;
+        if kind == "GeometryCollection" {
+            let Some(JItem::Array(geometries)) = member(item, "geometries") else {
+                return Err("geometry collection requires a \"geometries\" array".to_string());
+            };
+            let geometries = geometries
+                .iter()
+                .enumerate()
+                .map(|(n, geometry)| Geometry::try_from(geometry).map_err(|e| format!("geometry {}: {}", n, e)))
+                .collect::<Result<_, _>>()?;
+            return Ok(Geometry::GeometryCollection(geometries));
+        }
+
+        let Some(coordinates) = member(item, "coordinates") else {
+            return Err(format!("{} requires \"coordinates\"", kind));
+        };
+        return match kind {
+            "Point" => position(coordinates).map(Geometry::Point),
+            "MultiPoint" => positions(coordinates).map(Geometry::MultiPoint),
+            "LineString" => line(coordinates).map(Geometry::LineString),
+            "MultiLineString" => each(coordinates, line).map(Geometry::MultiLineString),
+            "Polygon" => polygon(coordinates).map(Geometry::Polygon),
+            "MultiPolygon" => each(coordinates, polygon).map(Geometry::MultiPolygon),
+            _ => Err(format!("unknown geometry type '{}'", kind)),
+        };
+    }
+}
+
+impl TryFrom<&JItem> for Feature {
+    type Error = String;
+
+    fn try_from(item: &JItem) -> Result<Self, String> {
+        if type_member(item)? != "Feature" {
+            return Err("expected a Feature".to_string());
+        }
+        let JItem::Object(hmap) = item else {
+            unreachable!("type_member only succeeds on objects");
+        };
+        let id = match hmap.get("id") {
+            None => None,
+            Some(id @ (JItem::String(_) | JItem::Number(_) | JItem::RawNumber(_))) => Some(id.clone()),
+            Some(_) => return Err("feature id must be a string or number".to_string()),
+        };
+        let geometry = match hmap.get("geometry") {
+            None => return Err("feature requires \"geometry\"".to_string()),
+            Some(JItem::Null) => None,
+            Some(geometry) => Some(Geometry::try_from(geometry).map_err(|e| format!("geometry: {}", e))?),
+        };
+        let properties = match hmap.get("properties") {
+            None => return Err("feature requires \"properties\"".to_string()),
+            Some(JItem::Null) => None,
+            Some(JItem::Object(properties)) => Some(properties.clone()),
+            Some(_) => return Err("feature properties must be an object or null".to_string()),
+        };
+        return Ok(Feature {
+            id,
+            geometry,
+            properties,
+            bbox: bbox(item)?,
+            foreign_members: foreign_members(hmap, &["type", "id", "geometry", "properties", "bbox"]),
+        });
+    }
+}
+
+impl TryFrom<&JItem> for FeatureCollection {
+    type Error = String;
+
+    fn try_from(item: &JItem) -> Result<Self, String> {
+        if type_member(item)? != "FeatureCollection" {
+            return Err("expected a FeatureCollection".to_string());
+        }
+        let JItem::Object(hmap) = item else {
+            unreachable!("type_member only succeeds on objects");
+        };
+        let Some(JItem::Array(features)) = hmap.get("features") else {
+            return Err("feature collection requires a \"features\" array".to_string());
+        };
+        let features = features
+            .iter()
+            .enumerate()
+            .map(|(n, feature)| Feature::try_from(feature).map_err(|e| format!("feature {}: {}", n, e)))
+            .collect::<Result<_, _>>()?;
+        return Ok(FeatureCollection {
+            features,
+            bbox: bbox(item)?,
+            foreign_members: foreign_members(hmap, &["type", "features", "bbox"]),
+        });
+    }
+}
+
+impl From<GeoJson> for JItem {
+    fn from(geojson: GeoJson) -> Self {
+        return match geojson {
+            GeoJson::Geometry(geometry) => geometry.into(),
+            GeoJson::Feature(feature) => feature.into(),
+            GeoJson::FeatureCollection(collection) => collection.into(),
+        };
+    }
+}
+
+impl From<Geometry> for JItem {
+    fn from(geometry: Geometry) -> Self {
+        let kind = geometry.type_name();
+        let (name, value) = match geometry {
+            Geometry::Point(p) => ("coordinates", numbers(p)),
+            Geometry::MultiPoint(ps) | Geometry::LineString(ps) => ("coordinates", array(ps, numbers)),
+            Geometry::MultiLineString(lines) | Geometry::Polygon(lines) => ("coordinates", array(lines, |ps| array(ps, numbers))),
+            Geometry::MultiPolygon(polygons) => ("coordinates", array(polygons, |lines| array(lines, |ps| array(ps, numbers)))),
+            Geometry::GeometryCollection(geometries) => ("geometries", array(geometries, JItem::from)),
+        };
+        return object(kind, [(name, value)], None, HashMap::new());
+    }
+}
+
+impl From<Feature> for JItem {
+    fn from(feature: Feature) -> Self {
+        let mut members = vec![
+            ("geometry", feature.geometry.map_or(JItem::Null, JItem::from)),
+            ("properties", feature.properties.map_or(JItem::Null, JItem::Object)),
+        ];
+        if let Some(id) = feature.id {
+            members.push(("id", id));
+        }
+        return object("Feature", members, feature.bbox, feature.foreign_members);
+    }
+}
+
+impl From<FeatureCollection> for JItem {
+    fn from(collection: FeatureCollection) -> Self {
+        let features = array(collection.features, JItem::from);
+        return object("FeatureCollection", [("features", features)], collection.bbox, collection.foreign_members);
+    }
+}
+
+fn type_member(item: &JItem) -> Result<&str, String> {
+    let JItem::Object(hmap) = item else {
+        return Err("geojson object must be an object".to_string());
+    };
+    let Some(JItem::String(kind)) = hmap.get("type") else {
+        return Err("geojson object requires a string \"type\"".to_string());
+    };
+    return Ok(kind);
+}
+
+fn member<'a>(item: &'a JItem, key: &str) -> Option<&'a JItem> {
+    let JItem::Object(hmap) = item else {
+        return None;
+    };
+    return hmap.get(key);
+}
+
+fn position(item: &JItem) -> Result<Position, String> {
+    let JItem::Array(elements) = item else {
+        return Err("position must be an array of numbers".to_string());
+    };
+    let position: Option<Vec<f64>> = elements.iter().map(JItem::number_value).collect();
+    return match position {
+        Some(position) if position.len() >= 2 => Ok(position),
+        Some(_) => Err("position requires at least two numbers".to_string()),
+        None => Err("position must be an array of numbers".to_string()),
+    };
+}
+
+fn positions(item: &JItem) -> Result<Vec<Position>, String> {
+    return each(item, position);
+}
+
+fn line(item: &JItem) -> Result<Vec<Position>, String> {
+    let line = positions(item)?;
+    if line.len() < 2 {
+        return Err("line string requires at least two positions".to_string());
+    }
+    return Ok(line);
+}
+
+fn polygon(item: &JItem) -> Result<Vec<Vec<Position>>, String> {
+    return each(item, |ring| {
+        let ring = positions(ring)?;
+        if ring.len() < 4 {
+            return Err("linear ring requires at least four positions".to_string());
+        }
+        if ring.first() != ring.last() {
+            return Err("linear ring must end where it starts".to_string());
+        }
+        return Ok(ring);
+    });
+}
+
+fn each<T>(item: &JItem, read: impl Fn(&JItem) -> Result<T, String>) -> Result<Vec<T>, String> {
+    let JItem::Array(elements) = item else {
+        return Err("coordinates must be nested arrays".to_string());
+    };
+    return elements.iter().map(read).collect();
+}
+
+fn bbox(item: &JItem) -> Result<Option<Vec<f64>>, String> {
+    let Some(bbox) = member(item, "bbox") else {
+        return Ok(None);
+    };
+    let bbox = match bbox {
+        JItem::Array(elements) => elements.iter().map(JItem::number_value).collect::<Option<Vec<f64>>>(),
+        _ => None,
+    };
+    return match bbox {
+        Some(bbox) if bbox.len() >= 4 && bbox.len() % 2 == 0 => Ok(Some(bbox)),
+        _ => Err("bbox must be an array of 2n numbers, n at least 2".to_string()),
+    };
+}
+
+fn foreign_members(hmap: &HashMap<String, JItem>, known: &[&str]) -> HashMap<String, JItem> {
+    return hmap.iter().filter(|(key, _)| !known.contains(&key.as_str())).map(|(key, value)| (key.clone(), value.clone())).collect();
+}
+
+fn numbers(position: Position) -> JItem {
+    return array(position, JItem::Number);
+}
+
+fn array<T>(elements: Vec<T>, convert: impl Fn(T) -> JItem) -> JItem {
+    return JItem::Array(elements.into_iter().map(convert).collect());
+}
+
+fn object<'a>(kind: &str, members: impl IntoIterator<Item = (&'a str, JItem)>, bbox: Option<Vec<f64>>, foreign_members: HashMap<String, JItem>) -> JItem {
+    let mut hmap = foreign_members;
+    hmap.insert("type".to_string(), JItem::String(kind.to_string()));
+    hmap.extend(members.into_iter().map(|(key, value)| (key.to_string(), value)));
+    if let Some(bbox) = bbox {
+        hmap.insert("bbox".to_string(), numbers(bbox));
+    }
+    return JItem::Object(hmap);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn feature_collection_round_trip() {
+        let input = parse(
+            r#"{"type": "FeatureCollection", "name": "parks", "features": [
+                {"type": "Feature", "id": 7, "geometry": {"type": "Point", "coordinates": [1.5, 2]}, "properties": {"open": true}},
+                {"type": "Feature", "geometry": null, "properties": null, "bbox": [0, 0, 1, 1]},
+                {"type": "Feature", "properties": {}, "geometry": {"type": "GeometryCollection", "geometries": [
+                    {"type": "Polygon", "coordinates": [[[0, 0], [1, 0], [1, 1], [0, 0]]]},
+                    {"type": "MultiLineString", "coordinates": [[[0, 0], [1, 1]]]}
+                ]}}
+            ]}"#,
+        )
+        .unwrap();
+        let GeoJson::FeatureCollection(collection) = GeoJson::try_from(&input).unwrap() else {
+            panic!("expected a feature collection");
+        };
+        assert_eq!(collection.features.len(), 3);
+        assert_eq!(collection.foreign_members.get("name"), Some(&JItem::String("parks".to_string())));
+        assert_eq!(collection.features[0].geometry, Some(Geometry::Point(vec![1.5, 2.0])));
+        assert_eq!(collection.features[1].bbox, Some(vec![0.0, 0.0, 1.0, 1.0]));
+        let Some(Geometry::GeometryCollection(geometries)) = &collection.features[2].geometry else {
+            panic!("expected a geometry collection");
+        };
+        assert_eq!(geometries[1].type_name(), "MultiLineString");
+        assert_eq!(JItem::from(collection), input);
+    }
+
+    #[test]
+    fn build_feature() {
+        let feature = Feature {
+            id: Some(JItem::String("a".to_string())),
+            geometry: Some(Geometry::LineString(vec![vec![0.0, 0.0], vec![1.0, 2.0]])),
+            ..Feature::default()
+        };
+        let expected = r#"{"type": "Feature", "id": "a", "geometry": {"type": "LineString", "coordinates": [[0, 0], [1, 2]]}, "properties": null}"#;
+        assert_eq!(JItem::from(feature), parse(expected).unwrap());
+    }
+
+    #[test]
+    fn required_members_are_checked() {
+        for (input, error) in [
+            (r#"[]"#, "geojson object must be an object"),
+            (r#"{"coordinates": [0, 0]}"#, "geojson object requires a string \"type\""),
+            (r#"{"type": "Point"}"#, "Point requires \"coordinates\""),
+            (r#"{"type": "Point", "coordinates": [0]}"#, "position requires at least two numbers"),
+            (r#"{"type": "LineString", "coordinates": [[0, 0]]}"#, "line string requires at least two positions"),
+            (r#"{"type": "Polygon", "coordinates": [[[0, 0], [1, 0], [1, 1], [0, 1]]]}"#, "linear ring must end where it starts"),
+            (r#"{"type": "Circle", "coordinates": [0, 0]}"#, "unknown geometry type 'Circle'"),
+            (r#"{"type": "Feature", "properties": {}}"#, "feature requires \"geometry\""),
+            (r#"{"type": "Feature", "geometry": null, "properties": {}, "id": true}"#, "feature id must be a string or number"),
+            (r#"{"type": "Feature", "geometry": null, "properties": {}, "bbox": [0, 0]}"#, "bbox must be an array of 2n numbers, n at least 2"),
+            (
+                r#"{"type": "FeatureCollection", "features": [{"type": "Feature", "geometry": {"type": "Point", "coordinates": "x"}, "properties": null}]}"#,
+                "feature 0: geometry: position must be an array of numbers",
+            ),
+        ] {
+            assert_eq!(GeoJson::try_from(&parse(input).unwrap()), Err(error.to_string()), "{}", input);
+        }
+    }
+}
@@ -0,0 +1,197 @@
+//! Parsing HTTP response bodies, from any client built on the `http` crate.
+//!
+//! `parse_response` takes an `http::Response` whose body implements `http_body::Body`,
+//! as `hyper` gives, and a `reqwest::Response` converts into one with
+//! `http::Response::from`. For blocking clients, `parse_reader` takes the headers and a
+//! reader over the body. Either way the body is decoded using the `charset` from its
+//! Content-Type, and reading stops with an error once it grows past `max_bytes`.
+
+use std::{
+    future::poll_fn,
+    io::{self, Read},
+    pin::pin,
+};
+
+use bytes::Buf;
+use http::{header, HeaderMap, Response};
+use http_body::Body;
+
+use crate::{error::JsonError, j_item::JItem, parse_with, parser::ParseOptions};
+
+/// Options for `parse_response` and `parse_reader`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpOptions {
+    /// The largest body to read, in bytes. The default is 16 MiB.
+    pub max_bytes: usize,
+    pub parse: ParseOptions,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        return HttpOptions { max_bytes: 16 * 1024 * 1024, parse: ParseOptions::default() };
+    }
+}
+
+/// Reads a response body and parses it. A Content-Length above `max_bytes` fails
+/// before anything is read.
+pub async fn parse_response<B>(response: Response<B>, options: &HttpOptions) -> Result<JItem, JsonError>
+where
+    B: Body,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let (parts, body) = response.into_parts();
+    check_length(&parts.headers, options.max_bytes)?;
+    let mut body = pin!(body);
+    let mut bytes = vec![];
+    while let Some(frame) = poll_fn(|cx| body.as_mut().poll_frame(cx)).await {
+        let frame = frame.map_err(io::Error::other)?;
+        let Ok(mut data) = frame.into_data() else {
+            continue;
+        };
+        if bytes.len() + data.remaining() > options.max_bytes {
+            return Err(too_large(options.max_bytes).into());
+        }
+        while data.has_remaining() {
+            let chunk = data.chunk();
+            bytes.extend_from_slice(chunk);
+            let read = chunk.len();
+            data.advance(read);
+        }
+    }
+    return parse_body(&parts.headers, &bytes, options);
+}
+
+/// Reads a body from `reader`, such as a `reqwest::blocking::Response`, and parses it
+/// using the charset given in `headers`.
+pub fn parse_reader(headers: &HeaderMap, reader: impl Read, options: &HttpOptions) -> Result<JItem, JsonError> {
+    check_length(headers, options.max_bytes)?;
+    let mut bytes = vec![];
+    reader.take(options.max_bytes as u64 + 1).read_to_end(&mut bytes)?;
+    if bytes.len() > options.max_bytes {
+        return Err(too_large(options.max_bytes).into());
+    }
+    return parse_body(headers, &bytes, options);
+}
+
+fn check_length(headers: &HeaderMap, max_bytes: usize) -> io::Result<()> {
+    let length = headers.get(header::CONTENT_LENGTH).and_then(|value| value.to_str().ok()).and_then(|value| value.trim().parse::<u64>().ok());
+    if length.is_some_and(|length| length > max_bytes as u64) {
+        return Err(too_large(max_bytes));
+    }
+    return Ok(());
+}
+
+fn too_large(max_bytes: usize) -> io::Error {
+    return io::Error::new(io::ErrorKind::FileTooLarge, format!("response body is larger than {} bytes", max_bytes));
+}
+
+fn parse_body(headers: &HeaderMap, bytes: &[u8], options: &HttpOptions) -> Result<JItem, JsonError> {
+    let charset = charset(headers).unwrap_or_else(|| "utf-8".to_string());
+    let text = match charset.as_str() {
+        "utf-8" | "utf8" => {
+            let bytes = bytes.strip_prefix(b"\xef\xbb\xbf").unwrap_or(bytes);
+            return parse_with(JsonError::check_utf8(bytes)?, &options.parse);
+        },
+        "utf-16" => match bytes {
+            [0xff, 0xfe, rest @ ..] => decode_utf16(rest, u16::from_le_bytes)?,
+            [0xfe, 0xff, rest @ ..] => decode_utf16(rest, u16::from_be_bytes)?,
+            _ => decode_utf16(bytes, u16::from_be_bytes)?,
+        },
+        "utf-16le" => decode_utf16(bytes.strip_prefix(b"\xff\xfe").unwrap_or(bytes), u16::from_le_bytes)?,
+        "utf-16be" => decode_utf16(bytes.strip_prefix(b"\xfe\xff").unwrap_or(bytes), u16::from_be_bytes)?,
+        "iso-8859-1" | "latin1" | "us-ascii" => bytes.iter().map(|&b| b as char).collect(),
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unsupported charset '{}'", charset)).into()),
+    };
+    return parse_with(&text, &options.parse);
+}
+
+/// The lowercased `charset` parameter of the Content-Type header, if it has one.
+fn charset(headers: &HeaderMap) -> Option<String> {
+    let content_type = headers.get(header::CONTENT_TYPE)?.to_str().ok()?;
+    for param in content_type.split(';').skip(1) {
+        let Some((name, value)) = param.split_once('=') else {
+            continue;
+        };
+        if name.trim().eq_ignore_ascii_case("charset") {
+            return Some(value.trim().trim_matches('"').to_ascii_lowercase());
+        }
+    }
+    return None;
+}
+
+fn decode_utf16(bytes: &[u8], unit: fn([u8; 2]) -> u16) -> io::Result<String> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "utf-16 body has an odd number of bytes"));
+    }
+    let units = bytes.chunks_exact(2).map(|pair| unit([pair[0], pair[1]]));
+    return char::decode_utf16(units).collect::<Result<String, _>>().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("invalid utf-16 in body: {}", e)));
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::VecDeque,
+        convert::Infallible,
+        future::Future,
+        pin::Pin,
+        task::{Context, Poll, Waker},
+    };
+
+    use http_body::Frame;
+
+    use super::*;
+    use crate::{error::ErrorKind, parse};
+
+    /// A body that hands out its chunks one frame at a time.
+    struct Chunks(VecDeque<&'static [u8]>);
+
+    impl Body for Chunks {
+        type Data = &'static [u8];
+        type Error = Infallible;
+
+        fn poll_frame(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+            return Poll::Ready(self.0.pop_front().map(|chunk| Ok(Frame::data(chunk))));
+        }
+    }
+
+    fn response(content_type: &str, chunks: &[&'static [u8]]) -> Result<JItem, JsonError> {
+        let response = Response::builder().header(header::CONTENT_TYPE, content_type).body(Chunks(chunks.iter().copied().collect())).unwrap();
+        let options = HttpOptions { max_bytes: 16, ..HttpOptions::default() };
+        // the body never waits, so polling once is enough
+        let Poll::Ready(output) = pin!(parse_response(response, &options)).poll(&mut Context::from_waker(Waker::noop())) else {
+            panic!("parsing should not wait");
+        };
+        return output;
+    }
+
+    #[test]
+    fn async_bodies() {
+        assert_eq!(response("application/json", &[b"[1, ", b"\"a\"]"]), parse(r#"[1, "a"]"#));
+        assert_eq!(response("application/json; charset=\"UTF-16LE\"", &[b"[\x001\x00]\x00"]), parse("[1]"));
+        let error = response("application/json", &[b"[1, 2, 3, ", b"4, 5, 6, 7]"]).unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::Io { kind: io::ErrorKind::FileTooLarge, message: "response body is larger than 16 bytes".to_string() });
+    }
+
+    #[test]
+    fn blocking_bodies() {
+        let mut headers = HeaderMap::new();
+        let options = HttpOptions::default();
+        assert_eq!(parse_reader(&headers, &b"\xef\xbb\xbf{\"a\": true}"[..], &options), parse(r#"{"a": true}"#));
+        headers.insert(header::CONTENT_TYPE, "application/json; charset=iso-8859-1".parse().unwrap());
+        assert_eq!(parse_reader(&headers, &b"\"caf\xe9\""[..], &options), Ok(JItem::String("café".to_string())));
+        headers.insert(header::CONTENT_TYPE, "application/json;charset=utf-16".parse().unwrap());
+        assert_eq!(parse_reader(&headers, &b"\xff\xfe[\x00]\x00"[..], &options), parse("[]"));
+        headers.insert(header::CONTENT_TYPE, "application/json; charset=koi8-r".parse().unwrap());
+        assert_eq!(parse_reader(&headers, &b"[]"[..], &options).unwrap_err().kind(), &ErrorKind::Io { kind: io::ErrorKind::InvalidData, message: "unsupported charset 'koi8-r'".to_string() });
+    }
+
+    #[test]
+    fn size_limits() {
+        let mut headers = HeaderMap::new();
+        let options = HttpOptions { max_bytes: 4, ..HttpOptions::default() };
+        assert_eq!(parse_reader(&headers, &b"[1,2]"[..], &options).unwrap_err().code(), "E018");
+        assert_eq!(parse_reader(&headers, &b"[12]"[..], &options), parse("[12]"));
+        headers.insert(header::CONTENT_LENGTH, "100".parse().unwrap());
+        assert!(parse_reader(&headers, &b"[]"[..], &options).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+use crate::{
+    j_item::JItem,
+    path::{PathSegment, format_path},
+};
+
+impl JItem {
+    /// Replaces `${VAR}` and `${VAR:-default}` placeholders in string values with
+    /// variables from the process environment. See `interpolate_with`.
+    pub fn interpolate_env(&mut self) -> Result<(), String> {
+        return self.interpolate_with(|name| std::env::var(name).ok());
+    }
+
+    /// Like `interpolate_env`, taking variables from `vars`.
+    pub fn interpolate_vars(&mut self, vars: &HashMap<String, String>) -> Result<(), String> {
+        return self.interpolate_with(|name| vars.get(name).cloned());
+    }
+
+    /// Replaces `${VAR}` and `${VAR:-default}` placeholders in string values using
+    /// `lookup`. The default is used when the variable is missing or empty, and `$$`
+    /// writes a literal `$`. Keys are left alone and substituted text is not expanded
+    /// again. A missing variable without a default is an error naming its path.
+    pub fn interpolate_with<F>(&mut self, lookup: F) -> Result<(), String>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let mut segments = vec![];
+        return interpolate_node(self, &mut segments, &lookup);
+    }
+}
+
+fn interpolate_node<F>(item: &mut JItem, segments: &mut Vec<PathSegment>, lookup: &F) -> Result<(), String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    match item {
+        JItem::Object(hmap) => {
+            for (key, value) in hmap.iter_mut() {
+                segments.push(PathSegment::Key(key.to_string()));
+                interpolate_node(value, segments, lookup)?;
+                segments.pop();
+            }
+        },
+        JItem::Array(elements) => {
+            for (index, element) in elements.iter_mut().enumerate() {
+                segments.push(PathSegment::Index(index));
+                interpolate_node(element, segments, lookup)?;
+                segments.pop();
+            }
+        },
+        JItem::String(s) if s.contains('$') => {
+            *s = interpolate_str(s, lookup).map_err(|e| match segments.is_empty() {
+                true => e,
+                false => format!("{} at '{}'", e, format_path(segments, ".")),
+            })?;
+        },
+        _ => {},
+    }
+    return Ok(());
+}
+
+fn interpolate_str<F>(s: &str, lookup: &F) -> Result<String, String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('$') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        if let Some(tail) = after.strip_prefix('$') {
+            out.push('$');
+            rest = tail;
+            continue;
+        }
+        let Some(body) = after.strip_prefix('{') else {
+            out.push('$');
+            rest = after;
+            continue;
+        };
+        let Some(end) = body.find('}') else {
+            return Err(format!("unterminated placeholder in '{}'", s));
+        };
+        let placeholder = &body[..end];
+        let (name, default) = match placeholder.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (placeholder, None),
+        };
+        if name.is_empty() {
+            return Err(format!("empty placeholder in '{}'", s));
+        }
+        match (lookup(name).filter(|value| !value.is_empty() || default.is_none()), default) {
+            (Some(value), _) => out.push_str(&value),
+            (None, Some(default)) => out.push_str(default),
+            (None, None) => return Err(format!("undefined variable '{}'", name)),
+        }
+        rest = &body[end + 1..];
+    }
+    out.push_str(rest);
+    return Ok(out);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    fn vars() -> HashMap<String, String> {
+        let mut vars = HashMap::new();
+        vars.insert("HOST".to_string(), "db.local".to_string());
+        vars.insert("PORT".to_string(), "5432".to_string());
+        vars.insert("EMPTY".to_string(), "".to_string());
+        return vars;
+    }
+
+    #[test]
+    fn interpolate_placeholders() {
+        let mut item = parse(r#"{"url": "postgres://${HOST}:${PORT}/app", "${HOST}": 1, "list": ["${USER:-guest}", "${EMPTY:-fallback}", "${EMPTY}"]}"#).unwrap();
+        item.interpolate_vars(&vars()).unwrap();
+        assert_eq!(item, parse(r#"{"url": "postgres://db.local:5432/app", "${HOST}": 1, "list": ["guest", "fallback", ""]}"#).unwrap());
+    }
+
+    #[test]
+    fn interpolate_literal_dollars() {
+        let mut item = parse(r#"["$$HOST costs $5", "${HOST:-a:-b}"]"#).unwrap();
+        item.interpolate_vars(&HashMap::new()).unwrap();
+        assert_eq!(item, parse(r#"["$HOST costs $5", "a:-b"]"#).unwrap());
+    }
+
+    #[test]
+    fn interpolate_does_not_expand_substitutions() {
+        let mut item = JItem::String("${A}".to_string());
+        item.interpolate_with(|name| (name == "A").then(|| "${B}".to_string())).unwrap();
+        assert_eq!(item, JItem::String("${B}".to_string()));
+    }
+
+    #[test]
+    fn interpolate_errors() {
+        let mut item = parse(r#"{"db": {"hosts": ["${HOST}", "${MISSING}"]}}"#).unwrap();
+        assert_eq!(item.interpolate_vars(&vars()), Err("undefined variable 'MISSING' at 'db.hosts[1]'".to_string()));
+        let mut item = JItem::String("${HOST".to_string());
+        assert_eq!(item.interpolate_vars(&vars()), Err("unterminated placeholder in '${HOST'".to_string()));
+    }
+
+    #[test]
+    fn interpolate_env_reads_process_environment() {
+        let mut item = JItem::String("${PATH}".to_string());
+        item.interpolate_env().unwrap();
+        assert_eq!(item, JItem::String(std::env::var("PATH").unwrap()));
+    }
+}
@@ -1,25 +1,164 @@
-use std::{collections::HashMap, fmt::Display};
+use alloc::{format, string::String, vec::Vec};
+use core::{cmp::Ordering, fmt::Display};
 
-#[derive(Debug, PartialEq)]
+use crate::{format::Escape, lexer::is_rfc_number, HashMap};
+
+#[derive(Debug, Clone, Default)]
 pub enum JItem {
     Object(HashMap<String, JItem>),
     String(String),
     Array(Vec<JItem>),
     Number(f64),
-    True,
-    False,
+    /// A number kept as its exact source text, such as `1.50` or `1e3`. Produced when
+    /// parsing with `ParseOptions::preserve_number_text`; compares equal to the number it
+    /// spells and is written back out unchanged. Text that is not a JSON number is written
+    /// as the number it parses to, or `null`.
+    RawNumber(String),
+    Bool(bool),
+    #[default]
     Null,
 }
 
+/// Why `JItem::as_i64_checked` or `JItem::as_u64_checked` could not give an exact
+/// integer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntegerError {
+    NotANumber,
+    /// Past the range of the integer type, or NaN or infinite.
+    OutOfRange,
+    /// In range, but with a fractional part.
+    Truncated,
+    /// A whole number, but stored as an `f64` of 2^53 or more, which nearby integers
+    /// also round to, so it may not be the number written.
+    Rounded,
+}
+
+impl Display for IntegerError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return f.write_str(match self {
+            IntegerError::NotANumber => "not a number",
+            IntegerError::OutOfRange => "number is out of range",
+            IntegerError::Truncated => "number has a fractional part",
+            IntegerError::Rounded => "number is too large to be known exactly",
+        });
+    }
+}
+
+#[allow(non_upper_case_globals)]
+impl JItem {
+    #[deprecated(note = "booleans are now represented by `JItem::Bool(true)`")]
+    pub const True: JItem = JItem::Bool(true);
+    #[deprecated(note = "booleans are now represented by `JItem::Bool(false)`")]
+    pub const False: JItem = JItem::Bool(false);
+}
+
+impl JItem {
+    /// The value of a `Number` or `RawNumber`. Raw text that does not spell a number
+    /// reads as NaN.
+    pub(crate) fn number_value(&self) -> Option<f64> {
+        return match self {
+            JItem::Number(x) => Some(*x),
+            JItem::RawNumber(text) => Some(text.parse().unwrap_or(f64::NAN)),
+            _ => None,
+        };
+    }
+}
+
+impl From<bool> for JItem {
+    fn from(b: bool) -> Self {
+        return JItem::Bool(b);
+    }
+}
+
+/// Structural equality. Numbers compare with `==`, except that NaN equals NaN so that
+/// equality stays reflexive and `JItem` can implement `Eq`. `0` and `-0` are equal,
+/// and raw numbers compare by the value they spell.
+impl PartialEq for JItem {
+    fn eq(&self, other: &Self) -> bool {
+        return match (self, other) {
+            (JItem::Object(a), JItem::Object(b)) => a == b,
+            (JItem::String(a), JItem::String(b)) => a == b,
+            (JItem::Array(a), JItem::Array(b)) => a == b,
+            (JItem::Number(_) | JItem::RawNumber(_), JItem::Number(_) | JItem::RawNumber(_)) => {
+                let (a, b) = (self.number_value().unwrap(), other.number_value().unwrap());
+                a == b || (a.is_nan() && b.is_nan())
+            },
+            (JItem::Bool(a), JItem::Bool(b)) => a == b,
+            (JItem::Null, JItem::Null) => true,
+            _ => false,
+        };
+    }
+}
+
+impl Eq for JItem {}
+
+/// Total ordering, consistent with `Eq`. Values of different types are ordered by type:
+/// null < false < true < number < string < array < object. Numbers are ordered
+/// numerically with `-0` equal to `0` and NaN greater than every other number. Strings
+/// compare bytewise, arrays lexicographically, and objects as their members sorted by key.
+impl Ord for JItem {
+    fn cmp(&self, other: &Self) -> Ordering {
+        return match (self, other) {
+            (JItem::Number(_) | JItem::RawNumber(_), JItem::Number(_) | JItem::RawNumber(_)) => {
+                cmp_numbers(self.number_value().unwrap(), other.number_value().unwrap())
+            },
+            (JItem::String(a), JItem::String(b)) => a.cmp(b),
+            (JItem::Array(a), JItem::Array(b)) => a.cmp(b),
+            (JItem::Object(a), JItem::Object(b)) => sorted_members(a).cmp(&sorted_members(b)),
+            (JItem::Bool(a), JItem::Bool(b)) => a.cmp(b),
+            _ => type_rank(self).cmp(&type_rank(other)),
+        };
+    }
+}
+
+impl PartialOrd for JItem {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        return Some(self.cmp(other));
+    }
+}
+
+fn type_rank(item: &JItem) -> u8 {
+    return match item {
+        JItem::Null => 0,
+        JItem::Bool(_) => 1,
+        JItem::Number(_) | JItem::RawNumber(_) => 2,
+        JItem::String(_) => 3,
+        JItem::Array(_) => 4,
+        JItem::Object(_) => 5,
+    };
+}
+
+fn cmp_numbers(a: f64, b: f64) -> Ordering {
+    return match (a.is_nan(), b.is_nan()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        // adding 0.0 turns -0.0 into 0.0 and leaves every other value alone
+        (false, false) => (a + 0.0).total_cmp(&(b + 0.0)),
+    };
+}
+
+pub(crate) fn sorted_members(hmap: &HashMap<String, JItem>) -> Vec<(&String, &JItem)> {
+    let mut members: Vec<_> = hmap.iter().collect();
+    members.sort_by_key(|(key, _)| *key);
+    return members;
+}
+
 impl Display for JItem {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         let formatted = match self {
             JItem::Object(hash_map) => &fmt_j_object(hash_map),
             JItem::Array(jitems) => &fmt_j_array(jitems),
-            JItem::String(s) => &format!("\"{}\"", s),
-            JItem::Number(x) => &format!("{}", x),
-            JItem::True => "true",
-            JItem::False => "false",
+            JItem::String(s) => &format!("\"{}\"", escape_string(s)),
+            JItem::Number(x) => &format_f64(*x),
+            JItem::RawNumber(text) if is_rfc_number(text) => text,
+            // text that is not a JSON number could break out of the value it is in
+            JItem::RawNumber(_) => match self.number_value() {
+                Some(x) if x.is_finite() => &format_f64(x),
+                _ => "null",
+            },
+            JItem::Bool(true) => "true",
+            JItem::Bool(false) => "false",
             JItem::Null => "null",
         };
 
@@ -27,7 +166,7 @@ impl Display for JItem {
     }
 }
 
-fn fmt_j_array(arr: &Vec<JItem>) -> String {
+fn fmt_j_array(arr: &[JItem]) -> String {
     let formatted_items:Vec<_> = arr
         .iter()
         .map(|i| format!("{}", i))
@@ -38,15 +177,230 @@ fn fmt_j_array(arr: &Vec<JItem>) -> String {
 fn fmt_j_object(hmap: &HashMap<String, JItem>) -> String {
     let formatted_items:Vec<_> = hmap
         .iter()
-        .map(|(k, v)| format!("\"{}\":{}", k, v))
+        .map(|(k, v)| format!("\"{}\":{}", escape_string(k), v))
         .collect();
     format!("{{{}}}", formatted_items.join(","))
 }
 
+/// The shortest text that parses back to exactly `x`, keeping the sign of `-0`. Like
+/// JavaScript, plain notation is used for magnitudes from `1e-6` up to `1e21`, and
+/// exponent notation such as `1e300` or `5e-324` outside that. Non-finite values are
+/// written as Rust does, since JSON has no form for them.
+pub(crate) fn format_f64(x: f64) -> String {
+    if !x.is_finite() {
+        return format!("{}", x);
+    }
+    // `{:e}` gives the shortest round-trip digits, as in `-1.25e-7`
+    let scientific = format!("{:e}", x);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    let (sign, mantissa) = match mantissa.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", mantissa),
+    };
+    let digits = mantissa.replace('.', "");
+    // the decimal point comes after this many digits
+    let point = exponent.parse::<i32>().unwrap() + 1;
+    let len = digits.len() as i32;
+    let body = if len <= point && point <= 21 {
+        format!("{}{}", digits, "0".repeat((point - len) as usize))
+    }
+    else if 0 < point && point <= 21 {
+        format!("{}.{}", &digits[..point as usize], &digits[point as usize..])
+    }
+    else if -6 < point && point <= 0 {
+        format!("0.{}{}", "0".repeat(-point as usize), digits)
+    }
+    else {
+        format!("{}e{}", mantissa, point - 1)
+    };
+    return format!("{}{}", sign, body);
+}
+
+/// Escapes quotes, backslashes and control characters so `s` can sit between quotes.
+pub(crate) fn escape_string(s: &str) -> String {
+    return escape_string_with(s, &Escape::Minimal);
+}
+
+/// `escape_string`, plus `\uXXXX` escapes for the characters `escape` picks. Characters
+/// outside the Basic Multilingual Plane become surrogate pairs.
+pub(crate) fn escape_string_with(s: &str, escape: &Escape) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '\u{8}' => escaped.push_str("\\b"),
+            '\u{c}' => escaped.push_str("\\f"),
+            c if c < ' ' || escape.escapes(c) => {
+                for unit in c.encode_utf16(&mut [0; 2]) {
+                    escaped.push_str(&format!("\\u{:04x}", unit));
+                }
+            },
+            c => escaped.push(c),
+        }
+    }
+    return escaped;
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn jitem_default_is_null() {
+        assert_eq!(JItem::default(), JItem::Null);
+    }
+
+    #[test]
+    fn format_f64_forms() {
+        for (x, text) in [
+            (0.0, "0"),
+            (-0.0, "-0"),
+            (1.0, "1"),
+            (-12.5, "-12.5"),
+            (0.1 + 0.2, "0.30000000000000004"),
+            (123456789.0, "123456789"),
+            (1e20, "100000000000000000000"),
+            (1e21, "1e21"),
+            (1.5e300, "1.5e300"),
+            (1e-6, "0.000001"),
+            (1.25e-7, "1.25e-7"),
+            (f64::MAX, "1.7976931348623157e308"),
+            (f64::MIN_POSITIVE, "2.2250738585072014e-308"),
+            (5e-324, "5e-324"),
+            (-f64::from_bits(100), "-4.94e-322"),
+        ] {
+            assert_eq!(format_f64(x), text);
+            assert_eq!(JItem::Number(x).to_string(), text);
+        }
+    }
+
+    #[test]
+    fn format_f64_round_trips() {
+        let mut state = 0x2545f4914f6cdd1d_u64;
+        let mut values = vec![-0.0, 0.0, 5e-324, f64::MAX, f64::MIN, f64::MIN_POSITIVE, f64::EPSILON, 9007199254740993.0];
+        for _ in 0..20_000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            values.push(f64::from_bits(state));
+        }
+        for x in values.into_iter().filter(|x| x.is_finite()) {
+            let JItem::Number(back) = crate::parse(&format_f64(x)).unwrap() else {
+                panic!("{} did not parse as a number", x);
+            };
+            assert_eq!(back.to_bits(), x.to_bits(), "{}", format_f64(x));
+        }
+    }
+
+    #[test]
+    fn jitem_from_bool() {
+        assert_eq!(JItem::from(true), JItem::Bool(true));
+        assert_eq!(JItem::from(false), JItem::Bool(false));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn jitem_deprecated_bool_constructors() {
+        assert_eq!(JItem::True, JItem::Bool(true));
+        assert_eq!(JItem::False, JItem::Bool(false));
+    }
+
+    #[test]
+    fn jitem_clone_is_equal() {
+        let item = JItem::Array(vec![JItem::Number(1.), JItem::String("foo".to_string())]);
+        assert_eq!(item.clone(), item);
+    }
+
+    #[test]
+    fn jitem_eq_nan_is_reflexive() {
+        let item = JItem::Array(vec![JItem::Number(f64::NAN)]);
+        assert_eq!(item, item.clone());
+    }
+
+    #[test]
+    fn jitem_eq_signed_zero() {
+        assert_eq!(JItem::Number(0.), JItem::Number(-0.));
+    }
+
+    #[test]
+    fn jitem_raw_number_compares_by_value() {
+        let raw = JItem::RawNumber("1.50".to_string());
+        assert_eq!(raw, JItem::Number(1.5));
+        assert_eq!(raw, JItem::RawNumber("15e-1".to_string()));
+        assert!(raw < JItem::Number(2.0));
+        assert!(raw > JItem::Bool(true));
+        assert_eq!(raw.to_string(), "1.50");
+        assert_eq!(JItem::RawNumber("+1.5".to_string()).to_string(), "1.5");
+        assert_eq!(JItem::RawNumber("1, \"x\": 2".to_string()).to_string(), "null");
+        assert_eq!(JItem::RawNumber("inf".to_string()).to_string(), "null");
+    }
+
+    #[test]
+    fn jitem_ne_different_variants() {
+        assert_ne!(JItem::Number(0.), JItem::Bool(false));
+        assert_ne!(JItem::String("null".to_string()), JItem::Null);
+    }
+
+    #[test]
+    fn jitem_ord_by_type() {
+        let mut items = vec![
+            JItem::Object(HashMap::new()),
+            JItem::Array(vec![]),
+            JItem::String("".to_string()),
+            JItem::Number(0.),
+            JItem::Bool(true),
+            JItem::Bool(false),
+            JItem::Null,
+        ];
+        items.sort();
+        assert_eq!(items, vec![
+            JItem::Null,
+            JItem::Bool(false),
+            JItem::Bool(true),
+            JItem::Number(0.),
+            JItem::String("".to_string()),
+            JItem::Array(vec![]),
+            JItem::Object(HashMap::new()),
+        ]);
+    }
+
+    #[test]
+    fn jitem_ord_numbers() {
+        let mut items = vec![JItem::Number(f64::NAN), JItem::Number(2.), JItem::Number(f64::NEG_INFINITY), JItem::Number(-1.5)];
+        items.sort();
+        assert_eq!(items, vec![JItem::Number(f64::NEG_INFINITY), JItem::Number(-1.5), JItem::Number(2.), JItem::Number(f64::NAN)]);
+        assert_eq!(JItem::Number(-0.).cmp(&JItem::Number(0.)), Ordering::Equal);
+    }
+
+    #[test]
+    fn jitem_ord_arrays_lexicographic() {
+        let shorter = JItem::Array(vec![JItem::Number(1.)]);
+        let longer = JItem::Array(vec![JItem::Number(1.), JItem::Null]);
+        let bigger = JItem::Array(vec![JItem::Number(2.)]);
+        assert!(shorter < longer);
+        assert!(longer < bigger);
+    }
+
+    #[test]
+    fn jitem_ord_objects_by_sorted_members() {
+        let mut a = HashMap::new();
+        a.insert("a".to_string(), JItem::Number(2.));
+        let mut b = HashMap::new();
+        b.insert("a".to_string(), JItem::Number(1.));
+        b.insert("b".to_string(), JItem::Number(1.));
+        assert!(JItem::Object(b) < JItem::Object(a));
+    }
+
+    #[test]
+    fn jitem_ord_usable_in_btreeset() {
+        let set: std::collections::BTreeSet<_> = vec![JItem::Bool(true), JItem::Number(1.), JItem::Bool(true), JItem::Number(1.)].into_iter().collect();
+        assert_eq!(set.len(), 2);
+    }
+
     #[test]
     fn jitem_fmt_null() {
         let formatted = format!("{}", JItem::Null);
@@ -55,13 +409,13 @@ mod test {
 
     #[test]
     fn jitem_fmt_true() {
-        let formatted = format!("{}", JItem::True);
+        let formatted = format!("{}", JItem::Bool(true));
         assert_eq!(formatted, "true");
     }
 
     #[test]
     fn jitem_fmt_false() {
-        let formatted = format!("{}", JItem::False);
+        let formatted = format!("{}", JItem::Bool(false));
         assert_eq!(formatted, "false");
     }
 
@@ -101,6 +455,12 @@ mod test {
         assert_eq!(formatted, "\"\"");
     }
 
+    #[test]
+    fn jitem_fmt_str_escaped() {
+        let formatted = format!("{}", JItem::String("a\"b\\c\nd\u{1}".to_string()));
+        assert_eq!(formatted, r#""a\"b\\c\nd\u0001""#);
+    }
+
     #[test]
     fn jitem_fmt_empty_list() {
         let formatted = format!("{}", JItem::Array(vec![]));
@@ -115,7 +475,7 @@ mod test {
 
     #[test]
     fn jitem_fmt_non_homogenous_list() {
-        let formatted = format!("{}", JItem::Array(vec![JItem::Number(10.), JItem::String("foobar".to_string()), JItem::True, JItem::False, JItem::Null]));
+        let formatted = format!("{}", JItem::Array(vec![JItem::Number(10.), JItem::String("foobar".to_string()), JItem::Bool(true), JItem::Bool(false), JItem::Null]));
         assert_eq!(formatted, "[10,\"foobar\",true,false,null]");
     }
 
@@ -129,7 +489,7 @@ mod test {
     #[test]
     fn jitem_fmt_object() {
         let mut hmap = HashMap::new();
-        hmap.insert("one".to_string(), JItem::True);
+        hmap.insert("one".to_string(), JItem::Bool(true));
         let formatted = format!("{}", JItem::Object(hmap));
         assert_eq!(formatted, r#"{"one":true}"#);
     }
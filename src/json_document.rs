@@ -0,0 +1,155 @@
+use std::{io, path::{Path, PathBuf}};
+
+use crate::{error::JsonError, file::parse_file_with, format::FormatOptions, j_item::JItem, parse_with, parser::ParseOptions};
+
+/// A parsed document together with where it came from and the options it was parsed
+/// with, bundling the common read/modify/write operations behind one handle.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonDocument {
+    root: JItem,
+    origin: Option<PathBuf>,
+    options: ParseOptions,
+}
+
+impl JsonDocument {
+    pub fn new(root: JItem) -> Self {
+        return JsonDocument { root, origin: None, options: ParseOptions::default() };
+    }
+
+    pub fn parse(input: &str) -> Result<Self, JsonError> {
+        return JsonDocument::parse_with(input, ParseOptions::default());
+    }
+
+    pub fn parse_with(input: &str, options: ParseOptions) -> Result<Self, JsonError> {
+        let root = parse_with(input, &options)?;
+        return Ok(JsonDocument { root, origin: None, options });
+    }
+
+    /// Reads and parses the file at `path`, as `parse_file` does, remembering it as the
+    /// document's origin.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, JsonError> {
+        return JsonDocument::open_with(path, ParseOptions::default());
+    }
+
+    pub fn open_with(path: impl AsRef<Path>, options: ParseOptions) -> Result<Self, JsonError> {
+        let path = path.as_ref();
+        let root = parse_file_with(path, &options)?;
+        return Ok(JsonDocument { root, origin: Some(path.to_path_buf()), options });
+    }
+
+    /// Reads the origin again with the same options, replacing the root.
+    pub fn reload(&mut self) -> Result<(), JsonError> {
+        let Some(origin) = &self.origin else {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "document has no origin to reload from").into());
+        };
+        self.root = parse_file_with(origin, &self.options)?;
+        return Ok(());
+    }
+
+    pub fn root(&self) -> &JItem {
+        return &self.root;
+    }
+
+    pub fn root_mut(&mut self) -> &mut JItem {
+        return &mut self.root;
+    }
+
+    pub fn into_root(self) -> JItem {
+        return self.root;
+    }
+
+    /// The file this document was opened from or last saved to, if any.
+    pub fn origin(&self) -> Option<&Path> {
+        return self.origin.as_deref();
+    }
+
+    /// The options the document was parsed with, used again by `reload`.
+    pub fn options(&self) -> &ParseOptions {
+        return &self.options;
+    }
+
+    pub fn get_pointer(&self, pointer: &str) -> Option<&JItem> {
+        return self.root.pointer(pointer);
+    }
+
+    /// Sets the value at a JSON Pointer. See `JItem::set_pointer`.
+    pub fn set(&mut self, pointer: &str, value: JItem) -> Result<Option<JItem>, String> {
+        return self.root.set_pointer(pointer, value);
+    }
+
+    pub fn to_pretty_string(&self) -> String {
+        return self.root.to_pretty_string();
+    }
+
+    /// Writes the document pretty printed to `path`, which becomes its new origin. As
+    /// with `JItem::write_file`, `path` is replaced whole or not at all.
+    pub fn save_to(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        self.root.write_file(path, &FormatOptions::pretty())?;
+        self.origin = Some(path.to_path_buf());
+        return Ok(());
+    }
+}
+
+impl From<JItem> for JsonDocument {
+    fn from(root: JItem) -> Self {
+        return JsonDocument::new(root);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+    use crate::error::ErrorKind;
+
+    #[test]
+    fn document_get_and_set() {
+        let mut document = JsonDocument::parse(r#"{"servers": [{"host": "a"}]}"#).unwrap();
+        assert_eq!(document.get_pointer("/servers/0/host"), Some(&JItem::String("a".to_string())));
+        let previous = document.set("/servers/0/host", JItem::String("b".to_string()));
+        assert_eq!(previous, Ok(Some(JItem::String("a".to_string()))));
+        assert_eq!(document.get_pointer("/servers/0/host"), Some(&JItem::String("b".to_string())));
+    }
+
+    #[test]
+    fn document_parse_error() {
+        assert!(JsonDocument::parse("[1,").is_err());
+    }
+
+    #[test]
+    fn document_save_and_open() {
+        let path = std::env::temp_dir().join(format!("sjp-document-{}.json", std::process::id()));
+        let mut document = JsonDocument::parse(r#"{"b": 1, "a": [true]}"#).unwrap();
+        assert_eq!(document.origin(), None);
+        document.save_to(&path).unwrap();
+        assert_eq!(document.origin(), Some(path.as_path()));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{\n  \"a\": [\n    true\n  ],\n  \"b\": 1\n}\n");
+
+        let reopened = JsonDocument::open(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(reopened.root(), document.root());
+        assert_eq!(reopened.origin(), Some(path.as_path()));
+    }
+
+    #[test]
+    fn document_keeps_options() {
+        let path = std::env::temp_dir().join(format!("sjp-document-options-{}.json", std::process::id()));
+        let options = ParseOptions { preserve_number_text: true, ..ParseOptions::default() };
+        let mut document = JsonDocument::parse_with("[1.50]", options.clone()).unwrap();
+        document.save_to(&path).unwrap();
+        fs::write(&path, "[2.50]").unwrap();
+        document.reload().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(document.options(), &options);
+        assert_eq!(document.root().to_string(), "[2.50]");
+        assert!(JsonDocument::new(JItem::Null).reload().is_err());
+    }
+
+    #[test]
+    fn document_open_missing_file() {
+        let error = JsonDocument::open("/definitely/not/here.json").unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::Io { kind: io::ErrorKind::NotFound, .. }));
+    }
+}
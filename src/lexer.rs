@@ -1,6 +1,12 @@
-use std::{iter::Peekable, str::Chars};
+use alloc::{format, string::{String, ToString}, vec, vec::Vec};
+use core::{iter::Peekable, ops::Range, str::CharIndices};
 
-#[derive(Debug, PartialEq)]
+use crate::{
+    error::{ErrorKind, JsonError, Warning, WarningKind},
+    parser::{ParseOptions, Strictness},
+};
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     LBrace,
     RBrace,
@@ -12,6 +18,8 @@ pub enum Token {
     Comma,
 
     Number(f64),
+    /// A number's exact source text, from `lex_preserving_numbers`.
+    RawNumber(String),
     String(String),
 
     True,
@@ -19,53 +27,212 @@ pub enum Token {
     Null
 }
 
+/// A token and the byte range of the input it was read from.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Spanned {
+    pub token: Token,
+    pub span: Range<usize>,
+}
+
 pub fn lex(s: &str) -> Result<Vec<Token>, String> {
-    let mut i = s.chars().peekable();
+    return lex_unspanned(s, &ParseOptions::default());
+}
+
+/// Like `lex`, but numbers come out as `Token::RawNumber` holding their source text.
+pub fn lex_preserving_numbers(s: &str) -> Result<Vec<Token>, String> {
+    return lex_unspanned(s, &ParseOptions { preserve_number_text: true, ..ParseOptions::default() });
+}
+
+fn lex_unspanned(s: &str, options: &ParseOptions) -> Result<Vec<Token>, String> {
+    let tokens = lex_spanned(s, options, &mut vec![]).map_err(|e| e.kind().to_string())?;
+    return Ok(tokens.into_iter().map(|t| t.token).collect());
+}
+
+pub(crate) fn lex_spanned(s: &str, options: &ParseOptions, warnings: &mut Vec<Warning>) -> Result<Vec<Spanned>, JsonError> {
+    let mut scratch = String::new();
+    let mut lexer = Lexer::new(s, options, warnings, &mut scratch);
     let mut tokens = vec![];
-    while let Some(c) = i.next() {
-        let token = match c {
-            '{' => Ok(Token::LBrace),
-            '}' => Ok(Token::RBrace),
-            '[' => Ok(Token::LSquareBracket),
-            ']' => Ok(Token::RSquareBracket),
-            ':' => Ok(Token::Colon),
-            ',' => Ok(Token::Comma),
-            '-' => lex_number(&mut i, c),
-            '"' => lex_string(&mut i),
-            'a'..='z' | 'A'..='Z' => lex_ident(&mut i, c),
-            '0'..='9' => lex_number(&mut i, c),
-            ' ' | '\n' | '\t' | '\r' => continue,
-            _ => Err(format!("Unknown symbol '{}'", c)),
-        }?;
-
-        tokens.push(token);
-    }
-    return Ok(tokens);
-}
-
-fn lex_string(i: &mut Peekable<Chars>) -> Result<Token, String> {
-    // we have consumed the first ", now consume characters until eof or "
-    let mut escaped = false;
-    let mut built_string = String::new();
-    while let Some(c) = i.next() {
-        if escaped {
-            escaped = false;
-            built_string.push(c);
-            continue;
+    loop {
+        match lexer.next_token() {
+            Ok(Some(token)) => tokens.push(token),
+            Ok(None) => return Ok(tokens),
+            Err((kind, span)) => return Err(JsonError::new(kind, s, span)),
         }
+    }
+}
+
+/// Reads tokens one at a time, for the parser to pull from as it goes rather than
+/// lexing the whole input up front. Each string is built in `scratch` before being
+/// copied out at its exact length, so a caller parsing many inputs can keep that
+/// buffer's capacity between them.
+pub(crate) struct Lexer<'a> {
+    s: &'a str,
+    i: Peekable<CharIndices<'a>>,
+    options: &'a ParseOptions,
+    warnings: &'a mut Vec<Warning>,
+    scratch: &'a mut String,
+    steps: usize,
+}
+
+impl<'a> Lexer<'a> {
+    pub(crate) fn new(s: &'a str, options: &'a ParseOptions, warnings: &'a mut Vec<Warning>, scratch: &'a mut String) -> Self {
+        return Lexer { s, i: s.char_indices().peekable(), options, warnings, scratch, steps: 0 };
+    }
+
+    /// The next token, or `None` at the end of the input. Errors are positioned in the
+    /// input but not yet made into a `JsonError`.
+    pub(crate) fn next_token(&mut self) -> Result<Option<Spanned>, (ErrorKind, Range<usize>)> {
+        let s = self.s;
+        let options = self.options;
+        let preserve_number_text = options.preserve_number_text;
+        let strictness = options.strictness;
+        let i = &mut self.i;
+        while let Some((start, c)) = i.next() {
+            self.steps += 1;
+            if options.past_deadline(self.steps) {
+                return Err((ErrorKind::Timeout, start..start));
+            }
+            let token = match c {
+                '{' => Ok(Token::LBrace),
+                '}' => Ok(Token::RBrace),
+                '[' => Ok(Token::LSquareBracket),
+                ']' => Ok(Token::RSquareBracket),
+                ':' => Ok(Token::Colon),
+                ',' => Ok(Token::Comma),
+                '-' => lex_number(i, c, preserve_number_text),
+                '"' => lex_string(i, s, c, options, self.warnings, self.scratch),
+                '\'' if options.python_literals => lex_string(i, s, c, options, self.warnings, self.scratch),
+                'a'..='z' | 'A'..='Z' => lex_ident(i, c, options.python_literals),
+                '0'..='9' => lex_number(i, c, preserve_number_text),
+                ' ' | '\n' | '\t' | '\r' => continue,
+                '\u{a0}' | '\u{b}' | '\u{c}' if options.extra_whitespace => continue,
+                '=' if options.lenient_separators => Ok(Token::Colon),
+                ';' if options.lenient_separators => Ok(Token::Comma),
+                '/' if strictness == Strictness::Lenient => match skip_comment(i) {
+                    Ok(()) => continue,
+                    Err(kind) => Err(kind),
+                },
+                _ => Err(ErrorKind::UnexpectedCharacter(c)),
+            };
+            let span = start..i.peek().map_or(s.len(), |(n, _)| *n);
+            let token = token.and_then(|token| match token {
+                Token::Number(_) | Token::RawNumber(_) if strictness == Strictness::Strict && !is_rfc_number(&s[span.clone()]) => {
+                    Err(ErrorKind::NonConformingNumber(s[span.clone()].to_string()))
+                },
+                token => Ok(token),
+            });
+            return match token {
+                Ok(Token::Number(number)) if lost_precision(&s[span.clone()], number) => {
+                    self.warnings.push(Warning::new(WarningKind::LostPrecision(s[span.clone()].to_string()), s, span.clone()));
+                    Ok(Some(Spanned { token: Token::Number(number), span }))
+                },
+                Ok(token) => Ok(Some(Spanned { token, span })),
+                Err(kind) => Err((kind, span)),
+            };
+        }
+        return Ok(None);
+    }
+}
+
+fn lex_string(i: &mut Peekable<CharIndices>, s: &str, quote: char, options: &ParseOptions, warnings: &mut Vec<Warning>, built_string: &mut String) -> Result<Token, ErrorKind> {
+    // we have consumed the opening quote, now consume characters until eof or the closing one
+    let strictness = options.strictness;
+    built_string.clear();
+    while let Some((start, c)) = i.next() {
         match c {
-            '\\' => escaped = true,
-            '"' => return Ok(Token::String(built_string)),
+            _ if c == quote => return Ok(Token::String(built_string.as_str().to_string())),
+            '\\' => {
+                let Some((_, escaped)) = i.next() else {
+                    break;
+                };
+                let decoded = match escaped {
+                    'b' => '\u{8}',
+                    'f' => '\u{c}',
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    'u' => lex_unicode_escape(i, s, start, strictness, warnings)?,
+                    'x' if options.python_literals => lex_hex2(i).ok_or_else(|| {
+                        let end = i.peek().map_or(s.len(), |(n, _)| *n);
+                        return ErrorKind::InvalidEscape(s[start..end].to_string());
+                    })?,
+                    '"' | '\\' | '/' => escaped,
+                    _ if strictness == Strictness::Strict => return Err(ErrorKind::InvalidEscape(format!("\\{}", escaped))),
+                    _ => escaped,
+                };
+                built_string.push(decoded);
+            },
+            '\u{0}'..='\u{1f}' if strictness == Strictness::Strict => return Err(ErrorKind::ControlCharacter(c)),
             _ => built_string.push(c),
         }
     }
-    return Err("unterminated string literal. reached EOF.".to_string());
+    return Err(ErrorKind::UnterminatedString);
 }
 
-fn lex_ident(i: &mut Peekable<Chars>, c: char) -> Result<Token, String> {
+/// Decodes the rest of a `\u` escape starting at `start`, pairing surrogates. An unpaired
+/// surrogate is an error in strict mode, and otherwise becomes U+FFFD with a warning.
+fn lex_unicode_escape(i: &mut Peekable<CharIndices>, s: &str, start: usize, strictness: Strictness, warnings: &mut Vec<Warning>) -> Result<char, ErrorKind> {
+    let Some(code) = lex_hex4(i) else {
+        let end = i.peek().map_or(s.len(), |(n, _)| *n);
+        return Err(ErrorKind::InvalidEscape(s[start..end].to_string()));
+    };
+    if (0xD800..0xDC00).contains(&code) {
+        let mut ahead = i.clone();
+        let paired = ahead.next().is_some_and(|(_, c)| c == '\\') && ahead.next().is_some_and(|(_, c)| c == 'u');
+        if let Some(low) = lex_hex4(&mut ahead).filter(|low| paired && (0xDC00..0xE000).contains(low)) {
+            *i = ahead;
+            return Ok(char::from_u32(0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00)).unwrap());
+        }
+    }
+    return match char::from_u32(code) {
+        Some(c) => Ok(c),
+        None if strictness == Strictness::Strict => Err(ErrorKind::InvalidEscape(s[start..start + 6].to_string())),
+        None => {
+            warnings.push(Warning::new(WarningKind::LoneSurrogate, s, start..start + 6));
+            Ok(char::REPLACEMENT_CHARACTER)
+        },
+    };
+}
+
+/// Reads the two digits of a Python `\x` escape.
+fn lex_hex2(i: &mut Peekable<CharIndices>) -> Option<char> {
+    let high = i.next_if(|(_, c)| c.is_ascii_hexdigit())?.1.to_digit(16)?;
+    let low = i.next_if(|(_, c)| c.is_ascii_hexdigit())?.1.to_digit(16)?;
+    return char::from_u32(high << 4 | low);
+}
+
+fn lex_hex4(i: &mut Peekable<CharIndices>) -> Option<u32> {
+    let mut code = 0;
+    for _ in 0..4 {
+        let (_, c) = i.next_if(|(_, c)| c.is_ascii_hexdigit())?;
+        code = code * 16 + c.to_digit(16).unwrap();
+    }
+    return Some(code);
+}
+
+/// Skips a `//` or `/* */` comment whose `/` has been consumed.
+fn skip_comment(i: &mut Peekable<CharIndices>) -> Result<(), ErrorKind> {
+    match i.next() {
+        Some((_, '/')) => {
+            while i.next_if(|(_, c)| *c != '\n').is_some() {}
+            return Ok(());
+        },
+        Some((_, '*')) => {
+            while let Some((_, c)) = i.next() {
+                if c == '*' && i.next_if(|(_, c)| *c == '/').is_some() {
+                    return Ok(());
+                }
+            }
+            return Err(ErrorKind::UnterminatedComment);
+        },
+        _ => return Err(ErrorKind::UnexpectedCharacter('/')),
+    }
+}
+
+fn lex_ident(i: &mut Peekable<CharIndices>, c: char, python_literals: bool) -> Result<Token, ErrorKind> {
     let mut built_string = String::new();
     built_string.push(c);
-    while let Some(c) = i.peek() {
+    while let Some((_, c)) = i.peek() {
         match c {
             'a'..='z' | 'A'..='Z' => built_string.push(*c),
             _ => break,
@@ -76,33 +243,95 @@ fn lex_ident(i: &mut Peekable<Chars>, c: char) -> Result<Token, String> {
         "true" => Ok(Token::True),
         "false" => Ok(Token::False),
         "null" => Ok(Token::Null),
-        _ => Err(format!("unknown keyword '{}'", built_string)),
+        "True" if python_literals => Ok(Token::True),
+        "False" if python_literals => Ok(Token::False),
+        "None" if python_literals => Ok(Token::Null),
+        _ => Err(ErrorKind::UnknownKeyword(built_string)),
     };
 }
 
-fn lex_number(i: &mut Peekable<Chars>, c: char) -> Result<Token, String> {
+fn lex_number(i: &mut Peekable<CharIndices>, c: char, preserve_number_text: bool) -> Result<Token, ErrorKind> {
     let mut built_string = String::new();
     built_string.push(c);
     let mut has_decimal = false;
-    while let Some(c) = i.peek() {
-        if c.is_digit(10) {
+    let mut has_exponent = false;
+    while let Some((_, c)) = i.peek() {
+        if c.is_ascii_digit() {
             built_string.push(*c);
         }
         else if *c == '.' {
-            if has_decimal {
-                return Err("multiple '.' found in number literal.".to_string());
+            built_string.push(*c);
+            if has_decimal || has_exponent {
+                // a second '.' can't be valid, so stop here and let the parse below fail
+                i.next();
+                break;
             }
-            else {
-                built_string.push(*c);
-                has_decimal = true;
+            has_decimal = true;
+        }
+        else if matches!(c, 'e' | 'E') && !has_exponent {
+            built_string.push(*c);
+            has_exponent = true;
+            i.next();
+            if let Some((_, sign)) = i.next_if(|(_, c)| matches!(c, '+' | '-')) {
+                built_string.push(sign);
             }
+            continue;
         }
         else {
             break;
         }
         i.next();
     }
-    return Ok(Token::Number(built_string.parse().unwrap()))
+    let number = match built_string.parse() {
+        Ok(number) => number,
+        Err(source) => return Err(ErrorKind::InvalidNumber { text: built_string, source }),
+    };
+    if preserve_number_text {
+        return Ok(Token::RawNumber(built_string));
+    }
+    return Ok(Token::Number(number))
+}
+
+/// Whether `text` matches the RFC 8259 number grammar.
+pub(crate) fn is_rfc_number(text: &str) -> bool {
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    let text = text.strip_prefix('-').unwrap_or(text);
+    let (mantissa, exponent) = match text.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, Some(exponent)),
+        None => (text, None),
+    };
+    let (int, frac) = match mantissa.split_once('.') {
+        Some((int, frac)) => (int, Some(frac)),
+        None => (mantissa, None),
+    };
+    return digits(int)
+        && (int == "0" || !int.starts_with('0'))
+        && frac.is_none_or(digits)
+        && exponent.is_none_or(|exponent| digits(exponent.strip_prefix(['+', '-']).unwrap_or(exponent)));
+}
+
+/// Whether `number` reads back as a different decimal value than the literal `text`,
+/// comparing significant digits against the shortest round-trip form of the float.
+fn lost_precision(text: &str, number: f64) -> bool {
+    if !number.is_finite() {
+        return true;
+    }
+    return significant_digits(text) != significant_digits(&format!("{:e}", number));
+}
+
+/// The significant digits of a number literal, and the power of ten just above the first.
+fn significant_digits(text: &str) -> (String, i64) {
+    let text = text.trim_start_matches('-');
+    let (mantissa, exponent) = text.split_once(['e', 'E']).unwrap_or((text, "0"));
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = format!("{}{}", int, frac);
+    let trimmed = digits.trim_start_matches('0');
+    let point = exponent.parse::<i64>().unwrap_or(0) + int.len() as i64 - (digits.len() - trimmed.len()) as i64;
+    let trimmed = trimmed.trim_end_matches('0');
+    if trimmed.is_empty() {
+        return (String::new(), 0);
+    }
+    return (trimmed.to_string(), point);
 }
 
 #[cfg(test)]
@@ -158,11 +387,89 @@ mod test {
         assert_eq!(tokens.unwrap(), expected_tokens);
     }
 
+    #[test]
+    fn exponent() {
+        let tokens = lex("[1e3, -2.5E-2]");
+        let expected_tokens = vec![
+            Token::LSquareBracket,
+            Token::Number(1000.0),
+            Token::Comma,
+            Token::Number(-0.025),
+            Token::RSquareBracket,
+        ];
+        assert_eq!(tokens, Ok(expected_tokens));
+    }
+
+    #[test]
+    fn invalid_number() {
+        assert_eq!(lex("-"), Err("invalid number literal '-'.".to_string()));
+        assert_eq!(lex("1e"), Err("invalid number literal '1e'.".to_string()));
+    }
+
+    #[test]
+    fn preserved_number_text() {
+        let tokens = lex_preserving_numbers("[1.50, 1e3]");
+        let expected_tokens = vec![
+            Token::LSquareBracket,
+            Token::RawNumber("1.50".to_string()),
+            Token::Comma,
+            Token::RawNumber("1e3".to_string()),
+            Token::RSquareBracket,
+        ];
+        assert_eq!(tokens, Ok(expected_tokens));
+    }
+
+    #[test]
+    fn string_escapes() {
+        let tokens = lex(r#"["a\"b\\c\/\n\t", "\u00e9\ud83d\ude00"]"#);
+        let expected_tokens = vec![
+            Token::LSquareBracket,
+            Token::String("a\"b\\c/\n\t".to_string()),
+            Token::Comma,
+            Token::String("é😀".to_string()),
+            Token::RSquareBracket,
+        ];
+        assert_eq!(tokens, Ok(expected_tokens));
+        assert_eq!(lex(r#""\u12x4""#), Err("invalid escape sequence '\\u12'".to_string()));
+    }
+
+    #[test]
+    fn extra_whitespace_and_lenient_separators() {
+        let source = "{\u{a0}\"a\" = 1;\u{b}\"b\"\u{c}: [true; null]}";
+        assert!(lex(source).is_err());
+        let whitespace = ParseOptions { extra_whitespace: true, ..ParseOptions::default() };
+        assert_eq!(lex_spanned(source, &whitespace, &mut vec![]).unwrap_err().kind(), &ErrorKind::UnexpectedCharacter('='));
+        let separators = ParseOptions { lenient_separators: true, ..ParseOptions::default() };
+        assert_eq!(lex_spanned(source, &separators, &mut vec![]).unwrap_err().kind(), &ErrorKind::UnexpectedCharacter('\u{a0}'));
+        let both = ParseOptions { extra_whitespace: true, lenient_separators: true, ..ParseOptions::default() };
+        let tokens: Vec<Token> = lex_spanned(source, &both, &mut vec![]).unwrap().into_iter().map(|t| t.token).collect();
+        assert_eq!(tokens, vec![
+            Token::LBrace, Token::String("a".to_string()), Token::Colon, Token::Number(1.0), Token::Comma,
+            Token::String("b".to_string()), Token::Colon, Token::LSquareBracket, Token::True, Token::Comma, Token::Null, Token::RSquareBracket,
+            Token::RBrace,
+        ]);
+    }
+
+    #[test]
+    fn python_literals() {
+        let source = r#"{'name': 'it\'s', "ok": True, 'gone': None, 'flags': [False, '\x41\n']}"#;
+        assert_eq!(lex(source), Err("Unknown symbol '\''".to_string()));
+        let tokens: Vec<Token> = lex_spanned(source, &ParseOptions::python(), &mut vec![]).unwrap().into_iter().map(|t| t.token).collect();
+        assert_eq!(tokens, vec![
+            Token::LBrace, Token::String("name".to_string()), Token::Colon, Token::String("it's".to_string()), Token::Comma,
+            Token::String("ok".to_string()), Token::Colon, Token::True, Token::Comma,
+            Token::String("gone".to_string()), Token::Colon, Token::Null, Token::Comma,
+            Token::String("flags".to_string()), Token::Colon, Token::LSquareBracket, Token::False, Token::Comma, Token::String("A\n".to_string()), Token::RSquareBracket,
+            Token::RBrace,
+        ]);
+        assert_eq!(lex_spanned(r#"['\x4']"#, &ParseOptions::python(), &mut vec![]).unwrap_err().kind(), &ErrorKind::InvalidEscape("\\x4".to_string()));
+    }
+
     #[test]
     fn illegal_ident() {
         let input = r#"[notarealident]"#;
         let tokens = lex(input);
         assert!(tokens.is_err());
-        assert_eq!("unknown keyword 'notarealident'", tokens.unwrap_err());
+        assert_eq!("unknown keyword 'notarealident', did you mean the string \"notarealident\"?", tokens.unwrap_err());
     }
 }
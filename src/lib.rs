@@ -1,13 +1,190 @@
+#![allow(clippy::needless_return)]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(not(any(feature = "std", feature = "alloc")))]
+compile_error!("either the `std` or the `alloc` feature must be enabled");
+
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+pub(crate) use hashbrown::HashMap;
+// only so that the `compile_error!` above is the one error reported
+#[cfg(not(any(feature = "std", feature = "alloc")))]
+pub(crate) type HashMap<K, V> = alloc::collections::BTreeMap<K, V>;
+#[cfg(feature = "std")]
+pub(crate) use std::collections::HashMap;
+
+use alloc::{string::String, vec, vec::Vec};
+use core::ops::Range;
+
+use error::{JsonError, Warning};
 use j_item::JItem;
-use lexer::lex;
+use parser::{parse_source, ParseOptions};
+use pointer::JsonPointer;
+use raw::RawValue;
+use spanned::SpannedJItem;
 
+mod access;
+#[cfg(any(feature = "arbitrary", feature = "proptest"))]
+pub mod arbitrary;
+#[cfg(feature = "std")]
+pub mod array_writer;
+#[cfg(feature = "std")]
+pub mod base64;
+#[cfg(feature = "bson")]
+pub mod bson;
+mod canonical;
+#[cfg(feature = "std")]
+pub mod case;
+#[cfg(feature = "cbor")]
+pub mod cbor;
+pub mod compare;
+#[cfg(feature = "std")]
+pub mod config;
+#[cfg(feature = "std")]
+pub mod csv;
+#[cfg(feature = "std")]
+pub mod cst;
+#[cfg(feature = "chrono")]
+pub mod datetime;
+#[cfg(feature = "std")]
+pub mod document;
+pub mod duplicates;
+pub mod error;
+pub mod events;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+pub mod file;
+#[cfg(feature = "std")]
+pub mod flatten;
+pub mod format;
+#[cfg(feature = "std")]
+pub mod generator;
+#[cfg(feature = "geojson")]
+pub mod geojson;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "std")]
+mod interpolate;
 pub mod j_item;
+#[cfg(feature = "std")]
+pub mod json_document;
+#[cfg(feature = "std")]
+pub mod jwt;
 pub mod lexer;
+#[cfg(feature = "msgpack")]
+pub mod msgpack;
+#[cfg(feature = "std")]
+pub mod normalize;
+#[cfg(feature = "std")]
+pub mod overlay;
+#[cfg(feature = "rayon")]
+pub mod parallel;
 pub mod parser;
+pub mod patch;
+pub mod path;
+pub mod pointer;
+mod projection;
+pub mod provenance;
+pub mod push;
+#[cfg(feature = "std")]
+pub mod query;
+pub mod raw;
+#[cfg(feature = "std")]
+pub mod redact;
+pub mod report;
+pub mod rewrite;
+#[cfg(feature = "std")]
+pub mod schema;
+mod search;
+#[cfg(feature = "serde")]
+pub mod serde;
+mod sha256;
+#[cfg(feature = "std")]
+pub mod shared;
+pub mod spanned;
+mod split;
+#[cfg(feature = "std")]
+pub mod stats;
+#[cfg(feature = "std")]
+mod template;
+pub mod tokenizer;
+#[cfg(feature = "toml")]
+pub mod toml;
+mod trace;
+#[cfg(feature = "std")]
+mod transform;
+#[cfg(feature = "uuid")]
+mod uuid;
+mod validate;
+pub mod view;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "std")]
+pub mod xml;
+#[cfg(feature = "yaml")]
+pub mod yaml;
+
+pub fn parse(input_string: &str) -> Result<JItem, JsonError> {
+    return parse_with(input_string, &ParseOptions::default());
+}
+
+pub fn parse_with(input_string: &str, options: &ParseOptions) -> Result<JItem, JsonError> {
+    return parse_with_warnings(input_string, options).map(|(item, _)| item);
+}
+
+/// Checks that `input_string` would parse, without building tokens or a tree. Syntax is
+/// checked as `parse` does, but duplicate keys are not, as that would mean remembering
+/// every key.
+pub fn validate(input_string: &str) -> Result<(), JsonError> {
+    return validate::validate(input_string);
+}
+
+/// Builds only the values at `pointers`, in the order given, with `None` for pointers
+/// that lead nowhere. Those values are parsed as `parse_with` would; the rest of the
+/// input is checked as `validate` checks it but never built, so pulling a few fields
+/// out of a large document costs little more than reading it.
+pub fn parse_pointers(input_string: &str, pointers: &[JsonPointer], options: &ParseOptions) -> Result<Vec<Option<JItem>>, JsonError> {
+    return projection::parse_pointers(input_string, pointers, options);
+}
 
-pub fn parse(input_string: &str) -> Result<JItem, String> {
-    let tokens = lex(input_string)?;
-    return parser::parse(tokens);
+/// Like `parse_pointers`, but keeps each value as its exact source text rather than
+/// building it.
+pub fn parse_raw_pointers(input_string: &str, pointers: &[JsonPointer]) -> Result<Vec<Option<RawValue>>, JsonError> {
+    let spans = projection::find_spans(input_string, pointers)?;
+    return Ok(spans.into_iter().map(|span| span.map(|span| RawValue::from_checked(&input_string[span]))).collect());
+}
+
+/// Finds and parses each top-level value in input holding several, such as a fixture
+/// file, along with the byte range of its source text. Values may be separated by
+/// whitespace and `//` or `/* */` comments, and comments are also allowed inside them.
+/// A value that fails to parse gets its error, with positions in the whole input, and
+/// does not stop the values after it from being read.
+pub fn split_documents(input_string: &str) -> Vec<(Range<usize>, Result<JItem, JsonError>)> {
+    return split::split_documents(input_string);
+}
+
+/// Like `parse_with`, also returning warnings for anything the options let through
+/// that may not be what the input meant: duplicate keys, numbers that lost
+/// precision, and unpaired surrogates.
+pub fn parse_with_warnings(input_string: &str, options: &ParseOptions) -> Result<(JItem, Vec<Warning>), JsonError> {
+    return parse_nodes(input_string, options);
+}
+
+/// Like `parse_with`, but every value in the tree records the byte range of its source
+/// text, and every object member the range of its key, for tools that report findings
+/// at exact source locations.
+pub fn parse_with_spans(input_string: &str, options: &ParseOptions) -> Result<SpannedJItem, JsonError> {
+    return parse_nodes(input_string, options).map(|(item, _)| item);
+}
+
+fn parse_nodes<N: parser::Node>(input_string: &str, options: &ParseOptions) -> Result<(N, Vec<Warning>), JsonError> {
+    let mut warnings = vec![];
+    let stage = trace::Stage::enter("parse");
+    let (item, tokens) = parse_source(input_string, options, &mut warnings, &mut String::new())?;
+    stage.finish(input_string.len(), Some(tokens));
+    return Ok((item, warnings));
 }
 
 #[cfg(test)]
@@ -17,11 +194,122 @@ mod test {
     #[test]
     fn parse_simple_list() {
         let input = r#"[true, false, null, "foobar", -10.5, ["no thanks"]]"#;
-        let expected = JItem::Array(vec![JItem::True, JItem::False, JItem::Null, JItem::String("foobar".to_string()), JItem::Number(-10.5), JItem::Array(vec![JItem::String("no thanks".to_string())])]);
+        let expected = JItem::Array(vec![JItem::Bool(true), JItem::Bool(false), JItem::Null, JItem::String("foobar".to_string()), JItem::Number(-10.5), JItem::Array(vec![JItem::String("no thanks".to_string())])]);
         let result = parse(input);
         let Ok(output) = result else {
             panic!("failure during parsing. failure: '{}'", result.unwrap_err());
         };
         assert_eq!(expected, output);
     }
+
+    #[test]
+    fn parse_with_preserved_number_text() {
+        let options = ParseOptions { preserve_number_text: true, ..ParseOptions::default() };
+        let output = parse_with(r#"{"price": 1.50, "count": 1e3, "list": [-0.0]}"#, &options).unwrap();
+        let JItem::Object(hmap) = &output else {
+            panic!("expected an object");
+        };
+        assert_eq!(hmap["price"], JItem::RawNumber("1.50".to_string()));
+        assert_eq!(hmap["price"], JItem::Number(1.5));
+        assert_eq!(output.to_string_with(&format::FormatOptions { sort_keys: true, ..format::FormatOptions::compact() }), r#"{"count":1e3,"list":[-0.0],"price":1.50}"#);
+        assert_eq!(output.to_canonical_string(), r#"{"count":1000,"list":[0],"price":1.5}"#);
+    }
+
+    #[test]
+    fn parse_with_warnings_reports_fix_ups() {
+        let options = ParseOptions { duplicate_keys: parser::DuplicateKeys::KeepFirst, ..ParseOptions::default() };
+        let input = "{\"a\": 1, \"a\": 2,\n \"big\": 12345678901234567891, \"s\": \"\\ud800!\"}";
+        let (output, warnings) = parse_with_warnings(input, &options).unwrap();
+        assert_eq!(output.pointer("/a"), Some(&JItem::Number(1.0)));
+        assert_eq!(output.pointer("/s"), Some(&JItem::String("\u{fffd}!".to_string())));
+        let described: Vec<String> = warnings.iter().map(|w| format!("{} {}", w.code(), w)).collect();
+        assert_eq!(described, vec![
+            "W002 number '12345678901234567891' lost precision at line 2, column 9",
+            "W003 unpaired surrogate replaced with U+FFFD at line 2, column 37",
+            "W001 duplicate key 'a' at line 1, column 10",
+        ]);
+
+        let options = ParseOptions { duplicate_keys: parser::DuplicateKeys::KeepLast, ..ParseOptions::default() };
+        let (output, _) = parse_with_warnings(r#"{"a": 1, "a": 2}"#, &options).unwrap();
+        assert_eq!(output.pointer("/a"), Some(&JItem::Number(2.0)));
+        assert_eq!(parse(r#"{"a": 1, "a": 2}"#).unwrap_err().code(), "E007");
+    }
+
+    #[test]
+    fn parse_deadline() {
+        use std::time::{Duration, Instant};
+
+        let input = format!("[{}]", vec!["1"; 5000].join(","));
+        let expired = ParseOptions::default().deadline(Instant::now());
+        let error = parse_with(&input, &expired).unwrap_err();
+        assert_eq!(error.kind(), &error::ErrorKind::Timeout);
+        assert_eq!(error.kind().code(), "E019");
+        assert_eq!(error.span(), 0..0);
+
+        let generous = ParseOptions::default().deadline(Instant::now() + Duration::from_secs(60));
+        assert_eq!(parse_with(&input, &generous), parse(&input));
+    }
+
+    #[test]
+    fn parse_with_hooks() {
+        fn tag_dates(item: JItem) -> Result<JItem, String> {
+            return match &item {
+                JItem::String(s) if s.len() == 10 && s.as_bytes()[4] == b'-' => Ok(parse(&format!(r#"{{"$date": "{}"}}"#, s)).unwrap()),
+                JItem::String(s) if s.len() > 8 => Err(format!("string of {} bytes is too long", s.len())),
+                _ => Ok(item),
+            };
+        }
+        let hooks = parser::Hooks { value: Some(tag_dates), key: Some(|key| Ok(key.to_lowercase())) };
+        let options = ParseOptions { hooks, ..ParseOptions::default() };
+        assert_eq!(parse_with(r#"{"Born": "1990-01-02", "Name": "ada"}"#, &options), parse(r#"{"born": {"$date": "1990-01-02"}, "name": "ada"}"#));
+        let error = parse_with(r#"["ok", "much too long"]"#, &options).unwrap_err();
+        assert_eq!((error.code(), error.span(), error.to_string().as_str()), ("E020", 7..22, "rejected: string of 13 bytes is too long at line 1, column 8"));
+        assert_eq!(parse_with(r#"{"a": 1, "A": 2}"#, &options).unwrap_err().kind(), &error::ErrorKind::DuplicateKey("a".to_string()));
+    }
+
+    #[test]
+    fn parse_with_key_filters() {
+        use parser::KeyFilter;
+        use path::PathSegment;
+
+        let input = r#"{"id": 1, "debug": {"trace": [1, {"deep": true}]}, "user": {"id": 2, "name": "ada", "debug": null}}"#;
+        let with = |keys| parse_with(input, &ParseOptions { keys, ..ParseOptions::default() });
+        assert_eq!(with(KeyFilter::Allow(vec!["id".to_string(), "user".to_string()])), parse(r#"{"id": 1, "user": {"id": 2}}"#));
+        assert_eq!(with(KeyFilter::Deny(vec!["debug".to_string()])), parse(r#"{"id": 1, "user": {"id": 2, "name": "ada"}}"#));
+        fn top_level_only(path: &[PathSegment]) -> bool {
+            return path.len() == 1 || matches!(path, [PathSegment::Key(user), PathSegment::Key(name)] if user == "user" && name == "name");
+        }
+        assert_eq!(with(KeyFilter::Path(top_level_only)), parse(r#"{"id": 1, "debug": {}, "user": {"name": "ada"}}"#));
+
+        // dropped members are still checked
+        let deny = ParseOptions { keys: KeyFilter::Deny(vec!["debug".to_string()]), ..ParseOptions::default() };
+        assert_eq!(parse_with(r#"{"debug": [1 2], "id": 1}"#, &deny).unwrap_err().code(), "E008");
+        assert_eq!(parse_with(r#"{"debug": {"a": 1, "a": 2}, "id": 1}"#, &deny), parse(r#"{"id": 1}"#));
+        let strict = ParseOptions { strictness: parser::Strictness::Strict, ..deny };
+        assert_eq!(parse_with(r#"{"debug": 1,}"#, &strict).unwrap_err().code(), "E006");
+    }
+
+    #[test]
+    fn parse_strictness_levels() {
+        let strict = ParseOptions { strictness: parser::Strictness::Strict, ..ParseOptions::default() };
+        let lenient = ParseOptions { strictness: parser::Strictness::Lenient, ..ParseOptions::default() };
+        let valid = r#"{"a": [0, -0.5, 1e+2, 2E-3], "b": "\"\\\/\b\f\n\r\t\u00e9\ud83d\ude00"}"#;
+        assert_eq!(parse_with(valid, &strict).unwrap(), parse(valid).unwrap());
+
+        for (input, code) in [("01", "E014"), ("1.", "E014"), ("-1.e5", "E014"), ("\"\\q\"", "E013"), ("\"\\udc00\"", "E013"), ("\"a\tb\"", "E015"), ("[1,]", "E005"), ("{\"a\": 1,}", "E006")] {
+            assert_eq!(parse_with(input, &strict).unwrap_err().code(), code, "{}", input);
+            assert!(parse(input).is_ok(), "{}", input);
+        }
+
+        let commented = "// settings\n{\"a\": /* inline */ 1}";
+        assert_eq!(parse_with(commented, &lenient).unwrap(), parse(r#"{"a": 1}"#).unwrap());
+        assert_eq!(parse(commented).unwrap_err().code(), "E002");
+        assert_eq!(parse_with("1 /* open", &lenient).unwrap_err().code(), "E016");
+    }
+
+    #[test]
+    fn parse_without_warnings() {
+        let (_, warnings) = parse_with_warnings(r#"[0.1, 1e300, -0.0, 9007199254740992, 5e-324, "\ud83d\ude00"]"#, &ParseOptions::default()).unwrap();
+        assert_eq!(warnings, vec![]);
+    }
 }
@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+
+use crate::j_item::JItem;
+
+/// How deeply arrays and maps may nest in decoded input, so that untrusted bytes cannot
+/// exhaust the stack.
+const MAX_DEPTH: usize = 128;
+
+/// Encodes `item` as MessagePack. Whole numbers use the smallest integer format that
+/// holds them and everything else is a float64. Map keys are written in sorted order.
+pub fn to_msgpack(item: &JItem) -> Vec<u8> {
+    let mut out = vec![];
+    write_item(item, &mut out);
+    return out;
+}
+
+/// Decodes a single MessagePack value. Binary and extension types, non-string map keys,
+/// nesting more than 128 deep and trailing bytes are errors, and integers beyond 2^53
+/// lose precision.
+pub fn from_msgpack(bytes: &[u8]) -> Result<JItem, String> {
+    let mut reader = Reader { bytes, pos: 0, depth: 0 };
+    let item = reader.read_item()?;
+    if reader.pos != bytes.len() {
+        return Err(format!("invalid msgpack: {} trailing bytes", bytes.len() - reader.pos));
+    }
+    return Ok(item);
+}
+
+fn write_length(len: usize, fix: u8, fix_max: usize, formats: [u8; 3], out: &mut Vec<u8>) {
+    if len <= fix_max {
+        out.push(fix | len as u8);
+    }
+    else if formats[0] != 0 && len <= u8::MAX as usize {
+        out.push(formats[0]);
+        out.push(len as u8);
+    }
+    else if len <= u16::MAX as usize {
+        out.push(formats[1]);
+        out.extend_from_slice(&(len as u16).to_be_bytes());
+    }
+    else {
+        out.push(formats[2]);
+        out.extend_from_slice(&(len as u32).to_be_bytes());
+    }
+}
+
+fn write_number(x: f64, out: &mut Vec<u8>) {
+    if x.fract() != 0.0 || !x.is_finite() || x < i64::MIN as f64 || x >= u64::MAX as f64 || (x == 0.0 && x.is_sign_negative()) {
+        out.push(0xcb);
+        out.extend_from_slice(&x.to_be_bytes());
+    }
+    else if x >= 0.0 {
+        let n = x as u64;
+        match n {
+            0..=0x7f => out.push(n as u8),
+            0x80..=0xff => out.extend_from_slice(&[0xcc, n as u8]),
+            0x100..=0xffff => {
+                out.push(0xcd);
+                out.extend_from_slice(&(n as u16).to_be_bytes());
+            },
+            0x1_0000..=0xffff_ffff => {
+                out.push(0xce);
+                out.extend_from_slice(&(n as u32).to_be_bytes());
+            },
+            _ => {
+                out.push(0xcf);
+                out.extend_from_slice(&n.to_be_bytes());
+            },
+        }
+    }
+    else {
+        let n = x as i64;
+        if n >= -32 {
+            out.push(n as u8);
+        }
+        else if n >= i8::MIN as i64 {
+            out.extend_from_slice(&[0xd0, n as u8]);
+        }
+        else if n >= i16::MIN as i64 {
+            out.push(0xd1);
+            out.extend_from_slice(&(n as i16).to_be_bytes());
+        }
+        else if n >= i32::MIN as i64 {
+            out.push(0xd2);
+            out.extend_from_slice(&(n as i32).to_be_bytes());
+        }
+        else {
+            out.push(0xd3);
+            out.extend_from_slice(&n.to_be_bytes());
+        }
+    }
+}
+
+fn write_str(s: &str, out: &mut Vec<u8>) {
+    write_length(s.len(), 0xa0, 31, [0xd9, 0xda, 0xdb], out);
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn write_item(item: &JItem, out: &mut Vec<u8>) {
+    match item {
+        JItem::RawNumber(_) => write_number(item.number_value().unwrap(), out),
+        JItem::Object(hmap) => {
+            write_length(hmap.len(), 0x80, 15, [0, 0xde, 0xdf], out);
+            let mut members: Vec<_> = hmap.iter().collect();
+            members.sort_by_key(|(key, _)| *key);
+            for (key, value) in members {
+                write_str(key, out);
+                write_item(value, out);
+            }
+        },
+        JItem::String(s) => write_str(s, out),
+        JItem::Array(elements) => {
+            write_length(elements.len(), 0x90, 15, [0, 0xdc, 0xdd], out);
+            for element in elements {
+                write_item(element, out);
+            }
+        },
+        JItem::Number(x) => write_number(*x, out),
+        JItem::Bool(b) => out.push(if *b { 0xc3 } else { 0xc2 }),
+        JItem::Null => out.push(0xc0),
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    /// Arrays and maps open around the current position.
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let Some(slice) = self.bytes.get(self.pos..self.pos + N) else {
+            return Err("invalid msgpack: unexpected end of input".to_string());
+        };
+        self.pos += N;
+        return Ok(slice.try_into().unwrap());
+    }
+
+    fn take_slice(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let Some(slice) = self.bytes.get(self.pos..self.pos + len) else {
+            return Err("invalid msgpack: unexpected end of input".to_string());
+        };
+        self.pos += len;
+        return Ok(slice);
+    }
+
+    fn read_str(&mut self, len: usize) -> Result<String, String> {
+        let slice = self.take_slice(len)?;
+        return String::from_utf8(slice.to_vec()).map_err(|_| "invalid msgpack: string is not valid utf-8".to_string());
+    }
+
+    fn enter(&mut self) -> Result<(), String> {
+        if self.depth == MAX_DEPTH {
+            return Err(format!("invalid msgpack: nested more than {} deep", MAX_DEPTH));
+        }
+        self.depth += 1;
+        return Ok(());
+    }
+
+    fn read_array(&mut self, len: usize) -> Result<JItem, String> {
+        self.enter()?;
+        let mut elements = Vec::with_capacity(len.min(self.bytes.len()));
+        for _ in 0..len {
+            elements.push(self.read_item()?);
+        }
+        self.depth -= 1;
+        return Ok(JItem::Array(elements));
+    }
+
+    fn read_map(&mut self, len: usize) -> Result<JItem, String> {
+        self.enter()?;
+        let mut hmap = HashMap::new();
+        for _ in 0..len {
+            let JItem::String(key) = self.read_item()? else {
+                return Err("invalid msgpack: map keys must be strings".to_string());
+            };
+            let value = self.read_item()?;
+            if hmap.contains_key(&key) {
+                return Err(format!("invalid msgpack: duplicate key '{}'", key));
+            }
+            hmap.insert(key, value);
+        }
+        self.depth -= 1;
+        return Ok(JItem::Object(hmap));
+    }
+
+    fn read_item(&mut self) -> Result<JItem, String> {
+        let [marker] = self.take()?;
+        return match marker {
+            0x00..=0x7f => Ok(JItem::Number(marker as f64)),
+            0x80..=0x8f => self.read_map((marker & 0x0f) as usize),
+            0x90..=0x9f => self.read_array((marker & 0x0f) as usize),
+            0xa0..=0xbf => Ok(JItem::String(self.read_str((marker & 0x1f) as usize)?)),
+            0xc0 => Ok(JItem::Null),
+            0xc2 => Ok(JItem::Bool(false)),
+            0xc3 => Ok(JItem::Bool(true)),
+            0xca => Ok(JItem::Number(f32::from_be_bytes(self.take()?) as f64)),
+            0xcb => Ok(JItem::Number(f64::from_be_bytes(self.take()?))),
+            0xcc => Ok(JItem::Number(u8::from_be_bytes(self.take()?) as f64)),
+            0xcd => Ok(JItem::Number(u16::from_be_bytes(self.take()?) as f64)),
+            0xce => Ok(JItem::Number(u32::from_be_bytes(self.take()?) as f64)),
+            0xcf => Ok(JItem::Number(u64::from_be_bytes(self.take()?) as f64)),
+            0xd0 => Ok(JItem::Number(i8::from_be_bytes(self.take()?) as f64)),
+            0xd1 => Ok(JItem::Number(i16::from_be_bytes(self.take()?) as f64)),
+            0xd2 => Ok(JItem::Number(i32::from_be_bytes(self.take()?) as f64)),
+            0xd3 => Ok(JItem::Number(i64::from_be_bytes(self.take()?) as f64)),
+            0xd9 => {
+                let len = u8::from_be_bytes(self.take()?) as usize;
+                Ok(JItem::String(self.read_str(len)?))
+            },
+            0xda => {
+                let len = u16::from_be_bytes(self.take()?) as usize;
+                Ok(JItem::String(self.read_str(len)?))
+            },
+            0xdb => {
+                let len = u32::from_be_bytes(self.take()?) as usize;
+                Ok(JItem::String(self.read_str(len)?))
+            },
+            0xdc => {
+                let len = u16::from_be_bytes(self.take()?) as usize;
+                self.read_array(len)
+            },
+            0xdd => {
+                let len = u32::from_be_bytes(self.take()?) as usize;
+                self.read_array(len)
+            },
+            0xde => {
+                let len = u16::from_be_bytes(self.take()?) as usize;
+                self.read_map(len)
+            },
+            0xdf => {
+                let len = u32::from_be_bytes(self.take()?) as usize;
+                self.read_map(len)
+            },
+            0xe0..=0xff => Ok(JItem::Number(marker as i8 as f64)),
+            0xc4..=0xc6 => Err("invalid msgpack: binary data has no json equivalent".to_string()),
+            0xc7..=0xc9 | 0xd4..=0xd8 => Err("invalid msgpack: extension types have no json equivalent".to_string()),
+            0xc1 => Err("invalid msgpack: reserved marker 0xc1".to_string()),
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn to_msgpack_encodings() {
+        assert_eq!(to_msgpack(&JItem::Null), vec![0xc0]);
+        assert_eq!(to_msgpack(&JItem::Bool(true)), vec![0xc3]);
+        assert_eq!(to_msgpack(&JItem::Number(5.0)), vec![0x05]);
+        assert_eq!(to_msgpack(&JItem::Number(-1.0)), vec![0xff]);
+        assert_eq!(to_msgpack(&JItem::Number(300.0)), vec![0xcd, 0x01, 0x2c]);
+        assert_eq!(to_msgpack(&JItem::Number(-200.0)), vec![0xd1, 0xff, 0x38]);
+        assert_eq!(to_msgpack(&JItem::Number(0.5)), vec![0xcb, 0x3f, 0xe0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(to_msgpack(&parse(r#"{"b": [1], "a": "hi"}"#).unwrap()), vec![0x82, 0xa1, b'a', 0xa2, b'h', b'i', 0xa1, b'b', 0x91, 0x01]);
+    }
+
+    #[test]
+    fn msgpack_round_trip() {
+        let item = parse(r#"{"name": "x", "values": [0, -33, 70000, 1000000000000000, -5000000000000, 2.5, true, null], "nested": {"empty": {}}}"#).unwrap();
+        assert_eq!(from_msgpack(&to_msgpack(&item)), Ok(item));
+
+        let long = JItem::String("y".repeat(70000));
+        assert_eq!(from_msgpack(&to_msgpack(&long)), Ok(long));
+        let negative_zero = JItem::Number(-0.0);
+        let Ok(JItem::Number(x)) = from_msgpack(&to_msgpack(&negative_zero)) else {
+            panic!("expected a number");
+        };
+        assert!(x == 0.0 && x.is_sign_negative());
+    }
+
+    #[test]
+    fn from_msgpack_other_formats() {
+        assert_eq!(from_msgpack(&[0xca, 0x3f, 0xc0, 0, 0]), Ok(JItem::Number(1.5)));
+        assert_eq!(from_msgpack(&[0xd0, 0x80]), Ok(JItem::Number(-128.0)));
+        assert_eq!(from_msgpack(&[0xd9, 0x01, b'z']), Ok(JItem::String("z".to_string())));
+    }
+
+    #[test]
+    fn from_msgpack_errors() {
+        assert!(from_msgpack(&[]).is_err());
+        assert!(from_msgpack(&[0x92, 0x01]).is_err());
+        assert!(from_msgpack(&[0x01, 0x02]).is_err());
+        assert!(from_msgpack(&[0x81, 0x01, 0x02]).is_err());
+        assert!(from_msgpack(&[0xc4, 0x00]).is_err());
+        assert!(from_msgpack(&[0xa1, 0xff]).is_err());
+        assert!(from_msgpack(&[0x82, 0xa1, b'a', 0x01, 0xa1, b'a', 0x02]).is_err());
+    }
+
+    #[test]
+    fn from_msgpack_depth_limit() {
+        let nested = |depth: usize| [vec![0x91; depth], vec![0xc0]].concat();
+        assert!(from_msgpack(&nested(MAX_DEPTH)).is_ok());
+        assert_eq!(from_msgpack(&nested(MAX_DEPTH + 1)), Err("invalid msgpack: nested more than 128 deep".to_string()));
+        assert!(from_msgpack(&nested(100_000)).is_err());
+        let maps = [[0x81, 0xa1, b'a'].repeat(MAX_DEPTH + 1), vec![0xc0]].concat();
+        assert!(from_msgpack(&maps).is_err());
+    }
+}
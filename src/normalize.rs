@@ -0,0 +1,199 @@
+use crate::{j_item::JItem, path::PathSegment};
+
+/// The steps `JItem::normalize` applies. `Default` turns every step on.
+///
+/// Object key order is not part of a `JItem`; serialize with `FormatOptions::sort_keys`
+/// or `to_canonical_string` for sorted keys.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NormalizeOptions {
+    /// Turn `RawNumber`s into plain numbers and `-0` into `0`, so numbers that are equal
+    /// are written the same way.
+    pub canonicalize_numbers: bool,
+    /// Remove leading and trailing whitespace from string values. Keys are left alone.
+    pub trim_strings: bool,
+    /// Convert string values and keys to Unicode Normalization Form C. Keys that become
+    /// equal are merged, keeping one of their values.
+    #[cfg(feature = "nfc")]
+    pub nfc: bool,
+    /// Remove object members whose value is `null`.
+    pub drop_nulls: bool,
+    /// Remove object members whose value is an empty string, array or object, after the
+    /// value itself has been normalized.
+    pub drop_empty: bool,
+}
+
+impl Default for NormalizeOptions {
+    fn default() -> Self {
+        return NormalizeOptions {
+            canonicalize_numbers: true,
+            trim_strings: true,
+            #[cfg(feature = "nfc")]
+            nfc: true,
+            drop_nulls: true,
+            drop_empty: true,
+        };
+    }
+}
+
+impl JItem {
+    /// Rewrites the item in place so that documents meaning the same thing to the
+    /// options look the same, ready for hashing or comparing. Array elements are never
+    /// removed, only object members.
+    pub fn normalize(&mut self, options: &NormalizeOptions) {
+        match self {
+            JItem::Object(hmap) => {
+                #[cfg(feature = "nfc")]
+                if options.nfc {
+                    use unicode_normalization::UnicodeNormalization;
+                    *hmap = core::mem::take(hmap).into_iter().map(|(key, value)| (key.nfc().collect(), value)).collect();
+                }
+                for value in hmap.values_mut() {
+                    value.normalize(options);
+                }
+                hmap.retain(|_, value| !((options.drop_nulls && *value == JItem::Null) || (options.drop_empty && is_empty(value))));
+            },
+            JItem::Array(elements) => elements.iter_mut().for_each(|element| element.normalize(options)),
+            JItem::String(s) => normalize_string(s, options),
+            JItem::Number(_) | JItem::RawNumber(_) if options.canonicalize_numbers => {
+                if let Some(x) = self.number_value() {
+                    // adding 0.0 turns -0 into 0 and leaves everything else alone
+                    *self = JItem::Number(x + 0.0);
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+impl JItem {
+    /// Sorts the array at `pointer` by each element's member `key`, so arrays whose order
+    /// carries no meaning compare and hash the same. Elements without the member come
+    /// first, and ties are broken by comparing the whole elements, so the result does not
+    /// depend on the order before.
+    pub fn sort_arrays_by(&mut self, pointer: &str, key: &str) -> Result<(), String> {
+        let Some(JItem::Array(elements)) = self.pointer_mut(pointer) else {
+            return Err(format!("json pointer '{}' does not point to an array", pointer));
+        };
+        sort_by_member(elements, key);
+        return Ok(());
+    }
+
+    /// Sorts every array, at any depth, for which `select` names a member to sort by when
+    /// given the path to the array and its elements. Nested arrays are sorted before the
+    /// arrays holding them.
+    pub fn sort_all_arrays_by(&mut self, mut select: impl FnMut(&[PathSegment], &[JItem]) -> Option<String>) {
+        sort_all(self, &mut vec![], &mut select);
+    }
+}
+
+fn sort_all(item: &mut JItem, path: &mut Vec<PathSegment>, select: &mut impl FnMut(&[PathSegment], &[JItem]) -> Option<String>) {
+    match item {
+        JItem::Object(hmap) => {
+            for (key, value) in hmap.iter_mut() {
+                path.push(PathSegment::Key(key.clone()));
+                sort_all(value, path, select);
+                path.pop();
+            }
+        },
+        JItem::Array(elements) => {
+            for (index, element) in elements.iter_mut().enumerate() {
+                path.push(PathSegment::Index(index));
+                sort_all(element, path, select);
+                path.pop();
+            }
+            if let Some(key) = select(path, elements) {
+                sort_by_member(elements, &key);
+            }
+        },
+        _ => {},
+    }
+}
+
+fn sort_by_member(elements: &mut [JItem], key: &str) {
+    elements.sort_by(|a, b| (a.get(key), a).cmp(&(b.get(key), b)));
+}
+
+fn normalize_string(s: &mut String, options: &NormalizeOptions) {
+    if options.trim_strings && s.trim().len() != s.len() {
+        *s = s.trim().to_string();
+    }
+    #[cfg(feature = "nfc")]
+    if options.nfc {
+        use unicode_normalization::UnicodeNormalization;
+        *s = s.nfc().collect();
+    }
+}
+
+fn is_empty(item: &JItem) -> bool {
+    return match item {
+        JItem::String(s) => s.is_empty(),
+        JItem::Array(elements) => elements.is_empty(),
+        JItem::Object(hmap) => hmap.is_empty(),
+        _ => false,
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parse_with, parser::ParseOptions};
+
+    fn parse(input: &str) -> JItem {
+        return parse_with(input, &ParseOptions { preserve_number_text: true, ..ParseOptions::default() }).unwrap();
+    }
+
+    #[test]
+    fn normalize_everything() {
+        let mut item = parse(r#"{"a": " x ", "b": null, "c": {"d": null, "e": ""}, "f": [null, 1.50, -0, 1e2, "  "], "g": []}"#);
+        item.normalize(&NormalizeOptions::default());
+        assert_eq!(item.to_canonical_string(), r#"{"a":"x","f":[null,1.5,0,100,""]}"#);
+        assert!(matches!(item.pointer("/f/1"), Some(JItem::Number(_))));
+    }
+
+    #[test]
+    fn sort_array_by_member() {
+        let mut item = parse(r#"{"users": [{"id": 3}, {"id": 1, "x": 2}, {"name": "none"}, {"id": 1, "x": 1}, {"id": "2"}]}"#);
+        item.sort_arrays_by("/users", "id").unwrap();
+        assert_eq!(item, parse(r#"{"users": [{"name": "none"}, {"id": 1, "x": 1}, {"id": 1, "x": 2}, {"id": 3}, {"id": "2"}]}"#));
+        assert_eq!(item.sort_arrays_by("/users/0", "id"), Err("json pointer '/users/0' does not point to an array".to_string()));
+        assert_eq!(item.sort_arrays_by("/groups", "id"), Err("json pointer '/groups' does not point to an array".to_string()));
+    }
+
+    #[test]
+    fn sort_all_arrays_with_selector() {
+        let mut item = parse(r#"{"teams": [{"name": "b", "members": [{"id": 2}, {"id": 1}]}, {"name": "a", "members": []}], "tags": ["z", "y"]}"#);
+        let mut seen = vec![];
+        item.sort_all_arrays_by(|path, _| {
+            seen.push(crate::path::format_path(path, "."));
+            return match path.last() {
+                Some(PathSegment::Key(key)) if key == "teams" => Some("name".to_string()),
+                Some(PathSegment::Key(key)) if key == "members" => Some("id".to_string()),
+                _ => None,
+            };
+        });
+        assert_eq!(item, parse(r#"{"teams": [{"name": "a", "members": []}, {"name": "b", "members": [{"id": 1}, {"id": 2}]}], "tags": ["z", "y"]}"#));
+        seen.sort();
+        assert_eq!(seen, ["tags", "teams", "teams[0].members", "teams[1].members"]);
+    }
+
+    #[test]
+    fn normalize_selected_steps() {
+        let mut item = parse(r#"{"a": " x ", "b": null, "c": {}, "n": 1.50}"#);
+        item.normalize(&NormalizeOptions { canonicalize_numbers: false, trim_strings: false, drop_empty: false, ..NormalizeOptions::default() });
+        assert_eq!(item.to_string_with(&crate::format::FormatOptions { sort_keys: true, ..Default::default() }), r#"{"a":" x ","c":{},"n":1.50}"#);
+    }
+
+    #[cfg(feature = "nfc")]
+    #[test]
+    fn normalize_nfc() {
+        let mut item = parse("{\"cafe\u{301}\": \"cafe\u{301}\", \"caf\u{e9}\": 1}");
+        item.normalize(&NormalizeOptions { drop_nulls: false, ..NormalizeOptions::default() });
+        let JItem::Object(hmap) = &item else { panic!() };
+        assert_eq!(hmap.len(), 1);
+        assert!(hmap.contains_key("caf\u{e9}"));
+
+        let mut item = JItem::String("e\u{301}".to_string());
+        item.normalize(&NormalizeOptions::default());
+        assert_eq!(item, JItem::String("\u{e9}".to_string()));
+    }
+}
@@ -0,0 +1,152 @@
+use crate::{j_item::JItem, shared::SharedJItem};
+
+/// One recorded change to an `Overlay`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Delta {
+    /// Set the value at a JSON Pointer, as `SharedJItem::set_pointer` does.
+    Set { pointer: String, value: SharedJItem },
+    /// Remove the value at a JSON Pointer.
+    Remove { pointer: String },
+}
+
+/// A variant of a shared base document, kept as the list of changes made to it. The
+/// current value copies only the containers on the paths that were changed and shares
+/// everything else with the base, so many variants of one large document cost little
+/// more than their edits. A full `JItem` is only built by `to_item`.
+#[derive(Debug, Clone)]
+pub struct Overlay {
+    base: SharedJItem,
+    deltas: Vec<Delta>,
+    current: SharedJItem,
+}
+
+impl Overlay {
+    pub fn new(base: SharedJItem) -> Self {
+        return Overlay { current: base.clone(), base, deltas: vec![] };
+    }
+
+    /// Replays stored deltas over `base`, such as a tenant's saved changes.
+    pub fn from_deltas(base: SharedJItem, deltas: impl IntoIterator<Item = Delta>) -> Result<Self, String> {
+        let mut overlay = Overlay::new(base);
+        for delta in deltas {
+            overlay.apply(delta)?;
+        }
+        return Ok(overlay);
+    }
+
+    /// Sets the value at `pointer`. Fails without recording anything when the parent
+    /// does not exist.
+    pub fn set(&mut self, pointer: &str, value: impl Into<SharedJItem>) -> Result<(), String> {
+        return self.apply(Delta::Set { pointer: pointer.to_string(), value: value.into() });
+    }
+
+    /// Removes the value at `pointer`. Fails without recording anything when there is
+    /// no such value.
+    pub fn remove(&mut self, pointer: &str) -> Result<(), String> {
+        return self.apply(Delta::Remove { pointer: pointer.to_string() });
+    }
+
+    pub fn apply(&mut self, delta: Delta) -> Result<(), String> {
+        match &delta {
+            Delta::Set { pointer, value } => {
+                self.current.set_pointer(pointer, value.clone())?;
+            },
+            Delta::Remove { pointer } => {
+                if self.current.remove_pointer(pointer).is_none() {
+                    return Err(format!("nothing to remove at json pointer '{}'", pointer));
+                }
+            },
+        }
+        self.deltas.push(delta);
+        return Ok(());
+    }
+
+    /// Looks up a value in the edited document.
+    pub fn pointer(&self, pointer: &str) -> Option<&SharedJItem> {
+        return self.current.pointer(pointer);
+    }
+
+    /// The edited document.
+    pub fn value(&self) -> &SharedJItem {
+        return &self.current;
+    }
+
+    pub fn base(&self) -> &SharedJItem {
+        return &self.base;
+    }
+
+    /// The changes made so far, in order.
+    pub fn deltas(&self) -> &[Delta] {
+        return &self.deltas;
+    }
+
+    /// Replays the same changes over a new version of the base document. Fails, leaving
+    /// this overlay as it was, when a change no longer applies.
+    pub fn rebase(&mut self, base: SharedJItem) -> Result<(), String> {
+        *self = Overlay::from_deltas(base, self.deltas.clone())?;
+        return Ok(());
+    }
+
+    /// Drops every change, going back to the base document.
+    pub fn reset(&mut self) {
+        self.current = self.base.clone();
+        self.deltas.clear();
+    }
+
+    /// Builds the edited document as an owned tree.
+    pub fn to_item(&self) -> JItem {
+        return self.current.to_item();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    fn base() -> SharedJItem {
+        return SharedJItem::from(&parse(r#"{"limits": {"rps": 100, "burst": 10}, "features": ["a", "b"], "theme": {"color": "blue"}}"#).unwrap());
+    }
+
+    #[test]
+    fn edits_share_the_base() {
+        let base = base();
+        let mut tenant = Overlay::new(base.clone());
+        tenant.set("/limits/rps", JItem::Number(500.0)).unwrap();
+        tenant.set("/features/-", JItem::String("c".to_string())).unwrap();
+        tenant.remove("/limits/burst").unwrap();
+
+        let expected = parse(r#"{"limits": {"rps": 500}, "features": ["a", "b", "c"], "theme": {"color": "blue"}}"#).unwrap();
+        assert_eq!(tenant.to_item(), expected);
+        assert_eq!(tenant.deltas().len(), 3);
+        assert!(tenant.pointer("/theme").unwrap().ptr_eq(base.pointer("/theme").unwrap()));
+        assert_eq!(base.pointer("/limits/rps"), Some(&SharedJItem::Number(100.0)));
+    }
+
+    #[test]
+    fn failed_edits_are_not_recorded() {
+        let mut tenant = Overlay::new(base());
+        assert_eq!(tenant.set("/missing/x", JItem::Null), Err("parent of json pointer '/missing/x' does not exist".to_string()));
+        assert_eq!(tenant.remove("/theme/size"), Err("nothing to remove at json pointer '/theme/size'".to_string()));
+        assert!(tenant.deltas().is_empty());
+        assert!(tenant.value().ptr_eq(tenant.base()));
+    }
+
+    #[test]
+    fn replay_and_rebase() {
+        let mut tenant = Overlay::new(base());
+        tenant.set("/theme/color", JItem::String("red".to_string())).unwrap();
+        let replayed = Overlay::from_deltas(base(), tenant.deltas().to_vec()).unwrap();
+        assert_eq!(replayed.to_item(), tenant.to_item());
+
+        let new_base = SharedJItem::from(&parse(r#"{"theme": {"color": "green", "font": "serif"}}"#).unwrap());
+        tenant.rebase(new_base).unwrap();
+        assert_eq!(tenant.to_item(), parse(r#"{"theme": {"color": "red", "font": "serif"}}"#).unwrap());
+
+        tenant.remove("/theme/font").unwrap();
+        assert!(tenant.rebase(SharedJItem::from(&parse(r#"{"theme": {}}"#).unwrap())).is_err());
+        assert_eq!(tenant.deltas().len(), 2);
+        tenant.reset();
+        assert_eq!(tenant.to_item(), parse(r#"{"theme": {"color": "green", "font": "serif"}}"#).unwrap());
+    }
+}
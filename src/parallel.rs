@@ -0,0 +1,45 @@
+use rayon::prelude::*;
+
+use crate::{error::JsonError, j_item::JItem, parse};
+
+/// Parses newline-delimited JSON, one value per line, spreading the lines across the
+/// rayon thread pool. Results come back in input order, one per line that is not blank,
+/// so a bad record does not stop the rest. Error positions are within the whole input.
+pub fn parse_lines_parallel(input: &str) -> Vec<Result<JItem, JsonError>> {
+    let mut lines = vec![];
+    let mut start = 0;
+    for (n, line) in input.split('\n').enumerate() {
+        if !line.trim().is_empty() {
+            lines.push((n, start, line));
+        }
+        start += line.len() + 1;
+    }
+    return lines.into_par_iter().map(|(n, start, line)| parse(line).map_err(|e| e.within_line(start, n))).collect();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_lines_in_order() {
+        let input: String = (0..10_000).map(|n| format!("{{\"n\": {}}}\n", n)).collect();
+        let parsed = parse_lines_parallel(&input);
+        assert_eq!(parsed.len(), 10_000);
+        for (n, item) in parsed.into_iter().enumerate() {
+            assert_eq!(item.unwrap().pointer("/n"), Some(&JItem::Number(n as f64)));
+        }
+    }
+
+    #[test]
+    fn parse_lines_errors() {
+        let input = "[1]\r\n\n  \n{\"a\": tru}\n\"x\"";
+        let parsed = parse_lines_parallel(input);
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed[0], Ok(JItem::Array(vec![JItem::Number(1.0)])));
+        let error = parsed[1].clone().unwrap_err();
+        assert_eq!((error.line(), error.column(), error.span()), (4, 7, 15..18));
+        assert_eq!(&input[error.span()], "tru");
+        assert_eq!(parsed[2], Ok(JItem::String("x".to_string())));
+    }
+}
@@ -1,112 +1,521 @@
-use std::{collections::HashMap, iter::Peekable, mem::discriminant, slice::Iter};
+use alloc::{string::{String, ToString}, vec, vec::Vec};
+use core::{mem::discriminant, ops::Range};
 
-use crate::{j_item::JItem, lexer::Token};
+use crate::{
+    error::{ErrorKind, JsonError, Warning, WarningKind},
+    j_item::JItem,
+    lexer::{Lexer, Spanned, Token},
+    path::PathSegment,
+    HashMap,
+};
+
+/// Options for `parse_with`. The default matches `parse`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Keep each number's source text as a `JItem::RawNumber`, so serializing the tree
+    /// writes `1.50` or `1e3` exactly as read rather than a normalized float.
+    pub preserve_number_text: bool,
+    pub duplicate_keys: DuplicateKeys,
+    pub strictness: Strictness,
+    /// Also treat no-break space (U+00A0), vertical tab and form feed as whitespace.
+    pub extra_whitespace: bool,
+    /// Accept `=` in place of `:` and `;` in place of `,`, as some loggers write.
+    pub lenient_separators: bool,
+    /// Accept Python literals: `True`, `False` and `None`, single-quoted strings, and
+    /// `\xhh` escapes.
+    pub python_literals: bool,
+    pub hooks: Hooks,
+    pub keys: KeyFilter,
+    /// Fail with `ErrorKind::TooManyMembers` at the first key past this many in any one
+    /// object, so that huge objects are turned away before they are built. Members a
+    /// `KeyFilter` drops still count.
+    pub max_object_members: Option<usize>,
+    /// Fail with `ErrorKind::TooManyElements` at the first element past this many in any
+    /// one array.
+    pub max_array_elements: Option<usize>,
+    /// Give up with `ErrorKind::Timeout` once this moment has passed. The clock is read
+    /// at the start and then every 1024 tokens, so the overrun is small but not zero.
+    #[cfg(feature = "std")]
+    pub deadline: Option<std::time::Instant>,
+}
+
+#[cfg(feature = "std")]
+const DEADLINE_INTERVAL: usize = 1024;
+
+impl ParseOptions {
+    /// Options for reading the `repr()` of Python dicts and lists, as found in old log
+    /// lines.
+    pub fn python() -> Self {
+        return ParseOptions { python_literals: true, ..ParseOptions::default() };
+    }
+
+    /// These options with `deadline` set, for bounding the time spent on untrusted input.
+    #[cfg(feature = "std")]
+    pub fn deadline(self, deadline: std::time::Instant) -> Self {
+        return ParseOptions { deadline: Some(deadline), ..self };
+    }
+
+    /// Whether the deadline has passed, reading the clock only on the first step and
+    /// every `DEADLINE_INTERVAL` steps after.
+    #[cfg(feature = "std")]
+    pub(crate) fn past_deadline(&self, step: usize) -> bool {
+        return step % DEADLINE_INTERVAL == 1 && self.deadline.is_some_and(|deadline| std::time::Instant::now() >= deadline);
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub(crate) fn past_deadline(&self, _step: usize) -> bool {
+        return false;
+    }
+}
+
+/// Functions the parser runs as it builds values, for per-field rules applied while
+/// parsing rather than in a second pass over the tree. Either may return an error
+/// message, which stops parsing with `ErrorKind::Rejected` at the value or key.
+/// These are plain functions so that options stay cheap to copy and compare.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Hooks {
+    /// Called with each string, number, boolean and null, returning the value to keep in
+    /// its place, which may be of any type.
+    pub value: Option<fn(JItem) -> Result<JItem, String>>,
+    /// Called with each object key before it is checked for duplicates, returning the
+    /// key to use.
+    pub key: Option<fn(String) -> Result<String, String>>,
+}
+
+impl PartialEq for Hooks {
+    fn eq(&self, other: &Self) -> bool {
+        let value = match (self.value, other.value) {
+            (Some(a), Some(b)) => core::ptr::fn_addr_eq(a, b),
+            (a, b) => a.is_none() && b.is_none(),
+        };
+        let key = match (self.key, other.key) {
+            (Some(a), Some(b)) => core::ptr::fn_addr_eq(a, b),
+            (a, b) => a.is_none() && b.is_none(),
+        };
+        return value && key;
+    }
+}
+
+impl Eq for Hooks {}
+
+/// Which object members `parse_with` keeps. Dropped members are still checked for
+/// syntax, but their values are never built, so leaving out most of a large payload
+/// saves most of the work of building it. Duplicates among dropped keys are not
+/// reported.
+#[derive(Debug, Clone, Default)]
+pub enum KeyFilter {
+    #[default]
+    All,
+    /// Keep only members with one of these keys, at any depth.
+    Allow(Vec<String>),
+    /// Drop members with any of these keys, at any depth.
+    Deny(Vec<String>),
+    /// Keep members for which the function returns true, given the path to the member,
+    /// ending with its key.
+    Path(fn(&[PathSegment]) -> bool),
+}
+
+impl KeyFilter {
+    fn keeps(&self, path: &[PathSegment], key: &str) -> bool {
+        return match self {
+            KeyFilter::All => true,
+            KeyFilter::Allow(keys) => keys.iter().any(|k| k == key),
+            KeyFilter::Deny(keys) => !keys.iter().any(|k| k == key),
+            KeyFilter::Path(keeps) => keeps(path),
+        };
+    }
+}
+
+impl PartialEq for KeyFilter {
+    fn eq(&self, other: &Self) -> bool {
+        return match (self, other) {
+            (KeyFilter::Allow(a), KeyFilter::Allow(b)) | (KeyFilter::Deny(a), KeyFilter::Deny(b)) => a == b,
+            (KeyFilter::Path(a), KeyFilter::Path(b)) => core::ptr::fn_addr_eq(*a, *b),
+            (KeyFilter::All, KeyFilter::All) => true,
+            _ => false,
+        };
+    }
+}
+
+impl Eq for KeyFilter {}
+
+/// How closely input must follow RFC 8259.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Strictness {
+    /// Exactly the RFC 8259 grammar: numbers without leading zeros, bare `.` or `+`;
+    /// only the escapes the RFC lists, with `\u` surrogates in pairs; no unescaped
+    /// control characters in strings; no trailing commas; a single top-level value with
+    /// nothing but whitespace around it.
+    Strict,
+    /// What `parse` has always accepted: as `Strict`, but allowing numbers like `01` or
+    /// `1.`, unknown escapes standing for the escaped character, raw control characters,
+    /// trailing commas, and unpaired surrogates replaced with U+FFFD.
+    #[default]
+    Default,
+    /// As `Default`, also skipping `//` and `/* */` comments.
+    Lenient,
+}
+
+/// What to do when an object repeats a key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateKeys {
+    #[default]
+    Reject,
+    /// Keep the first value, with a warning.
+    KeepFirst,
+    /// Keep the last value, with a warning.
+    KeepLast,
+}
+
+/// A parser that keeps its working buffers between calls, for hot loops parsing many
+/// small documents, where allocating fresh ones for each would dominate. Results are
+/// the same as `parse_with` with the same options.
+#[derive(Debug, Clone, Default)]
+pub struct Parser {
+    options: ParseOptions,
+    /// Where the lexer builds each string before copying it out.
+    scratch: String,
+    warnings: Vec<Warning>,
+}
+
+impl Parser {
+    pub fn new(options: ParseOptions) -> Self {
+        return Parser { options, ..Parser::default() };
+    }
+
+    pub fn options(&self) -> &ParseOptions {
+        return &self.options;
+    }
+
+    pub fn parse(&mut self, input_string: &str) -> Result<JItem, JsonError> {
+        self.warnings.clear();
+        return parse_source(input_string, &self.options, &mut self.warnings, &mut self.scratch).map(|(item, _)| item);
+    }
+}
 
 pub fn parse(tokens: Vec<Token>) -> Result<JItem, String> {
-    let mut i = tokens.iter().peekable();
-    let item = parse_jitem(&mut i)?;
-    if i.peek().is_some() {
-        return Err("Parsing finished with tokens left.".to_string());
+    // without source text, each token's span is just its index
+    let spanned: Vec<Spanned> = tokens.into_iter().enumerate().map(|(n, token)| Spanned { token, span: n..n + 1 }).collect();
+    let options = ParseOptions::default();
+    let mut i = Cursor::new(Tokens::Lexed(spanned.into_iter()), &options);
+    return parse_tokens(&mut i).map_err(|(kind, _)| kind.to_string());
+}
+
+/// Parses `source` in a single pass, pulling each token from the lexer as it is needed
+/// rather than lexing the whole input first. The first problem in the input is the one
+/// reported, whether the lexer or the parser finds it. Warnings from the lexer come
+/// before those from the parser, and `scratch` is the lexer's buffer for strings.
+pub(crate) fn parse_source<N: Node>(source: &str, options: &ParseOptions, warnings: &mut Vec<Warning>, scratch: &mut String) -> Result<(N, usize), JsonError> {
+    let mut i = Cursor::new(Tokens::Lexer(Lexer::new(source, options, warnings, scratch)), options);
+    let result = parse_tokens(&mut i);
+    let Cursor { warnings: parser_warnings, pulled, .. } = i;
+    warnings.extend(parser_warnings.into_iter().map(|(kind, span)| Warning::new(kind, source, span)));
+    return result.map(|item| (item, pulled)).map_err(|(kind, span)| JsonError::new(kind, source, span));
+}
+
+pub(crate) type Failure = (ErrorKind, Range<usize>);
+
+/// Where the parser's tokens come from.
+enum Tokens<'a> {
+    Lexer(Lexer<'a>),
+    /// Tokens lexed up front, for the two-phase `lexer::lex` and `parse` API.
+    Lexed(alloc::vec::IntoIter<Spanned>),
+}
+
+struct Cursor<'a> {
+    tokens: Tokens<'a>,
+    peeked: Option<Spanned>,
+    /// Tokens taken from `tokens` so far.
+    pulled: usize,
+    options: &'a ParseOptions,
+    values: usize,
+    /// The end of the last token taken from `tokens`, which is the end of the input once
+    /// they run out.
+    end: usize,
+    duplicate_keys: DuplicateKeys,
+    strict: bool,
+    warnings: Vec<(WarningKind, Range<usize>)>,
+    /// The path to the value being parsed, kept only for `KeyFilter::Path`.
+    path: Vec<PathSegment>,
+    track_path: bool,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(tokens: Tokens<'a>, options: &'a ParseOptions) -> Self {
+        return Cursor { tokens, peeked: None, pulled: 0, options, values: 0, end: 0, duplicate_keys: options.duplicate_keys, strict: options.strictness == Strictness::Strict, warnings: vec![], path: vec![], track_path: matches!(options.keys, KeyFilter::Path(_)) };
+    }
+
+    fn pull(&mut self) -> Result<Option<Spanned>, Failure> {
+        let token = match &mut self.tokens {
+            Tokens::Lexer(lexer) => lexer.next_token()?,
+            Tokens::Lexed(tokens) => tokens.next(),
+        };
+        if let Some(token) = &token {
+            self.pulled += 1;
+            self.end = token.span.end;
+        }
+        return Ok(token);
+    }
+
+    fn peek(&mut self) -> Result<Option<&Spanned>, Failure> {
+        if self.peeked.is_none() {
+            self.peeked = self.pull()?;
+        }
+        return Ok(self.peeked.as_ref());
+    }
+
+    fn next(&mut self) -> Result<Option<Spanned>, Failure> {
+        return match self.peeked.take() {
+            Some(token) => Ok(Some(token)),
+            None => self.pull(),
+        };
+    }
+
+    fn next_if(&mut self, expected: &Token) -> Result<Option<Spanned>, Failure> {
+        if self.peek()?.is_some_and(|t| t.token == *expected) {
+            return self.next();
+        }
+        return Ok(None);
+    }
+
+    /// An empty span at the end of the input, for errors found there. Only meaningful
+    /// once the tokens have run out.
+    fn eof(&self) -> Range<usize> {
+        return self.end..self.end;
+    }
+}
+
+/// What the parser builds for each value, so the one grammar can produce plain `JItem`
+/// trees or `SpannedJItem` trees that remember where each value was in the source.
+pub(crate) trait Node: Sized {
+    type Members: Default;
+    /// False for `()`, which only checks syntax, so hooks and filters are not run.
+    const BUILDS: bool = true;
+
+    fn scalar(item: JItem, span: Range<usize>) -> Self;
+    fn array(elements: Vec<Self>, span: Range<usize>) -> Self;
+    fn object(members: Self::Members, span: Range<usize>) -> Self;
+    fn contains_key(members: &Self::Members, key: &str) -> bool;
+    fn insert(members: &mut Self::Members, key: String, key_span: Range<usize>, value: Self);
+}
+
+impl Node for JItem {
+    type Members = HashMap<String, JItem>;
+
+    fn scalar(item: JItem, _: Range<usize>) -> Self {
+        return item;
+    }
+
+    fn array(elements: Vec<Self>, _: Range<usize>) -> Self {
+        return JItem::Array(elements);
+    }
+
+    fn object(members: Self::Members, _: Range<usize>) -> Self {
+        return JItem::Object(members);
+    }
+
+    fn contains_key(members: &Self::Members, key: &str) -> bool {
+        return members.contains_key(key);
+    }
+
+    fn insert(members: &mut Self::Members, key: String, _: Range<usize>, value: Self) {
+        members.insert(key, value);
+    }
+}
+
+/// Checks a value's syntax without building anything, for members a `KeyFilter` drops.
+impl Node for () {
+    type Members = ();
+    const BUILDS: bool = false;
+
+    fn scalar(_: JItem, _: Range<usize>) -> Self {}
+
+    fn array(_: Vec<Self>, _: Range<usize>) -> Self {}
+
+    fn object(_: Self::Members, _: Range<usize>) -> Self {}
+
+    fn contains_key(_: &Self::Members, _: &str) -> bool {
+        return false;
+    }
+
+    fn insert(_: &mut Self::Members, _: String, _: Range<usize>, _: Self) {}
+}
+
+fn parse_tokens<N: Node>(i: &mut Cursor) -> Result<N, Failure> {
+    let item = parse_jitem(i)?;
+    if let Some(next) = i.next()? {
+        // the error covers everything left, so read to the end
+        while i.next()?.is_some() {}
+        return Err((ErrorKind::TrailingTokens, next.span.start..i.end));
     }
     return Ok(item);
 }
 
-fn parse_jitem(tokens: &mut Peekable<Iter<Token>>) -> Result<JItem, String> {
-    let Some(next) = tokens.next() else {
-        return Err("tried to parse JItem, but got EOF.".to_string());
+fn parse_jitem<N: Node>(i: &mut Cursor) -> Result<N, Failure> {
+    let Some(next) = i.next()? else {
+        return Err((ErrorKind::UnexpectedEof, i.eof()));
+    };
+    i.values += 1;
+    if i.options.past_deadline(i.values) {
+        return Err((ErrorKind::Timeout, next.span));
+    }
+    let scalar = match next.token {
+        Token::LBrace => return parse_jobject(i, next.span),
+        Token::LSquareBracket => return parse_jarray(i, next.span),
+        Token::Number(num) => JItem::Number(num),
+        // nothing is kept of a value that is only being checked
+        Token::RawNumber(_) | Token::String(_) if !N::BUILDS => JItem::Null,
+        Token::RawNumber(text) => JItem::RawNumber(text),
+        Token::String(s) => JItem::String(s),
+        Token::True => JItem::Bool(true),
+        Token::False => JItem::Bool(false),
+        Token::Null => JItem::Null,
+        token => return Err((ErrorKind::UnexpectedToken(token), next.span)),
     };
-    return match next {
-        Token::LBrace => parse_jobject(tokens),
-        Token::LSquareBracket => parse_jarray(tokens),
-        Token::Number(num) => Ok(JItem::Number(*num)),
-        Token::String(s) => Ok(JItem::String(s.to_string())),
-        Token::True => Ok(JItem::True),
-        Token::False => Ok(JItem::False),
-        Token::Null => Ok(JItem::Null),
-        _ => Err(format!("Unexpected '{:?}' during parse.", next)),
+    let scalar = match i.options.hooks.value {
+        Some(hook) if N::BUILDS => hook(scalar).map_err(|reason| (ErrorKind::Rejected(reason), next.span.clone()))?,
+        _ => scalar,
     };
+    return Ok(N::scalar(scalar, next.span));
 }
 
-fn parse_jobject(tokens: &mut Peekable<Iter<Token>>) -> Result<JItem, String> {
-    let mut hmap:HashMap<String, JItem> = HashMap::new();
-    while let Some(next) = tokens.peek() {
-        if **next == Token::RBrace {
-            tokens.next();
-            return Ok(JItem::Object(hmap));
+fn parse_jobject<N: Node>(i: &mut Cursor, open: Range<usize>) -> Result<N, Failure> {
+    let mut hmap = N::Members::default();
+    let mut members = 0;
+    while let Some(next) = i.next()? {
+        if next.token == Token::RBrace {
+            if i.strict && members > 0 {
+                return Err((ErrorKind::ExpectedKey(next.token), next.span));
+            }
+            return Ok(N::object(hmap, open.start..next.span.end));
         }
 
-        let Token::String(key) = next else {
-            return Err(format!("expected string key for jobject but got {:?}", next));
+        let Token::String(key) = next.token else {
+            return Err((ErrorKind::ExpectedKey(next.token), next.span));
         };
-
-        if hmap.contains_key(key) {
-            return Err(format!("duplicate key found in jobject: '{}'", key));
+        let key_span = next.span;
+        if let Some(limit) = i.options.max_object_members.filter(|limit| members == *limit) {
+            return Err((ErrorKind::TooManyMembers(limit), key_span));
         }
+        let key = match i.options.hooks.key {
+            Some(hook) if N::BUILDS => hook(key).map_err(|reason| (ErrorKind::Rejected(reason), key_span.clone()))?,
+            _ => key,
+        };
 
-        tokens.next(); // advance and eat the key token
-
-        expect_token(tokens, &Token::Colon)?; // there needs to be a : between key and item
+        members += 1;
+        if i.track_path {
+            i.path.push(PathSegment::Key(key.clone()));
+        }
+        if N::BUILDS && !i.options.keys.keeps(&i.path, &key) {
+            expect_token(i, &Token::Colon)?;
+            parse_jitem::<()>(i)?;
+        }
+        else {
+            let duplicate = N::contains_key(&hmap, &key);
+            if duplicate {
+                if i.duplicate_keys == DuplicateKeys::Reject {
+                    return Err((ErrorKind::DuplicateKey(key), key_span));
+                }
+                i.warnings.push((WarningKind::DuplicateKey(key.to_string()), key_span.clone()));
+            }
 
-        let inner_item = parse_jitem(tokens)?;
+            expect_token(i, &Token::Colon)?; // there needs to be a : between key and item
 
-        hmap.insert(key.to_string(), inner_item);
+            let inner_item = parse_jitem(i)?;
 
-        if dbg!(tokens.peek()).is_some_and(|t| **t == Token::RBrace) {
-            tokens.next();
-            return Ok(JItem::Object(hmap));
+            if !duplicate || i.duplicate_keys == DuplicateKeys::KeepLast {
+                N::insert(&mut hmap, key, key_span, inner_item);
+            }
+        }
+        if i.track_path {
+            i.path.pop();
         }
-        expect_token(tokens, &Token::Comma)?;
+        if let Some(close) = i.next_if(&Token::RBrace)? {
+            return Ok(N::object(hmap, open.start..close.span.end));
+        }
+        if i.peek()?.is_none() {
+            break;
+        }
+        expect_token(i, &Token::Comma)?;
     }
-    return Err("unexpected EOF during parse of array.".to_string());
+    return Err((ErrorKind::UnterminatedObject, open));
 }
 
-fn parse_jarray(tokens: &mut Peekable<Iter<Token>>) -> Result<JItem, String> {
+fn parse_jarray<N: Node>(i: &mut Cursor, open: Range<usize>) -> Result<N, Failure> {
+    let max_elements = i.options.max_array_elements;
     let mut elements = vec![];
-    while let Some(next) = tokens.peek() {
-        if **next == Token::RSquareBracket {
-            tokens.next();
-            return Ok(JItem::Array(elements));
+    while let Some(next) = i.peek()? {
+        if next.token == Token::RSquareBracket {
+            let close = i.next()?.unwrap();
+            if i.strict && !elements.is_empty() {
+                return Err((ErrorKind::UnexpectedToken(close.token), close.span));
+            }
+            return Ok(N::array(elements, open.start..close.span.end));
+        }
+        if let Some(limit) = max_elements.filter(|limit| elements.len() == *limit) {
+            return Err((ErrorKind::TooManyElements(limit), next.span.clone()));
         }
 
-        let inner_item = parse_jitem(tokens)?;
-
-        dbg!(&inner_item);
+        if i.track_path {
+            i.path.push(PathSegment::Index(elements.len()));
+        }
+        let inner_item = parse_jitem(i)?;
+        if i.track_path {
+            i.path.pop();
+        }
 
         elements.push(inner_item);
 
-        if dbg!(tokens.peek()).is_some_and(|t| **t == Token::RSquareBracket) {
-            tokens.next();
-            return Ok(JItem::Array(elements));
+        if let Some(close) = i.next_if(&Token::RSquareBracket)? {
+            return Ok(N::array(elements, open.start..close.span.end));
         }
-        expect_token(tokens, &Token::Comma)?;
+        if i.peek()?.is_none() {
+            break;
+        }
+        expect_token(i, &Token::Comma)?;
     }
-    return Err("unexpected EOF during parse of array.".to_string());
+    return Err((ErrorKind::UnterminatedArray, open));
 }
 
-fn expect_token(tokens: &mut Peekable<Iter<Token>>, expected: &Token) -> Result<(), String> {
-    if let Some(tok) = tokens.next() {
-        if discriminant(tok) == discriminant(expected) {
+fn expect_token(i: &mut Cursor, expected: &Token) -> Result<(), Failure> {
+    if let Some(next) = i.next()? {
+        if discriminant(&next.token) == discriminant(expected) {
             return Ok(());
         }
         else {
-            return Err(format!("Unexpected token during parse. Expected {:?} but got {:?}", expected, tok));
+            return Err((ErrorKind::Expected { expected: expected.clone(), found: Some(next.token) }, next.span));
         }
     };
-    return Err(format!("Unexpected EOF during parse. Expected {:?} but got EOF.", expected));
+    return Err((ErrorKind::Expected { expected: expected.clone(), found: None }, i.eof()));
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn parse_deadline_between_values() {
+        let mut tokens = vec![Token::LSquareBracket];
+        for _ in 0..2000 {
+            tokens.extend([Token::Null, Token::Comma]);
+        }
+        tokens.push(Token::RSquareBracket);
+        let spanned: Vec<Spanned> = tokens.into_iter().enumerate().map(|(n, token)| Spanned { token, span: n..n + 1 }).collect();
+
+        let expired = ParseOptions::default().deadline(std::time::Instant::now());
+        assert_eq!(parse_tokens::<JItem>(&mut Cursor::new(Tokens::Lexed(spanned.clone().into_iter()), &expired)).unwrap_err(), (ErrorKind::Timeout, 0..1));
+        let options = ParseOptions::default();
+        assert!(parse_tokens::<JItem>(&mut Cursor::new(Tokens::Lexed(spanned.into_iter()), &options)).is_ok());
+    }
+
     #[test]
     fn parse_single_true() {
-        assert_successful_parse(vec![Token::True], JItem::True);
+        assert_successful_parse(vec![Token::True], JItem::Bool(true));
     }
 
     #[test]
     fn parse_single_false() {
-        assert_successful_parse(vec![Token::False], JItem::False);
+        assert_successful_parse(vec![Token::False], JItem::Bool(false));
     }
 
     #[test]
@@ -121,14 +530,14 @@ mod test {
 
     #[test]
     fn parse_array_single() {
-        assert_successful_parse(vec![Token::LSquareBracket, Token::True, Token::RSquareBracket], JItem::Array(vec![JItem::True]));
+        assert_successful_parse(vec![Token::LSquareBracket, Token::True, Token::RSquareBracket], JItem::Array(vec![JItem::Bool(true)]));
     }
 
     #[test]
     fn parse_array_multi() {
         assert_successful_parse(
             vec![Token::LSquareBracket, Token::True, Token::Comma, Token::Number(5.), Token::Comma, Token::String("foo".to_string()), Token::RSquareBracket],
-            JItem::Array(vec![JItem::True, JItem::Number(5.), JItem::String("foo".to_string())])
+            JItem::Array(vec![JItem::Bool(true), JItem::Number(5.), JItem::String("foo".to_string())])
         );
     }
 
@@ -136,7 +545,7 @@ mod test {
     fn parse_array_nested() {
         assert_successful_parse(
             vec![Token::LSquareBracket, Token::True, Token::Comma, Token::LSquareBracket, Token::Number(5.), Token::RSquareBracket, Token::RSquareBracket],
-            JItem::Array(vec![JItem::True, JItem::Array(vec![JItem::Number(5.)])])
+            JItem::Array(vec![JItem::Bool(true), JItem::Array(vec![JItem::Number(5.)])])
         );
     }
 
@@ -173,7 +582,7 @@ mod test {
     fn parse_object_nested() {
         let mut expected_hashmap = HashMap::new();
         let mut expected_nested_hashmap = HashMap::new();
-        expected_nested_hashmap.insert("foo".to_string(), JItem::True);
+        expected_nested_hashmap.insert("foo".to_string(), JItem::Bool(true));
         expected_hashmap.insert("foo".to_string(), JItem::String("bar".to_string()));
         expected_hashmap.insert("baz".to_string(), JItem::Object(expected_nested_hashmap));
         assert_successful_parse(
@@ -182,6 +591,16 @@ mod test {
         );
     }
 
+    #[test]
+    fn parse_tokens_left_over() {
+        assert_failed_parse(vec![Token::True, Token::False], "Parsing finished with tokens left.");
+    }
+
+    #[test]
+    fn parse_array_unterminated() {
+        assert_failed_parse(vec![Token::LSquareBracket], "unexpected EOF during parse of array.");
+    }
+
     fn assert_successful_parse(input: Vec<Token>, output: JItem) {
         let result = parse(input);
         let Ok(output_tokens) = result else {
@@ -197,4 +616,36 @@ mod test {
         };
         assert_eq!(error_message, expected_error_message);
     }
+
+    #[test]
+    fn reused_parser_matches_parse_with() {
+        let options = ParseOptions { duplicate_keys: DuplicateKeys::KeepLast, ..ParseOptions::default() };
+        let mut parser = Parser::new(options.clone());
+        for input in [r#"{"a": "x\ty", "a": [1, 2]}"#, "[1,", r#"{"long": "a longer string than before"}"#, "\"s\""] {
+            assert_eq!(parser.parse(input), crate::parse_with(input, &options));
+        }
+    }
+
+    #[test]
+    fn single_pass_reports_first_problem() {
+        // the parser stops at the missing comma before the lexer reaches the '@'
+        let error = crate::parse("[1 2, @]").unwrap_err();
+        assert_eq!((error.kind(), error.span()), (&ErrorKind::Expected { expected: Token::Comma, found: Some(Token::Number(2.0)) }, 3..4));
+        let error = crate::parse("[1] 2 @").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::UnexpectedCharacter('@'));
+        let error = crate::parse("[1] 2 3 ").unwrap_err();
+        assert_eq!((error.kind(), error.span()), (&ErrorKind::TrailingTokens, 4..7));
+    }
+
+    #[test]
+    fn container_limits() {
+        let options = ParseOptions { max_object_members: Some(2), max_array_elements: Some(3), ..ParseOptions::default() };
+        assert!(crate::parse_with(r#"{"a": [1, 2, 3], "b": {"c": [], "d": 0}}"#, &options).is_ok());
+        let error = crate::parse_with(r#"{"a": 1, "b": 2, "c": 3, "d": 4}"#, &options).unwrap_err();
+        assert_eq!((error.code(), error.span(), error.to_string().as_str()), ("E021", 17..20, "object has more than 2 members at line 1, column 18"));
+        let error = crate::parse_with("[[1, 2, 3, [4]]]", &options).unwrap_err();
+        assert_eq!((error.kind(), error.span()), (&ErrorKind::TooManyElements(3), 11..12));
+        let dropped = ParseOptions { keys: KeyFilter::Deny(vec!["b".to_string(), "c".to_string()]), ..options };
+        assert_eq!(crate::parse_with(r#"{"a": 1, "b": 2, "c": 3}"#, &dropped).unwrap_err().code(), "E021");
+    }
 }
@@ -0,0 +1,476 @@
+use alloc::{format, string::{String, ToString}, vec, vec::Vec};
+
+use crate::{
+    compare::{pair_elements, ArrayOrder, CompareOptions},
+    j_item::JItem,
+    pointer::{array_index, escape_token, parse_pointer, resolve, resolve_mut},
+};
+
+/// An RFC 6902 JSON Patch that turns `from` into `to`, as an array of operations.
+/// Object members are visited in key order, so the same inputs always give the same
+/// patch. Arrays are compared element by element, with elements added or removed at the
+/// end, rather than searched for a minimal edit.
+pub fn diff(from: &JItem, to: &JItem) -> JItem {
+    return diff_with(from, to, &CompareOptions::default());
+}
+
+/// Like `diff`, leaving out changes `options` says do not matter. With an unordered
+/// `ArrayOrder`, paired elements are diffed in place, unpaired ones in `from` are removed,
+/// and unpaired ones in `to` are appended, so the patch gives `to` up to array order.
+pub fn diff_with(from: &JItem, to: &JItem, options: &CompareOptions) -> JItem {
+    let mut ops = vec![];
+    diff_into(from, to, options, &mut String::new(), &mut ops);
+    return JItem::Array(ops);
+}
+
+fn diff_into(from: &JItem, to: &JItem, options: &CompareOptions, path: &mut String, ops: &mut Vec<JItem>) {
+    match (from, to) {
+        (JItem::Object(old), JItem::Object(new)) => {
+            let mut keys: Vec<&String> = old.keys().chain(new.keys().filter(|key| !old.contains_key(*key))).collect();
+            keys.sort();
+            for key in keys {
+                let len = path.len();
+                path.push('/');
+                path.push_str(&escape_token(key));
+                match (old.get(key), new.get(key)) {
+                    (Some(old), Some(new)) => diff_into(old, new, options, path, ops),
+                    (Some(_), None) => ops.push(op("remove", path, None)),
+                    (None, Some(new)) => ops.push(op("add", path, Some(new))),
+                    (None, None) => unreachable!(),
+                }
+                path.truncate(len);
+            }
+        },
+        (JItem::Array(old), JItem::Array(new)) if options.array_order == ArrayOrder::Ordered => {
+            let len = path.len();
+            for (n, (old, new)) in old.iter().zip(new).enumerate() {
+                path.push_str(&format!("/{}", n));
+                diff_into(old, new, options, path, ops);
+                path.truncate(len);
+            }
+            for n in new.len()..old.len() {
+                // remove from the end so earlier indices stay put
+                path.push_str(&format!("/{}", old.len() - 1 - n + new.len()));
+                ops.push(op("remove", path, None));
+                path.truncate(len);
+            }
+            for (n, new) in new.iter().enumerate().skip(old.len()) {
+                path.push_str(&format!("/{}", n));
+                ops.push(op("add", path, Some(new)));
+                path.truncate(len);
+            }
+        },
+        (JItem::Array(old), JItem::Array(new)) => {
+            let len = path.len();
+            let pairs = pair_elements(old, new, options);
+            for (n, pair) in pairs.iter().enumerate() {
+                if let Some(m) = pair {
+                    path.push_str(&format!("/{}", n));
+                    diff_into(&old[n], &new[*m], options, path, ops);
+                    path.truncate(len);
+                }
+            }
+            for n in (0..old.len()).rev().filter(|n| pairs[*n].is_none()) {
+                path.push_str(&format!("/{}", n));
+                ops.push(op("remove", path, None));
+                path.truncate(len);
+            }
+            path.push_str("/-");
+            for (m, new) in new.iter().enumerate() {
+                if !pairs.contains(&Some(m)) {
+                    ops.push(op("add", path, Some(new)));
+                }
+            }
+            path.truncate(len);
+        },
+        (old, new) if old.approx_eq_with(new, options) => {},
+        (_, new) => ops.push(op("replace", path, Some(new))),
+    }
+}
+
+fn op(name: &str, path: &str, value: Option<&JItem>) -> JItem {
+    let mut members = vec![("op".to_string(), JItem::String(name.to_string())), ("path".to_string(), JItem::String(path.to_string()))];
+    if let Some(value) = value {
+        members.push(("value".to_string(), value.clone()));
+    }
+    return JItem::Object(members.into_iter().collect());
+}
+
+/// Options for `render_diff`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderOptions {
+    /// Color removed lines red, added lines green and hunk headers cyan with ANSI escapes.
+    pub color: bool,
+    /// Unchanged lines shown around each change.
+    pub context: usize,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        return RenderOptions { color: false, context: 3 };
+    }
+}
+
+/// A unified diff of the pretty-printed forms of two documents, with keys sorted so only
+/// real changes show up: `-` lines are only in `from`, `+` lines only in `to`, and each
+/// hunk starts with an `@@ -line,count +line,count @@` header. Equal documents give an
+/// empty string.
+pub fn render_diff(from: &JItem, to: &JItem, options: &RenderOptions) -> String {
+    let (from, to) = (from.to_pretty_string(), to.to_pretty_string());
+    let (old, new): (Vec<&str>, Vec<&str>) = (from.lines().collect(), to.lines().collect());
+    let edits = line_edits(&old, &new);
+    let paint = |code: &str, line: String| if options.color { format!("\x1b[{}m{}\x1b[0m\n", code, line) } else { format!("{}\n", line) };
+
+    let mut out = String::new();
+    let mut n = 0;
+    while let Some(first) = edits[n..].iter().position(|edit| *edit != Edit::Same).map(|found| n + found) {
+        // a hunk runs until a stretch of more than twice the context lines is unchanged
+        let start = first.saturating_sub(options.context);
+        let mut end = first;
+        let mut same = 0;
+        for (m, edit) in edits.iter().enumerate().skip(first) {
+            if *edit == Edit::Same {
+                same += 1;
+                if same > 2 * options.context {
+                    break;
+                }
+            }
+            else {
+                same = 0;
+                end = m + 1;
+            }
+        }
+        let end = (end + options.context).min(edits.len());
+        let (old_start, new_start) = positions(&edits[..start]);
+        let (old_len, new_len) = positions(&edits[start..end]);
+        out.push_str(&paint("36", format!("@@ -{},{} +{},{} @@", old_start + 1, old_len, new_start + 1, new_len)));
+        let (mut old_line, mut new_line) = (old_start, new_start);
+        for edit in &edits[start..end] {
+            match edit {
+                Edit::Same => {
+                    out.push_str(&format!(" {}\n", old[old_line]));
+                    old_line += 1;
+                    new_line += 1;
+                },
+                Edit::Remove => {
+                    out.push_str(&paint("31", format!("-{}", old[old_line])));
+                    old_line += 1;
+                },
+                Edit::Add => {
+                    out.push_str(&paint("32", format!("+{}", new[new_line])));
+                    new_line += 1;
+                },
+            }
+        }
+        n = end;
+    }
+    return out;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Same,
+    Remove,
+    Add,
+}
+
+/// How many old and new lines a run of edits covers.
+fn positions(edits: &[Edit]) -> (usize, usize) {
+    let old = edits.iter().filter(|edit| **edit != Edit::Add).count();
+    let new = edits.iter().filter(|edit| **edit != Edit::Remove).count();
+    return (old, new);
+}
+
+/// A shortest edit script from `old` to `new`, using Myers' algorithm.
+fn line_edits(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let offset = n + m + 1;
+    let mut v = vec![0_isize; 2 * offset as usize + 1];
+    let mut trace = vec![];
+    'search: for d in 0..=n + m {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let index = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[index - 1] < v[index + 1]) { v[index + 1] } else { v[index - 1] + 1 };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    let mut edits = vec![];
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let index = (k + offset) as usize;
+        let previous_k = if k == -d || (k != d && v[index - 1] < v[index + 1]) { k + 1 } else { k - 1 };
+        let previous_x = v[(previous_k + offset) as usize];
+        let previous_y = previous_x - previous_k;
+        while x > previous_x && y > previous_y {
+            edits.push(Edit::Same);
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            edits.push(if x == previous_x { Edit::Add } else { Edit::Remove });
+        }
+        (x, y) = (previous_x, previous_y);
+    }
+    edits.reverse();
+    return edits;
+}
+
+/// Applies an RFC 6902 JSON Patch. Either every operation succeeds, or `item` is left
+/// unchanged and the error names the first operation that failed.
+pub fn apply_patch(item: &mut JItem, patch: &JItem) -> Result<(), String> {
+    let JItem::Array(ops) = patch else {
+        return Err("json patch must be an array of operations".to_string());
+    };
+    let mut patched = item.clone();
+    for (n, op) in ops.iter().enumerate() {
+        apply_op(&mut patched, op).map_err(|e| format!("json patch operation {} failed: {}", n, e))?;
+    }
+    *item = patched;
+    return Ok(());
+}
+
+/// One operation of a JSON Patch, checked for the members it needs.
+pub(crate) enum Op<'a> {
+    Add { path: &'a str, value: &'a JItem },
+    Remove { path: &'a str },
+    Replace { path: &'a str, value: &'a JItem },
+    Move { from: &'a str, path: &'a str },
+    Copy { from: &'a str, path: &'a str },
+    Test { path: &'a str, value: &'a JItem },
+}
+
+pub(crate) fn parse_op(op: &JItem) -> Result<Op<'_>, String> {
+    let JItem::Object(members) = op else {
+        return Err("operation must be an object".to_string());
+    };
+    let string_member = |name: &str| match members.get(name) {
+        Some(JItem::String(s)) => Ok(s.as_str()),
+        Some(_) => Err(format!("'{}' must be a string", name)),
+        None => Err(format!("missing '{}'", name)),
+    };
+    let value = || members.get("value").ok_or_else(|| "missing 'value'".to_string());
+    let path = string_member("path")?;
+    return match string_member("op")? {
+        "add" => Ok(Op::Add { path, value: value()? }),
+        "remove" => Ok(Op::Remove { path }),
+        "replace" => Ok(Op::Replace { path, value: value()? }),
+        "move" => {
+            let from = string_member("from")?;
+            if path.starts_with(from) && path[from.len()..].starts_with('/') {
+                return Err(format!("cannot move '{}' into itself", from));
+            }
+            Ok(Op::Move { from, path })
+        },
+        "copy" => Ok(Op::Copy { from: string_member("from")?, path }),
+        "test" => Ok(Op::Test { path, value: value()? }),
+        other => Err(format!("unknown op '{}'", other)),
+    };
+}
+
+fn apply_op(item: &mut JItem, op: &JItem) -> Result<(), String> {
+    return match parse_op(op)? {
+        Op::Add { path, value } => add(item, path, value.clone()),
+        Op::Remove { path } => remove(item, path).map(|_| ()),
+        Op::Replace { path, value } => replace(item, path, value.clone()),
+        Op::Move { from, path } => {
+            let moved = remove(item, from)?;
+            add(item, path, moved)
+        },
+        Op::Copy { from, path } => {
+            let copied = resolve(item, &parse_pointer(from)?).ok_or_else(|| format!("'{}' does not exist", from))?.clone();
+            add(item, path, copied)
+        },
+        Op::Test { path, value } => test(item, path, value),
+    };
+}
+
+pub(crate) fn replace(item: &mut JItem, path: &str, value: JItem) -> Result<(), String> {
+    let target = resolve_mut(item, &parse_pointer(path)?).ok_or_else(|| format!("'{}' does not exist", path))?;
+    *target = value;
+    return Ok(());
+}
+
+pub(crate) fn test(item: &JItem, path: &str, value: &JItem) -> Result<(), String> {
+    return match resolve(item, &parse_pointer(path)?) {
+        Some(found) if found == value => Ok(()),
+        Some(_) => Err(format!("value at '{}' does not match", path)),
+        None => Err(format!("'{}' does not exist", path)),
+    };
+}
+
+/// Adds `value` at `path`. Unlike `JItem::set_pointer`, an array index inserts before the
+/// element already there.
+pub(crate) fn add(item: &mut JItem, path: &str, value: JItem) -> Result<(), String> {
+    let mut tokens = parse_pointer(path)?;
+    let Some(last) = tokens.pop() else {
+        *item = value;
+        return Ok(());
+    };
+    return match resolve_mut(item, &tokens) {
+        Some(JItem::Object(hmap)) => {
+            hmap.insert(last, value);
+            Ok(())
+        },
+        Some(JItem::Array(elements)) => {
+            let index = if last == "-" { Some(elements.len()) } else { array_index(&last) };
+            match index {
+                Some(index) if index <= elements.len() => {
+                    elements.insert(index, value);
+                    Ok(())
+                },
+                _ => Err(format!("array index '{}' out of bounds in '{}'", last, path)),
+            }
+        },
+        Some(_) => Err(format!("parent of '{}' is not an object or array", path)),
+        None => Err(format!("parent of '{}' does not exist", path)),
+    };
+}
+
+pub(crate) fn remove(item: &mut JItem, path: &str) -> Result<JItem, String> {
+    if path.is_empty() {
+        return Ok(core::mem::take(item));
+    }
+    return item.remove_pointer(path).ok_or_else(|| format!("'{}' does not exist", path));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn diff_then_apply() {
+        let pairs = [
+            (r#"{"a": 1, "b": [1, 2, 3], "c": {"d": true}}"#, r#"{"a": 2, "b": [1, 5], "c": {"e/f": null}, "g": "new"}"#),
+            (r#"[1, 2]"#, r#"[1, 2, {"x": []}, 4]"#),
+            (r#"{"a": 1}"#, r#"[1]"#),
+            (r#"null"#, r#"null"#),
+        ];
+        for (from, to) in pairs {
+            let (mut from, to) = (parse(from).unwrap(), parse(to).unwrap());
+            let patch = diff(&from, &to);
+            apply_patch(&mut from, &patch).unwrap();
+            assert_eq!(from, to, "{}", patch);
+        }
+    }
+
+    #[test]
+    fn diff_is_ordered() {
+        let from = parse(r#"{"b": 1, "a": [1, 2, 3], "c": 0}"#).unwrap();
+        let to = parse(r#"{"b": 2, "a": [1]}"#).unwrap();
+        let expected = parse(r#"[
+            {"op": "remove", "path": "/a/2"},
+            {"op": "remove", "path": "/a/1"},
+            {"op": "replace", "path": "/b", "value": 2},
+            {"op": "remove", "path": "/c"}
+        ]"#).unwrap();
+        assert_eq!(diff(&from, &to), expected);
+    }
+
+    #[test]
+    fn diff_unordered_arrays() {
+        let from = parse(r#"{"items": [{"id": 1, "n": 1}, {"id": 2, "n": 2}, {"id": 3, "n": 3}], "tags": ["a", "b", "c"]}"#).unwrap();
+        let to = parse(r#"{"items": [{"id": 3, "n": 3}, {"id": 4, "n": 4}, {"id": 1, "n": 10}], "tags": ["c", "a", "d"]}"#).unwrap();
+        let keyed = CompareOptions { epsilon: 0.0, array_order: ArrayOrder::KeyedBy("id".to_string()) };
+        let expected = parse(r#"[
+            {"op": "replace", "path": "/items/0/n", "value": 10},
+            {"op": "remove", "path": "/items/1"},
+            {"op": "add", "path": "/items/-", "value": {"id": 4, "n": 4}},
+            {"op": "remove", "path": "/tags/1"},
+            {"op": "add", "path": "/tags/-", "value": "d"}
+        ]"#).unwrap();
+        let patch = diff_with(&from, &to, &keyed);
+        assert_eq!(patch, expected);
+        let mut patched = from.clone();
+        apply_patch(&mut patched, &patch).unwrap();
+        assert!(patched.approx_eq_with(&to, &keyed));
+        assert_ne!(patched, to);
+
+        let unordered = CompareOptions { epsilon: 0.0, array_order: ArrayOrder::Unordered };
+        assert_eq!(diff_with(&to, &parse(r#"{"items": [{"id": 1, "n": 10}, {"id": 3, "n": 3}, {"id": 4, "n": 4}], "tags": ["d", "c", "a"]}"#).unwrap(), &unordered), JItem::Array(vec![]));
+    }
+
+    #[test]
+    fn render_unified_diff() {
+        let from = parse(r#"{"a": 1, "b": [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12], "c": "x"}"#).unwrap();
+        let to = parse(r#"{"a": 2, "b": [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13], "c": "x"}"#).unwrap();
+        let expected = "\
+@@ -1,5 +1,5 @@
+ {
+-  \"a\": 1,
++  \"a\": 2,
+   \"b\": [
+     1,
+     2,
+@@ -12,7 +12,8 @@
+     9,
+     10,
+     11,
+-    12
++    12,
++    13
+   ],
+   \"c\": \"x\"
+ }
+";
+        assert_eq!(render_diff(&from, &to, &RenderOptions::default()), expected);
+        assert_eq!(render_diff(&from, &from, &RenderOptions::default()), "");
+
+        let colored = render_diff(&JItem::Bool(true), &JItem::Bool(false), &RenderOptions { color: true, context: 0 });
+        assert_eq!(colored, "\x1b[36m@@ -1,1 +1,1 @@\x1b[0m\n\x1b[31m-true\x1b[0m\n\x1b[32m+false\x1b[0m\n");
+    }
+
+    #[test]
+    fn line_edits_are_minimal() {
+        let old = ["a", "b", "c", "a", "b", "b", "a"];
+        let new = ["c", "b", "a", "b", "a", "c"];
+        let edits = line_edits(&old, &new);
+        assert_eq!(edits.iter().filter(|edit| **edit != Edit::Same).count(), 5);
+        assert_eq!(positions(&edits), (7, 6));
+        assert_eq!(line_edits(&[], &["x"]), vec![Edit::Add]);
+        assert_eq!(line_edits(&["x"], &[]), vec![Edit::Remove]);
+        assert_eq!(line_edits(&[], &[]), vec![]);
+    }
+
+    #[test]
+    fn apply_rfc_operations() {
+        let mut item = parse(r#"{"foo": ["bar", "baz"], "qux": {"n": 1}}"#).unwrap();
+        let patch = parse(r#"[
+            {"op": "add", "path": "/foo/1", "value": "inserted"},
+            {"op": "test", "path": "/foo/2", "value": "baz"},
+            {"op": "move", "from": "/qux/n", "path": "/foo/-"},
+            {"op": "copy", "from": "/foo/0", "path": "/qux/m"},
+            {"op": "replace", "path": "/qux/m", "value": false}
+        ]"#).unwrap();
+        apply_patch(&mut item, &patch).unwrap();
+        assert_eq!(item, parse(r#"{"foo": ["bar", "inserted", "baz", 1], "qux": {"m": false}}"#).unwrap());
+    }
+
+    #[test]
+    fn apply_is_all_or_nothing() {
+        let mut item = parse(r#"{"a": 1}"#).unwrap();
+        let original = item.clone();
+        for (patch, error) in [
+            (r#"[{"op": "remove", "path": "/a"}, {"op": "test", "path": "/a", "value": 1}]"#, "json patch operation 1 failed: '/a' does not exist"),
+            (r#"[{"op": "add", "path": "/x/y", "value": 1}]"#, "json patch operation 0 failed: parent of '/x/y' does not exist"),
+            (r#"[{"op": "move", "from": "", "path": "/b"}]"#, "json patch operation 0 failed: cannot move '' into itself"),
+            (r#"[{"op": "frobnicate", "path": ""}]"#, "json patch operation 0 failed: unknown op 'frobnicate'"),
+            (r#"{"op": "add"}"#, "json patch must be an array of operations"),
+        ] {
+            assert_eq!(apply_patch(&mut item, &parse(patch).unwrap()), Err(error.to_string()));
+            assert_eq!(item, original);
+        }
+    }
+}
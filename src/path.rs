@@ -0,0 +1,328 @@
+use alloc::{format, string::{String, ToString}, vec, vec::Vec};
+
+use crate::{j_item::JItem, pointer::array_index, HashMap};
+
+/// One step of a path through a JItem tree: an object key or an array index.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// Formats segments as `a.b[0].c`, using `sep` between object keys.
+/// Keys containing `sep`, `[`, `]` or `\` have those characters escaped with a `\`.
+/// An empty first key that nothing but indices follow is written as `[]`, as it would
+/// otherwise read as the root or vanish before the index.
+pub fn format_path(segments: &[PathSegment], sep: &str) -> String {
+    let mut formatted = String::new();
+    for (n, segment) in segments.iter().enumerate() {
+        match segment {
+            PathSegment::Key(key) if n == 0 && key.is_empty() && !matches!(segments.get(1), Some(PathSegment::Key(_))) => formatted.push_str("[]"),
+            PathSegment::Key(key) => {
+                if n > 0 {
+                    formatted.push_str(sep);
+                }
+                push_escaped_key(&mut formatted, key, sep);
+            },
+            PathSegment::Index(index) => formatted.push_str(&format!("[{}]", index)),
+        }
+    }
+    return formatted;
+}
+
+pub(crate) fn push_escaped_key(formatted: &mut String, key: &str, sep: &str) {
+    let mut rest = key;
+    while let Some(c) = rest.chars().next() {
+        if !sep.is_empty() && rest.starts_with(sep) {
+            for sep_char in sep.chars() {
+                formatted.push('\\');
+                formatted.push(sep_char);
+            }
+            rest = &rest[sep.len()..];
+            continue;
+        }
+        if matches!(c, '[' | ']' | '\\') {
+            formatted.push('\\');
+        }
+        formatted.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+}
+
+/// Parses a path produced by `format_path` back into its segments.
+pub fn parse_path(path: &str, sep: &str) -> Result<Vec<PathSegment>, String> {
+    let mut segments = vec![];
+    if path.is_empty() {
+        return Ok(segments);
+    }
+
+    let mut rest = path;
+    // the key currently being built. there is none directly after an index like `a[0]`,
+    // where only a separator or another index may follow.
+    let mut pending_key = Some(String::new());
+    while let Some(c) = rest.chars().next() {
+        if !sep.is_empty() && rest.starts_with(sep) {
+            if let Some(key) = pending_key {
+                segments.push(PathSegment::Key(key));
+            }
+            pending_key = Some(String::new());
+            rest = &rest[sep.len()..];
+            continue;
+        }
+        if c == '[' {
+            if let Some(key) = pending_key.take() {
+                // a path may start with an index when the root is an array
+                if !(key.is_empty() && rest.len() == path.len()) {
+                    segments.push(PathSegment::Key(key));
+                }
+            }
+            let Some(end) = rest.find(']') else {
+                return Err(format!("unterminated index in path '{}'", path));
+            };
+            let index = &rest[1..end];
+            if index.is_empty() && rest.len() == path.len() {
+                segments.push(PathSegment::Key(String::new()));
+                rest = &rest[end + 1..];
+                continue;
+            }
+            let Ok(index) = index.parse() else {
+                return Err(format!("invalid array index '{}' in path '{}'", index, path));
+            };
+            segments.push(PathSegment::Index(index));
+            rest = &rest[end + 1..];
+            continue;
+        }
+
+        let Some(key) = pending_key.as_mut() else {
+            return Err(format!("expected separator after index in path '{}'", path));
+        };
+        if c == '\\' {
+            let Some(escaped) = rest[1..].chars().next() else {
+                return Err(format!("dangling escape at end of path '{}'", path));
+            };
+            key.push(escaped);
+            rest = &rest[1 + escaped.len_utf8()..];
+        }
+        else {
+            key.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    if let Some(key) = pending_key {
+        segments.push(PathSegment::Key(key));
+    }
+    return Ok(segments);
+}
+
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+pub(crate) fn get_at<'a>(root: &'a JItem, segments: &[PathSegment]) -> Option<&'a JItem> {
+    let mut current = root;
+    for segment in segments {
+        current = match (segment, current) {
+            (PathSegment::Key(key), JItem::Object(hmap)) => hmap.get(key)?,
+            (PathSegment::Index(index), JItem::Array(elements)) => elements.get(*index)?,
+            _ => return None,
+        };
+    }
+    return Some(current);
+}
+
+/// How far past the end of an array `insert_at` extends it with nulls. Paths often come
+/// from untrusted input, and an index further out would only ask for a huge allocation.
+pub(crate) const MAX_ARRAY_GAP: usize = 1024;
+
+/// Sets `value` at `segments` below `root`, turning nulls into the objects or arrays the
+/// path requires and extending arrays with nulls so the index exists. An index more
+/// than `MAX_ARRAY_GAP` past the end of its array is an error.
+pub(crate) fn insert_at(root: &mut JItem, segments: &[PathSegment], value: JItem) -> Result<(), String> {
+    let mut current = root;
+    for segment in segments {
+        current = match segment {
+            PathSegment::Key(key) => {
+                if *current == JItem::Null {
+                    *current = JItem::Object(HashMap::new());
+                }
+                let JItem::Object(hmap) = current else {
+                    return Err(format!("cannot set key '{}' on a non-object value", key));
+                };
+                hmap.entry(key.to_string()).or_insert(JItem::Null)
+            },
+            PathSegment::Index(index) => {
+                if *current == JItem::Null {
+                    *current = JItem::Array(vec![]);
+                }
+                let JItem::Array(elements) = current else {
+                    return Err(format!("cannot set index {} on a non-array value", index));
+                };
+                if *index > elements.len().saturating_add(MAX_ARRAY_GAP) {
+                    return Err(format!("index {} is too far past the end of an array of length {}", index, elements.len()));
+                }
+                if elements.len() <= *index {
+                    elements.resize_with(index + 1, || JItem::Null);
+                }
+                &mut elements[*index]
+            },
+        };
+    }
+    *current = value;
+    return Ok(());
+}
+
+impl JItem {
+    /// Looks up a path like `servers.0.host` or `servers[0].host`, a simpler alternative
+    /// to a JSON Pointer. A key of digits indexes into an array as well as naming an
+    /// object member. A `.`, `[`, `]` or `\` in a key is escaped with a `\`, as
+    /// `format_path` writes it. The empty path is the value itself, and a malformed path
+    /// finds nothing.
+    pub fn path(&self, path: &str) -> Option<&JItem> {
+        let mut current = self;
+        for segment in parse_path(path, ".").ok()? {
+            current = match (segment, current) {
+                (PathSegment::Key(key), JItem::Object(hmap)) => hmap.get(&key)?,
+                (PathSegment::Key(key), JItem::Array(elements)) => elements.get(array_index(&key)?)?,
+                (PathSegment::Index(index), JItem::Array(elements)) => elements.get(index)?,
+                _ => return None,
+            };
+        }
+        return Some(current);
+    }
+
+    pub fn path_mut(&mut self, path: &str) -> Option<&mut JItem> {
+        let mut current = self;
+        for segment in parse_path(path, ".").ok()? {
+            current = match (segment, current) {
+                (PathSegment::Key(key), JItem::Object(hmap)) => hmap.get_mut(&key)?,
+                (PathSegment::Key(key), JItem::Array(elements)) => elements.get_mut(array_index(&key)?)?,
+                (PathSegment::Index(index), JItem::Array(elements)) => elements.get_mut(index)?,
+                _ => return None,
+            };
+        }
+        return Some(current);
+    }
+
+    /// Sets `value` at a path like `a.b[2].c`, creating missing objects and arrays and
+    /// extending arrays with nulls as needed. Null values along the path are replaced by
+    /// the container the path requires; any other scalar in the way is an error, as is an
+    /// index more than 1024 past the end of its array.
+    pub fn set_path(&mut self, path: &str, value: JItem) -> Result<(), String> {
+        let segments = parse_path(path, ".")?;
+        return insert_at(self, &segments, value);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn format_path_keys_and_indices() {
+        let segments = vec![PathSegment::Key("a".to_string()), PathSegment::Key("b".to_string()), PathSegment::Index(0), PathSegment::Key("c".to_string())];
+        assert_eq!(format_path(&segments, "."), "a.b[0].c");
+    }
+
+    #[test]
+    fn format_path_root_index() {
+        let segments = vec![PathSegment::Index(2), PathSegment::Key("a".to_string())];
+        assert_eq!(format_path(&segments, "."), "[2].a");
+    }
+
+    #[test]
+    fn format_path_leading_empty_key() {
+        assert_eq!(format_path(&[PathSegment::Key(String::new())], "."), "[]");
+        assert_eq!(format_path(&[PathSegment::Key(String::new()), PathSegment::Index(0)], "."), "[][0]");
+        assert_eq!(format_path(&[PathSegment::Key(String::new()), PathSegment::Key(String::new())], "."), ".");
+        assert_eq!(parse_path("[][0]", "."), Ok(vec![PathSegment::Key(String::new()), PathSegment::Index(0)]));
+        assert!(parse_path("a[]", ".").is_err());
+    }
+
+    #[test]
+    fn format_path_escapes_separator() {
+        let segments = vec![PathSegment::Key("a.b".to_string()), PathSegment::Key("c[0]".to_string())];
+        assert_eq!(format_path(&segments, "."), r"a\.b.c\[0\]");
+    }
+
+    #[test]
+    fn parse_path_round_trip() {
+        let segments = vec![PathSegment::Index(1), PathSegment::Key("a__b".to_string()), PathSegment::Index(0), PathSegment::Index(3), PathSegment::Key("".to_string())];
+        let formatted = format_path(&segments, "__");
+        assert_eq!(parse_path(&formatted, "__"), Ok(segments));
+    }
+
+    #[test]
+    fn parse_path_empty() {
+        assert_eq!(parse_path("", "."), Ok(vec![]));
+    }
+
+    #[test]
+    fn parse_path_bad_index() {
+        assert_eq!(parse_path("a[x]", "."), Err("invalid array index 'x' in path 'a[x]'".to_string()));
+    }
+
+    #[test]
+    fn parse_path_text_after_index() {
+        assert_eq!(parse_path("a[0]b", "."), Err("expected separator after index in path 'a[0]b'".to_string()));
+    }
+
+    #[test]
+    fn insert_at_creates_intermediates() {
+        let mut root = JItem::Null;
+        let segments = parse_path("a[1].b", ".").unwrap();
+        insert_at(&mut root, &segments, JItem::Bool(true)).unwrap();
+        let mut inner = HashMap::new();
+        inner.insert("b".to_string(), JItem::Bool(true));
+        let mut expected = HashMap::new();
+        expected.insert("a".to_string(), JItem::Array(vec![JItem::Null, JItem::Object(inner)]));
+        assert_eq!(root, JItem::Object(expected));
+    }
+
+    #[test]
+    fn set_path_builds_structure() {
+        let mut item = JItem::Object(HashMap::new());
+        item.set_path("a.b[2].c", JItem::Number(1.)).unwrap();
+        item.set_path("a.b[0]", JItem::Bool(true)).unwrap();
+        item.set_path("a.d", JItem::String("x".to_string())).unwrap();
+        assert_eq!(item, crate::parse(r#"{"a": {"b": [true, null, {"c": 1}], "d": "x"}}"#).unwrap());
+    }
+
+    #[test]
+    fn set_path_overwrites_existing() {
+        let mut item = crate::parse(r#"{"a": {"b": [1, 2]}}"#).unwrap();
+        item.set_path("a.b", JItem::Null).unwrap();
+        assert_eq!(item, crate::parse(r#"{"a": {"b": null}}"#).unwrap());
+    }
+
+    #[test]
+    fn path_lookup() {
+        let mut item = crate::parse(r#"{"servers": [{"host": "a.example.com", "tags": ["x"]}, {"host": "b"}], "a.b": {"c[0]": 1}, "0": "zero"}"#).unwrap();
+        assert_eq!(item.path("servers.0.host"), Some(&JItem::String("a.example.com".to_string())));
+        assert_eq!(item.path("servers[1].host"), Some(&JItem::String("b".to_string())));
+        assert_eq!(item.path("servers.0.tags.0"), Some(&JItem::String("x".to_string())));
+        assert_eq!(item.path(r"a\.b.c\[0\]"), Some(&JItem::Number(1.0)));
+        assert_eq!(item.path("0"), Some(&JItem::String("zero".to_string())));
+        assert_eq!(item.path(""), Some(&item));
+        for missing in ["servers.2", "servers.01", "servers.host", "a.b", "servers.0.host.x", "servers[x]"] {
+            assert_eq!(item.path(missing), None, "{}", missing);
+        }
+        *item.path_mut("servers.1.host").unwrap() = JItem::Null;
+        assert_eq!(item.pointer("/servers/1/host"), Some(&JItem::Null));
+        assert!(item.path_mut("servers.5").is_none());
+    }
+
+    #[test]
+    fn set_path_through_scalar_fails() {
+        let mut item = crate::parse(r#"{"a": 1}"#).unwrap();
+        assert_eq!(item.set_path("a.b", JItem::Null), Err("cannot set key 'b' on a non-object value".to_string()));
+        assert_eq!(item.set_path("a[0]", JItem::Null), Err("cannot set index 0 on a non-array value".to_string()));
+    }
+
+    #[test]
+    fn set_path_index_too_far() {
+        let mut item = JItem::Null;
+        assert_eq!(item.set_path("a[18446744073709551615]", JItem::Null), Err("index 18446744073709551615 is too far past the end of an array of length 0".to_string()));
+        assert_eq!(item.set_path("a[4000000000]", JItem::Null), Err("index 4000000000 is too far past the end of an array of length 0".to_string()));
+        item.set_path("a[1024]", JItem::Bool(true)).unwrap();
+        assert_eq!(item.set_path("a[2050]", JItem::Null), Err("index 2050 is too far past the end of an array of length 1025".to_string()));
+        item.set_path("a[2049]", JItem::Bool(true)).unwrap();
+        assert_eq!(item.path("a[2049]"), Some(&JItem::Bool(true)));
+    }
+}
@@ -0,0 +1,461 @@
+use alloc::{format, string::String, vec, vec::Vec};
+
+use crate::j_item::{sorted_members, JItem};
+
+/// Splits an RFC 6901 JSON Pointer like `/a/b~1c/0` into its unescaped reference tokens.
+/// The empty pointer refers to the whole document.
+pub(crate) fn parse_pointer(pointer: &str) -> Result<Vec<String>, String> {
+    if pointer.is_empty() {
+        return Ok(vec![]);
+    }
+    let Some(rest) = pointer.strip_prefix('/') else {
+        return Err(format!("json pointer '{}' must be empty or start with '/'", pointer));
+    };
+    return rest.split('/').map(|token| unescape_token(token, pointer)).collect();
+}
+
+fn unescape_token(token: &str, pointer: &str) -> Result<String, String> {
+    let mut unescaped = String::with_capacity(token.len());
+    let mut chars = token.chars();
+    while let Some(c) = chars.next() {
+        if c != '~' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('0') => unescaped.push('~'),
+            Some('1') => unescaped.push('/'),
+            _ => return Err(format!("invalid '~' escape in json pointer '{}'", pointer)),
+        }
+    }
+    return Ok(unescaped);
+}
+
+/// Escapes an object key for use as a reference token, the reverse of `unescape_token`.
+pub(crate) fn escape_token(key: &str) -> String {
+    return key.replace('~', "~0").replace('/', "~1");
+}
+
+/// Interprets a reference token as an array index. RFC 6901 forbids leading zeros.
+pub(crate) fn array_index(token: &str) -> Option<usize> {
+    if token.is_empty() || (token.len() > 1 && token.starts_with('0')) || !token.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    return token.parse().ok();
+}
+
+pub(crate) fn resolve<'a>(item: &'a JItem, tokens: &[String]) -> Option<&'a JItem> {
+    let mut current = item;
+    for token in tokens {
+        current = match current {
+            JItem::Object(hmap) => hmap.get(token)?,
+            JItem::Array(elements) => elements.get(array_index(token)?)?,
+            _ => return None,
+        };
+    }
+    return Some(current);
+}
+
+pub(crate) fn resolve_mut<'a>(item: &'a mut JItem, tokens: &[String]) -> Option<&'a mut JItem> {
+    let mut current = item;
+    for token in tokens {
+        current = match current {
+            JItem::Object(hmap) => hmap.get_mut(token)?,
+            JItem::Array(elements) => elements.get_mut(array_index(token)?)?,
+            _ => return None,
+        };
+    }
+    return Some(current);
+}
+
+/// Bounds for `JItem::clone_subtree`. `None` leaves that part unlimited.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CloneLimits {
+    /// Deepest container nesting, counted as `Stats::max_depth` does: 0 for a scalar, 1
+    /// for `[]`.
+    pub max_depth: Option<usize>,
+    /// Values in the subtree, counting containers and the subtree itself.
+    pub max_values: Option<usize>,
+    /// Total UTF-8 length of string values and object keys.
+    pub max_bytes: Option<usize>,
+}
+
+/// Running totals while checking a subtree against `CloneLimits`.
+#[derive(Default)]
+struct Size {
+    values: usize,
+    bytes: usize,
+}
+
+/// A JSON Pointer split and unescaped once, for resolving against many documents
+/// without parsing the pointer text each time.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct JsonPointer {
+    tokens: Vec<Reference>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Reference {
+    key: String,
+    /// The token read as an array index, worked out up front.
+    index: Option<usize>,
+}
+
+impl JsonPointer {
+    pub fn parse(pointer: &str) -> Result<Self, String> {
+        return Ok(JsonPointer::from_tokens(parse_pointer(pointer)?));
+    }
+
+    /// A pointer from reference tokens that are already unescaped, such as object keys.
+    pub fn from_tokens<T: Into<String>>(tokens: impl IntoIterator<Item = T>) -> Self {
+        let tokens = tokens
+            .into_iter()
+            .map(|token| {
+                let key = token.into();
+                let index = array_index(&key);
+                Reference { key, index }
+            })
+            .collect();
+        return JsonPointer { tokens };
+    }
+
+    /// The unescaped reference tokens.
+    pub fn tokens(&self) -> impl Iterator<Item = &str> {
+        return self.tokens.iter().map(|token| token.key.as_str());
+    }
+
+    /// Whether this is the empty pointer, which refers to the whole document.
+    pub fn is_root(&self) -> bool {
+        return self.tokens.is_empty();
+    }
+
+    pub fn resolve<'a>(&self, item: &'a JItem) -> Option<&'a JItem> {
+        let mut current = item;
+        for token in &self.tokens {
+            current = match current {
+                JItem::Object(hmap) => hmap.get(&token.key)?,
+                JItem::Array(elements) => elements.get(token.index?)?,
+                _ => return None,
+            };
+        }
+        return Some(current);
+    }
+
+    pub fn resolve_mut<'a>(&self, item: &'a mut JItem) -> Option<&'a mut JItem> {
+        return JsonPointer::resolve_tokens_mut(&self.tokens, item);
+    }
+
+    fn resolve_tokens_mut<'a>(tokens: &[Reference], item: &'a mut JItem) -> Option<&'a mut JItem> {
+        let mut current = item;
+        for token in tokens {
+            current = match current {
+                JItem::Object(hmap) => hmap.get_mut(&token.key)?,
+                JItem::Array(elements) => elements.get_mut(token.index?)?,
+                _ => return None,
+            };
+        }
+        return Some(current);
+    }
+
+    /// Sets the value this pointer refers to in `item`, returning the value it replaced.
+    /// The parent must already exist. On arrays the index may be one past the end, or
+    /// `-`, to append.
+    pub fn set(&self, item: &mut JItem, value: JItem) -> Result<Option<JItem>, String> {
+        let Some((last, parents)) = self.tokens.split_last() else {
+            return Ok(Some(core::mem::replace(item, value)));
+        };
+        let Some(parent) = JsonPointer::resolve_tokens_mut(parents, item) else {
+            return Err(format!("parent of json pointer '{}' does not exist", self));
+        };
+        return match parent {
+            JItem::Object(hmap) => Ok(hmap.insert(last.key.clone(), value)),
+            JItem::Array(elements) => {
+                let index = if last.key == "-" { Some(elements.len()) } else { last.index };
+                match index {
+                    Some(index) if index < elements.len() => Ok(Some(core::mem::replace(&mut elements[index], value))),
+                    Some(index) if index == elements.len() => {
+                        elements.push(value);
+                        Ok(None)
+                    },
+                    _ => Err(format!("array index '{}' out of bounds in json pointer '{}'", last.key, self)),
+                }
+            },
+            _ => Err(format!("cannot set a member of a scalar value at json pointer '{}'", self)),
+        };
+    }
+
+    /// Removes and returns the value this pointer refers to in `item`. Later array
+    /// elements shift down to fill the gap. The root itself cannot be removed.
+    pub fn remove(&self, item: &mut JItem) -> Option<JItem> {
+        let (last, parents) = self.tokens.split_last()?;
+        return match JsonPointer::resolve_tokens_mut(parents, item)? {
+            JItem::Object(hmap) => hmap.remove(&last.key),
+            JItem::Array(elements) => {
+                let index = last.index?;
+                if index >= elements.len() {
+                    return None;
+                }
+                Some(elements.remove(index))
+            },
+            _ => None,
+        };
+    }
+}
+
+/// Walks `item` at nesting `depth`, stopping at the first limit it passes. Members are
+/// visited in key order, so which limit is reported does not depend on hash order.
+fn check_size(item: &JItem, depth: usize, limits: &CloneLimits, size: &mut Size) -> Result<(), String> {
+    size.values += 1;
+    if let Some(max) = limits.max_values.filter(|max| size.values > *max) {
+        return Err(format!("has more than {} values", max));
+    }
+    let bytes = match item {
+        JItem::String(s) => s.len(),
+        JItem::Object(hmap) => hmap.keys().map(String::len).sum(),
+        _ => 0,
+    };
+    size.bytes += bytes;
+    if let Some(max) = limits.max_bytes.filter(|max| size.bytes > *max) {
+        return Err(format!("has more than {} bytes of strings and keys", max));
+    }
+    let children: Vec<&JItem> = match item {
+        JItem::Object(hmap) => sorted_members(hmap).into_iter().map(|(_, value)| value).collect(),
+        JItem::Array(elements) => elements.iter().collect(),
+        _ => return Ok(()),
+    };
+    if let Some(max) = limits.max_depth.filter(|max| depth + 1 > *max) {
+        return Err(format!("is nested more than {} deep", max));
+    }
+    for child in children {
+        check_size(child, depth + 1, limits, size)?;
+    }
+    return Ok(());
+}
+
+/// The pointer text, with `~` and `/` in tokens escaped.
+impl core::fmt::Display for JsonPointer {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for token in &self.tokens {
+            write!(f, "/{}", escape_token(&token.key))?;
+        }
+        return Ok(());
+    }
+}
+
+impl core::str::FromStr for JsonPointer {
+    type Err = String;
+
+    fn from_str(pointer: &str) -> Result<Self, String> {
+        return JsonPointer::parse(pointer);
+    }
+}
+
+impl JItem {
+    /// Looks up the value referenced by an RFC 6901 JSON Pointer. To look up the same
+    /// pointer many times, parse it once into a `JsonPointer`.
+    pub fn pointer(&self, pointer: &str) -> Option<&JItem> {
+        return JsonPointer::parse(pointer).ok()?.resolve(self);
+    }
+
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut JItem> {
+        return JsonPointer::parse(pointer).ok()?.resolve_mut(self);
+    }
+
+    /// Sets the value at `pointer`, returning the value it replaced. See `JsonPointer::set`.
+    pub fn set_pointer(&mut self, pointer: &str, value: JItem) -> Result<Option<JItem>, String> {
+        return JsonPointer::parse(pointer)?.set(self, value);
+    }
+
+    /// Removes and returns the value at `pointer`. See `JsonPointer::remove`.
+    pub fn remove_pointer(&mut self, pointer: &str) -> Option<JItem> {
+        return JsonPointer::parse(pointer).ok()?.remove(self);
+    }
+
+    /// Clones only the value at `pointer`, first checking it against `limits` so that an
+    /// oversized fragment of an untrusted document is refused before anything is copied.
+    pub fn clone_subtree(&self, pointer: &str, limits: &CloneLimits) -> Result<JItem, String> {
+        let Some(subtree) = self.pointer(pointer) else {
+            JsonPointer::parse(pointer)?;
+            return Err(format!("nothing at json pointer '{}'", pointer));
+        };
+        if let Err(limit) = check_size(subtree, 0, limits, &mut Size::default()) {
+            return Err(format!("subtree at json pointer '{}' {}", pointer, limit));
+        }
+        return Ok(subtree.clone());
+    }
+
+    /// Resolves many pointers at once, in the order given. Pointers that share a prefix
+    /// share the walk down to it, so each value is visited at most once however many
+    /// pointers pass through it.
+    pub fn extract(&self, pointers: &[JsonPointer]) -> Vec<Option<&JItem>> {
+        let mut found = vec![None; pointers.len()];
+        // sorting puts pointers with a common prefix next to each other, shortest first
+        let mut order: Vec<usize> = (0..pointers.len()).collect();
+        order.sort_by(|a, b| pointers[*a].tokens().cmp(pointers[*b].tokens()));
+        extract_group(self, pointers, &order, 0, &mut found);
+        return found;
+    }
+}
+
+/// Resolves the pointers in `group`, which all lead to `item` through their first
+/// `depth` tokens.
+fn extract_group<'a>(item: &'a JItem, pointers: &[JsonPointer], group: &[usize], depth: usize, found: &mut [Option<&'a JItem>]) {
+    let ending = group.iter().take_while(|n| pointers[**n].tokens.len() == depth).count();
+    for n in &group[..ending] {
+        found[*n] = Some(item);
+    }
+    let mut rest = &group[ending..];
+    while let Some(first) = rest.first() {
+        let token = &pointers[*first].tokens[depth];
+        let same = rest.iter().take_while(|n| pointers[**n].tokens[depth].key == token.key).count();
+        let child = match item {
+            JItem::Object(hmap) => hmap.get(&token.key),
+            JItem::Array(elements) => token.index.and_then(|index| elements.get(index)),
+            _ => None,
+        };
+        if let Some(child) = child {
+            extract_group(child, pointers, &rest[..same], depth + 1, found);
+        }
+        rest = &rest[same..];
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn parse_pointer_escapes() {
+        assert_eq!(parse_pointer("/a~1b/c~0d/"), Ok(vec!["a/b".to_string(), "c~d".to_string(), "".to_string()]));
+    }
+
+    #[test]
+    fn parse_pointer_invalid() {
+        assert!(parse_pointer("a/b").is_err());
+        assert!(parse_pointer("/a~2").is_err());
+    }
+
+    #[test]
+    fn pointer_lookup() {
+        let item = parse(r#"{"a": {"b": [10, {"c/d": true}]}, "": 1}"#).unwrap();
+        assert_eq!(item.pointer(""), Some(&item));
+        assert_eq!(item.pointer("/a/b/0"), Some(&JItem::Number(10.)));
+        assert_eq!(item.pointer("/a/b/1/c~1d"), Some(&JItem::Bool(true)));
+        assert_eq!(item.pointer("/"), Some(&JItem::Number(1.)));
+    }
+
+    #[test]
+    fn pointer_lookup_missing() {
+        let item = parse(r#"{"a": [1, 2]}"#).unwrap();
+        assert_eq!(item.pointer("/b"), None);
+        assert_eq!(item.pointer("/a/2"), None);
+        assert_eq!(item.pointer("/a/01"), None);
+        assert_eq!(item.pointer("/a/0/x"), None);
+    }
+
+    #[test]
+    fn pointer_mut_modifies() {
+        let mut item = parse(r#"{"a": [1, 2]}"#).unwrap();
+        *item.pointer_mut("/a/1").unwrap() = JItem::Null;
+        assert_eq!(item, parse(r#"{"a": [1, null]}"#).unwrap());
+    }
+
+    #[test]
+    fn set_pointer_replaces_and_inserts() {
+        let mut item = parse(r#"{"a": [1, 2], "b": {}}"#).unwrap();
+        assert_eq!(item.set_pointer("/a/0", JItem::Bool(true)), Ok(Some(JItem::Number(1.))));
+        assert_eq!(item.set_pointer("/a/-", JItem::Null), Ok(None));
+        assert_eq!(item.set_pointer("/b/c", JItem::Number(3.)), Ok(None));
+        assert_eq!(item, parse(r#"{"a": [true, 2, null], "b": {"c": 3}}"#).unwrap());
+    }
+
+    #[test]
+    fn set_pointer_errors() {
+        let mut item = parse(r#"{"a": [1], "s": "x"}"#).unwrap();
+        assert!(item.set_pointer("/missing/b", JItem::Null).is_err());
+        assert!(item.set_pointer("/a/5", JItem::Null).is_err());
+        assert!(item.set_pointer("/s/x", JItem::Null).is_err());
+    }
+
+    #[test]
+    fn set_pointer_root() {
+        let mut item = JItem::Null;
+        assert_eq!(item.set_pointer("", JItem::Bool(false)), Ok(Some(JItem::Null)));
+        assert_eq!(item, JItem::Bool(false));
+    }
+
+    #[test]
+    fn remove_pointer_object_member() {
+        let mut item = parse(r#"{"a": {"b": 1, "c": 2}}"#).unwrap();
+        assert_eq!(item.remove_pointer("/a/b"), Some(JItem::Number(1.)));
+        assert_eq!(item, parse(r#"{"a": {"c": 2}}"#).unwrap());
+    }
+
+    #[test]
+    fn remove_pointer_array_element_shifts() {
+        let mut item = parse(r#"{"a": [0, 1, 2, [3]]}"#).unwrap();
+        assert_eq!(item.remove_pointer("/a/1"), Some(JItem::Number(1.)));
+        assert_eq!(item.remove_pointer("/a/2"), Some(parse("[3]").unwrap()));
+        assert_eq!(item, parse(r#"{"a": [0, 2]}"#).unwrap());
+    }
+
+    #[test]
+    fn compiled_pointer_reuse() {
+        let pointer = JsonPointer::parse("/a~1b/0").unwrap();
+        assert_eq!(pointer.tokens().collect::<Vec<_>>(), ["a/b", "0"]);
+        assert_eq!(pointer.to_string(), "/a~1b/0");
+        assert_eq!(JsonPointer::from_tokens(["a/b", "0"]), pointer);
+        assert_eq!("".parse::<JsonPointer>().map(|root| root.is_root()), Ok(true));
+
+        let mut documents = [parse(r#"{"a/b": [1]}"#).unwrap(), parse(r#"{"a/b": {"0": 2}}"#).unwrap(), parse("[]").unwrap()];
+        let found: Vec<_> = documents.iter().map(|item| pointer.resolve(item).cloned()).collect();
+        assert_eq!(found, [Some(JItem::Number(1.)), Some(JItem::Number(2.)), None]);
+
+        assert_eq!(pointer.set(&mut documents[0], JItem::Null), Ok(Some(JItem::Number(1.))));
+        assert_eq!(pointer.remove(&mut documents[1]), Some(JItem::Number(2.)));
+        assert_eq!(pointer.set(&mut documents[2], JItem::Null), Err("parent of json pointer '/a~1b/0' does not exist".to_string()));
+        *pointer.resolve_mut(&mut documents[0]).unwrap() = JItem::Bool(true);
+        assert_eq!(documents[0], parse(r#"{"a/b": [true]}"#).unwrap());
+        assert_eq!(documents[1], parse(r#"{"a/b": {}}"#).unwrap());
+    }
+
+    #[test]
+    fn extract_many() {
+        let item = parse(r#"{"user": {"name": "x", "tags": ["a", "b"]}, "id": 7, "": {"": 0}}"#).unwrap();
+        let pointers: Vec<JsonPointer> = ["/user/tags/1", "/id", "/user", "/missing/x", "/user/name", "", "/user/tags/9", "/id", "//"]
+            .iter()
+            .map(|pointer| JsonPointer::parse(pointer).unwrap())
+            .collect();
+        let expected: Vec<_> = pointers.iter().map(|pointer| pointer.resolve(&item)).collect();
+        assert_eq!(item.extract(&pointers), expected);
+        assert_eq!(expected[0], Some(&JItem::String("b".to_string())));
+        assert_eq!(expected[3], None);
+        assert_eq!(expected[8], Some(&JItem::Number(0.)));
+        assert!(item.extract(&[]).is_empty());
+    }
+
+    #[test]
+    fn remove_pointer_missing() {
+        let mut item = parse(r#"{"a": [0], "s": "x"}"#).unwrap();
+        assert_eq!(item.remove_pointer("/a/1"), None);
+        assert_eq!(item.remove_pointer("/a/-"), None);
+        assert_eq!(item.remove_pointer("/b"), None);
+        assert_eq!(item.remove_pointer("/s/0"), None);
+        assert_eq!(item.remove_pointer(""), None);
+        assert_eq!(item, parse(r#"{"a": [0], "s": "x"}"#).unwrap());
+    }
+
+    #[test]
+    fn clone_subtree_within_limits() {
+        let item = parse(r#"{"user": {"name": "ada", "roles": [["admin"]], "bio": "x"}, "other": 1}"#).unwrap();
+        let limits = CloneLimits { max_depth: Some(3), max_values: Some(6), max_bytes: Some(21) };
+        assert_eq!(item.clone_subtree("/user", &limits), parse(r#"{"name": "ada", "roles": [["admin"]], "bio": "x"}"#).map_err(String::from));
+        assert_eq!(item.clone_subtree("/user/name", &CloneLimits { max_depth: Some(0), ..CloneLimits::default() }), Ok(JItem::String("ada".to_string())));
+        assert_eq!(item.clone_subtree("/user", &CloneLimits { max_depth: Some(2), ..limits.clone() }), Err("subtree at json pointer '/user' is nested more than 2 deep".to_string()));
+        assert_eq!(item.clone_subtree("/user", &CloneLimits { max_values: Some(5), ..limits.clone() }), Err("subtree at json pointer '/user' has more than 5 values".to_string()));
+        assert_eq!(item.clone_subtree("", &limits), Err("subtree at json pointer '' has more than 21 bytes of strings and keys".to_string()));
+        assert_eq!(item.clone_subtree("/missing", &limits), Err("nothing at json pointer '/missing'".to_string()));
+        assert_eq!(item.clone_subtree("user", &limits), Err("json pointer 'user' must be empty or start with '/'".to_string()));
+    }
+}
@@ -0,0 +1,153 @@
+use alloc::{string::{String, ToString}, vec, vec::Vec};
+use core::ops::Range;
+
+use crate::{
+    error::JsonError,
+    events::{Event, Events},
+    j_item::JItem,
+    parse_with,
+    parser::ParseOptions,
+    pointer::JsonPointer,
+};
+
+/// An open object or array on the way to a wanted value.
+struct Frame {
+    start: usize,
+    is_array: bool,
+    /// Whether this container is itself wanted.
+    wanted: bool,
+    count: usize,
+    key: Option<String>,
+}
+
+/// Finds the source text of the values at `pointers`, checking everything else as
+/// `validate` does. Containers on the way to a pointer are walked through, and every
+/// other value is passed over with `Events::skip_value`.
+pub(crate) fn find_spans(s: &str, pointers: &[JsonPointer]) -> Result<Vec<Option<Range<usize>>>, JsonError> {
+    let wanted: Vec<Vec<&str>> = pointers.iter().map(|pointer| pointer.tokens().collect()).collect();
+    let mut spans = vec![None; pointers.len()];
+    let mut record = |path: &[String], span: Range<usize>| {
+        for (tokens, found) in wanted.iter().zip(spans.iter_mut()) {
+            if tokens.iter().eq(path.iter()) {
+                *found = Some(span.clone());
+            }
+        }
+    };
+    let mut events = Events::new(s);
+    let mut frames: Vec<Frame> = vec![];
+    let mut path: Vec<String> = vec![];
+    let mut at_value = true;
+    // whether the value at `path` is wanted, once `path` has a token for it
+    let mut pending: Option<bool> = None;
+    loop {
+        if at_value {
+            at_value = false;
+            if let Some(parent) = frames.last() {
+                path.push(parent.key.clone().unwrap_or_else(|| parent.count.to_string()));
+            }
+            let exact = wanted.iter().any(|tokens| tokens.iter().eq(path.iter()));
+            let on_the_way = wanted.iter().any(|tokens| tokens.len() > path.len() && tokens.iter().zip(&path).all(|(a, b)| a == b));
+            if on_the_way {
+                pending = Some(exact);
+            }
+            else if let Some(span) = events.skip_value()? {
+                if exact {
+                    record(&path, span);
+                }
+                at_value = finish(&mut path, &mut frames);
+            }
+            else if !frames.is_empty() {
+                path.pop();
+            }
+            continue;
+        }
+        let Some(event) = events.next() else {
+            return Ok(spans);
+        };
+        match event? {
+            Event::Key(token) => {
+                frames.last_mut().unwrap().key = Some(token.string_value().unwrap_or_default().into_owned());
+                at_value = true;
+            },
+            Event::Scalar(token) => {
+                if pending.take() == Some(true) {
+                    record(&path, token.span);
+                }
+                at_value = finish(&mut path, &mut frames);
+            },
+            Event::StartObject(span) => {
+                frames.push(Frame { start: span.start, is_array: false, wanted: pending.take() == Some(true), count: 0, key: None });
+            },
+            Event::StartArray(span) => {
+                frames.push(Frame { start: span.start, is_array: true, wanted: pending.take() == Some(true), count: 0, key: None });
+                at_value = true;
+            },
+            Event::EndObject(span) | Event::EndArray(span) => {
+                // an array can end where another element might have started
+                if pending.take().is_some() {
+                    path.pop();
+                }
+                let frame = frames.pop().unwrap();
+                if frame.wanted {
+                    record(&path, frame.start..span.end);
+                }
+                at_value = finish(&mut path, &mut frames);
+            },
+        }
+    }
+}
+
+/// Moves past a value that has ended, returning whether another element may follow.
+fn finish(path: &mut Vec<String>, frames: &mut [Frame]) -> bool {
+    let Some(parent) = frames.last_mut() else {
+        return false;
+    };
+    path.pop();
+    parent.count += 1;
+    parent.key = None;
+    return parent.is_array;
+}
+
+/// Builds only the values at `pointers`, parsing each as `parse_with` would.
+pub(crate) fn parse_pointers(s: &str, pointers: &[JsonPointer], options: &ParseOptions) -> Result<Vec<Option<JItem>>, JsonError> {
+    let spans = find_spans(s, pointers)?;
+    return spans.into_iter().map(|span| {
+        let Some(span) = span else {
+            return Ok(None);
+        };
+        let start = span.start;
+        return parse_with(&s[span], options).map(Some).map_err(|e| JsonError::new(e.kind().clone(), s, e.span().start + start..e.span().end + start));
+    }).collect();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{error::ErrorKind, parse, parse_pointers};
+
+    fn pointers(pointers: &[&str]) -> Vec<JsonPointer> {
+        return pointers.iter().map(|pointer| JsonPointer::parse(pointer).unwrap()).collect();
+    }
+
+    #[test]
+    fn builds_only_wanted_values() {
+        let input = r#"{"meta": {"id": 7, "tags": ["a", "b"]}, "rows": [[1, 2], {"big": [1, 2, 3]}], "skip": {"x": [true]}, "a/b": null}"#;
+        let wanted = pointers(&["/meta/id", "/rows/1", "/rows/1/big/2", "/meta/tags", "/missing", "/rows/5", "/a~1b", ""]);
+        let found = parse_pointers(input, &wanted, &ParseOptions::default()).unwrap();
+        let whole = parse(input).unwrap();
+        for (pointer, found) in wanted.iter().zip(found) {
+            assert_eq!(found.as_ref(), pointer.resolve(&whole), "{}", pointer);
+        }
+        assert_eq!(parse_pointers("[1, [2, 3]]", &pointers(&["/1/0", "/0"]), &ParseOptions::default()).unwrap(), [Some(JItem::Number(2.0)), Some(JItem::Number(1.0))]);
+    }
+
+    #[test]
+    fn skipped_values_are_still_checked() {
+        let error = parse_pointers(r#"{"a": 1, "b": [1 2]}"#, &pointers(&["/a"]), &ParseOptions::default()).unwrap_err();
+        assert_eq!(error.span(), 17..18);
+        let error = parse_pointers(r#"{"a": 1, "b": {"c": 1, "c": 2}}"#, &pointers(&["/b"]), &ParseOptions::default()).unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::DuplicateKey(_)));
+        assert_eq!((error.span(), error.line()), (23..26, 1));
+        assert!(parse_pointers(r#"{"a": 1, "b": {"c": 1, "c": 2}}"#, &pointers(&["/a"]), &ParseOptions::default()).is_ok());
+    }
+}
@@ -0,0 +1,428 @@
+//! Tracking where each value in a document came from, through merges and patches, for
+//! answering "which file set this?" when debugging layered configuration.
+//!
+//! A `Tracked` document keeps a tree of origins shaped like the value. A value set by
+//! one source as a whole has a single origin, and the values inside it are traced back
+//! through it, so tracking costs little until sources start to overlap.
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+use crate::{
+    j_item::JItem,
+    patch::{add, parse_op, remove, replace, test, Op},
+    path::{insert_at, parse_path, PathSegment},
+    pointer::{array_index, escape_token, parse_pointer, resolve, resolve_mut},
+    HashMap,
+};
+
+/// Where a value came from: the id of a source document, and a JSON Pointer to the value
+/// within that source.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Origin {
+    pub source: String,
+    pub pointer: String,
+}
+
+/// A document with the origin of every value recorded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tracked {
+    value: JItem,
+    origins: OriginNode,
+}
+
+/// The origin recorded for one value, if it differs from the one it inherits, and the
+/// nodes for its members or elements once any of those have been set separately.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct OriginNode {
+    origin: Option<Origin>,
+    children: Children,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+enum Children {
+    /// Everything inside the value shares its origin.
+    #[default]
+    Whole,
+    Object(HashMap<String, OriginNode>),
+    Array(Vec<OriginNode>),
+}
+
+impl OriginNode {
+    fn set_by(origin: Origin) -> Self {
+        return OriginNode { origin: Some(origin), children: Children::Whole };
+    }
+
+    /// Gives the node its own children, inheriting its origin, so that one of them can be
+    /// changed. `value` is the value the node stands for.
+    fn expand(&mut self, value: &JItem) {
+        if let Children::Whole = self.children {
+            self.children = match value {
+                JItem::Object(hmap) => Children::Object(hmap.keys().map(|key| (key.clone(), OriginNode::default())).collect()),
+                JItem::Array(elements) => Children::Array(vec![OriginNode::default(); elements.len()]),
+                _ => Children::Whole,
+            };
+        }
+    }
+
+    /// The node for the value at `tokens` under `value`, expanding nodes on the way.
+    /// Members and elements added to `value` since a node was expanded are given nodes
+    /// that inherit.
+    fn walk_mut(&mut self, value: &JItem, tokens: &[String]) -> Option<&mut OriginNode> {
+        let Some((token, rest)) = tokens.split_first() else {
+            return Some(self);
+        };
+        self.expand(value);
+        return match (&mut self.children, value) {
+            (Children::Object(nodes), JItem::Object(hmap)) => nodes.entry(token.clone()).or_default().walk_mut(hmap.get(token)?, rest),
+            (Children::Array(nodes), JItem::Array(elements)) => {
+                let index = array_index(token)?;
+                if nodes.len() < elements.len() {
+                    nodes.resize_with(elements.len(), OriginNode::default);
+                }
+                nodes.get_mut(index)?.walk_mut(elements.get(index)?, rest)
+            },
+            _ => None,
+        };
+    }
+}
+
+impl Tracked {
+    /// Starts tracking `value`, read whole from `source`.
+    pub fn new(value: JItem, source: &str) -> Self {
+        return Tracked { value, origins: OriginNode::set_by(Origin { source: source.to_string(), pointer: String::new() }) };
+    }
+
+    pub fn value(&self) -> &JItem {
+        return &self.value;
+    }
+
+    pub fn into_value(self) -> JItem {
+        return self.value;
+    }
+
+    /// Tracks `value` with no origin recorded for it, for documents built up entirely
+    /// from later merges and paths. Values no source has set have no origin.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn unsourced(value: JItem) -> Self {
+        return Tracked { value, origins: OriginNode::default() };
+    }
+
+    /// Where the value at `pointer` came from, or `None` if there is no value there.
+    pub fn origin_of(&self, pointer: &str) -> Option<Origin> {
+        let tokens = parse_pointer(pointer).ok()?;
+        resolve(&self.value, &tokens)?;
+        return self.origin_at(&tokens);
+    }
+
+    /// The source of the value at `pointer`, as `origin_of` gives it, without building
+    /// the pointer within that source.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn source_of(&self, pointer: &str) -> Option<&str> {
+        let tokens = parse_pointer(pointer).ok()?;
+        resolve(&self.value, &tokens)?;
+        return self.nearest_origin(&tokens).map(|(origin, _)| origin.source.as_str());
+    }
+
+    fn origin_at(&self, tokens: &[String]) -> Option<Origin> {
+        let (origin, depth) = self.nearest_origin(tokens)?;
+        let mut pointer = origin.pointer.clone();
+        for token in &tokens[depth..] {
+            pointer.push('/');
+            pointer.push_str(&escape_token(token));
+        }
+        return Some(Origin { source: origin.source.clone(), pointer });
+    }
+
+    /// The origin recorded nearest above the value at `tokens`, or for it, and how many
+    /// tokens deep it was recorded.
+    fn nearest_origin(&self, tokens: &[String]) -> Option<(&Origin, usize)> {
+        let mut node = &self.origins;
+        let mut found = node.origin.as_ref().map(|origin| (origin, 0));
+        for (depth, token) in tokens.iter().enumerate() {
+            let child = match &node.children {
+                Children::Object(nodes) => nodes.get(token),
+                Children::Array(nodes) => array_index(token).and_then(|index| nodes.get(index)),
+                Children::Whole => None,
+            };
+            let Some(child) = child else {
+                break;
+            };
+            node = child;
+            if let Some(origin) = &node.origin {
+                found = Some((origin, depth + 1));
+            }
+        }
+        return found;
+    }
+
+    /// Every value with an origin of its own, by JSON Pointer in pointer order. Values
+    /// not listed came from inside the nearest listed value above them.
+    pub fn origins(&self) -> Vec<(String, &Origin)> {
+        let mut origins = vec![];
+        collect_origins(&self.origins, &mut String::new(), &mut origins);
+        origins.sort_by(|a, b| a.0.cmp(&b.0));
+        return origins;
+    }
+
+    /// Merges `layer` over the value, as `config::load` merges its sources: objects
+    /// member by member, and anything else replacing what was there. Each value `layer`
+    /// sets is recorded as coming from the same place in `source`. `config::load` is
+    /// built on this.
+    pub fn merge(&mut self, layer: JItem, source: &str) {
+        self.merge_at(&mut vec![], layer, source);
+    }
+
+    fn merge_at(&mut self, tokens: &mut Vec<String>, layer: JItem, source: &str) {
+        let into_object = matches!(resolve(&self.value, tokens), Some(JItem::Object(_)));
+        let JItem::Object(members) = layer else {
+            self.set_at(tokens, layer, source);
+            return;
+        };
+        if !into_object {
+            self.set_at(tokens, JItem::Object(members), source);
+            return;
+        }
+        for (key, value) in members {
+            tokens.push(key);
+            self.merge_at(tokens, value, source);
+            tokens.pop();
+        }
+    }
+
+    /// Sets the value at `tokens`, the root or a member of an object, as coming from the
+    /// same place in `source`.
+    fn set_at(&mut self, tokens: &[String], value: JItem, source: &str) {
+        let pointer = tokens.iter().map(|token| format!("/{}", escape_token(token))).collect();
+        match tokens.split_last() {
+            None => self.value = value,
+            Some((key, parents)) => {
+                if let Some(JItem::Object(hmap)) = resolve_mut(&mut self.value, parents) {
+                    hmap.insert(key.clone(), value);
+                }
+            },
+        }
+        self.set_node(tokens, OriginNode::set_by(Origin { source: source.to_string(), pointer }));
+    }
+
+    /// Sets `value` at a path like `a.b[0]`, as `JItem::set_path` does, recording
+    /// `origin` for it. Containers the path creates inherit their parent's origin.
+    pub fn set_path(&mut self, path: &str, value: JItem, origin: Origin) -> Result<(), String> {
+        return self.set_segments(&parse_path(path, ".")?, value, origin);
+    }
+
+    /// Like `set_path`, with the path already split into segments.
+    pub(crate) fn set_segments(&mut self, segments: &[PathSegment], value: JItem, origin: Origin) -> Result<(), String> {
+        insert_at(&mut self.value, segments, value)?;
+        let tokens: Vec<String> = segments.iter().map(|segment| match segment {
+            PathSegment::Key(key) => key.clone(),
+            PathSegment::Index(index) => index.to_string(),
+        }).collect();
+        self.set_node(&tokens, OriginNode::set_by(origin));
+        return Ok(());
+    }
+
+    /// Applies an RFC 6902 JSON Patch as `patch::apply_patch` does, recording each value
+    /// an `add` or `replace` sets as coming from that operation's `value` in `source`.
+    /// Moved and copied values keep their origins.
+    pub fn apply_patch(&mut self, patch: &JItem, source: &str) -> Result<(), String> {
+        let JItem::Array(ops) = patch else {
+            return Err("json patch must be an array of operations".to_string());
+        };
+        let mut patched = self.clone();
+        for (n, op) in ops.iter().enumerate() {
+            let origin = Origin { source: source.to_string(), pointer: format!("/{}/value", n) };
+            patched.apply_op(op, origin).map_err(|e| format!("json patch operation {} failed: {}", n, e))?;
+        }
+        *self = patched;
+        return Ok(());
+    }
+
+    fn apply_op(&mut self, op: &JItem, origin: Origin) -> Result<(), String> {
+        match parse_op(op)? {
+            Op::Add { path, value } => {
+                self.attach(&parse_pointer(path)?, OriginNode::set_by(origin));
+                add(&mut self.value, path, value.clone())?;
+            },
+            Op::Remove { path } => {
+                self.detach(&parse_pointer(path)?);
+                remove(&mut self.value, path)?;
+            },
+            Op::Replace { path, value } => {
+                replace(&mut self.value, path, value.clone())?;
+                self.set_node(&parse_pointer(path)?, OriginNode::set_by(origin));
+            },
+            Op::Move { from, path } => {
+                let node = self.detach(&parse_pointer(from)?);
+                let moved = remove(&mut self.value, from)?;
+                self.attach(&parse_pointer(path)?, node);
+                add(&mut self.value, path, moved)?;
+            },
+            Op::Copy { from, path } => {
+                let tokens = parse_pointer(from)?;
+                let copied = resolve(&self.value, &tokens).ok_or_else(|| format!("'{}' does not exist", from))?.clone();
+                let node = self.node_with_origin(&tokens);
+                self.attach(&parse_pointer(path)?, node);
+                add(&mut self.value, path, copied)?;
+            },
+            Op::Test { path, value } => test(&self.value, path, value)?,
+        }
+        return Ok(());
+    }
+
+    /// A copy of the node at `tokens`, holding the origin it would otherwise inherit, so
+    /// it can be placed elsewhere.
+    fn node_with_origin(&mut self, tokens: &[String]) -> OriginNode {
+        let origin = self.origin_at(tokens);
+        let mut node = self.origins.walk_mut(&self.value, tokens).cloned().unwrap_or_default();
+        node.origin = origin;
+        return node;
+    }
+
+    /// Replaces the node for the existing value at `tokens`.
+    fn set_node(&mut self, tokens: &[String], node: OriginNode) {
+        if let Some(target) = self.origins.walk_mut(&self.value, tokens) {
+            *target = node;
+        }
+    }
+
+    /// Inserts `node` where `add` is about to insert a value, shifting later elements of
+    /// an array along with their values.
+    fn attach(&mut self, tokens: &[String], node: OriginNode) {
+        let Some((last, parents)) = tokens.split_last() else {
+            self.origins = node;
+            return;
+        };
+        let Some(parent_value) = resolve(&self.value, parents) else {
+            return;
+        };
+        let parent_value = parent_value.clone();
+        let Some(parent) = self.origins.walk_mut(&self.value, parents) else {
+            return;
+        };
+        parent.expand(&parent_value);
+        match &mut parent.children {
+            Children::Object(nodes) => {
+                nodes.insert(last.clone(), node);
+            },
+            Children::Array(nodes) => {
+                let index = if last == "-" { Some(nodes.len()) } else { array_index(last) };
+                if let Some(index) = index.filter(|index| *index <= nodes.len()) {
+                    nodes.insert(index, node);
+                }
+            },
+            Children::Whole => {},
+        }
+    }
+
+    /// Takes out the node for the value `remove` is about to take out, holding the origin
+    /// it inherited.
+    fn detach(&mut self, tokens: &[String]) -> OriginNode {
+        let node = self.node_with_origin(tokens);
+        let Some((last, parents)) = tokens.split_last() else {
+            return node;
+        };
+        if let Some(parent) = self.origins.walk_mut(&self.value, parents) {
+            match &mut parent.children {
+                Children::Object(nodes) => {
+                    nodes.remove(last);
+                },
+                Children::Array(nodes) => {
+                    if let Some(index) = array_index(last).filter(|index| *index < nodes.len()) {
+                        nodes.remove(index);
+                    }
+                },
+                Children::Whole => {},
+            }
+        }
+        return node;
+    }
+}
+
+fn collect_origins<'a>(node: &'a OriginNode, pointer: &mut String, origins: &mut Vec<(String, &'a Origin)>) {
+    if let Some(origin) = &node.origin {
+        origins.push((pointer.clone(), origin));
+    }
+    let len = pointer.len();
+    match &node.children {
+        Children::Object(nodes) => {
+            for (key, child) in nodes {
+                pointer.push('/');
+                pointer.push_str(&escape_token(key));
+                collect_origins(child, pointer, origins);
+                pointer.truncate(len);
+            }
+        },
+        Children::Array(nodes) => {
+            for (index, child) in nodes.iter().enumerate() {
+                pointer.push_str(&format!("/{}", index));
+                collect_origins(child, pointer, origins);
+                pointer.truncate(len);
+            }
+        },
+        Children::Whole => {},
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    fn origin(source: &str, pointer: &str) -> Option<Origin> {
+        return Some(Origin { source: source.to_string(), pointer: pointer.to_string() });
+    }
+
+    #[test]
+    fn origins_through_merges() {
+        let mut doc = Tracked::new(parse(r#"{"db": {"host": "localhost", "pool": {"size": 5}}, "tags": ["a"]}"#).unwrap(), "base.json");
+        doc.merge(parse(r#"{"db": {"host": "db.internal"}, "tags": ["b", "c"]}"#).unwrap(), "prod.json");
+        assert_eq!(doc.value(), &parse(r#"{"db": {"host": "db.internal", "pool": {"size": 5}}, "tags": ["b", "c"]}"#).unwrap());
+        assert_eq!(doc.origin_of("/db/host"), origin("prod.json", "/db/host"));
+        assert_eq!(doc.origin_of("/db/pool/size"), origin("base.json", "/db/pool/size"));
+        assert_eq!(doc.origin_of("/tags/1"), origin("prod.json", "/tags/1"));
+        assert_eq!(doc.origin_of("/missing"), None);
+        let listed: Vec<(String, String)> = doc.origins().into_iter().map(|(pointer, origin)| (pointer, origin.source.clone())).collect();
+        assert_eq!(listed, [("".to_string(), "base.json".to_string()), ("/db/host".to_string(), "prod.json".to_string()), ("/tags".to_string(), "prod.json".to_string())]);
+    }
+
+    #[test]
+    fn origins_through_patches() {
+        let mut doc = Tracked::new(parse(r#"{"list": [1, 2, 3], "a": {"b": true}}"#).unwrap(), "doc");
+        let patch = parse(r#"[
+            {"op": "add", "path": "/list/0", "value": 0},
+            {"op": "remove", "path": "/list/2"},
+            {"op": "move", "from": "/a/b", "path": "/list/-"},
+            {"op": "copy", "from": "/list/0", "path": "/zero"},
+            {"op": "replace", "path": "/a", "value": {"c": [null]}}
+        ]"#).unwrap();
+        doc.apply_patch(&patch, "fix.json").unwrap();
+        assert_eq!(doc.value(), &parse(r#"{"list": [0, 1, 3, true], "a": {"c": [null]}, "zero": 0}"#).unwrap());
+        assert_eq!(doc.origin_of("/list/0"), origin("fix.json", "/0/value"));
+        assert_eq!(doc.origin_of("/list/1"), origin("doc", "/list/1"));
+        // the third element was the fourth before the add and remove
+        assert_eq!(doc.origin_of("/list/2"), origin("doc", "/list/2"));
+        assert_eq!(doc.origin_of("/list/3"), origin("doc", "/a/b"));
+        assert_eq!(doc.origin_of("/zero"), origin("fix.json", "/0/value"));
+        assert_eq!(doc.origin_of("/a/c/0"), origin("fix.json", "/4/value/c/0"));
+
+        let before = doc.clone();
+        assert!(doc.apply_patch(&parse(r#"[{"op": "remove", "path": "/list/0"}, {"op": "remove", "path": "/nope"}]"#).unwrap(), "bad").is_err());
+        assert_eq!(doc, before);
+    }
+
+    #[test]
+    fn origins_through_paths() {
+        let mut doc = Tracked::new(parse(r#"{"servers": [{"port": 80}]}"#).unwrap(), "base");
+        doc.set_path("servers[0].port", JItem::Number(8080.0), Origin { source: "$PORT".to_string(), pointer: String::new() }).unwrap();
+        doc.set_path("log.level", JItem::String("debug".to_string()), Origin { source: "cli".to_string(), pointer: String::new() }).unwrap();
+        assert_eq!(doc.origin_of("/servers/0/port"), origin("$PORT", ""));
+        assert_eq!(doc.origin_of("/servers/0"), origin("base", "/servers/0"));
+        assert_eq!(doc.origin_of("/log/level"), origin("cli", ""));
+        assert_eq!(doc.origin_of("/log"), origin("base", "/log"));
+    }
+}
@@ -0,0 +1,239 @@
+//! Incremental parsing of JSON Lines, for input that arrives in chunks, such as a log
+//! file being tailed. Bytes are fed in as they come and each complete line is parsed as
+//! soon as its newline arrives. A `Checkpoint` saves the parser's place, so ingestion
+//! can stop and later carry on without reading the input again from the start.
+
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+use crate::{error::JsonError, j_item::JItem, parse_with, parser::ParseOptions};
+
+const CHECKPOINT_MAGIC: &[u8; 4] = b"SJPC";
+const CHECKPOINT_VERSION: u8 = 1;
+
+/// A push parser for JSON Lines. Blank lines are skipped, and a trailing `\r` is allowed.
+#[derive(Debug, Clone)]
+pub struct LinesParser {
+    options: ParseOptions,
+    /// The start of a line whose newline has not arrived yet.
+    partial: Vec<u8>,
+    /// Bytes fed in so far, counting from the start of the input.
+    offset: u64,
+    /// Newlines seen so far.
+    line: u64,
+}
+
+/// A `LinesParser` that returns each value with where its line was in the input, from
+/// `LinesParser::with_positions`.
+#[derive(Debug, Clone)]
+pub struct WithPositions(LinesParser);
+
+/// A value parsed from one line of the input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub value: JItem,
+    /// The line number, counting from 1 as error positions do.
+    pub line: u64,
+    /// The offset of the start of the line, counting from the start of the input.
+    pub offset: u64,
+}
+
+/// A saved position in a JSON Lines input. `to_bytes` gives an opaque encoding that
+/// `from_bytes` reads back, for storing between runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    offset: u64,
+    line: u64,
+    partial: Vec<u8>,
+}
+
+impl LinesParser {
+    pub fn new(options: ParseOptions) -> Self {
+        return LinesParser { options, partial: vec![], offset: 0, line: 0 };
+    }
+
+    /// Carries on from a checkpoint. The next bytes fed must be those of the input
+    /// starting at `checkpoint.offset()`.
+    pub fn resume(checkpoint: Checkpoint, options: ParseOptions) -> Self {
+        return LinesParser { options, partial: checkpoint.partial, offset: checkpoint.offset, line: checkpoint.line };
+    }
+
+    /// Takes the next chunk of input and parses every line it completes, returning one
+    /// result per line that is not blank. Error positions count from the start of the
+    /// input, across resumes.
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<Result<JItem, JsonError>> {
+        return self.feed_records(bytes).into_iter().map(|result| result.map(|record| record.value)).collect();
+    }
+
+    /// Ends the input, parsing a last line that has no newline after it.
+    pub fn finish(self) -> Option<Result<JItem, JsonError>> {
+        return self.finish_record().map(|result| result.map(|record| record.value));
+    }
+
+    /// Turns this parser into one that returns the line number and offset of every
+    /// value, not only of errors, so records failing later checks can be traced back.
+    pub fn with_positions(self) -> WithPositions {
+        return WithPositions(self);
+    }
+
+    fn feed_records(&mut self, bytes: &[u8]) -> Vec<Result<Record, JsonError>> {
+        let mut results = vec![];
+        let mut rest = bytes;
+        while let Some(end) = rest.iter().position(|b| *b == b'\n') {
+            let line_start = self.offset - self.partial.len() as u64;
+            self.partial.extend_from_slice(&rest[..end]);
+            self.offset += end as u64 + 1;
+            rest = &rest[end + 1..];
+            let line = core::mem::take(&mut self.partial);
+            if let Some(result) = self.parse_line(&line, line_start) {
+                results.push(result);
+            }
+            self.line += 1;
+        }
+        self.partial.extend_from_slice(rest);
+        self.offset += rest.len() as u64;
+        return results;
+    }
+
+    fn finish_record(mut self) -> Option<Result<Record, JsonError>> {
+        let line_start = self.offset - self.partial.len() as u64;
+        let line = core::mem::take(&mut self.partial);
+        return self.parse_line(&line, line_start);
+    }
+
+    /// The number of bytes fed so far, counting from the start of the input.
+    pub fn offset(&self) -> u64 {
+        return self.offset;
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        return Checkpoint { offset: self.offset, line: self.line, partial: self.partial.clone() };
+    }
+
+    fn parse_line(&self, line: &[u8], line_start: u64) -> Option<Result<Record, JsonError>> {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.iter().all(u8::is_ascii_whitespace) {
+            return None;
+        }
+        let parsed = JsonError::check_utf8(line).and_then(|text| parse_with(text, &self.options));
+        let record = parsed.map(|value| Record { value, line: self.line + 1, offset: line_start });
+        return Some(record.map_err(|e| e.within_line(line_start as usize, self.line as usize)));
+    }
+}
+
+impl WithPositions {
+    /// Takes the next chunk of input, as `LinesParser::feed` does.
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<Result<Record, JsonError>> {
+        return self.0.feed_records(bytes);
+    }
+
+    /// Ends the input, as `LinesParser::finish` does.
+    pub fn finish(self) -> Option<Result<Record, JsonError>> {
+        return self.0.finish_record();
+    }
+
+    pub fn offset(&self) -> u64 {
+        return self.0.offset();
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        return self.0.checkpoint();
+    }
+
+    pub fn into_inner(self) -> LinesParser {
+        return self.0;
+    }
+}
+
+impl Checkpoint {
+    /// Where in the input to carry on reading from.
+    pub fn offset(&self) -> u64 {
+        return self.offset;
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(21 + self.partial.len());
+        out.extend_from_slice(CHECKPOINT_MAGIC);
+        out.push(CHECKPOINT_VERSION);
+        out.extend_from_slice(&self.offset.to_le_bytes());
+        out.extend_from_slice(&self.line.to_le_bytes());
+        out.extend_from_slice(&self.partial);
+        return out;
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let Some(rest) = bytes.strip_prefix(CHECKPOINT_MAGIC) else {
+            return Err("not a checkpoint".to_string());
+        };
+        let Some((&version, rest)) = rest.split_first() else {
+            return Err("checkpoint is truncated".to_string());
+        };
+        if version != CHECKPOINT_VERSION {
+            return Err(alloc::format!("unsupported checkpoint version {}", version));
+        }
+        let (Some(offset), Some(line)) = (rest.get(..8), rest.get(8..16)) else {
+            return Err("checkpoint is truncated".to_string());
+        };
+        let checkpoint = Checkpoint { offset: u64::from_le_bytes(offset.try_into().unwrap()), line: u64::from_le_bytes(line.try_into().unwrap()), partial: rest[16..].to_vec() };
+        if checkpoint.partial.len() as u64 > checkpoint.offset {
+            return Err("checkpoint is corrupt".to_string());
+        }
+        return Ok(checkpoint);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn lines_split_across_chunks() {
+        let mut parser = LinesParser::new(ParseOptions::default());
+        assert_eq!(parser.feed(b"{\"a\": 1}\n{\"b\""), vec![parse(r#"{"a": 1}"#)]);
+        assert_eq!(parser.feed(b": [2"), vec![]);
+        assert_eq!(parser.feed(b"]}\r\n\n  \n3"), vec![parse(r#"{"b": [2]}"#)]);
+        assert_eq!(parser.offset(), 26);
+        assert_eq!(parser.finish(), Some(Ok(JItem::Number(3.0))));
+    }
+
+    #[test]
+    fn resume_from_checkpoint() {
+        let input = b"[1]\n[2]\n{\"x\": tru}\n[4]\n";
+        let mut parser = LinesParser::new(ParseOptions::default());
+        assert_eq!(parser.feed(&input[..10]).len(), 2);
+        let saved = parser.checkpoint().to_bytes();
+        drop(parser);
+
+        let checkpoint = Checkpoint::from_bytes(&saved).unwrap();
+        assert_eq!(checkpoint.offset(), 10);
+        let mut parser = LinesParser::resume(checkpoint, ParseOptions::default());
+        let results = parser.feed(&input[10..]);
+        let error = results[0].clone().unwrap_err();
+        assert_eq!((error.span(), error.line(), error.column()), (14..17, 3, 7));
+        assert_eq!(results[1], parse("[4]"));
+        assert_eq!(parser.finish(), None);
+    }
+
+    #[test]
+    fn records_with_positions() {
+        let mut parser = LinesParser::resume(Checkpoint { offset: 100, line: 9, partial: vec![] }, ParseOptions::default()).with_positions();
+        let records = parser.feed(b"[1]\n\n{\"a\"");
+        assert_eq!(records, vec![Ok(Record { value: parse("[1]").unwrap(), line: 10, offset: 100 })]);
+        assert_eq!(parser.feed(b": 2}\r\n\"x\""), vec![Ok(Record { value: parse(r#"{"a": 2}"#).unwrap(), line: 12, offset: 105 })]);
+        assert_eq!(parser.finish(), Some(Ok(Record { value: JItem::String("x".to_string()), line: 13, offset: 115 })));
+    }
+
+    #[test]
+    fn bad_checkpoints() {
+        assert_eq!(Checkpoint::from_bytes(b"JSON"), Err("not a checkpoint".to_string()));
+        assert_eq!(Checkpoint::from_bytes(b"SJPC\x01\x00"), Err("checkpoint is truncated".to_string()));
+        assert_eq!(Checkpoint::from_bytes(b"SJPC\x02"), Err("unsupported checkpoint version 2".to_string()));
+        let mut corrupt = Checkpoint { offset: 1, line: 0, partial: vec![] }.to_bytes();
+        corrupt.extend_from_slice(b"ab");
+        assert_eq!(Checkpoint::from_bytes(&corrupt), Err("checkpoint is corrupt".to_string()));
+    }
+}
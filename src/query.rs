@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+
+use crate::{j_item::JItem, path::PathSegment};
+
+/// How far past the end of an array an index in a query key may reach, as qs's
+/// `arrayLimit` bounds indices, so a short key cannot ask for a huge array.
+const ARRAY_GAP_LIMIT: usize = 20;
+
+/// Parses an `application/x-www-form-urlencoded` string into an object of strings.
+///
+/// Brackets in keys build structure: `a[b]=1` sets a member, `a[]=1` appends to an array
+/// and `a[0]=1` sets an index, which may be at most 20 past the end of the array. A key
+/// repeated without brackets collects its values into an array. Values are never
+/// converted to other types, and a key with no `=` gets an empty string.
+pub fn from_query_string(query: &str) -> Result<JItem, String> {
+    let mut root = JItem::Object(HashMap::new());
+    for pair in query.trim_start_matches('?').split('&').filter(|pair| !pair.is_empty()) {
+        let (raw_key, raw_value) = pair.split_once('=').unwrap_or((pair, ""));
+        let segments = parse_key(raw_key)?;
+        insert(&mut root, &segments, percent_decode(raw_value)?, raw_key)?;
+    }
+    return Ok(root);
+}
+
+/// Writes an object as a query string using the bracket conventions of
+/// `from_query_string`, with keys in sorted order. Arrays of scalars are written as
+/// `a[]=x`, other arrays with indices. Nulls become empty values and empty containers
+/// are left out.
+pub fn to_query_string(item: &JItem) -> Result<String, String> {
+    let JItem::Object(hmap) = item else {
+        return Err("query strings must be built from an object".to_string());
+    };
+    let mut pairs = vec![];
+    let mut keys: Vec<_> = hmap.keys().collect();
+    keys.sort();
+    for key in keys {
+        write_pairs(&percent_encode(key), &hmap[key], &mut pairs);
+    }
+    return Ok(pairs.join("&"));
+}
+
+// splits `a[b][]` into its segments, with None for `[]`. keys without well-formed
+// brackets are used whole.
+fn parse_key(raw_key: &str) -> Result<Vec<Option<PathSegment>>, String> {
+    let whole = || -> Result<Vec<Option<PathSegment>>, String> {
+        return Ok(vec![Some(PathSegment::Key(percent_decode(raw_key)?))]);
+    };
+    let Some(open) = raw_key.find('[').filter(|open| *open > 0) else {
+        return whole();
+    };
+    let mut segments = vec![Some(PathSegment::Key(percent_decode(&raw_key[..open])?))];
+    let mut rest = &raw_key[open..];
+    while !rest.is_empty() {
+        let (Some(inner), Some(close)) = (rest.strip_prefix('['), rest.find(']')) else {
+            return whole();
+        };
+        let inner = &inner[..close - 1];
+        segments.push(match inner {
+            "" => None,
+            index if index.bytes().all(|b| b.is_ascii_digit()) => match index.parse() {
+                Ok(index) => Some(PathSegment::Index(index)),
+                Err(_) => return Err(format!("array index '{}' is too large", index)),
+            },
+            key => Some(PathSegment::Key(percent_decode(key)?)),
+        });
+        rest = &rest[close + 1..];
+    }
+    return Ok(segments);
+}
+
+fn insert(root: &mut JItem, segments: &[Option<PathSegment>], value: String, raw_key: &str) -> Result<(), String> {
+    let conflict = || format!("conflicting structure for query key '{}'", raw_key);
+    let mut current = root;
+    for segment in segments {
+        current = match segment {
+            Some(PathSegment::Key(key)) => {
+                if *current == JItem::Null {
+                    *current = JItem::Object(HashMap::new());
+                }
+                let JItem::Object(hmap) = current else {
+                    return Err(conflict());
+                };
+                hmap.entry(key.to_string()).or_insert(JItem::Null)
+            },
+            Some(PathSegment::Index(index)) => {
+                if *current == JItem::Null {
+                    *current = JItem::Array(vec![]);
+                }
+                let JItem::Array(elements) = current else {
+                    return Err(conflict());
+                };
+                if *index > elements.len() + ARRAY_GAP_LIMIT {
+                    return Err(format!("array index {} in query key '{}' is too far past the end of the array", index, raw_key));
+                }
+                if elements.len() <= *index {
+                    elements.resize_with(index + 1, || JItem::Null);
+                }
+                &mut elements[*index]
+            },
+            None => {
+                if *current == JItem::Null {
+                    *current = JItem::Array(vec![]);
+                }
+                let JItem::Array(elements) = current else {
+                    return Err(conflict());
+                };
+                elements.push(JItem::Null);
+                elements.last_mut().unwrap()
+            },
+        };
+    }
+    match current {
+        JItem::Null => *current = JItem::String(value),
+        JItem::String(existing) => *current = JItem::Array(vec![JItem::String(std::mem::take(existing)), JItem::String(value)]),
+        JItem::Array(elements) => elements.push(JItem::String(value)),
+        _ => return Err(conflict()),
+    }
+    return Ok(());
+}
+
+fn write_pairs(prefix: &str, item: &JItem, pairs: &mut Vec<String>) {
+    match item {
+        JItem::Object(hmap) => {
+            let mut keys: Vec<_> = hmap.keys().collect();
+            keys.sort();
+            for key in keys {
+                write_pairs(&format!("{}[{}]", prefix, percent_encode(key)), &hmap[key], pairs);
+            }
+        },
+        JItem::Array(elements) => {
+            let all_scalars = elements.iter().all(|element| !matches!(element, JItem::Object(_) | JItem::Array(_)));
+            for (n, element) in elements.iter().enumerate() {
+                if all_scalars {
+                    write_pairs(&format!("{}[]", prefix), element, pairs);
+                }
+                else {
+                    write_pairs(&format!("{}[{}]", prefix, n), element, pairs);
+                }
+            }
+        },
+        JItem::String(s) => pairs.push(format!("{}={}", prefix, percent_encode(s))),
+        JItem::Null => pairs.push(format!("{}=", prefix)),
+        scalar => pairs.push(format!("{}={}", prefix, percent_encode(&scalar.to_string()))),
+    }
+}
+
+fn percent_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(b as char),
+            b' ' => encoded.push('+'),
+            _ => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    return encoded;
+}
+
+fn percent_decode(s: &str) -> Result<String, String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let Some((b, tail)) = rest.split_first() {
+        rest = tail;
+        match b {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex = rest.get(..2).and_then(|hex| std::str::from_utf8(hex).ok());
+                let Some(decoded) = hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) else {
+                    return Err(format!("invalid percent escape in '{}'", s));
+                };
+                bytes.push(decoded);
+                rest = &rest[2..];
+            },
+            _ => bytes.push(*b),
+        }
+    }
+    return String::from_utf8(bytes).map_err(|_| format!("percent escapes in '{}' are not valid utf-8", s));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn from_query_string_brackets() {
+        let item = from_query_string("a=1&b[]=x&b[]=y&c[d]=2&c[e][]=3&f[1]=z").unwrap();
+        assert_eq!(item, parse(r#"{"a": "1", "b": ["x", "y"], "c": {"d": "2", "e": ["3"]}, "f": [null, "z"]}"#).unwrap());
+    }
+
+    #[test]
+    fn from_query_string_decoding() {
+        let item = from_query_string("?q=hello+world&name=%C3%A9t%C3%A9&flag&a%5Bb%5D=1").unwrap();
+        assert_eq!(item, parse(r#"{"q": "hello world", "name": "été", "flag": "", "a[b]": "1"}"#).unwrap());
+    }
+
+    #[test]
+    fn from_query_string_repeated_keys() {
+        assert_eq!(from_query_string("a=1&a=2&a=3"), Ok(parse(r#"{"a": ["1", "2", "3"]}"#).unwrap()));
+    }
+
+    #[test]
+    fn from_query_string_errors() {
+        assert_eq!(from_query_string("a=1&a[b]=2"), Err("conflicting structure for query key 'a[b]'".to_string()));
+        assert!(from_query_string("a=%zz").is_err());
+        assert!(from_query_string("a=%ff").is_err());
+        assert_eq!(from_query_string("a[18446744073709551615]=x"), Err("array index 18446744073709551615 in query key 'a[18446744073709551615]' is too far past the end of the array".to_string()));
+        assert!(from_query_string("a[4000000000]=x").is_err());
+        assert!(from_query_string("a[21]=x&a[20]=y").is_err());
+        assert!(matches!(from_query_string("a[20]=x&a[41]=y").unwrap().get("a"), Some(JItem::Array(elements)) if elements.len() == 42));
+    }
+
+    #[test]
+    fn to_query_string_conventions() {
+        let item = parse(r#"{"q": "a b&c", "tags": ["x", "y"], "rows": [{"id": 1}], "opt": {"on": true, "none": null}, "empty": []}"#).unwrap();
+        assert_eq!(to_query_string(&item), Ok("opt[none]=&opt[on]=true&q=a+b%26c&rows[0][id]=1&tags[]=x&tags[]=y".to_string()));
+    }
+
+    #[test]
+    fn query_string_round_trip() {
+        let item = parse(r#"{"a": "1", "b": ["x", "y"], "c": {"d[e]": "2"}, "f": [{"g": "h"}, {"g": "i"}]}"#).unwrap();
+        assert_eq!(from_query_string(&to_query_string(&item).unwrap()), Ok(item));
+    }
+}
@@ -0,0 +1,74 @@
+use alloc::string::String;
+use core::fmt::Display;
+
+use crate::{error::JsonError, j_item::JItem, parse_with, parser::ParseOptions, validate};
+
+/// A value kept as its exact source text, for passing on unchanged or parsing later.
+/// Writing it back out gives the same bytes it was read from, so nothing drifts when a
+/// document is forwarded.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RawValue {
+    text: String,
+}
+
+impl RawValue {
+    /// Keeps `text` as it is, after checking that it is one JSON value, as `validate`
+    /// does. Whitespace around the value is kept too.
+    pub fn new(text: impl Into<String>) -> Result<Self, JsonError> {
+        let text = text.into();
+        validate(&text)?;
+        return Ok(RawValue { text });
+    }
+
+    /// For text that has already been checked.
+    pub(crate) fn from_checked(text: &str) -> Self {
+        return RawValue { text: text.into() };
+    }
+
+    pub fn as_str(&self) -> &str {
+        return &self.text;
+    }
+
+    pub fn into_string(self) -> String {
+        return self.text;
+    }
+
+    pub fn parse(&self) -> Result<JItem, JsonError> {
+        return self.parse_with(&ParseOptions::default());
+    }
+
+    pub fn parse_with(&self, options: &ParseOptions) -> Result<JItem, JsonError> {
+        return parse_with(&self.text, options);
+    }
+}
+
+impl Display for RawValue {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return f.write_str(&self.text);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parse_raw_pointers, pointer::JsonPointer};
+
+    #[test]
+    fn raw_values_keep_their_text() {
+        let input = r#"{"id": 1.50, "payload": {"b": 2,  "a": [1e3, "é"]}, "other": [ true ]}"#;
+        let pointers = ["/payload", "/id", "/payload/a/1", "/missing"].map(|pointer| JsonPointer::parse(pointer).unwrap());
+        let raw = parse_raw_pointers(input, &pointers).unwrap();
+        let texts: Vec<Option<&str>> = raw.iter().map(|raw| raw.as_ref().map(RawValue::as_str)).collect();
+        assert_eq!(texts, [Some(r#"{"b": 2,  "a": [1e3, "é"]}"#), Some("1.50"), Some(r#""é""#), None]);
+        let payload = raw[0].as_ref().unwrap();
+        assert_eq!(payload.parse().unwrap(), crate::parse(r#"{"a": [1000, "é"], "b": 2}"#).unwrap());
+        assert_eq!(format!("[{}]", payload), r#"[{"b": 2,  "a": [1e3, "é"]}]"#);
+    }
+
+    #[test]
+    fn new_checks_the_text() {
+        assert_eq!(RawValue::new(" [1, 2] ").unwrap().as_str(), " [1, 2] ");
+        assert!(RawValue::new("[1, 2").is_err());
+        assert!(RawValue::new("1 2").is_err());
+    }
+}
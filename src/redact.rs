@@ -0,0 +1,84 @@
+use crate::j_item::JItem;
+
+/// Replaces sensitive values with `replacement` and returns how many were replaced.
+///
+/// Targets starting with `/` are JSON Pointers naming one exact location. Any other
+/// target is a key name, matched ASCII case-insensitively on object members at any
+/// depth, so `password` also scrubs `Password` and `PASSWORD`. Matched values are
+/// replaced whole, whatever their type.
+pub fn redact(item: &mut JItem, targets: &[&str], replacement: &JItem) -> usize {
+    let mut redacted = 0;
+    let mut key_names = vec![];
+    for target in targets {
+        if target.starts_with('/') {
+            if let Some(value) = item.pointer_mut(target) {
+                *value = replacement.clone();
+                redacted += 1;
+            }
+        }
+        else {
+            key_names.push(*target);
+        }
+    }
+    if !key_names.is_empty() {
+        redacted += redact_keys(item, &key_names, replacement);
+    }
+    return redacted;
+}
+
+fn redact_keys(item: &mut JItem, key_names: &[&str], replacement: &JItem) -> usize {
+    let mut redacted = 0;
+    match item {
+        JItem::Object(hmap) => {
+            for (key, value) in hmap.iter_mut() {
+                if key_names.iter().any(|name| name.eq_ignore_ascii_case(key)) {
+                    *value = replacement.clone();
+                    redacted += 1;
+                }
+                else {
+                    redacted += redact_keys(value, key_names, replacement);
+                }
+            }
+        },
+        JItem::Array(elements) => {
+            for element in elements {
+                redacted += redact_keys(element, key_names, replacement);
+            }
+        },
+        _ => {},
+    }
+    return redacted;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    fn mask() -> JItem {
+        return JItem::String("***".to_string());
+    }
+
+    #[test]
+    fn redact_key_names_recursively() {
+        let mut item = parse(r#"{"user": "a", "Password": "x", "sessions": [{"token": {"v": 1}}, {"id": 2}]}"#).unwrap();
+        let redacted = redact(&mut item, &["password", "token"], &mask());
+        assert_eq!(redacted, 2);
+        assert_eq!(item, parse(r#"{"user": "a", "Password": "***", "sessions": [{"token": "***"}, {"id": 2}]}"#).unwrap());
+    }
+
+    #[test]
+    fn redact_pointers() {
+        let mut item = parse(r#"{"headers": {"authorization": "Bearer x"}, "body": [1, 2]}"#).unwrap();
+        let redacted = redact(&mut item, &["/headers/authorization", "/body/1", "/missing"], &JItem::Null);
+        assert_eq!(redacted, 2);
+        assert_eq!(item, parse(r#"{"headers": {"authorization": null}, "body": [1, null]}"#).unwrap());
+    }
+
+    #[test]
+    fn redact_nothing_matches() {
+        let mut item = parse(r#"{"a": "password"}"#).unwrap();
+        assert_eq!(redact(&mut item, &["password"], &mask()), 0);
+        assert_eq!(item, parse(r#"{"a": "password"}"#).unwrap());
+    }
+}
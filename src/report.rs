@@ -0,0 +1,169 @@
+//! Detailed reports of parse failures, for debugging bad input seen in the field where
+//! an error message alone says too little. A report holds a bounded window of the
+//! source around the error, the last tokens read before it, and where in the document
+//! the parser was, and converts to a `JItem` to be logged or sent on as JSON.
+
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+use crate::{
+    error::JsonError,
+    j_item::JItem,
+    pointer::JsonPointer,
+    tokenizer::{TokenKind, Tokenizer},
+    HashMap,
+};
+
+/// How much context `JsonError::report` captures.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportOptions {
+    /// Bytes of source kept on each side of the error, and of each token in the history.
+    pub context_bytes: usize,
+    /// Tokens kept from just before the error.
+    pub history: usize,
+}
+
+impl Default for ReportOptions {
+    fn default() -> Self {
+        return ReportOptions { context_bytes: 64, history: 8 };
+    }
+}
+
+/// Everything known about a parse failure. Text is cut at character boundaries, so the
+/// window may be a little shorter than asked for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorReport {
+    pub code: &'static str,
+    pub message: String,
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+    /// Source text just before the error.
+    pub before: String,
+    /// The offending text itself.
+    pub at: String,
+    /// Source text just after the error.
+    pub after: String,
+    /// The lexemes of the last tokens before the error, comments included, oldest first.
+    pub tokens: Vec<String>,
+    /// The containers open at the error, outermost first, as `{` and `[`.
+    pub open: String,
+    /// A JSON pointer to the value being read at the error.
+    pub pointer: String,
+}
+
+enum Frame {
+    Object(Option<String>),
+    Array(usize),
+}
+
+impl JsonError {
+    /// Captures a report of this error against the `source` it came from.
+    pub fn report(&self, source: &str, options: &ReportOptions) -> ErrorReport {
+        let start = source.floor_char_boundary(self.span().start);
+        let end = source.floor_char_boundary(self.span().end).max(start);
+        let window_start = source.floor_char_boundary(start.saturating_sub(options.context_bytes));
+        let window_end = source.floor_char_boundary(end.saturating_add(options.context_bytes));
+
+        let mut tokens = vec![];
+        let mut stack = vec![];
+        let mut awaiting_key = false;
+        for token in Tokenizer::with_comments(&source[..start]).map_while(Result::ok) {
+            let text = token.as_str();
+            tokens.push(text[..text.floor_char_boundary(options.context_bytes)].to_string());
+            match (token.kind, stack.last_mut()) {
+                (TokenKind::LBrace, _) => {
+                    stack.push(Frame::Object(None));
+                    awaiting_key = true;
+                },
+                (TokenKind::LSquareBracket, _) => stack.push(Frame::Array(0)),
+                (TokenKind::RBrace | TokenKind::RSquareBracket, _) => {
+                    stack.pop();
+                },
+                (TokenKind::String, Some(Frame::Object(key))) if awaiting_key => {
+                    *key = token.string_value().map(|s| s.to_string());
+                    awaiting_key = false;
+                },
+                (TokenKind::Comma, Some(Frame::Object(key))) => {
+                    *key = None;
+                    awaiting_key = true;
+                },
+                (TokenKind::Comma, Some(Frame::Array(index))) => *index += 1,
+                _ => {},
+            }
+        }
+        let history = tokens.len().saturating_sub(options.history);
+        let open = stack.iter().map(|frame| if let Frame::Object(_) = frame { '{' } else { '[' }).collect();
+        let pointer = stack.into_iter().map_while(|frame| match frame {
+            Frame::Object(key) => key,
+            Frame::Array(index) => Some(index.to_string()),
+        });
+
+        return ErrorReport {
+            code: self.code(),
+            message: self.kind().to_string(),
+            offset: self.offset(),
+            line: self.line(),
+            column: self.column(),
+            before: source[window_start..start].to_string(),
+            at: source[start..end].to_string(),
+            after: source[end..window_end].to_string(),
+            tokens: tokens.split_off(history),
+            open,
+            pointer: JsonPointer::from_tokens(pointer).to_string(),
+        };
+    }
+}
+
+impl ErrorReport {
+    pub fn to_item(&self) -> JItem {
+        let text = |s: &str| JItem::String(s.to_string());
+        return JItem::Object(HashMap::from_iter([
+            ("code".to_string(), text(self.code)),
+            ("message".to_string(), text(&self.message)),
+            ("offset".to_string(), JItem::Number(self.offset as f64)),
+            ("line".to_string(), JItem::Number(self.line as f64)),
+            ("column".to_string(), JItem::Number(self.column as f64)),
+            ("before".to_string(), text(&self.before)),
+            ("at".to_string(), text(&self.at)),
+            ("after".to_string(), text(&self.after)),
+            ("tokens".to_string(), JItem::Array(self.tokens.iter().map(|token| text(token)).collect())),
+            ("open".to_string(), text(&self.open)),
+            ("pointer".to_string(), text(&self.pointer)),
+        ]));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn report_captures_context() {
+        let source = r#"{"users": [{"id": 1}, {"id": 2, "name": "é" @ }], "next": null}"#;
+        let error = parse(source).unwrap_err();
+        let report = error.report(source, &ReportOptions { context_bytes: 8, history: 3 });
+        assert_eq!((report.code, report.offset, report.line, report.column), ("E002", 45, 1, 45));
+        assert_eq!((report.before.as_str(), report.at.as_str(), report.after.as_str()), ("\": \"é\" ", "@", " }], \"ne"));
+        assert_eq!(report.tokens, ["\"name\"", ":", "\"é\""]);
+        assert_eq!((report.open.as_str(), report.pointer.as_str()), ("{[{", "/users/1/name"));
+        assert_eq!(report.to_item().pointer("/tokens/0"), Some(&JItem::String("\"name\"".to_string())));
+    }
+
+    #[test]
+    fn report_at_edges() {
+        let error = parse("[1, 2 3]").unwrap_err();
+        let report = error.report("[1, 2 3]", &ReportOptions::default());
+        assert_eq!((report.before.as_str(), report.at.as_str(), report.after.as_str()), ("[1, 2 ", "3", "]"));
+        assert_eq!((report.open.as_str(), report.pointer.as_str()), ("[", "/1"));
+
+        // keys not yet read leave the pointer at their object
+        let source = "{\"a\": {,}}";
+        let report = parse(source).unwrap_err().report(source, &ReportOptions::default());
+        assert_eq!((report.open.as_str(), report.pointer.as_str()), ("{{", "/a"));
+    }
+}
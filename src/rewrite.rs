@@ -0,0 +1,99 @@
+//! Surgical edits to JSON source at the level of tokens. `rewrite` runs a callback over
+//! every token, comments included, and builds new text from what it returns. Anything
+//! the callback leaves alone, including all whitespace between tokens, comes out byte
+//! for byte as it went in. The result is not checked, so edits are free to produce
+//! text that no longer parses.
+
+use alloc::{string::String, vec::Vec};
+
+use crate::{
+    error::JsonError,
+    tokenizer::{Token, TokenKind, Tokenizer},
+};
+
+/// What to do with a token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Edit {
+    Keep,
+    Replace(String),
+    /// Drops the token but not the whitespace around it.
+    Delete,
+    /// Keeps the token, with text inserted just before it.
+    InsertBefore(String),
+    /// Keeps the token, with text inserted just after it.
+    InsertAfter(String),
+}
+
+/// Rewrites `source` one token at a time. The callback is given every token in order
+/// and the index of the one to edit, so it can look at the tokens around it; see
+/// `is_key`. Fails only if `source` cannot be tokenized.
+pub fn rewrite<'a>(source: &'a str, mut edit: impl FnMut(&[Token<'a>], usize) -> Edit) -> Result<String, JsonError> {
+    let tokens = Tokenizer::with_comments(source).collect::<Result<Vec<_>, _>>()?;
+    let mut out = String::with_capacity(source.len());
+    let mut copied = 0;
+    for index in 0..tokens.len() {
+        let span = tokens[index].span.clone();
+        out.push_str(&source[copied..span.start]);
+        match edit(&tokens, index) {
+            Edit::Keep => out.push_str(&source[span.clone()]),
+            Edit::Replace(text) => out.push_str(&text),
+            Edit::Delete => {},
+            Edit::InsertBefore(text) => {
+                out.push_str(&text);
+                out.push_str(&source[span.clone()]);
+            },
+            Edit::InsertAfter(text) => {
+                out.push_str(&source[span.clone()]);
+                out.push_str(&text);
+            },
+        }
+        copied = span.end;
+    }
+    out.push_str(&source[copied..]);
+    return Ok(out);
+}
+
+/// Whether `tokens[index]` is an object key, that is a string followed by a colon with
+/// only comments between.
+pub fn is_key(tokens: &[Token<'_>], index: usize) -> bool {
+    if tokens.get(index).is_none_or(|token| token.kind != TokenKind::String) {
+        return false;
+    }
+    let next = tokens[index + 1..].iter().find(|token| token.kind != TokenKind::Comment);
+    return next.is_some_and(|token| token.kind == TokenKind::Colon);
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::string::ToString;
+
+    use super::*;
+
+    const SOURCE: &str = "{\n  // the user\n  \"id\": 7,\n  \"tags\": [\"id\", 1.50],\n  \"nested\": {\"id\" /* again */ : null}\n}\n";
+
+    #[test]
+    fn rename_key_everywhere() {
+        let output = rewrite(SOURCE, |tokens, i| {
+            if is_key(tokens, i) && tokens[i].string_value().unwrap() == "id" {
+                return Edit::Replace("\"user_id\"".to_string());
+            }
+            return Edit::Keep;
+        });
+        assert_eq!(output.unwrap(), "{\n  // the user\n  \"user_id\": 7,\n  \"tags\": [\"id\", 1.50],\n  \"nested\": {\"user_id\" /* again */ : null}\n}\n");
+    }
+
+    #[test]
+    fn strip_comments_and_insert() {
+        let output = rewrite(SOURCE, |tokens, i| {
+            return match tokens[i].kind {
+                TokenKind::Comment => Edit::Delete,
+                TokenKind::Null => Edit::InsertAfter(", \"added\": true".to_string()),
+                TokenKind::Number if tokens[i].as_str() == "7" => Edit::InsertBefore("-".to_string()),
+                _ => Edit::Keep,
+            };
+        });
+        assert_eq!(output.unwrap(), "{\n  \n  \"id\": -7,\n  \"tags\": [\"id\", 1.50],\n  \"nested\": {\"id\"  : null, \"added\": true}\n}\n");
+        assert_eq!(rewrite(SOURCE, |_, _| Edit::Keep).unwrap(), SOURCE);
+        assert_eq!(rewrite("[1, @]", |_, _| Edit::Keep).unwrap_err().code(), "E002");
+    }
+}
@@ -0,0 +1,1075 @@
+//! JSON Schema documents, the `$ref`s between them, and checking documents against
+//! them as they are read.
+
+use std::ops::Range;
+
+use crate::{
+    error::JsonError,
+    events::{Event, Events},
+    j_item::JItem,
+    pointer::JsonPointer,
+    tokenizer::TokenKind as Kind,
+    HashMap,
+};
+
+/// Fetches a schema that is not registered, given its absolute URI without a fragment.
+pub type Loader = Box<dyn FnMut(&str) -> Result<JItem, String>>;
+
+/// Schema documents by URI, for resolving `$ref`. Documents are added with `register`,
+/// or on first use by the loader, if there is one; nothing is fetched otherwise. URIs
+/// are compared as written once relative references have been resolved.
+#[derive(Default)]
+pub struct SchemaRegistry {
+    documents: HashMap<String, JItem>,
+    loader: Option<Loader>,
+}
+
+impl SchemaRegistry {
+    pub fn new() -> Self {
+        return SchemaRegistry::default();
+    }
+
+    /// Calls `loader` for URIs that are not registered, keeping what it returns. The
+    /// loader decides how, and whether, to fetch anything.
+    pub fn with_loader(mut self, loader: impl FnMut(&str) -> Result<JItem, String> + 'static) -> Self {
+        self.loader = Some(Box::new(loader));
+        return self;
+    }
+
+    /// Adds a document under `uri`. Subschemas with their own `$id` are also added under
+    /// that, resolved against the URI of the schema around them.
+    pub fn register(&mut self, uri: &str, schema: JItem) {
+        let uri = without_fragment(uri);
+        self.index(uri, &schema);
+        self.documents.insert(uri.to_string(), schema);
+    }
+
+    fn index(&mut self, base: &str, schema: &JItem) {
+        match schema {
+            JItem::Object(hmap) => {
+                let joined;
+                let base = match hmap.get("$id") {
+                    Some(JItem::String(id)) if !id.starts_with('#') => {
+                        joined = join(base, id);
+                        let uri = without_fragment(&joined);
+                        self.documents.insert(uri.to_string(), schema.clone());
+                        uri
+                    },
+                    _ => base,
+                };
+                for value in hmap.values() {
+                    self.index(base, value);
+                }
+            },
+            JItem::Array(elements) => elements.iter().for_each(|element| self.index(base, element)),
+            _ => {},
+        }
+    }
+
+    pub fn get(&self, uri: &str) -> Option<&JItem> {
+        return self.documents.get(without_fragment(uri));
+    }
+
+    /// Finds the schema `reference` points to, resolved against `base`. The fragment may
+    /// be a JSON Pointer, as in `#/$defs/item`, or the name of a `$anchor`. Returns the
+    /// URI of the document the schema was found in, which is the base for references
+    /// inside it, along with a copy of the schema.
+    pub fn resolve(&mut self, base: &str, reference: &str) -> Result<(String, JItem), String> {
+        let target = join(base, reference);
+        let (uri, fragment) = target.split_once('#').unwrap_or((&target, ""));
+        if !self.documents.contains_key(uri) {
+            let Some(loader) = &mut self.loader else {
+                return Err(format!("no schema is registered for '{}'", uri));
+            };
+            let document = loader(uri).map_err(|e| format!("could not load '{}': {}", uri, e))?;
+            self.register(uri, document);
+        }
+        let document = &self.documents[uri];
+        let fragment = percent_decode(fragment).ok_or_else(|| format!("invalid percent-encoding in '{}'", reference))?;
+        let schema = match fragment.is_empty() || fragment.starts_with('/') {
+            true => document.pointer(&fragment),
+            false => find_anchor(document, &fragment),
+        };
+        let Some(schema) = schema else {
+            return Err(format!("'{}' does not point to anything in '{}'", reference, uri));
+        };
+        return Ok((uri.to_string(), schema.clone()));
+    }
+}
+
+/// Checks a string against a `format`.
+pub type FormatChecker = Box<dyn Fn(&str) -> bool>;
+
+/// Checkers for the `format` keyword. The built-in ones cover `date-time`, `date`,
+/// `time`, `email`, `hostname`, `ipv4`, `ipv6`, `uri` and `uuid`, and more can be added
+/// with `register`. By default formats are only annotations, as the specification
+/// leaves them, and `check` accepts everything; `assertive` makes it reject strings
+/// that do not match and formats it does not know.
+pub struct Formats {
+    checkers: HashMap<String, FormatChecker>,
+    assertive: bool,
+}
+
+impl Default for Formats {
+    fn default() -> Self {
+        let mut formats = Formats { checkers: HashMap::new(), assertive: false };
+        formats.register("date-time", is_date_time);
+        formats.register("date", is_date);
+        formats.register("time", is_time);
+        formats.register("email", is_email);
+        formats.register("hostname", is_hostname);
+        formats.register("ipv4", |s| s.parse::<std::net::Ipv4Addr>().is_ok());
+        formats.register("ipv6", |s| s.parse::<std::net::Ipv6Addr>().is_ok());
+        formats.register("uri", is_uri);
+        formats.register("uuid", is_uuid);
+        return formats;
+    }
+}
+
+impl Formats {
+    pub fn new() -> Self {
+        return Formats::default();
+    }
+
+    pub fn assertive(self, assertive: bool) -> Self {
+        return Formats { assertive, ..self };
+    }
+
+    /// Adds a checker, replacing any existing one for `format`.
+    pub fn register(&mut self, format: &str, checker: impl Fn(&str) -> bool + 'static) {
+        self.checkers.insert(format.to_string(), Box::new(checker));
+    }
+
+    /// Whether `value` matches `format`, or `None` for a format with no checker.
+    pub fn matches(&self, format: &str, value: &str) -> Option<bool> {
+        return self.checkers.get(format).map(|checker| checker(value));
+    }
+
+    /// Applies `format` to a value. Only strings are checked, and only in assertive
+    /// mode.
+    pub fn check(&self, format: &str, value: &JItem) -> Result<(), String> {
+        let JItem::String(value) = value else {
+            return Ok(());
+        };
+        if !self.assertive {
+            return Ok(());
+        }
+        return match self.matches(format, value) {
+            Some(true) => Ok(()),
+            Some(false) => Err(format!("\"{}\" is not a valid {}", value, format)),
+            None => Err(format!("unknown format '{}'", format)),
+        };
+    }
+}
+
+fn digits(s: &str, len: usize) -> Option<u32> {
+    if s.len() != len || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    return s.parse().ok();
+}
+
+/// A full date, `YYYY-MM-DD`, as in RFC 3339.
+fn is_date(s: &str) -> bool {
+    let mut parts = s.split('-');
+    let (Some(year), Some(month), Some(day), None) = (parts.next().and_then(|p| digits(p, 4)), parts.next().and_then(|p| digits(p, 2)), parts.next().and_then(|p| digits(p, 2)), parts.next()) else {
+        return false;
+    };
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return false,
+    };
+    return (1..=days).contains(&day);
+}
+
+/// A time with an offset, `HH:MM:SS[.frac](Z|+HH:MM|-HH:MM)`, as in RFC 3339. A leap
+/// second is allowed.
+fn is_time(s: &str) -> bool {
+    let s = s.to_ascii_uppercase();
+    let (clock, offset) = match s.find(['Z', '+', '-']) {
+        Some(i) => s.split_at(i),
+        None => return false,
+    };
+    let offset_ok = match offset.strip_prefix(['+', '-']) {
+        Some(offset) => offset.split_once(':').is_some_and(|(h, m)| digits(h, 2).is_some_and(|h| h < 24) && digits(m, 2).is_some_and(|m| m < 60)),
+        None => offset == "Z",
+    };
+    let (clock, fraction) = clock.split_once('.').unwrap_or((clock, "0"));
+    let mut parts = clock.split(':');
+    let (Some(hour), Some(minute), Some(second), None) = (parts.next().and_then(|p| digits(p, 2)), parts.next().and_then(|p| digits(p, 2)), parts.next().and_then(|p| digits(p, 2)), parts.next()) else {
+        return false;
+    };
+    return offset_ok && hour < 24 && minute < 60 && second <= 60 && !fraction.is_empty() && fraction.bytes().all(|b| b.is_ascii_digit());
+}
+
+fn is_date_time(s: &str) -> bool {
+    return match s.find(['T', 't']) {
+        Some(i) => is_date(&s[..i]) && is_time(&s[i + 1..]),
+        None => false,
+    };
+}
+
+fn is_hostname(s: &str) -> bool {
+    let s = s.strip_suffix('.').unwrap_or(s);
+    return !s.is_empty() && s.len() <= 253 && s.split('.').all(|label| {
+        return (1..=63).contains(&label.len()) && !label.starts_with('-') && !label.ends_with('-') && label.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-');
+    });
+}
+
+/// An address like `name@example.com`. Quoted local parts are not accepted, but a
+/// bracketed IP address is.
+fn is_email(s: &str) -> bool {
+    let Some((local, domain)) = s.rsplit_once('@') else {
+        return false;
+    };
+    let local_ok = !local.is_empty() && local.len() <= 64 && local.split('.').all(|atom| !atom.is_empty() && atom.chars().all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+/=?^_`{|}~-".contains(c)));
+    let domain_ok = match domain.strip_prefix('[').and_then(|d| d.strip_suffix(']')) {
+        Some(address) => address.parse::<std::net::Ipv4Addr>().is_ok() || address.strip_prefix("IPv6:").is_some_and(|a| a.parse::<std::net::Ipv6Addr>().is_ok()),
+        None => is_hostname(domain),
+    };
+    return local_ok && domain_ok;
+}
+
+/// An absolute URI: a scheme, then characters a URI may contain, with `%` only before
+/// two hex digits.
+fn is_uri(s: &str) -> bool {
+    let Some((_, rest)) = s.split_once(':') else {
+        return false;
+    };
+    let escapes_ok = s.match_indices('%').all(|(i, _)| s.get(i + 1..i + 3).is_some_and(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit())));
+    return has_scheme(s) && escapes_ok && rest.chars().all(|c| c.is_ascii_alphanumeric() || "-._~:/?#[]@!$&'()*+,;=%".contains(c));
+}
+
+fn is_uuid(s: &str) -> bool {
+    let groups: Vec<&str> = s.split('-').collect();
+    return groups.iter().map(|group| group.len()).eq([8, 4, 4, 4, 12]) && groups.iter().all(|group| group.bytes().all(|b| b.is_ascii_hexdigit()));
+}
+
+/// A place where a document breaks a schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// A JSON Pointer to the value.
+    pub pointer: String,
+    /// The byte range of the value in the source text. Problems found as soon as an
+    /// object or array opens, such as its type, are reported before its end is known
+    /// and cover only the opening bracket.
+    pub span: Range<usize>,
+    pub message: String,
+}
+
+const NULL: u8 = 1;
+const BOOLEAN: u8 = 2;
+const OBJECT: u8 = 4;
+const ARRAY: u8 = 8;
+const NUMBER: u8 = 16;
+const STRING: u8 = 32;
+const INTEGER: u8 = 64;
+const TYPES: [(u8, &str); 7] = [(NULL, "null"), (BOOLEAN, "boolean"), (OBJECT, "object"), (ARRAY, "array"), (NUMBER, "number"), (STRING, "string"), (INTEGER, "integer")];
+
+/// One subschema, with its subschemas replaced by indices into `CompiledSchema::nodes`.
+#[derive(Default)]
+struct Node {
+    never: bool,
+    types: Option<u8>,
+    enumeration: Option<Vec<JItem>>,
+    constant: Option<JItem>,
+    minimum: Option<f64>,
+    maximum: Option<f64>,
+    exclusive_minimum: Option<f64>,
+    exclusive_maximum: Option<f64>,
+    multiple_of: Option<f64>,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    format: Option<String>,
+    properties: HashMap<String, usize>,
+    additional_properties: Option<usize>,
+    required: Vec<String>,
+    min_properties: Option<usize>,
+    max_properties: Option<usize>,
+    prefix_items: Vec<usize>,
+    items: Option<usize>,
+    min_items: Option<usize>,
+    max_items: Option<usize>,
+    unique_items: bool,
+    all_of: Vec<usize>,
+    any_of: Vec<usize>,
+    one_of: Vec<usize>,
+    not: Option<usize>,
+}
+
+/// A schema prepared for checking documents as they are read, without building them.
+/// `$ref`s are resolved once, when compiling, and may be recursive. Keywords that
+/// would need a value kept whole, `enum`, `const` and `uniqueItems`, parse just the
+/// value they apply to. Keywords that are not supported, such as `pattern` and
+/// `patternProperties`, make compiling fail rather than being skipped.
+pub struct CompiledSchema {
+    nodes: Vec<Node>,
+    root: usize,
+    formats: Formats,
+}
+
+impl CompiledSchema {
+    /// Compiles `schema`, resolving `$ref`s through `registry`. The schema is registered
+    /// under `""`, and under its `$id` if it has one.
+    pub fn compile(schema: &JItem, registry: &mut SchemaRegistry) -> Result<Self, String> {
+        registry.register("", schema.clone());
+        let mut compiler = Compiler { registry, nodes: vec![], refs: HashMap::new() };
+        let root = compiler.compile(schema, "")?;
+        return Ok(CompiledSchema { nodes: compiler.nodes, root, formats: Formats::default() });
+    }
+
+    /// Uses `formats` for the `format` keyword. The default only annotates.
+    pub fn with_formats(self, formats: Formats) -> Self {
+        return CompiledSchema { formats, ..self };
+    }
+
+    /// Checks `source` in one pass, returning every violation in document order. Fails
+    /// if `source` is not valid JSON.
+    pub fn validate_str(&self, source: &str) -> Result<Vec<Violation>, JsonError> {
+        return self.run(source, usize::MAX);
+    }
+
+    /// Checks `source` up to its first violation, without reading the rest. Malformed
+    /// JSON after that point is not noticed.
+    pub fn first_violation(&self, source: &str) -> Result<Option<Violation>, JsonError> {
+        return Ok(self.run(source, 1)?.pop());
+    }
+
+    fn run(&self, source: &str, limit: usize) -> Result<Vec<Violation>, JsonError> {
+        let mut walker = Walker { schema: self, source, sinks: vec![vec![]], path: vec![], frames: vec![] };
+        for event in Events::new(source) {
+            walker.event(event?);
+            if walker.sinks[0].len() >= limit {
+                break;
+            }
+        }
+        return Ok(walker.sinks.swap_remove(0));
+    }
+}
+
+struct Compiler<'r> {
+    registry: &'r mut SchemaRegistry,
+    nodes: Vec<Node>,
+    /// Nodes by the absolute reference that led to them.
+    refs: HashMap<String, usize>,
+}
+
+impl Compiler<'_> {
+    fn compile(&mut self, schema: &JItem, base: &str) -> Result<usize, String> {
+        let index = self.nodes.len();
+        self.nodes.push(Node::default());
+        self.compile_into(index, schema, base)?;
+        return Ok(index);
+    }
+
+    fn reference(&mut self, base: &str, reference: &str) -> Result<usize, String> {
+        let key = join(base, reference);
+        if let Some(&index) = self.refs.get(&key) {
+            return Ok(index);
+        }
+        let index = self.nodes.len();
+        self.nodes.push(Node::default());
+        self.refs.insert(key, index);
+        let (uri, target) = self.registry.resolve(base, reference)?;
+        self.compile_into(index, &target, &uri)?;
+        return Ok(index);
+    }
+
+    fn compile_all(&mut self, keyword: &str, schemas: &JItem, base: &str) -> Result<Vec<usize>, String> {
+        return match schemas {
+            JItem::Array(schemas) if !schemas.is_empty() => schemas.iter().map(|schema| self.compile(schema, base)).collect(),
+            _ => Err(format!("'{}' must be a non-empty array", keyword)),
+        };
+    }
+
+    fn compile_into(&mut self, index: usize, schema: &JItem, base: &str) -> Result<(), String> {
+        let members = match schema {
+            JItem::Bool(true) => return Ok(()),
+            JItem::Bool(false) => {
+                self.nodes[index].never = true;
+                return Ok(());
+            },
+            JItem::Object(members) => members,
+            _ => return Err(format!("schema must be an object or boolean, not {}", schema)),
+        };
+        let base = match members.get("$id") {
+            Some(JItem::String(id)) => join(base, id),
+            _ => base.to_string(),
+        };
+        let number = |keyword: &str, value: &JItem| value.number_value().ok_or_else(|| format!("'{}' must be a number", keyword));
+        let count = |keyword: &str, value: &JItem| match value.number_value() {
+            Some(n) if n >= 0.0 && n.fract() == 0.0 => Ok(n as usize),
+            _ => Err(format!("'{}' must be a non-negative integer", keyword)),
+        };
+        let mut node = Node::default();
+        let mut keywords: Vec<&String> = members.keys().collect();
+        keywords.sort();
+        for keyword in keywords {
+            let value = &members[keyword];
+            match keyword.as_str() {
+                "$ref" => {
+                    let JItem::String(reference) = value else {
+                        return Err("'$ref' must be a string".to_string());
+                    };
+                    node.all_of.push(self.reference(&base, reference)?);
+                },
+                "type" => {
+                    let names = match value {
+                        JItem::Array(names) => names.iter().collect(),
+                        name => vec![name],
+                    };
+                    let mut types = 0;
+                    for name in names {
+                        types |= match TYPES.iter().find(|(_, type_name)| matches!(name, JItem::String(s) if s == type_name)) {
+                            Some((bit, _)) => *bit,
+                            None => return Err(format!("invalid type {}", name)),
+                        };
+                    }
+                    node.types = Some(types);
+                },
+                "enum" => match value {
+                    JItem::Array(values) => node.enumeration = Some(values.clone()),
+                    _ => return Err("'enum' must be an array".to_string()),
+                },
+                "const" => node.constant = Some(value.clone()),
+                "minimum" => node.minimum = Some(number(keyword, value)?),
+                "maximum" => node.maximum = Some(number(keyword, value)?),
+                "exclusiveMinimum" => node.exclusive_minimum = Some(number(keyword, value)?),
+                "exclusiveMaximum" => node.exclusive_maximum = Some(number(keyword, value)?),
+                "multipleOf" => match number(keyword, value)? {
+                    step if step > 0.0 => node.multiple_of = Some(step),
+                    _ => return Err("'multipleOf' must be greater than 0".to_string()),
+                },
+                "minLength" => node.min_length = Some(count(keyword, value)?),
+                "maxLength" => node.max_length = Some(count(keyword, value)?),
+                "format" => match value {
+                    JItem::String(format) => node.format = Some(format.clone()),
+                    _ => return Err("'format' must be a string".to_string()),
+                },
+                "properties" => {
+                    let JItem::Object(properties) = value else {
+                        return Err("'properties' must be an object".to_string());
+                    };
+                    for (key, schema) in properties {
+                        let child = self.compile(schema, &base)?;
+                        node.properties.insert(key.clone(), child);
+                    }
+                },
+                "additionalProperties" => node.additional_properties = Some(self.compile(value, &base)?),
+                "required" => {
+                    node.required = match value {
+                        JItem::Array(keys) => keys.iter().map(|key| match key {
+                            JItem::String(key) => Ok(key.clone()),
+                            _ => Err("'required' must list strings".to_string()),
+                        }).collect::<Result<_, _>>()?,
+                        _ => return Err("'required' must be an array".to_string()),
+                    };
+                },
+                "minProperties" => node.min_properties = Some(count(keyword, value)?),
+                "maxProperties" => node.max_properties = Some(count(keyword, value)?),
+                "prefixItems" => node.prefix_items = self.compile_all(keyword, value, &base)?,
+                // before 2020-12 an array of schemas here meant what prefixItems does now
+                "items" => match value {
+                    JItem::Array(_) => node.prefix_items = self.compile_all(keyword, value, &base)?,
+                    _ => node.items = Some(self.compile(value, &base)?),
+                },
+                "additionalItems" => {
+                    if let Some(JItem::Array(_)) = members.get("items") {
+                        node.items = Some(self.compile(value, &base)?);
+                    }
+                },
+                "minItems" => node.min_items = Some(count(keyword, value)?),
+                "maxItems" => node.max_items = Some(count(keyword, value)?),
+                "uniqueItems" => node.unique_items = matches!(value, JItem::Bool(true)),
+                "allOf" => node.all_of.extend(self.compile_all(keyword, value, &base)?),
+                "anyOf" => node.any_of = self.compile_all(keyword, value, &base)?,
+                "oneOf" => node.one_of = self.compile_all(keyword, value, &base)?,
+                "not" => node.not = Some(self.compile(value, &base)?),
+                "$schema" | "$id" | "$anchor" | "$comment" | "$defs" | "definitions" | "title" | "description" | "default" | "examples" | "deprecated" | "readOnly" | "writeOnly" => {},
+                other => return Err(format!("keyword '{}' is not supported", other)),
+            }
+        }
+        self.nodes[index] = node;
+        return Ok(());
+    }
+}
+
+/// A node applied to the current value, and the sink its violations go to.
+#[derive(Clone, Copy, PartialEq)]
+struct Applied {
+    node: usize,
+    sink: usize,
+}
+
+#[derive(Clone, Copy)]
+enum GroupKind {
+    AnyOf,
+    OneOf,
+    Not,
+}
+
+/// An `anyOf`, `oneOf` or `not` applied to the current value. Each branch collects
+/// its violations in its own sink, and the group is decided once the value ends.
+struct Group {
+    kind: GroupKind,
+    sink: usize,
+    branches: Range<usize>,
+}
+
+/// Everything applied to one value, kept until it ends.
+struct Frame {
+    applied: Vec<Applied>,
+    groups: Vec<Group>,
+    /// The first sink opened for this value's groups.
+    first_sink: usize,
+    start: usize,
+    /// Members or elements read so far.
+    count: usize,
+    /// The key whose value comes next.
+    key: Option<String>,
+    /// The keys read, when something is `required`.
+    keys: Option<Vec<String>>,
+    /// The element spans, when items must be unique.
+    elements: Option<Vec<Range<usize>>>,
+}
+
+struct Walker<'s, 'a> {
+    schema: &'s CompiledSchema,
+    source: &'a str,
+    /// Violations; the first sink is the result, the rest belong to open groups.
+    sinks: Vec<Vec<Violation>>,
+    path: Vec<String>,
+    /// The open objects and arrays.
+    frames: Vec<Frame>,
+}
+
+impl Walker<'_, '_> {
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Key(token) => {
+                let key = token.string_value().unwrap_or_default().into_owned();
+                let frame = self.frames.last_mut().unwrap();
+                if let Some(keys) = &mut frame.keys {
+                    keys.push(key.clone());
+                }
+                frame.key = Some(key);
+            },
+            Event::Scalar(token) => {
+                let frame = self.begin(token.span.start);
+                let (mask, number, string) = match token.kind {
+                    Kind::Null => (NULL, None, None),
+                    Kind::True | Kind::False => (BOOLEAN, None, None),
+                    Kind::Number => {
+                        let n = token.number_value().unwrap_or(f64::NAN);
+                        (if n.fract() == 0.0 { NUMBER | INTEGER } else { NUMBER }, Some(n), None)
+                    },
+                    _ => (STRING, None, token.string_value()),
+                };
+                for &applied in &frame.applied {
+                    self.check_type(applied, mask, &token.span);
+                    if let Some(n) = number {
+                        self.check_number(applied, n, &token.span);
+                    }
+                    if let Some(string) = &string {
+                        self.check_string(applied, string, &token.span);
+                    }
+                }
+                self.end(frame, token.span.end);
+            },
+            Event::StartObject(span) => self.open(span, OBJECT),
+            Event::StartArray(span) => self.open(span, ARRAY),
+            Event::EndObject(span) => self.close(span, OBJECT),
+            Event::EndArray(span) => self.close(span, ARRAY),
+        }
+    }
+
+    fn open(&mut self, span: Range<usize>, mask: u8) {
+        let mut frame = self.begin(span.start);
+        let nodes = &self.schema.nodes;
+        for &applied in &frame.applied {
+            self.check_type(applied, mask, &span);
+        }
+        if mask == OBJECT && frame.applied.iter().any(|applied| !nodes[applied.node].required.is_empty()) {
+            frame.keys = Some(vec![]);
+        }
+        if mask == ARRAY && frame.applied.iter().any(|applied| nodes[applied.node].unique_items) {
+            frame.elements = Some(vec![]);
+        }
+        self.frames.push(frame);
+    }
+
+    fn close(&mut self, span: Range<usize>, mask: u8) {
+        let frame = self.frames.pop().unwrap();
+        let whole = frame.start..span.end;
+        for &applied in &frame.applied {
+            let node = &self.schema.nodes[applied.node];
+            let (min, max, noun) = match mask {
+                OBJECT => (node.min_properties, node.max_properties, "properties"),
+                _ => (node.min_items, node.max_items, "items"),
+            };
+            if let Some(min) = min.filter(|&min| frame.count < min) {
+                self.report(applied.sink, &whole, format!("has {} {}, fewer than the minimum of {}", frame.count, noun, min));
+            }
+            if let Some(max) = max.filter(|&max| frame.count > max) {
+                self.report(applied.sink, &whole, format!("has {} {}, more than the maximum of {}", frame.count, noun, max));
+            }
+            if let Some(keys) = &frame.keys {
+                for key in node.required.iter().filter(|key| !keys.contains(key)) {
+                    self.report(applied.sink, &whole, format!("required property '{}' is missing", key));
+                }
+            }
+            if let (true, Some(elements)) = (node.unique_items, &frame.elements) {
+                let values: Vec<Option<JItem>> = elements.iter().map(|span| crate::parse(&self.source[span.clone()]).ok()).collect();
+                if let Some((i, j)) = (0..values.len()).flat_map(|j| (0..j).map(move |i| (i, j))).find(|&(i, j)| values[i] == values[j]) {
+                    self.report(applied.sink, &whole, format!("items {} and {} are equal", i, j));
+                }
+            }
+        }
+        self.end(frame, span.end);
+    }
+
+    /// Starts a value at `start`, working out which nodes apply to it.
+    fn begin(&mut self, start: usize) -> Frame {
+        let nodes = &self.schema.nodes;
+        let roots = match self.frames.last() {
+            None => vec![Applied { node: self.schema.root, sink: 0 }],
+            Some(parent) => {
+                self.path.push(parent.key.clone().unwrap_or_else(|| parent.count.to_string()));
+                parent.applied.iter().filter_map(|applied| {
+                    let node = &nodes[applied.node];
+                    let child = match &parent.key {
+                        Some(key) => node.properties.get(key).or(node.additional_properties.as_ref()),
+                        None => node.prefix_items.get(parent.count).or(node.items.as_ref()),
+                    };
+                    return child.map(|&child| Applied { node: child, sink: applied.sink });
+                }).collect()
+            },
+        };
+        let mut frame = Frame { applied: vec![], groups: vec![], first_sink: self.sinks.len(), start, count: 0, key: None, keys: None, elements: None };
+        for root in roots {
+            self.apply(root, &mut vec![], &mut frame);
+        }
+        return frame;
+    }
+
+    /// Adds `applied` and the nodes it brings in through `allOf`, `anyOf`, `oneOf` and
+    /// `not`. `path` holds the nodes being added, so circular `$ref`s stop.
+    fn apply(&mut self, applied: Applied, path: &mut Vec<usize>, frame: &mut Frame) {
+        if path.contains(&applied.node) || frame.applied.contains(&applied) {
+            return;
+        }
+        frame.applied.push(applied);
+        path.push(applied.node);
+        let node = &self.schema.nodes[applied.node];
+        for &sub in &node.all_of {
+            self.apply(Applied { node: sub, sink: applied.sink }, path, frame);
+        }
+        for (kind, branches) in [(GroupKind::AnyOf, node.any_of.as_slice()), (GroupKind::OneOf, node.one_of.as_slice()), (GroupKind::Not, node.not.as_slice())] {
+            if branches.is_empty() {
+                continue;
+            }
+            let first = self.sinks.len();
+            self.sinks.extend(branches.iter().map(|_| vec![]));
+            frame.groups.push(Group { kind, sink: applied.sink, branches: first..first + branches.len() });
+            for (n, &branch) in branches.iter().enumerate() {
+                self.apply(Applied { node: branch, sink: first + n }, path, frame);
+            }
+        }
+        path.pop();
+    }
+
+    /// Finishes the value that began with `frame`, at byte `end`.
+    fn end(&mut self, frame: Frame, end: usize) {
+        let span = frame.start..end;
+        let mut value = None;
+        for &applied in &frame.applied {
+            let node = &self.schema.nodes[applied.node];
+            if node.enumeration.is_none() && node.constant.is_none() {
+                continue;
+            }
+            let value = value.get_or_insert_with(|| crate::parse(&self.source[span.clone()]).ok());
+            if node.constant.as_ref().is_some_and(|constant| value.as_ref() != Some(constant)) {
+                self.report(applied.sink, &span, format!("expected {}", node.constant.as_ref().unwrap()));
+            }
+            if node.enumeration.as_ref().is_some_and(|values| !values.iter().any(|allowed| value.as_ref() == Some(allowed))) {
+                self.report(applied.sink, &span, "is not one of the values in 'enum'".to_string());
+            }
+        }
+        for group in frame.groups.iter().rev() {
+            let matched = group.branches.clone().filter(|&sink| self.sinks[sink].is_empty()).count();
+            let message = match group.kind {
+                GroupKind::AnyOf if matched == 0 => "does not match any schema in 'anyOf'".to_string(),
+                GroupKind::OneOf if matched != 1 => format!("matches {} schemas in 'oneOf' rather than exactly one", matched),
+                GroupKind::Not if matched == 1 => "matches the schema in 'not'".to_string(),
+                _ => continue,
+            };
+            self.report(group.sink, &span, message);
+        }
+        self.sinks.truncate(frame.first_sink);
+        if let Some(parent) = self.frames.last_mut() {
+            self.path.pop();
+            parent.count += 1;
+            parent.key = None;
+            if let Some(elements) = &mut parent.elements {
+                elements.push(span);
+            }
+        }
+    }
+
+    fn report(&mut self, sink: usize, span: &Range<usize>, message: String) {
+        let pointer = JsonPointer::from_tokens(self.path.iter().cloned()).to_string();
+        self.sinks[sink].push(Violation { pointer, span: span.clone(), message });
+    }
+
+    fn check_type(&mut self, applied: Applied, mask: u8, span: &Range<usize>) {
+        let node = &self.schema.nodes[applied.node];
+        if node.never {
+            self.report(applied.sink, span, "no value is allowed here".to_string());
+        }
+        let Some(types) = node.types.filter(|types| types & mask == 0) else {
+            return;
+        };
+        let names = |mask: u8| TYPES.iter().filter(|(bit, _)| mask & bit != 0).map(|(_, name)| *name).collect::<Vec<_>>();
+        let found = if mask & INTEGER != 0 { "integer" } else { names(mask)[0] };
+        self.report(applied.sink, span, format!("expected {}, found {}", names(types).join(" or "), found));
+    }
+
+    fn check_number(&mut self, applied: Applied, n: f64, span: &Range<usize>) {
+        let node = &self.schema.nodes[applied.node];
+        let mut problems = vec![];
+        if let Some(min) = node.minimum.filter(|&min| n < min) {
+            problems.push(format!("{} is less than the minimum of {}", n, min));
+        }
+        if let Some(max) = node.maximum.filter(|&max| n > max) {
+            problems.push(format!("{} is greater than the maximum of {}", n, max));
+        }
+        if let Some(min) = node.exclusive_minimum.filter(|&min| n <= min) {
+            problems.push(format!("{} is not greater than {}", n, min));
+        }
+        if let Some(max) = node.exclusive_maximum.filter(|&max| n >= max) {
+            problems.push(format!("{} is not less than {}", n, max));
+        }
+        // a little slack, so 0.3 counts as a multiple of 0.1
+        if let Some(step) = node.multiple_of.filter(|&step| ((n / step) - (n / step).round()).abs() > 1e-9) {
+            problems.push(format!("{} is not a multiple of {}", n, step));
+        }
+        for problem in problems {
+            self.report(applied.sink, span, problem);
+        }
+    }
+
+    fn check_string(&mut self, applied: Applied, s: &str, span: &Range<usize>) {
+        let node = &self.schema.nodes[applied.node];
+        let len = s.chars().count();
+        let mut problems = vec![];
+        if let Some(min) = node.min_length.filter(|&min| len < min) {
+            problems.push(format!("is {} characters long, shorter than the minimum of {}", len, min));
+        }
+        if let Some(max) = node.max_length.filter(|&max| len > max) {
+            problems.push(format!("is {} characters long, longer than the maximum of {}", len, max));
+        }
+        if let Some(format) = &node.format {
+            problems.extend(self.schema.formats.check(format, &JItem::String(s.to_string())).err());
+        }
+        for problem in problems {
+            self.report(applied.sink, span, problem);
+        }
+    }
+}
+
+fn without_fragment(uri: &str) -> &str {
+    return uri.split_once('#').map_or(uri, |(uri, _)| uri);
+}
+
+/// The subschema with `"$anchor": name`, or the older `"$id": "#name"`.
+fn find_anchor<'a>(schema: &'a JItem, name: &str) -> Option<&'a JItem> {
+    return match schema {
+        JItem::Object(hmap) => {
+            let named = |keyword: &str, prefix: &str| matches!(hmap.get(keyword), Some(JItem::String(s)) if s.strip_prefix(prefix) == Some(name));
+            if named("$anchor", "") || named("$id", "#") {
+                return Some(schema);
+            }
+            hmap.values().find_map(|value| find_anchor(value, name))
+        },
+        JItem::Array(elements) => elements.iter().find_map(|element| find_anchor(element, name)),
+        _ => None,
+    };
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = vec![];
+    let mut rest = s.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = core::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        }
+        else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    return String::from_utf8(bytes).ok();
+}
+
+fn has_scheme(uri: &str) -> bool {
+    let Some((scheme, _)) = uri.split_once(':') else {
+        return false;
+    };
+    return scheme.starts_with(|c: char| c.is_ascii_alphabetic()) && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c));
+}
+
+/// Resolves a URI reference against `base`, as RFC 3986 does for the forms schemas use.
+/// A base without a scheme is treated as a path, so documents can be registered under
+/// names like `common.json`.
+pub fn join(base: &str, reference: &str) -> String {
+    let base = without_fragment(base);
+    if reference.is_empty() || reference.starts_with('#') {
+        return format!("{}{}", base, reference);
+    }
+    if has_scheme(reference) {
+        return reference.to_string();
+    }
+    let (scheme, rest) = match has_scheme(base) {
+        true => base.split_at(base.find(':').unwrap() + 1),
+        false => ("", base),
+    };
+    if reference.starts_with("//") {
+        return format!("{}{}", scheme, reference);
+    }
+    let authority_end = match rest.strip_prefix("//") {
+        Some(after) => 2 + after.find('/').unwrap_or(after.len()),
+        None => 0,
+    };
+    let (authority, path) = rest.split_at(authority_end);
+    let path = path.split_once('?').map_or(path, |(path, _)| path);
+    let path = match reference.starts_with('/') {
+        true => reference.to_string(),
+        false => format!("{}{}", &path[..path.rfind('/').map_or(0, |i| i + 1)], reference),
+    };
+    return format!("{}{}{}", scheme, authority, remove_dots(&path));
+}
+
+fn remove_dots(path: &str) -> String {
+    let segments: Vec<&str> = path.split('/').collect();
+    let mut out: Vec<&str> = vec![];
+    for (i, segment) in segments.iter().enumerate() {
+        let last = i + 1 == segments.len();
+        match *segment {
+            "." | ".." => {
+                if *segment == ".." && out.last().is_some_and(|s| !s.is_empty() || out.len() > 1) {
+                    out.pop();
+                }
+                if last {
+                    out.push("");
+                }
+            },
+            segment => out.push(segment),
+        }
+    }
+    return out.join("/");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn join_references() {
+        let base = "https://example.com/schemas/order.json#/items";
+        for (reference, joined) in [
+            ("", "https://example.com/schemas/order.json"),
+            ("#/$defs/a", "https://example.com/schemas/order.json#/$defs/a"),
+            ("item.json", "https://example.com/schemas/item.json"),
+            ("./common/money.json#/$defs/amount", "https://example.com/schemas/common/money.json#/$defs/amount"),
+            ("../shared.json", "https://example.com/shared.json"),
+            ("../../../up.json", "https://example.com/up.json"),
+            ("/root.json", "https://example.com/root.json"),
+            ("//other.org/x.json", "https://other.org/x.json"),
+            ("urn:uuid:1234", "urn:uuid:1234"),
+        ] {
+            assert_eq!(join(base, reference), joined, "{}", reference);
+        }
+        assert_eq!(join("", "common.json#/a"), "common.json#/a");
+        assert_eq!(join("dir/main.json", "types.json"), "dir/types.json");
+    }
+
+    #[test]
+    fn resolve_within_and_across_documents() {
+        let mut registry = SchemaRegistry::new();
+        registry.register("https://example.com/main.json", parse(r#"{
+            "$defs": {
+                "name": {"type": "string"},
+                "a/b": {"const": 1},
+                "tagged": {"$anchor": "tag", "enum": ["x"]},
+                "embedded": {"$id": "nested/inner.json", "$defs": {"n": {"type": "null"}}}
+            }
+        }"#).unwrap());
+        registry.register("https://example.com/types.json", parse(r#"{"$defs": {"id": {"type": "integer"}}}"#).unwrap());
+        let base = "https://example.com/main.json";
+        for (reference, uri, schema) in [
+            ("#/$defs/name", "https://example.com/main.json", r#"{"type": "string"}"#),
+            ("#/$defs/a~1b", "https://example.com/main.json", r#"{"const": 1}"#),
+            ("#/%24defs/a~1b", "https://example.com/main.json", r#"{"const": 1}"#),
+            ("#tag", "https://example.com/main.json", r#"{"$anchor": "tag", "enum": ["x"]}"#),
+            ("types.json#/$defs/id", "https://example.com/types.json", r#"{"type": "integer"}"#),
+            ("nested/inner.json#/$defs/n", "https://example.com/nested/inner.json", r#"{"type": "null"}"#),
+        ] {
+            assert_eq!(registry.resolve(base, reference), Ok((uri.to_string(), parse(schema).unwrap())), "{}", reference);
+        }
+        assert_eq!(registry.resolve(base, "#/$defs/missing"), Err("'#/$defs/missing' does not point to anything in 'https://example.com/main.json'".to_string()));
+        assert_eq!(registry.resolve(base, "other.json"), Err("no schema is registered for 'https://example.com/other.json'".to_string()));
+    }
+
+    #[test]
+    fn loader_fetches_once() {
+        let calls = std::rc::Rc::new(std::cell::Cell::new(0));
+        let counter = calls.clone();
+        let mut registry = SchemaRegistry::new().with_loader(move |uri| {
+            counter.set(counter.get() + 1);
+            return match uri {
+                "https://example.com/remote.json" => parse(r#"{"$defs": {"flag": {"type": "boolean"}}}"#).map_err(|e| e.to_string()),
+                _ => Err("not found".to_string()),
+            };
+        });
+        for _ in 0..3 {
+            let (_, schema) = registry.resolve("https://example.com/", "remote.json#/$defs/flag").unwrap();
+            assert_eq!(schema, parse(r#"{"type": "boolean"}"#).unwrap());
+        }
+        assert_eq!(calls.get(), 1);
+        assert!(registry.get("https://example.com/remote.json#ignored").is_some());
+        assert_eq!(registry.resolve("https://example.com/", "gone.json"), Err("could not load 'https://example.com/gone.json': not found".to_string()));
+    }
+
+    #[test]
+    fn built_in_formats() {
+        let formats = Formats::new();
+        for (format, valid, invalid) in [
+            ("date-time", vec!["2024-02-29T23:59:60Z", "1999-12-31t08:30:00.125+05:30"], vec!["2023-02-29T00:00:00Z", "2024-01-01T24:00:00Z", "2024-01-01 00:00:00Z", "2024-01-01T00:00:00"]),
+            ("date", vec!["2000-02-29"], vec!["1900-02-29", "2024-13-01", "24-01-01"]),
+            ("time", vec!["08:30:00-01:00"], vec!["08:30:00+1:00", "8:30:00Z", "08:30:00.Z"]),
+            ("email", vec!["jo.smith+tag@example.co.uk", "a@[127.0.0.1]"], vec!["jo@", "@example.com", "jo..smith@example.com", "jo@-bad.com"]),
+            ("hostname", vec!["example.com", "a-b.c1.", "localhost"], vec!["", "-a.com", "a..com", "under_score.com"]),
+            ("ipv4", vec!["192.168.0.1"], vec!["256.0.0.1", "1.2.3", "01.2.3.4"]),
+            ("ipv6", vec!["::1", "2001:db8::8a2e:370:7334"], vec!["2001:db8:::1", "1.2.3.4"]),
+            ("uri", vec!["https://example.com/a?b=%20c#d", "urn:isbn:0451450523"], vec!["example.com/a", "https://example.com/a b", "http://x/%zz"]),
+            ("uuid", vec!["123e4567-e89b-12d3-a456-426614174000"], vec!["123e4567e89b12d3a456426614174000", "123e4567-e89b-12d3-a456-42661417400g"]),
+        ] {
+            for value in valid {
+                assert_eq!(formats.matches(format, value), Some(true), "{} {}", format, value);
+            }
+            for value in invalid {
+                assert_eq!(formats.matches(format, value), Some(false), "{} {}", format, value);
+            }
+        }
+        assert_eq!(formats.matches("color", "red"), None);
+    }
+
+    #[test]
+    fn assertive_and_custom_formats() {
+        let mut formats = Formats::new();
+        let bad = JItem::String("not an email".to_string());
+        assert_eq!(formats.check("email", &bad), Ok(()));
+        assert_eq!(formats.check("color", &bad), Ok(()));
+
+        formats = formats.assertive(true);
+        formats.register("color", |s| s.len() == 7 && s.starts_with('#') && s[1..].bytes().all(|b| b.is_ascii_hexdigit()));
+        assert_eq!(formats.check("email", &bad), Err("\"not an email\" is not a valid email".to_string()));
+        assert_eq!(formats.check("color", &JItem::String("#a0b1c2".to_string())), Ok(()));
+        assert!(formats.check("color", &JItem::String("red".to_string())).is_err());
+        assert_eq!(formats.check("email", &JItem::Number(1.0)), Ok(()));
+        assert_eq!(formats.check("phone", &bad), Err("unknown format 'phone'".to_string()));
+    }
+
+    fn violations(schema: &str, source: &str) -> Vec<(String, String, String)> {
+        let schema = CompiledSchema::compile(&parse(schema).unwrap(), &mut SchemaRegistry::new()).unwrap();
+        return schema.validate_str(source).unwrap().into_iter().map(|v| (v.pointer, source[v.span].to_string(), v.message)).collect();
+    }
+
+    fn owned(expected: &[(&str, &str, &str)]) -> Vec<(String, String, String)> {
+        return expected.iter().map(|(a, b, c)| (a.to_string(), b.to_string(), c.to_string())).collect();
+    }
+
+    #[test]
+    fn stream_violations_with_positions() {
+        let schema = r##"{
+            "type": "object",
+            "required": ["id", "tags", "owner"],
+            "additionalProperties": false,
+            "properties": {
+                "id": {"type": "integer", "minimum": 1},
+                "price": {"type": "number", "exclusiveMaximum": 100, "multipleOf": 0.01},
+                "name": {"type": ["string", "null"], "minLength": 2},
+                "tags": {"type": "array", "items": {"enum": ["a", "b"]}, "uniqueItems": true, "maxItems": 3},
+                "owner": {"$ref": "#/$defs/person"}
+            },
+            "$defs": {"person": {"properties": {"email": {"type": "string", "format": "email"}}, "required": ["email"]}}
+        }"##;
+        assert_eq!(violations(schema, r#"{"id": 3, "tags": ["a"], "owner": {"email": "x@y.com"}}"#), []);
+        let source = r#"{"id": 1.5, "price": 100, "name": "é", "tags": ["a", "c", "a", "b"], "owner": {}, "extra": [1]}"#;
+        assert_eq!(violations(schema, source), owned(&[
+            ("/id", "1.5", "expected integer, found number"),
+            ("/price", "100", "100 is not less than 100"),
+            ("/name", "\"é\"", "is 1 characters long, shorter than the minimum of 2"),
+            ("/tags/1", "\"c\"", "is not one of the values in 'enum'"),
+            ("/tags", r#"["a", "c", "a", "b"]"#, "has 4 items, more than the maximum of 3"),
+            ("/tags", r#"["a", "c", "a", "b"]"#, "items 0 and 2 are equal"),
+            ("/owner", "{}", "required property 'email' is missing"),
+            ("/extra", "[", "no value is allowed here"),
+        ]));
+    }
+
+    #[test]
+    fn stream_combinators_and_recursion() {
+        let schema = r##"{
+            "$defs": {"node": {"type": "object", "properties": {"value": {"type": "integer"}, "children": {"type": "array", "items": {"$ref": "#/$defs/node"}}}}},
+            "properties": {
+                "tree": {"$ref": "#/$defs/node"},
+                "id": {"anyOf": [{"type": "integer"}, {"type": "string", "format": "uuid"}]},
+                "shape": {"oneOf": [{"required": ["radius"]}, {"required": ["width"]}]},
+                "name": {"not": {"const": "admin"}}
+            }
+        }"##;
+        let source = r#"{"tree": {"value": 1, "children": [{"value": 2, "children": [{"value": "3"}]}]}, "id": true, "shape": {"radius": 1, "width": 2}, "name": "admin"}"#;
+        assert_eq!(violations(schema, source), owned(&[
+            ("/tree/children/0/children/0/value", "\"3\"", "expected integer, found string"),
+            ("/id", "true", "does not match any schema in 'anyOf'"),
+            ("/shape", r#"{"radius": 1, "width": 2}"#, "matches 2 schemas in 'oneOf' rather than exactly one"),
+            ("/name", "\"admin\"", "matches the schema in 'not'"),
+        ]));
+        assert_eq!(violations(schema, r#"{"id": "x", "shape": {"width": 1}, "name": "me"}"#), []);
+    }
+
+    #[test]
+    fn stream_stops_early() {
+        let mut registry = SchemaRegistry::new().with_loader(|_| parse(r#"{"type": "integer"}"#).map_err(|e| e.to_string()));
+        let schema = CompiledSchema::compile(&parse(r#"{"items": {"$ref": "https://example.com/int.json"}}"#).unwrap(), &mut registry).unwrap();
+        let source = r#"[1, "two", 3, "four", oops"#;
+        let first = schema.first_violation(source).unwrap().unwrap();
+        assert_eq!((first.pointer.as_str(), first.span), ("/1", 4..9));
+        assert!(schema.validate_str(source).is_err());
+        assert_eq!(schema.first_violation("[1, 2]"), Ok(None));
+
+        let assertive = CompiledSchema::compile(&parse(r#"{"format": "date"}"#).unwrap(), &mut SchemaRegistry::new()).unwrap().with_formats(Formats::new().assertive(true));
+        assert_eq!(assertive.validate_str(r#""2024-02-30""#).unwrap()[0].message, "\"2024-02-30\" is not a valid date");
+    }
+
+    #[test]
+    fn compile_errors() {
+        for (schema, error) in [
+            (r#"{"pattern": "^a"}"#, "keyword 'pattern' is not supported"),
+            (r##"{"$ref": "#/$defs/missing"}"##, "'#/$defs/missing' does not point to anything in ''"),
+            (r#"{"type": "text"}"#, "invalid type \"text\""),
+            (r#"{"anyOf": []}"#, "'anyOf' must be a non-empty array"),
+            (r#"[]"#, "schema must be an object or boolean, not []"),
+        ] {
+            assert_eq!(CompiledSchema::compile(&parse(schema).unwrap(), &mut SchemaRegistry::new()).err(), Some(error.to_string()), "{}", schema);
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use alloc::{string::{String, ToString}, vec, vec::Vec};
+
+use crate::{j_item::JItem, path::PathSegment, pointer::JsonPointer};
+
+impl JItem {
+    /// Every value, this one included, for which `matches` returns true when given the
+    /// path to the value and the value itself. Results are in depth-first order, with
+    /// object members visited in sorted key order so the order is repeatable.
+    pub fn find_all(&self, mut matches: impl FnMut(&[PathSegment], &JItem) -> bool) -> Vec<(JsonPointer, &JItem)> {
+        let mut found = vec![];
+        find(self, &mut vec![], &mut matches, &mut found);
+        return found;
+    }
+
+    /// Every value held under a member named `key`, at any depth.
+    pub fn find_key(&self, key: &str) -> Vec<(JsonPointer, &JItem)> {
+        return self.find_all(|path, _| matches!(path.last(), Some(PathSegment::Key(last)) if last == key));
+    }
+
+    /// Every string value containing `needle`.
+    pub fn find_string_containing(&self, needle: &str) -> Vec<(JsonPointer, &JItem)> {
+        return self.find_all(|_, value| matches!(value, JItem::String(s) if s.contains(needle)));
+    }
+}
+
+fn find<'a>(item: &'a JItem, path: &mut Vec<PathSegment>, matches: &mut impl FnMut(&[PathSegment], &JItem) -> bool, found: &mut Vec<(JsonPointer, &'a JItem)>) {
+    if matches(path, item) {
+        let tokens = path.iter().map(|segment| match segment {
+            PathSegment::Key(key) => key.clone(),
+            PathSegment::Index(index) => index.to_string(),
+        });
+        found.push((JsonPointer::from_tokens(tokens), item));
+    }
+    match item {
+        JItem::Object(hmap) => {
+            let mut keys: Vec<&String> = hmap.keys().collect();
+            keys.sort();
+            for key in keys {
+                path.push(PathSegment::Key(key.clone()));
+                find(&hmap[key], path, matches, found);
+                path.pop();
+            }
+        },
+        JItem::Array(elements) => {
+            for (index, element) in elements.iter().enumerate() {
+                path.push(PathSegment::Index(index));
+                find(element, path, matches, found);
+                path.pop();
+            }
+        },
+        _ => {},
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    fn pointers(found: Vec<(JsonPointer, &JItem)>) -> Vec<String> {
+        return found.iter().map(|(pointer, _)| pointer.to_string()).collect();
+    }
+
+    #[test]
+    fn find_by_key_and_content() {
+        let item = parse(r#"{"db": {"password": "hunter2", "hosts": ["a", "error-prone"]}, "users": [{"password": null, "log": "no error"}], "a/b": {"password": 1}}"#).unwrap();
+        assert_eq!(pointers(item.find_key("password")), ["/a~1b/password", "/db/password", "/users/0/password"]);
+        assert_eq!(pointers(item.find_string_containing("error")), ["/db/hosts/1", "/users/0/log"]);
+        let (pointer, value) = &item.find_key("password")[1];
+        assert_eq!((pointer.resolve(&item), *value), (Some(&JItem::String("hunter2".to_string())), &JItem::String("hunter2".to_string())));
+    }
+
+    #[test]
+    fn find_all_sees_paths() {
+        let item = parse(r#"[{"n": 1}, {"n": 5}, [7]]"#).unwrap();
+        let big = item.find_all(|_, value| value.number_value().is_some_and(|n| n > 2.0));
+        assert_eq!(pointers(big), ["/1/n", "/2/0"]);
+        let top_level = item.find_all(|path, _| path.len() == 1);
+        assert_eq!(pointers(top_level), ["/0", "/1", "/2"]);
+        assert_eq!(pointers(item.find_all(|path, _| path.is_empty())), [""]);
+    }
+}
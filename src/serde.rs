@@ -0,0 +1,857 @@
+//! Conversions between `JItem` and serde. `JItem` implements `Serialize` and
+//! `Deserialize`, `&JItem` implements `Deserializer`, and `ItemSerializer` builds a
+//! `JItem` from any `Serialize` type, so typed values can move in and out of a tree
+//! without going through text again.
+
+use std::fmt;
+
+use ::serde::{
+    de::{self, DeserializeSeed, Deserializer, EnumAccess, IntoDeserializer, MapAccess, SeqAccess, VariantAccess, Visitor},
+    forward_to_deserialize_any,
+    ser::{self, Impossible, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple, SerializeTupleStruct, SerializeTupleVariant, Serializer},
+    Deserialize, Serialize,
+};
+
+use crate::{j_item::JItem, report::ErrorReport, HashMap};
+
+/// An error converting between a `JItem` and a serde type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SerdeError {
+    message: String,
+}
+
+impl fmt::Display for SerdeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f.write_str(&self.message);
+    }
+}
+
+impl std::error::Error for SerdeError {}
+
+impl de::Error for SerdeError {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        return SerdeError { message: message.to_string() };
+    }
+}
+
+impl ser::Error for SerdeError {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        return SerdeError { message: message.to_string() };
+    }
+}
+
+/// Reads a `T` from an already parsed tree, as `T::deserialize(item)` does.
+pub fn from_item<'de, T: Deserialize<'de>>(item: &'de JItem) -> Result<T, SerdeError> {
+    return T::deserialize(item);
+}
+
+/// Builds a `JItem` from `value`, as `value.serialize(ItemSerializer)` does.
+pub fn to_item<T: Serialize + ?Sized>(value: &T) -> Result<JItem, SerdeError> {
+    return value.serialize(ItemSerializer);
+}
+
+impl Serialize for JItem {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        return match self {
+            JItem::Object(hmap) => {
+                let mut map = serializer.serialize_map(Some(hmap.len()))?;
+                for (key, value) in hmap {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            },
+            JItem::Array(elements) => {
+                let mut seq = serializer.serialize_seq(Some(elements.len()))?;
+                for element in elements {
+                    seq.serialize_element(element)?;
+                }
+                seq.end()
+            },
+            JItem::String(s) => serializer.serialize_str(s),
+            JItem::Number(_) | JItem::RawNumber(_) => match Integer::of(self) {
+                Some(Integer::Signed(n)) => serializer.serialize_i64(n),
+                Some(Integer::Unsigned(n)) => serializer.serialize_u64(n),
+                None => serializer.serialize_f64(self.number_value().unwrap()),
+            },
+            JItem::Bool(b) => serializer.serialize_bool(*b),
+            JItem::Null => serializer.serialize_unit(),
+        };
+    }
+}
+
+/// Written as `ErrorReport::to_item` lays it out.
+impl Serialize for ErrorReport {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        return self.to_item().serialize(serializer);
+    }
+}
+
+impl<'de> Deserialize<'de> for JItem {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        return deserializer.deserialize_any(ItemVisitor);
+    }
+}
+
+struct ItemVisitor;
+
+impl<'de> Visitor<'de> for ItemVisitor {
+    type Value = JItem;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f.write_str("any json value");
+    }
+
+    fn visit_bool<E>(self, b: bool) -> Result<JItem, E> {
+        return Ok(JItem::Bool(b));
+    }
+
+    fn visit_i64<E>(self, n: i64) -> Result<JItem, E> {
+        return Ok(JItem::Number(n as f64));
+    }
+
+    fn visit_u64<E>(self, n: u64) -> Result<JItem, E> {
+        return Ok(JItem::Number(n as f64));
+    }
+
+    fn visit_f64<E>(self, x: f64) -> Result<JItem, E> {
+        return Ok(JItem::Number(x));
+    }
+
+    fn visit_str<E>(self, s: &str) -> Result<JItem, E> {
+        return Ok(JItem::String(s.to_string()));
+    }
+
+    fn visit_string<E>(self, s: String) -> Result<JItem, E> {
+        return Ok(JItem::String(s));
+    }
+
+    fn visit_unit<E>(self) -> Result<JItem, E> {
+        return Ok(JItem::Null);
+    }
+
+    fn visit_none<E>(self) -> Result<JItem, E> {
+        return Ok(JItem::Null);
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<JItem, D::Error> {
+        return deserializer.deserialize_any(ItemVisitor);
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<JItem, A::Error> {
+        let mut elements = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(element) = seq.next_element()? {
+            elements.push(element);
+        }
+        return Ok(JItem::Array(elements));
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<JItem, A::Error> {
+        let mut hmap = HashMap::with_capacity(map.size_hint().unwrap_or(0));
+        while let Some((key, value)) = map.next_entry()? {
+            hmap.insert(key, value);
+        }
+        return Ok(JItem::Object(hmap));
+    }
+}
+
+/// A number that is a whole value in range of `i64` or `u64`, which serde types expect
+/// integers as.
+enum Integer {
+    Signed(i64),
+    Unsigned(u64),
+}
+
+impl Integer {
+    fn of(item: &JItem) -> Option<Integer> {
+        if let JItem::RawNumber(text) = item {
+            if let Ok(n) = text.parse() {
+                return Some(Integer::Unsigned(n));
+            }
+            if let Ok(n) = text.parse() {
+                return Some(Integer::Signed(n));
+            }
+        }
+        if let Some(n) = item.as_i64() {
+            return Some(if n >= 0 { Integer::Unsigned(n as u64) } else { Integer::Signed(n) });
+        }
+        let x = item.number_value()?;
+        // whole numbers from 2^63 up to 2^64, which only fit a u64
+        if (9223372036854775808.0..18446744073709551616.0).contains(&x) && x as u64 as f64 == x {
+            return Some(Integer::Unsigned(x as u64));
+        }
+        return None;
+    }
+}
+
+impl<'de> Deserializer<'de> for &'de JItem {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        return match self {
+            JItem::Object(hmap) => visitor.visit_map(Members { members: hmap.iter(), value: None }),
+            JItem::Array(elements) => visitor.visit_seq(Elements(elements.iter())),
+            JItem::String(s) => visitor.visit_borrowed_str(s),
+            JItem::Number(_) | JItem::RawNumber(_) => match Integer::of(self) {
+                Some(Integer::Signed(n)) => visitor.visit_i64(n),
+                Some(Integer::Unsigned(n)) => visitor.visit_u64(n),
+                None => visitor.visit_f64(self.number_value().unwrap()),
+            },
+            JItem::Bool(b) => visitor.visit_bool(*b),
+            JItem::Null => visitor.visit_unit(),
+        };
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        return match self {
+            JItem::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        };
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, SerdeError> {
+        return visitor.visit_newtype_struct(self);
+    }
+
+    /// Enums are read as serde writes them to JSON: a unit variant as its name, and any
+    /// other as an object with the variant name as its only key.
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value, SerdeError> {
+        return match self {
+            JItem::String(variant) => visitor.visit_enum(Variant { name: variant, value: None }),
+            JItem::Object(hmap) if hmap.len() == 1 => {
+                let (variant, value) = hmap.iter().next().unwrap();
+                visitor.visit_enum(Variant { name: variant, value: Some(value) })
+            },
+            _ => Err(de::Error::invalid_type(unexpected(self), &"a string or an object with one member")),
+        };
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        return visitor.visit_unit();
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier
+    }
+}
+
+impl<'de> IntoDeserializer<'de, SerdeError> for &'de JItem {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        return self;
+    }
+}
+
+fn unexpected(item: &JItem) -> de::Unexpected<'_> {
+    return match item {
+        JItem::Object(_) => de::Unexpected::Map,
+        JItem::Array(_) => de::Unexpected::Seq,
+        JItem::String(s) => de::Unexpected::Str(s),
+        JItem::Number(_) | JItem::RawNumber(_) => de::Unexpected::Float(item.number_value().unwrap()),
+        JItem::Bool(b) => de::Unexpected::Bool(*b),
+        JItem::Null => de::Unexpected::Unit,
+    };
+}
+
+struct Elements<'de>(std::slice::Iter<'de, JItem>);
+
+impl<'de> SeqAccess<'de> for Elements<'de> {
+    type Error = SerdeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, SerdeError> {
+        return self.0.next().map(|element| seed.deserialize(element)).transpose();
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        return Some(self.0.len());
+    }
+}
+
+struct Members<'de> {
+    members: std::collections::hash_map::Iter<'de, String, JItem>,
+    /// The value of the member whose key was just read.
+    value: Option<&'de JItem>,
+}
+
+impl<'de> MapAccess<'de> for Members<'de> {
+    type Error = SerdeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, SerdeError> {
+        let Some((key, value)) = self.members.next() else {
+            return Ok(None);
+        };
+        self.value = Some(value);
+        return seed.deserialize(de::value::BorrowedStrDeserializer::new(key)).map(Some);
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, SerdeError> {
+        let value = self.value.take().expect("next_value called before next_key");
+        return seed.deserialize(value);
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        return Some(self.members.len());
+    }
+}
+
+struct Variant<'de> {
+    name: &'de str,
+    value: Option<&'de JItem>,
+}
+
+impl<'de> EnumAccess<'de> for Variant<'de> {
+    type Error = SerdeError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), SerdeError> {
+        let name = seed.deserialize(de::value::BorrowedStrDeserializer::new(self.name))?;
+        return Ok((name, self));
+    }
+}
+
+impl<'de> VariantAccess<'de> for Variant<'de> {
+    type Error = SerdeError;
+
+    fn unit_variant(self) -> Result<(), SerdeError> {
+        return match self.value {
+            None | Some(JItem::Null) => Ok(()),
+            Some(value) => Err(de::Error::invalid_type(unexpected(value), &"a unit variant")),
+        };
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, SerdeError> {
+        return seed.deserialize(self.value.unwrap_or(&JItem::Null));
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, SerdeError> {
+        return self.value.unwrap_or(&JItem::Null).deserialize_seq(visitor);
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, SerdeError> {
+        return self.value.unwrap_or(&JItem::Null).deserialize_map(visitor);
+    }
+}
+
+/// A serde `Serializer` whose output is a `JItem`. Values are laid out as they would be
+/// in JSON written by serde: `None` and unit as null, bytes as an array of numbers, and
+/// enum variants other than unit ones as an object with the variant name as its only
+/// key. Integers too large to be exact as an `f64` are kept as a `RawNumber`. Map keys
+/// must be strings, chars, integers or bools.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ItemSerializer;
+
+fn integer(n: i128) -> JItem {
+    if (n as f64) as i128 == n && n.unsigned_abs() <= 1 << 53 {
+        return JItem::Number(n as f64);
+    }
+    return JItem::RawNumber(n.to_string());
+}
+
+fn variant(name: &str, value: JItem) -> JItem {
+    return JItem::Object(HashMap::from([(name.to_string(), value)]));
+}
+
+impl Serializer for ItemSerializer {
+    type Ok = JItem;
+    type Error = SerdeError;
+    type SerializeSeq = SeqBuilder;
+    type SerializeTuple = SeqBuilder;
+    type SerializeTupleStruct = SeqBuilder;
+    type SerializeTupleVariant = SeqBuilder;
+    type SerializeMap = MapBuilder;
+    type SerializeStruct = MapBuilder;
+    type SerializeStructVariant = MapBuilder;
+
+    fn serialize_bool(self, b: bool) -> Result<JItem, SerdeError> {
+        return Ok(JItem::Bool(b));
+    }
+
+    fn serialize_i8(self, n: i8) -> Result<JItem, SerdeError> {
+        return Ok(integer(n.into()));
+    }
+
+    fn serialize_i16(self, n: i16) -> Result<JItem, SerdeError> {
+        return Ok(integer(n.into()));
+    }
+
+    fn serialize_i32(self, n: i32) -> Result<JItem, SerdeError> {
+        return Ok(integer(n.into()));
+    }
+
+    fn serialize_i64(self, n: i64) -> Result<JItem, SerdeError> {
+        return Ok(integer(n.into()));
+    }
+
+    fn serialize_i128(self, n: i128) -> Result<JItem, SerdeError> {
+        return Ok(integer(n));
+    }
+
+    fn serialize_u8(self, n: u8) -> Result<JItem, SerdeError> {
+        return Ok(integer(n.into()));
+    }
+
+    fn serialize_u16(self, n: u16) -> Result<JItem, SerdeError> {
+        return Ok(integer(n.into()));
+    }
+
+    fn serialize_u32(self, n: u32) -> Result<JItem, SerdeError> {
+        return Ok(integer(n.into()));
+    }
+
+    fn serialize_u64(self, n: u64) -> Result<JItem, SerdeError> {
+        return Ok(integer(n.into()));
+    }
+
+    fn serialize_u128(self, n: u128) -> Result<JItem, SerdeError> {
+        return match i128::try_from(n) {
+            Ok(n) => Ok(integer(n)),
+            Err(_) => Ok(JItem::RawNumber(n.to_string())),
+        };
+    }
+
+    fn serialize_f32(self, x: f32) -> Result<JItem, SerdeError> {
+        return Ok(JItem::Number(x.into()));
+    }
+
+    fn serialize_f64(self, x: f64) -> Result<JItem, SerdeError> {
+        return Ok(JItem::Number(x));
+    }
+
+    fn serialize_char(self, c: char) -> Result<JItem, SerdeError> {
+        return Ok(JItem::String(c.to_string()));
+    }
+
+    fn serialize_str(self, s: &str) -> Result<JItem, SerdeError> {
+        return Ok(JItem::String(s.to_string()));
+    }
+
+    fn serialize_bytes(self, bytes: &[u8]) -> Result<JItem, SerdeError> {
+        return Ok(JItem::Array(bytes.iter().map(|&b| JItem::Number(b.into())).collect()));
+    }
+
+    fn serialize_none(self) -> Result<JItem, SerdeError> {
+        return Ok(JItem::Null);
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<JItem, SerdeError> {
+        return value.serialize(self);
+    }
+
+    fn serialize_unit(self) -> Result<JItem, SerdeError> {
+        return Ok(JItem::Null);
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<JItem, SerdeError> {
+        return Ok(JItem::Null);
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<JItem, SerdeError> {
+        return Ok(JItem::String(variant.to_string()));
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<JItem, SerdeError> {
+        return value.serialize(self);
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(self, _name: &'static str, _index: u32, name: &'static str, value: &T) -> Result<JItem, SerdeError> {
+        return Ok(variant(name, value.serialize(self)?));
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqBuilder, SerdeError> {
+        return Ok(SeqBuilder { variant: None, elements: Vec::with_capacity(len.unwrap_or(0)) });
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqBuilder, SerdeError> {
+        return self.serialize_seq(Some(len));
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SeqBuilder, SerdeError> {
+        return self.serialize_seq(Some(len));
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _index: u32, name: &'static str, len: usize) -> Result<SeqBuilder, SerdeError> {
+        return Ok(SeqBuilder { variant: Some(name), elements: Vec::with_capacity(len) });
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<MapBuilder, SerdeError> {
+        return Ok(MapBuilder { variant: None, members: HashMap::with_capacity(len.unwrap_or(0)), key: None });
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<MapBuilder, SerdeError> {
+        return self.serialize_map(Some(len));
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _index: u32, name: &'static str, len: usize) -> Result<MapBuilder, SerdeError> {
+        return Ok(MapBuilder { variant: Some(name), members: HashMap::with_capacity(len), key: None });
+    }
+}
+
+/// Collects the elements of a sequence, tuple or tuple variant.
+pub struct SeqBuilder {
+    variant: Option<&'static str>,
+    elements: Vec<JItem>,
+}
+
+impl SeqBuilder {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.elements.push(value.serialize(ItemSerializer)?);
+        return Ok(());
+    }
+
+    fn build(self) -> JItem {
+        let array = JItem::Array(self.elements);
+        return match self.variant {
+            Some(name) => variant(name, array),
+            None => array,
+        };
+    }
+}
+
+impl SerializeSeq for SeqBuilder {
+    type Ok = JItem;
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        return self.push(value);
+    }
+
+    fn end(self) -> Result<JItem, SerdeError> {
+        return Ok(self.build());
+    }
+}
+
+impl SerializeTuple for SeqBuilder {
+    type Ok = JItem;
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        return self.push(value);
+    }
+
+    fn end(self) -> Result<JItem, SerdeError> {
+        return Ok(self.build());
+    }
+}
+
+impl SerializeTupleStruct for SeqBuilder {
+    type Ok = JItem;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        return self.push(value);
+    }
+
+    fn end(self) -> Result<JItem, SerdeError> {
+        return Ok(self.build());
+    }
+}
+
+impl SerializeTupleVariant for SeqBuilder {
+    type Ok = JItem;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        return self.push(value);
+    }
+
+    fn end(self) -> Result<JItem, SerdeError> {
+        return Ok(self.build());
+    }
+}
+
+/// Collects the members of a map, struct or struct variant.
+pub struct MapBuilder {
+    variant: Option<&'static str>,
+    members: HashMap<String, JItem>,
+    /// The key whose value is to come next, for maps.
+    key: Option<String>,
+}
+
+impl MapBuilder {
+    fn insert<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), SerdeError> {
+        self.members.insert(key.to_string(), value.serialize(ItemSerializer)?);
+        return Ok(());
+    }
+
+    fn build(self) -> JItem {
+        let object = JItem::Object(self.members);
+        return match self.variant {
+            Some(name) => variant(name, object),
+            None => object,
+        };
+    }
+}
+
+impl SerializeMap for MapBuilder {
+    type Ok = JItem;
+    type Error = SerdeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerdeError> {
+        self.key = Some(key.serialize(KeySerializer)?);
+        return Ok(());
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        let key = self.key.take().expect("serialize_value called before serialize_key");
+        self.members.insert(key, value.serialize(ItemSerializer)?);
+        return Ok(());
+    }
+
+    fn end(self) -> Result<JItem, SerdeError> {
+        return Ok(self.build());
+    }
+}
+
+impl SerializeStruct for MapBuilder {
+    type Ok = JItem;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), SerdeError> {
+        return self.insert(key, value);
+    }
+
+    fn end(self) -> Result<JItem, SerdeError> {
+        return Ok(self.build());
+    }
+}
+
+impl SerializeStructVariant for MapBuilder {
+    type Ok = JItem;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), SerdeError> {
+        return self.insert(key, value);
+    }
+
+    fn end(self) -> Result<JItem, SerdeError> {
+        return Ok(self.build());
+    }
+}
+
+/// Turns map keys into strings, as JSON object keys must be.
+struct KeySerializer;
+
+fn key_error() -> SerdeError {
+    return SerdeError { message: "map keys must be strings, chars, integers or bools".to_string() };
+}
+
+impl Serializer for KeySerializer {
+    type Ok = String;
+    type Error = SerdeError;
+    type SerializeSeq = Impossible<String, SerdeError>;
+    type SerializeTuple = Impossible<String, SerdeError>;
+    type SerializeTupleStruct = Impossible<String, SerdeError>;
+    type SerializeTupleVariant = Impossible<String, SerdeError>;
+    type SerializeMap = Impossible<String, SerdeError>;
+    type SerializeStruct = Impossible<String, SerdeError>;
+    type SerializeStructVariant = Impossible<String, SerdeError>;
+
+    fn serialize_bool(self, b: bool) -> Result<String, SerdeError> {
+        return Ok(b.to_string());
+    }
+
+    fn serialize_i8(self, n: i8) -> Result<String, SerdeError> {
+        return Ok(n.to_string());
+    }
+
+    fn serialize_i16(self, n: i16) -> Result<String, SerdeError> {
+        return Ok(n.to_string());
+    }
+
+    fn serialize_i32(self, n: i32) -> Result<String, SerdeError> {
+        return Ok(n.to_string());
+    }
+
+    fn serialize_i64(self, n: i64) -> Result<String, SerdeError> {
+        return Ok(n.to_string());
+    }
+
+    fn serialize_i128(self, n: i128) -> Result<String, SerdeError> {
+        return Ok(n.to_string());
+    }
+
+    fn serialize_u8(self, n: u8) -> Result<String, SerdeError> {
+        return Ok(n.to_string());
+    }
+
+    fn serialize_u16(self, n: u16) -> Result<String, SerdeError> {
+        return Ok(n.to_string());
+    }
+
+    fn serialize_u32(self, n: u32) -> Result<String, SerdeError> {
+        return Ok(n.to_string());
+    }
+
+    fn serialize_u64(self, n: u64) -> Result<String, SerdeError> {
+        return Ok(n.to_string());
+    }
+
+    fn serialize_u128(self, n: u128) -> Result<String, SerdeError> {
+        return Ok(n.to_string());
+    }
+
+    fn serialize_f32(self, _x: f32) -> Result<String, SerdeError> {
+        return Err(key_error());
+    }
+
+    fn serialize_f64(self, _x: f64) -> Result<String, SerdeError> {
+        return Err(key_error());
+    }
+
+    fn serialize_char(self, c: char) -> Result<String, SerdeError> {
+        return Ok(c.to_string());
+    }
+
+    fn serialize_str(self, s: &str) -> Result<String, SerdeError> {
+        return Ok(s.to_string());
+    }
+
+    fn serialize_bytes(self, _bytes: &[u8]) -> Result<String, SerdeError> {
+        return Err(key_error());
+    }
+
+    fn serialize_none(self) -> Result<String, SerdeError> {
+        return Err(key_error());
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _value: &T) -> Result<String, SerdeError> {
+        return Err(key_error());
+    }
+
+    fn serialize_unit(self) -> Result<String, SerdeError> {
+        return Err(key_error());
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String, SerdeError> {
+        return Err(key_error());
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<String, SerdeError> {
+        return Ok(variant.to_string());
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<String, SerdeError> {
+        return value.serialize(self);
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(self, _name: &'static str, _index: u32, _variant: &'static str, _value: &T) -> Result<String, SerdeError> {
+        return Err(key_error());
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, SerdeError> {
+        return Err(key_error());
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, SerdeError> {
+        return Err(key_error());
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeTupleStruct, SerdeError> {
+        return Err(key_error());
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _index: u32, _variant: &'static str, _len: usize) -> Result<Self::SerializeTupleVariant, SerdeError> {
+        return Err(key_error());
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, SerdeError> {
+        return Err(key_error());
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct, SerdeError> {
+        return Err(key_error());
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _index: u32, _variant: &'static str, _len: usize) -> Result<Self::SerializeStructVariant, SerdeError> {
+        return Err(key_error());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use ::serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::parse;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Event<'a> {
+        id: u64,
+        kind: Kind,
+        #[serde(borrow)]
+        source: &'a str,
+        tags: Vec<String>,
+        ratio: Option<f32>,
+        extra: JItem,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Kind {
+        Click,
+        Move { x: i32, y: i32 },
+        Key(char),
+    }
+
+    #[test]
+    fn typed_values_from_a_tree() {
+        let item = parse(r#"{"id": 9223372036854775808, "kind": {"move": {"x": -3, "y": 4}}, "source": "mouse", "tags": ["a"], "ratio": null, "extra": {"n": [1.5]}}"#).unwrap();
+        let event: Event = from_item(&item).unwrap();
+        assert_eq!(event, Event { id: 1 << 63, kind: Kind::Move { x: -3, y: 4 }, source: "mouse", tags: vec!["a".to_string()], ratio: None, extra: parse(r#"{"n": [1.5]}"#).unwrap() });
+        assert_eq!(Kind::deserialize(&JItem::String("click".to_string())), Ok(Kind::Click));
+        assert_eq!(from_item::<Kind>(&parse(r#"{"key": "k"}"#).unwrap()), Ok(Kind::Key('k')));
+
+        // dynamic inspection first, then typed extraction of one part
+        let tags: Vec<String> = from_item(item.pointer("/tags").unwrap()).unwrap();
+        assert_eq!(tags, ["a"]);
+    }
+
+    #[test]
+    fn type_mismatches() {
+        assert_eq!(from_item::<u8>(&JItem::Number(300.0)).unwrap_err().to_string(), "invalid value: integer `300`, expected u8");
+        assert_eq!(from_item::<i64>(&JItem::Number(1.5)).unwrap_err().to_string(), "invalid type: floating point `1.5`, expected i64");
+        assert_eq!(from_item::<Kind>(&JItem::Bool(true)).unwrap_err().to_string(), "invalid type: boolean `true`, expected a string or an object with one member");
+        assert!(from_item::<Event>(&parse(r#"{"id": 1}"#).unwrap()).unwrap_err().to_string().starts_with("missing field"));
+    }
+
+    #[test]
+    fn items_through_serde() {
+        let item = parse(r#"{"a": [1, -2, 2.5, "x", null, true], "b": {}}"#).unwrap();
+        let copy = JItem::deserialize(&item).unwrap();
+        assert_eq!(copy, item);
+        let JItem::Object(hmap) = &copy else {
+            panic!("expected an object");
+        };
+        assert_eq!(hmap["a"].pointer("/1"), Some(&JItem::Number(-2.0)));
+    }
+
+    #[test]
+    fn typed_values_into_a_tree() {
+        let event = Event { id: 1 << 63, kind: Kind::Move { x: -3, y: 4 }, source: "mouse", tags: vec![], ratio: Some(0.5), extra: JItem::Null };
+        let item = to_item(&event).unwrap();
+        assert_eq!(item, parse(r#"{"id": 9223372036854775808, "kind": {"move": {"x": -3, "y": 4}}, "source": "mouse", "tags": [], "ratio": 0.5, "extra": null}"#).unwrap());
+        assert_eq!(from_item::<Event>(&item), Ok(event));
+        assert_eq!(to_item(&Kind::Click), Ok(JItem::String("click".to_string())));
+        assert_eq!(to_item(&Kind::Key('k')), Ok(parse(r#"{"key": "k"}"#).unwrap()));
+        assert_eq!(to_item(&(1u8, "a", [true])), Ok(parse(r#"[1, "a", [true]]"#).unwrap()));
+    }
+
+    #[test]
+    fn integers_keep_their_precision() {
+        assert_eq!(to_item(&u64::MAX), Ok(JItem::RawNumber("18446744073709551615".to_string())));
+        assert_eq!(to_item(&(i64::MIN + 1)), Ok(JItem::RawNumber("-9223372036854775807".to_string())));
+        assert_eq!(to_item(&(1u64 << 53)), Ok(JItem::Number(9007199254740992.0)));
+        assert_eq!(from_item::<u64>(&to_item(&u64::MAX).unwrap()), Ok(u64::MAX));
+    }
+
+    #[test]
+    fn map_keys() {
+        let map = BTreeMap::from([(1, "a"), (-2, "b")]);
+        assert_eq!(to_item(&map).unwrap().to_canonical_string(), r#"{"-2":"b","1":"a"}"#);
+        let map = BTreeMap::from([((1, 2), 3)]);
+        assert_eq!(to_item(&map).unwrap_err().to_string(), "map keys must be strings, chars, integers or bools");
+    }
+}
@@ -0,0 +1,435 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    error::JsonError,
+    j_item::JItem,
+    parse,
+    pointer::{array_index, parse_pointer},
+};
+
+/// A value tree whose strings and containers sit behind `Arc`s, so identical subtrees
+/// can be stored once and referenced from many places. Build one with
+/// `SharedJItem::compact` to share repeated content, or `From<&JItem>` for a plain copy.
+///
+/// Cloning is cheap and clones can be handed to other threads. Changes go through
+/// `set_pointer` and `remove_pointer`, which copy only the containers on the path to the
+/// change, so a modified clone shares everything else with the original.
+#[derive(Debug, Clone)]
+pub enum SharedJItem {
+    Object(Arc<HashMap<Arc<str>, SharedJItem>>),
+    String(Arc<str>),
+    Array(Arc<Vec<SharedJItem>>),
+    Number(f64),
+    Bool(bool),
+    Null,
+}
+
+impl SharedJItem {
+    /// Converts `item`, storing every distinct string and every distinct subtree once.
+    /// Documents with heavy repetition, such as per-row metadata, shrink accordingly.
+    /// Since equal values are merged, `-0` comes back as `0`. Raw numbers become plain
+    /// numbers.
+    pub fn compact(item: &JItem) -> SharedJItem {
+        let mut interner = Interner::default();
+        let id = interner.intern(item);
+        return interner.nodes.swap_remove(id);
+    }
+
+    pub fn to_item(&self) -> JItem {
+        return match self {
+            SharedJItem::Object(hmap) => {
+                JItem::Object(hmap.iter().map(|(key, value)| (key.to_string(), value.to_item())).collect())
+            },
+            SharedJItem::String(s) => JItem::String(s.to_string()),
+            SharedJItem::Array(elements) => JItem::Array(elements.iter().map(SharedJItem::to_item).collect()),
+            SharedJItem::Number(x) => JItem::Number(*x),
+            SharedJItem::Bool(b) => JItem::Bool(*b),
+            SharedJItem::Null => JItem::Null,
+        };
+    }
+
+    /// Looks up the value referenced by an RFC 6901 JSON Pointer.
+    pub fn pointer(&self, pointer: &str) -> Option<&SharedJItem> {
+        return self.resolve(&parse_pointer(pointer).ok()?);
+    }
+
+    /// Sets the value at `pointer` like `JItem::set_pointer`, returning the value it
+    /// replaced. Containers on the path that are shared with other clones are copied
+    /// first; their children are not. Nothing is copied when this fails.
+    pub fn set_pointer(&mut self, pointer: &str, value: SharedJItem) -> Result<Option<SharedJItem>, String> {
+        let mut tokens = parse_pointer(pointer)?;
+        let Some(last) = tokens.pop() else {
+            return Ok(Some(std::mem::replace(self, value)));
+        };
+        let index = match self.resolve(&tokens) {
+            None => return Err(format!("parent of json pointer '{}' does not exist", pointer)),
+            Some(SharedJItem::Object(_)) => None,
+            Some(SharedJItem::Array(elements)) => match if last == "-" { Some(elements.len()) } else { array_index(&last) } {
+                Some(index) if index <= elements.len() => Some(index),
+                _ => return Err(format!("array index '{}' out of bounds in json pointer '{}'", last, pointer)),
+            },
+            Some(_) => return Err(format!("cannot set a member of a scalar value at json pointer '{}'", pointer)),
+        };
+        return Ok(match (self.resolve_mut(&tokens), index) {
+            (SharedJItem::Object(hmap), _) => Arc::make_mut(hmap).insert(Arc::from(last), value),
+            (SharedJItem::Array(elements), Some(index)) if index == elements.len() => {
+                Arc::make_mut(elements).push(value);
+                None
+            },
+            (SharedJItem::Array(elements), Some(index)) => Some(std::mem::replace(&mut Arc::make_mut(elements)[index], value)),
+            _ => unreachable!("checked above"),
+        });
+    }
+
+    /// Removes and returns the value at `pointer` like `JItem::remove_pointer`, copying
+    /// shared containers on the path as `set_pointer` does.
+    pub fn remove_pointer(&mut self, pointer: &str) -> Option<SharedJItem> {
+        let mut tokens = parse_pointer(pointer).ok()?;
+        let last = tokens.pop()?;
+        let index = match self.resolve(&tokens)? {
+            SharedJItem::Object(hmap) => {
+                hmap.get(last.as_str())?;
+                None
+            },
+            SharedJItem::Array(elements) => Some(array_index(&last).filter(|index| *index < elements.len())?),
+            _ => return None,
+        };
+        return match (self.resolve_mut(&tokens), index) {
+            (SharedJItem::Object(hmap), _) => Arc::make_mut(hmap).remove(last.as_str()),
+            (SharedJItem::Array(elements), Some(index)) => Some(Arc::make_mut(elements).remove(index)),
+            _ => unreachable!("checked above"),
+        };
+    }
+
+    fn resolve(&self, tokens: &[String]) -> Option<&SharedJItem> {
+        let mut current = self;
+        for token in tokens {
+            current = match current {
+                SharedJItem::Object(hmap) => hmap.get(token.as_str())?,
+                SharedJItem::Array(elements) => elements.get(array_index(token)?)?,
+                _ => return None,
+            };
+        }
+        return Some(current);
+    }
+
+    /// The value at `tokens`, which must exist, making each container on the way unique
+    /// to this tree.
+    fn resolve_mut(&mut self, tokens: &[String]) -> &mut SharedJItem {
+        let mut current = self;
+        for token in tokens {
+            current = match current {
+                SharedJItem::Object(hmap) => Arc::make_mut(hmap).get_mut(token.as_str()).unwrap(),
+                SharedJItem::Array(elements) => &mut Arc::make_mut(elements)[array_index(token).unwrap()],
+                _ => unreachable!("path was resolved before"),
+            };
+        }
+        return current;
+    }
+
+    /// Whether both values are backed by the same allocation, i.e. were shared.
+    pub fn ptr_eq(&self, other: &SharedJItem) -> bool {
+        return match (self, other) {
+            (SharedJItem::Object(a), SharedJItem::Object(b)) => Arc::ptr_eq(a, b),
+            (SharedJItem::String(a), SharedJItem::String(b)) => Arc::ptr_eq(a, b),
+            (SharedJItem::Array(a), SharedJItem::Array(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        };
+    }
+}
+
+impl From<&JItem> for SharedJItem {
+    fn from(item: &JItem) -> Self {
+        return match item {
+            JItem::Object(hmap) => {
+                SharedJItem::Object(Arc::new(hmap.iter().map(|(key, value)| (Arc::from(key.as_str()), value.into())).collect()))
+            },
+            JItem::String(s) => SharedJItem::String(Arc::from(s.as_str())),
+            JItem::Array(elements) => SharedJItem::Array(Arc::new(elements.iter().map(SharedJItem::from).collect())),
+            JItem::Number(_) | JItem::RawNumber(_) => SharedJItem::Number(item.number_value().unwrap()),
+            JItem::Bool(b) => SharedJItem::Bool(*b),
+            JItem::Null => SharedJItem::Null,
+        };
+    }
+}
+
+impl From<JItem> for SharedJItem {
+    fn from(item: JItem) -> Self {
+        return SharedJItem::from(&item);
+    }
+}
+
+/// Structural equality with the same number semantics as `JItem`.
+impl PartialEq for SharedJItem {
+    fn eq(&self, other: &Self) -> bool {
+        if self.ptr_eq(other) {
+            return true;
+        }
+        return match (self, other) {
+            (SharedJItem::Object(a), SharedJItem::Object(b)) => a == b,
+            (SharedJItem::String(a), SharedJItem::String(b)) => a == b,
+            (SharedJItem::Array(a), SharedJItem::Array(b)) => a == b,
+            (SharedJItem::Number(a), SharedJItem::Number(b)) => a == b || (a.is_nan() && b.is_nan()),
+            (SharedJItem::Bool(a), SharedJItem::Bool(b)) => a == b,
+            (SharedJItem::Null, SharedJItem::Null) => true,
+            _ => false,
+        };
+    }
+}
+
+impl Eq for SharedJItem {}
+
+/// A pool of subtrees kept across many documents, so content repeated between them,
+/// such as the common parts of near-identical event payloads, is stored once for all
+/// of them. Each document is converted as `SharedJItem::compact` converts one, but
+/// against everything the pool has seen before.
+///
+/// The pool holds on to every distinct subtree it has seen, so memory only goes down
+/// once the documents are dropped and the pool is cleared or dropped too.
+#[derive(Default)]
+pub struct SubtreePool {
+    interner: Interner,
+    stats: PoolStats,
+}
+
+/// Counts from a `SubtreePool`. Every value in every document added counts as one
+/// lookup, and as a hit when an equal value was already pooled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    pub lookups: u64,
+    pub hits: u64,
+    /// Distinct values held by the pool.
+    pub nodes: usize,
+}
+
+impl PoolStats {
+    /// The share of lookups that were hits, or 0 before any lookup.
+    pub fn hit_rate(&self) -> f64 {
+        if self.lookups == 0 {
+            return 0.0;
+        }
+        return self.hits as f64 / self.lookups as f64;
+    }
+}
+
+impl SubtreePool {
+    pub fn new() -> Self {
+        return SubtreePool::default();
+    }
+
+    /// Converts `item`, reusing any subtree equal to one already pooled.
+    pub fn add(&mut self, item: &JItem) -> SharedJItem {
+        let before = self.interner.nodes.len();
+        let (id, lookups) = self.interner.intern_counting(item);
+        let added = self.interner.nodes.len() - before;
+        self.stats.lookups += lookups;
+        self.stats.hits += lookups - added as u64;
+        self.stats.nodes = self.interner.nodes.len();
+        return self.interner.nodes[id].clone();
+    }
+
+    /// Parses `input` and adds the result to the pool.
+    pub fn parse(&mut self, input: &str) -> Result<SharedJItem, JsonError> {
+        return Ok(self.add(&parse(input)?));
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        return self.stats;
+    }
+
+    /// Forgets every pooled value, keeping the counts. Documents already returned keep
+    /// their values.
+    pub fn clear(&mut self) {
+        self.interner = Interner::default();
+        self.stats.nodes = 0;
+    }
+}
+
+// identifies a node by its content, with children referred to by their interned ids
+#[derive(PartialEq, Eq, Hash)]
+enum NodeKey {
+    Object(Vec<(usize, usize)>),
+    String(usize),
+    Array(Vec<usize>),
+    Number(u64),
+    Bool(bool),
+    Null,
+}
+
+#[derive(Default)]
+struct Interner {
+    string_ids: HashMap<Arc<str>, usize>,
+    strings: Vec<Arc<str>>,
+    node_ids: HashMap<NodeKey, usize>,
+    nodes: Vec<SharedJItem>,
+}
+
+impl Interner {
+    /// Interns `item`, also returning how many values it holds, itself included.
+    fn intern_counting(&mut self, item: &JItem) -> (usize, u64) {
+        let mut count = 0;
+        let id = self.intern_with(item, &mut count);
+        return (id, count);
+    }
+
+    fn intern(&mut self, item: &JItem) -> usize {
+        return self.intern_with(item, &mut 0);
+    }
+
+    fn intern_with(&mut self, item: &JItem, count: &mut u64) -> usize {
+        *count += 1;
+        let key = match item {
+            JItem::Object(hmap) => {
+                let mut members: Vec<_> = hmap
+                    .iter()
+                    .map(|(key, value)| (self.intern_string(key), self.intern_with(value, count)))
+                    .collect();
+                members.sort_unstable();
+                NodeKey::Object(members)
+            },
+            JItem::String(s) => NodeKey::String(self.intern_string(s)),
+            JItem::Array(elements) => NodeKey::Array(elements.iter().map(|element| self.intern_with(element, count)).collect()),
+            // adding 0.0 turns -0.0 into 0.0, matching equality
+            JItem::Number(_) | JItem::RawNumber(_) => NodeKey::Number((item.number_value().unwrap() + 0.0).to_bits()),
+            JItem::Bool(b) => NodeKey::Bool(*b),
+            JItem::Null => NodeKey::Null,
+        };
+        if let Some(id) = self.node_ids.get(&key) {
+            return *id;
+        }
+
+        let node = match &key {
+            NodeKey::Object(members) => SharedJItem::Object(Arc::new(
+                members.iter().map(|(key, value)| (self.strings[*key].clone(), self.nodes[*value].clone())).collect(),
+            )),
+            NodeKey::String(id) => SharedJItem::String(self.strings[*id].clone()),
+            NodeKey::Array(elements) => SharedJItem::Array(Arc::new(elements.iter().map(|id| self.nodes[*id].clone()).collect())),
+            NodeKey::Number(bits) => SharedJItem::Number(f64::from_bits(*bits)),
+            NodeKey::Bool(b) => SharedJItem::Bool(*b),
+            NodeKey::Null => SharedJItem::Null,
+        };
+        let id = self.nodes.len();
+        self.nodes.push(node);
+        self.node_ids.insert(key, id);
+        return id;
+    }
+
+    fn intern_string(&mut self, s: &str) -> usize {
+        if let Some(id) = self.string_ids.get(s) {
+            return *id;
+        }
+        let id = self.strings.len();
+        let shared: Arc<str> = Arc::from(s);
+        self.strings.push(shared.clone());
+        self.string_ids.insert(shared, id);
+        return id;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn compact_round_trip() {
+        let item = parse(r#"{"a": [1, "x", {"b": null}], "c": true, "d": -0.5}"#).unwrap();
+        assert_eq!(SharedJItem::compact(&item).to_item(), item);
+    }
+
+    #[test]
+    fn compact_shares_identical_subtrees() {
+        let item = parse(r#"[{"meta": {"source": "sensor", "unit": "C"}, "v": 1}, {"meta": {"unit": "C", "source": "sensor"}, "v": 2}]"#).unwrap();
+        let SharedJItem::Array(rows) = SharedJItem::compact(&item) else {
+            panic!("expected an array");
+        };
+        let (SharedJItem::Object(first), SharedJItem::Object(second)) = (&rows[0], &rows[1]) else {
+            panic!("expected objects");
+        };
+        assert!(first["meta"].ptr_eq(&second["meta"]));
+        assert!(!rows[0].ptr_eq(&rows[1]));
+    }
+
+    #[test]
+    fn compact_shares_strings() {
+        let item = parse(r#"["repeated", {"repeated": "repeated"}]"#).unwrap();
+        let SharedJItem::Array(elements) = SharedJItem::compact(&item) else {
+            panic!("expected an array");
+        };
+        let SharedJItem::Object(hmap) = &elements[1] else {
+            panic!("expected an object");
+        };
+        let (key, value) = hmap.iter().next().unwrap();
+        let SharedJItem::String(first) = &elements[0] else {
+            panic!("expected a string");
+        };
+        assert!(Arc::ptr_eq(first, key));
+        assert!(elements[0].ptr_eq(value));
+    }
+
+    #[test]
+    fn modified_clone_shares_untouched_subtrees() {
+        let original = SharedJItem::from(&parse(r#"{"config": {"limits": [1, 2]}, "users": [{"name": "a"}, {"name": "b"}]}"#).unwrap());
+        let mut copy = original.clone();
+        assert_eq!(copy.set_pointer("/users/1/name", SharedJItem::String(Arc::from("c"))), Ok(Some(SharedJItem::String(Arc::from("b")))));
+        assert_eq!(copy.remove_pointer("/config/limits/0"), Some(SharedJItem::Number(1.0)));
+        assert_eq!(copy.set_pointer("/users/-", SharedJItem::Null), Ok(None));
+
+        assert_eq!(original.pointer("/users/1/name").unwrap().to_item(), JItem::String("b".to_string()));
+        assert_eq!(original.pointer("/config/limits").unwrap().to_item(), parse("[1, 2]").unwrap());
+        assert_eq!(copy.to_item(), parse(r#"{"config": {"limits": [2]}, "users": [{"name": "a"}, {"name": "c"}, null]}"#).unwrap());
+        assert!(copy.pointer("/users/0").unwrap().ptr_eq(original.pointer("/users/0").unwrap()));
+        assert!(!copy.pointer("/users/1").unwrap().ptr_eq(original.pointer("/users/1").unwrap()));
+    }
+
+    #[test]
+    fn pool_shares_across_documents() {
+        let mut pool = SubtreePool::new();
+        let first = pool.parse(r#"{"meta": {"source": "sensor", "unit": "C"}, "v": 1}"#).unwrap();
+        assert_eq!(pool.stats(), PoolStats { lookups: 5, hits: 0, nodes: 5 });
+        let second = pool.parse(r#"{"v": 2, "meta": {"unit": "C", "source": "sensor"}}"#).unwrap();
+        assert!(first.pointer("/meta").unwrap().ptr_eq(second.pointer("/meta").unwrap()));
+        assert_eq!(pool.stats(), PoolStats { lookups: 10, hits: 3, nodes: 7 });
+        assert_eq!(pool.stats().hit_rate(), 0.3);
+        assert_eq!(second.to_item(), parse(r#"{"v": 2, "meta": {"unit": "C", "source": "sensor"}}"#).unwrap());
+
+        pool.clear();
+        let third = pool.parse(r#"{"source": "sensor", "unit": "C"}"#).unwrap();
+        assert!(!third.ptr_eq(first.pointer("/meta").unwrap()));
+        assert_eq!(pool.stats().nodes, 3);
+        assert!(pool.parse("[1,").is_err());
+    }
+
+    #[test]
+    fn readable_across_threads() {
+        let item = SharedJItem::from(&parse(r#"{"a": [1, 2, 3]}"#).unwrap());
+        let handles: Vec<_> = (0..4).map(|_| {
+            let item = item.clone();
+            std::thread::spawn(move || item.pointer("/a/2").cloned())
+        }).collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), Some(SharedJItem::Number(3.0)));
+        }
+    }
+
+    #[test]
+    fn failed_changes_keep_sharing() {
+        let original = SharedJItem::from(&parse(r#"{"a": {"b": [1]}}"#).unwrap());
+        let mut copy = original.clone();
+        assert_eq!(copy.set_pointer("/a/x/y", SharedJItem::Null), Err("parent of json pointer '/a/x/y' does not exist".to_string()));
+        assert_eq!(copy.set_pointer("/a/b/5", SharedJItem::Null), Err("array index '5' out of bounds in json pointer '/a/b/5'".to_string()));
+        assert_eq!(copy.remove_pointer("/a/missing"), None);
+        assert!(copy.ptr_eq(&original));
+        assert!(copy.pointer("/a/b").unwrap().ptr_eq(original.pointer("/a/b").unwrap()));
+    }
+
+    #[test]
+    fn from_item_does_not_share() {
+        let item = parse(r#"["x", "x"]"#).unwrap();
+        let SharedJItem::Array(elements) = SharedJItem::from(&item) else {
+            panic!("expected an array");
+        };
+        assert_eq!(elements[0], elements[1]);
+        assert!(!elements[0].ptr_eq(&elements[1]));
+    }
+}
@@ -0,0 +1,112 @@
+use alloc::{string::String, vec::Vec};
+use core::ops::Range;
+
+use crate::{
+    j_item::JItem,
+    parser::Node,
+    pointer::{array_index, parse_pointer},
+    HashMap,
+};
+
+/// A value from `parse_with_spans`, with the byte range of its source text. For arrays
+/// and objects the range runs from the opening bracket to the closing one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpannedJItem {
+    pub span: Range<usize>,
+    pub value: SpannedValue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpannedValue {
+    Object(HashMap<String, SpannedMember>),
+    Array(Vec<SpannedJItem>),
+    /// A string, number, boolean or null, or whatever a `Hooks::value` function put in
+    /// its place.
+    Scalar(JItem),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpannedMember {
+    /// The range of the key, quotes included.
+    pub key_span: Range<usize>,
+    pub value: SpannedJItem,
+}
+
+impl SpannedJItem {
+    /// Drops the spans.
+    pub fn to_item(&self) -> JItem {
+        return match &self.value {
+            SpannedValue::Object(hmap) => JItem::Object(hmap.iter().map(|(key, member)| (key.clone(), member.value.to_item())).collect()),
+            SpannedValue::Array(elements) => JItem::Array(elements.iter().map(SpannedJItem::to_item).collect()),
+            SpannedValue::Scalar(item) => item.clone(),
+        };
+    }
+
+    /// Looks up the value referenced by an RFC 6901 JSON Pointer.
+    pub fn pointer(&self, pointer: &str) -> Option<&SpannedJItem> {
+        let mut current = self;
+        for token in parse_pointer(pointer).ok()? {
+            current = match &current.value {
+                SpannedValue::Object(hmap) => &hmap.get(&token)?.value,
+                SpannedValue::Array(elements) => elements.get(array_index(&token)?)?,
+                SpannedValue::Scalar(_) => return None,
+            };
+        }
+        return Some(current);
+    }
+}
+
+impl Node for SpannedJItem {
+    type Members = HashMap<String, SpannedMember>;
+
+    fn scalar(item: JItem, span: Range<usize>) -> Self {
+        return SpannedJItem { span, value: SpannedValue::Scalar(item) };
+    }
+
+    fn array(elements: Vec<Self>, span: Range<usize>) -> Self {
+        return SpannedJItem { span, value: SpannedValue::Array(elements) };
+    }
+
+    fn object(members: Self::Members, span: Range<usize>) -> Self {
+        return SpannedJItem { span, value: SpannedValue::Object(members) };
+    }
+
+    fn contains_key(members: &Self::Members, key: &str) -> bool {
+        return members.contains_key(key);
+    }
+
+    fn insert(members: &mut Self::Members, key: String, key_span: Range<usize>, value: Self) {
+        members.insert(key, SpannedMember { key_span, value });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parse, parse_with_spans, parser::{DuplicateKeys, ParseOptions}};
+
+    #[test]
+    fn spans_cover_source_text() {
+        let source = "{\n  \"name\": \"x\",\n  \"list\": [1, true, {}]\n}";
+        let root = parse_with_spans(source, &ParseOptions::default()).unwrap();
+        assert_eq!(root.span, 0..source.len());
+        assert_eq!(root.to_item(), parse(source).unwrap());
+        for (pointer, text) in [("/name", "\"x\""), ("/list", "[1, true, {}]"), ("/list/1", "true"), ("/list/2", "{}")] {
+            assert_eq!(&source[root.pointer(pointer).unwrap().span.clone()], text, "{}", pointer);
+        }
+        let SpannedValue::Object(hmap) = &root.value else {
+            panic!("expected an object");
+        };
+        assert_eq!(&source[hmap["list"].key_span.clone()], "\"list\"");
+        assert_eq!(root.pointer("/list/3"), None);
+    }
+
+    #[test]
+    fn spans_follow_options() {
+        let options = ParseOptions { duplicate_keys: DuplicateKeys::KeepLast, ..ParseOptions::default() };
+        let source = r#"{"a": 1, "a": 22}"#;
+        let root = parse_with_spans(source, &options).unwrap();
+        assert_eq!(root.pointer("/a").unwrap().span, 14..16);
+        assert_eq!(parse_with_spans(source, &ParseOptions::default()).unwrap_err().span(), 9..12);
+    }
+}
@@ -0,0 +1,130 @@
+use alloc::{vec, vec::Vec};
+use core::ops::Range;
+
+use crate::{
+    error::JsonError,
+    j_item::JItem,
+    parse_with,
+    parser::{ParseOptions, Strictness},
+};
+
+/// Finds and parses each top-level value in `s`, as `split_documents` does.
+pub(crate) fn split_documents(s: &str) -> Vec<(Range<usize>, Result<JItem, JsonError>)> {
+    let options = ParseOptions { strictness: Strictness::Lenient, ..ParseOptions::default() };
+    return document_spans(s).into_iter().map(|span| {
+        let start = span.start;
+        let result = parse_with(&s[span.clone()], &options).map_err(|e| JsonError::new(e.kind().clone(), s, e.span().start + start..e.span().end + start));
+        return (span, result);
+    }).collect();
+}
+
+/// The range of each top-level value. Only brackets, strings and comments are tracked,
+/// so a malformed value still ends where its brackets balance and the next one can be
+/// found.
+fn document_spans(s: &str) -> Vec<Range<usize>> {
+    let bytes = s.as_bytes();
+    let mut spans = vec![];
+    let mut i = 0;
+    loop {
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if i == bytes.len() {
+            return spans;
+        }
+        if bytes[i] == b'/' && let Some(end) = comment_end(bytes, i) {
+            i = end;
+            continue;
+        }
+        let start = i;
+        match bytes[i] {
+            b'{' | b'[' => {
+                let mut depth = 0;
+                while i < bytes.len() {
+                    match bytes[i] {
+                        b'{' | b'[' => depth += 1,
+                        b'}' | b']' => depth -= 1,
+                        b'"' => {
+                            i = string_end(bytes, i);
+                            continue;
+                        },
+                        b'/' if let Some(end) = comment_end(bytes, i) => {
+                            i = end;
+                            continue;
+                        },
+                        _ => {},
+                    }
+                    i += 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+            },
+            b'"' => i = string_end(bytes, i),
+            _ => {
+                while i < bytes.len() && !bytes[i].is_ascii_whitespace() && !b"{}[]\"/".contains(&bytes[i]) {
+                    i += 1;
+                }
+                // a stray bracket or slash becomes a document of its own, to be reported
+                if i == start {
+                    i += s[i..].chars().next().map_or(1, char::len_utf8);
+                }
+            },
+        }
+        spans.push(start..i);
+    }
+}
+
+/// The end of the string starting at `start`, or of the input if it is unterminated.
+fn string_end(bytes: &[u8], start: usize) -> usize {
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => return i + 1,
+            b'\\' => i += 2,
+            _ => i += 1,
+        }
+    }
+    return bytes.len();
+}
+
+/// The end of the comment starting at `start`, or `None` if there is no complete
+/// comment there.
+fn comment_end(bytes: &[u8], start: usize) -> Option<usize> {
+    return match bytes.get(start + 1) {
+        Some(b'/') => Some(bytes[start..].iter().position(|b| *b == b'\n').map_or(bytes.len(), |n| start + n + 1)),
+        Some(b'*') => bytes[start + 2..].windows(2).position(|w| w == b"*/").map(|n| start + 2 + n + 2),
+        _ => None,
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{error::ErrorKind, parse};
+
+    #[test]
+    fn finds_each_document() {
+        let source = "// first\n{\"a\": [1, \"]\"]}\n/* second */ [true]\n\"x\" 42 null";
+        let documents = split_documents(source);
+        let texts: Vec<&str> = documents.iter().map(|(span, _)| &source[span.clone()]).collect();
+        assert_eq!(texts, ["{\"a\": [1, \"]\"]}", "[true]", "\"x\"", "42", "null"]);
+        assert_eq!(documents[0].1, parse(r#"{"a": [1, "]"]}"#));
+        assert_eq!(documents[4].1, Ok(JItem::Null));
+    }
+
+    #[test]
+    fn bad_documents_do_not_stop_the_rest() {
+        let source = "{\"a\": 1}\n{\"b\": [1 2]}\n}\n{\"c\": 3}";
+        let documents = split_documents(source);
+        assert_eq!(documents.len(), 4);
+        let error = documents[1].1.clone().unwrap_err();
+        assert_eq!((error.span(), error.line(), error.column()), (18..19, 2, 10));
+        assert_eq!(documents[2].0, 22..23);
+        assert!(documents[2].1.is_err());
+        assert_eq!(documents[3].1, parse(r#"{"c": 3}"#));
+        let unterminated = split_documents("[1] [2, /* x");
+        assert_eq!(unterminated[1].0, 4..12);
+        assert_eq!(unterminated[1].1.clone().unwrap_err().kind(), &ErrorKind::UnterminatedComment);
+    }
+}
@@ -0,0 +1,228 @@
+use std::{collections::BTreeMap, mem::size_of};
+
+use crate::{j_item::JItem, path::push_escaped_key};
+
+/// Summary of a document's shape, as returned by `JItem::stats`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub objects: usize,
+    pub arrays: usize,
+    pub strings: usize,
+    pub numbers: usize,
+    pub booleans: usize,
+    pub nulls: usize,
+    /// Deepest container nesting: 0 for a scalar, 1 for `[]`, 2 for `[[]]`.
+    pub max_depth: usize,
+    /// Total UTF-8 length of all string values.
+    pub string_bytes: usize,
+    /// Total UTF-8 length of all object keys.
+    pub key_bytes: usize,
+    pub largest_array: usize,
+    pub largest_object: usize,
+}
+
+/// The values seen at one path pattern, as returned by `JItem::key_profile`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyProfile {
+    pub objects: usize,
+    pub arrays: usize,
+    pub strings: usize,
+    pub numbers: usize,
+    pub booleans: usize,
+    pub nulls: usize,
+}
+
+impl KeyProfile {
+    /// How many values were seen.
+    pub fn count(&self) -> usize {
+        return self.objects + self.arrays + self.strings + self.numbers + self.booleans + self.nulls;
+    }
+
+    /// The JSON Schema names of the types seen, such as `["null", "string"]`.
+    pub fn types(&self) -> Vec<&'static str> {
+        let counts = [(self.nulls, "null"), (self.booleans, "boolean"), (self.objects, "object"), (self.arrays, "array"), (self.numbers, "number"), (self.strings, "string")];
+        return counts.iter().filter(|(count, _)| *count > 0).map(|(_, name)| *name).collect();
+    }
+}
+
+impl JItem {
+    /// An inventory of the document's shape: for every path pattern, what kinds of value
+    /// appear there and how often. Patterns join keys with `.` and write array elements
+    /// as `[]`, so every element shares one pattern, as in `users[].address.city`. The
+    /// root is the empty pattern. Keys are escaped as `format_path` does.
+    pub fn key_profile(&self) -> BTreeMap<String, KeyProfile> {
+        let mut profile = BTreeMap::new();
+        collect_profile(self, &mut String::new(), &mut profile);
+        return profile;
+    }
+
+    pub fn stats(&self) -> Stats {
+        let mut stats = Stats::default();
+        collect_stats(self, 0, &mut stats);
+        return stats;
+    }
+
+    /// Estimates the bytes this value occupies, counting the value itself plus the
+    /// allocated capacity of every nested string, array and object. Object tables are
+    /// estimated as one control byte plus one key/value slot per bucket, so the figure is
+    /// approximate but stable for budgeting.
+    pub fn estimated_size(&self) -> usize {
+        return size_of::<JItem>() + heap_size(self);
+    }
+}
+
+fn heap_size(item: &JItem) -> usize {
+    return match item {
+        JItem::Object(hmap) => {
+            let table = hmap.capacity() * (size_of::<(String, JItem)>() + 1);
+            let members: usize = hmap.iter().map(|(key, value)| key.capacity() + heap_size(value)).sum();
+            table + members
+        },
+        JItem::Array(elements) => {
+            elements.capacity() * size_of::<JItem>() + elements.iter().map(heap_size).sum::<usize>()
+        },
+        JItem::String(s) | JItem::RawNumber(s) => s.capacity(),
+        JItem::Number(_) | JItem::Bool(_) | JItem::Null => 0,
+    };
+}
+
+fn collect_stats(item: &JItem, depth: usize, stats: &mut Stats) {
+    match item {
+        JItem::Object(hmap) => {
+            stats.objects += 1;
+            stats.max_depth = stats.max_depth.max(depth + 1);
+            stats.largest_object = stats.largest_object.max(hmap.len());
+            for (key, value) in hmap {
+                stats.key_bytes += key.len();
+                collect_stats(value, depth + 1, stats);
+            }
+        },
+        JItem::Array(elements) => {
+            stats.arrays += 1;
+            stats.max_depth = stats.max_depth.max(depth + 1);
+            stats.largest_array = stats.largest_array.max(elements.len());
+            for element in elements {
+                collect_stats(element, depth + 1, stats);
+            }
+        },
+        JItem::String(s) => {
+            stats.strings += 1;
+            stats.string_bytes += s.len();
+        },
+        JItem::Number(_) | JItem::RawNumber(_) => stats.numbers += 1,
+        JItem::Bool(_) => stats.booleans += 1,
+        JItem::Null => stats.nulls += 1,
+    }
+}
+
+fn collect_profile(item: &JItem, pattern: &mut String, profile: &mut BTreeMap<String, KeyProfile>) {
+    let counts = profile.entry(pattern.clone()).or_default();
+    match item {
+        JItem::Object(hmap) => {
+            counts.objects += 1;
+            let len = pattern.len();
+            for (key, value) in hmap {
+                if len > 0 {
+                    pattern.push('.');
+                }
+                push_escaped_key(pattern, key, ".");
+                collect_profile(value, pattern, profile);
+                pattern.truncate(len);
+            }
+        },
+        JItem::Array(elements) => {
+            counts.arrays += 1;
+            pattern.push_str("[]");
+            for element in elements {
+                collect_profile(element, pattern, profile);
+            }
+            pattern.truncate(pattern.len() - 2);
+        },
+        JItem::String(_) => counts.strings += 1,
+        JItem::Number(_) | JItem::RawNumber(_) => counts.numbers += 1,
+        JItem::Bool(_) => counts.booleans += 1,
+        JItem::Null => counts.nulls += 1,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn stats_scalar() {
+        let stats = JItem::String("héllo".to_string()).stats();
+        assert_eq!(stats, Stats { strings: 1, string_bytes: 6, ..Stats::default() });
+    }
+
+    #[test]
+    fn stats_nested() {
+        let item = parse(r#"{"ab": [1, 2, [true, null]], "c": {"d": "xyz", "e": false}}"#).unwrap();
+        let expected = Stats {
+            objects: 2,
+            arrays: 2,
+            strings: 1,
+            numbers: 2,
+            booleans: 2,
+            nulls: 1,
+            max_depth: 3,
+            string_bytes: 3,
+            key_bytes: 5,
+            largest_array: 3,
+            largest_object: 2,
+        };
+        assert_eq!(item.stats(), expected);
+    }
+
+    #[test]
+    fn estimated_size_scalar() {
+        assert_eq!(JItem::Null.estimated_size(), size_of::<JItem>());
+        assert_eq!(JItem::String(String::with_capacity(10)).estimated_size(), size_of::<JItem>() + 10);
+    }
+
+    #[test]
+    fn estimated_size_array() {
+        let mut elements = Vec::with_capacity(4);
+        elements.push(JItem::String("abc".to_string()));
+        let item = JItem::Array(elements);
+        assert_eq!(item.estimated_size(), 5 * size_of::<JItem>() + 3);
+    }
+
+    #[test]
+    fn estimated_size_grows_with_content() {
+        let small = parse(r#"{"a": [1]}"#).unwrap();
+        let large = parse(r#"{"a": [1, 2, 3, 4, 5, 6, 7, 8], "b": "a longer string value"}"#).unwrap();
+        assert!(small.estimated_size() < large.estimated_size());
+    }
+
+    #[test]
+    fn stats_empty_containers_count_depth() {
+        let item = parse("[[]]").unwrap();
+        let stats = item.stats();
+        assert_eq!(stats.max_depth, 2);
+        assert_eq!(stats.arrays, 2);
+        assert_eq!(stats.largest_array, 1);
+    }
+
+    #[test]
+    fn key_profile_patterns() {
+        let item = parse(r#"{"users": [{"name": "a", "address": {"city": "x"}}, {"name": null, "address": {"city": "y", "zip": 1}}, {"name": "c"}], "a.b": [[1, "2"]], "total": 3}"#).unwrap();
+        let profile = item.key_profile();
+        let summary: Vec<(&str, usize, Vec<&str>)> = profile.iter().map(|(pattern, counts)| (pattern.as_str(), counts.count(), counts.types())).collect();
+        assert_eq!(summary, [
+            ("", 1, vec!["object"]),
+            (r"a\.b", 1, vec!["array"]),
+            (r"a\.b[]", 1, vec!["array"]),
+            (r"a\.b[][]", 2, vec!["number", "string"]),
+            ("total", 1, vec!["number"]),
+            ("users", 1, vec!["array"]),
+            ("users[]", 3, vec!["object"]),
+            ("users[].address", 2, vec!["object"]),
+            ("users[].address.city", 2, vec!["string"]),
+            ("users[].address.zip", 1, vec!["number"]),
+            ("users[].name", 3, vec!["null", "string"]),
+        ]);
+        assert_eq!(profile["users[].name"], KeyProfile { strings: 2, nulls: 1, ..KeyProfile::default() });
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use crate::{
+    j_item::JItem,
+    path::{get_at, parse_path},
+};
+
+impl JItem {
+    /// Builds a new document from this template, filling `{{path}}` placeholders with
+    /// values from `context`, where `path` is a dotted path like `user.emails[0]`.
+    ///
+    /// A string that is exactly one placeholder is replaced by the value itself, keeping
+    /// its type, so `"{{user}}"` can become an object. Placeholders inside longer strings
+    /// and in keys are replaced by text: strings as they are, other values as JSON. A
+    /// placeholder with no value in `context` is an error.
+    pub fn render_template(&self, context: &JItem) -> Result<JItem, String> {
+        return match self {
+            JItem::Object(hmap) => {
+                let mut rendered = HashMap::new();
+                for (key, value) in hmap {
+                    let key = render_text(key, context)?;
+                    if rendered.contains_key(&key) {
+                        return Err(format!("template renders the key '{}' more than once", key));
+                    }
+                    let value = value.render_template(context)?;
+                    rendered.insert(key, value);
+                }
+                Ok(JItem::Object(rendered))
+            },
+            JItem::Array(elements) => {
+                let rendered: Result<Vec<_>, _> = elements.iter().map(|element| element.render_template(context)).collect();
+                Ok(JItem::Array(rendered?))
+            },
+            JItem::String(s) => match whole_placeholder(s) {
+                Some(path) => lookup(path, context).cloned(),
+                None => Ok(JItem::String(render_text(s, context)?)),
+            },
+            scalar => Ok(scalar.clone()),
+        };
+    }
+}
+
+// the path of a string consisting of a single placeholder
+fn whole_placeholder(s: &str) -> Option<&str> {
+    let inner = s.strip_prefix("{{")?.strip_suffix("}}")?;
+    if inner.contains("{{") || inner.contains("}}") {
+        return None;
+    }
+    return Some(inner);
+}
+
+fn lookup<'a>(path: &str, context: &'a JItem) -> Result<&'a JItem, String> {
+    let segments = parse_path(path.trim(), ".")?;
+    let Some(value) = get_at(context, &segments) else {
+        return Err(format!("template placeholder '{{{{{}}}}}' has no value in the context", path));
+    };
+    return Ok(value);
+}
+
+fn render_text(s: &str, context: &JItem) -> Result<String, String> {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find("}}") else {
+            return Err(format!("unterminated template placeholder in '{}'", s));
+        };
+        match lookup(&rest[start + 2..start + end], context)? {
+            JItem::String(value) => out.push_str(value),
+            value => out.push_str(&value.to_string()),
+        }
+        rest = &rest[start + end + 2..];
+    }
+    out.push_str(rest);
+    return Ok(out);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    fn context() -> JItem {
+        return parse(r#"{"user": {"name": "Ada", "id": 7, "roles": ["admin", "dev"]}, "env": "prod"}"#).unwrap();
+    }
+
+    #[test]
+    fn render_whole_placeholders_keep_type() {
+        let template = parse(r#"{"id": "{{user.id}}", "roles": "{{ user.roles }}", "first": "{{user.roles[0]}}", "fixed": [1, true]}"#).unwrap();
+        let expected = parse(r#"{"id": 7, "roles": ["admin", "dev"], "first": "admin", "fixed": [1, true]}"#).unwrap();
+        assert_eq!(template.render_template(&context()), Ok(expected));
+    }
+
+    #[test]
+    fn render_embedded_placeholders_as_text() {
+        let template = parse(r#"{"greeting": "hi {{user.name}} (#{{user.id}})", "{{env}}_roles": "{{user.roles}} in {{env}}"}"#).unwrap();
+        let expected = parse(r#"{"greeting": "hi Ada (#7)", "prod_roles": "[\"admin\",\"dev\"] in prod"}"#).unwrap();
+        assert_eq!(template.render_template(&context()), Ok(expected));
+    }
+
+    #[test]
+    fn render_errors() {
+        let missing = parse(r#"{"a": "{{user.email}}"}"#).unwrap();
+        assert_eq!(missing.render_template(&context()), Err("template placeholder '{{user.email}}' has no value in the context".to_string()));
+        let unterminated = JItem::String("{{user.name".to_string());
+        assert!(unterminated.render_template(&context()).is_err());
+        let colliding = parse(r#"{"prod": 1, "{{env}}": 2}"#).unwrap();
+        assert!(colliding.render_template(&context()).is_err());
+    }
+}
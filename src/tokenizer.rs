@@ -0,0 +1,316 @@
+//! A tokenizer over borrowed input, for tools like linters and formatters that work on
+//! the token stream rather than a parsed tree.
+//!
+//! Tokens follow the same rules as `parse` with the default options, apart from the
+//! comments `Tokenizer::with_comments` reads, and each one
+//! borrows its lexeme from the input along with its byte span. Nothing is allocated
+//! while tokenizing: string and number values are only decoded when asked for. Token
+//! kinds may be added in later versions, but the meaning of existing kinds and spans
+//! will not change.
+
+use alloc::{borrow::Cow, string::{String, ToString}};
+use core::ops::Range;
+
+use crate::error::{ErrorKind, JsonError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TokenKind {
+    LBrace,
+    RBrace,
+    LSquareBracket,
+    RSquareBracket,
+    Colon,
+    Comma,
+    String,
+    Number,
+    True,
+    False,
+    Null,
+    /// A `//` comment up to its newline, or a `/* */` comment. Only read by
+    /// `Tokenizer::with_comments`.
+    Comment,
+}
+
+/// A token and the text it was read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub span: Range<usize>,
+    text: &'a str,
+}
+
+impl<'a> Token<'a> {
+    /// The token's lexeme exactly as written, quotes and escapes included.
+    pub fn as_str(&self) -> &'a str {
+        return self.text;
+    }
+
+    /// The contents of a string token with escapes decoded, borrowed when there are none.
+    /// Unpaired surrogates become U+FFFD, as in `parse`.
+    pub fn string_value(&self) -> Option<Cow<'a, str>> {
+        if self.kind != TokenKind::String {
+            return None;
+        }
+        let inner = &self.text[1..self.text.len() - 1];
+        if !inner.contains('\\') {
+            return Some(Cow::Borrowed(inner));
+        }
+        let mut out = String::with_capacity(inner.len());
+        let mut chars = inner.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                out.push(c);
+                continue;
+            }
+            let escaped = chars.next().unwrap();
+            out.push(match escaped {
+                'b' => '\u{8}',
+                'f' => '\u{c}',
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                'u' => {
+                    let rest = chars.as_str();
+                    let code = hex4(rest);
+                    let low = rest[4..].strip_prefix("\\u").map(hex4).filter(|low| (0xDC00..0xE000).contains(low));
+                    let (decoded, used) = match low {
+                        Some(low) if (0xD800..0xDC00).contains(&code) => (char::from_u32(0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00)), 10),
+                        _ => (char::from_u32(code), 4),
+                    };
+                    chars = rest[used..].chars();
+                    decoded.unwrap_or(char::REPLACEMENT_CHARACTER)
+                },
+                _ => escaped,
+            });
+        }
+        return Some(Cow::Owned(out));
+    }
+
+    pub fn number_value(&self) -> Option<f64> {
+        if self.kind != TokenKind::Number {
+            return None;
+        }
+        return self.text.parse().ok();
+    }
+}
+
+/// The value of four hex digits the tokenizer has already checked.
+fn hex4(s: &str) -> u32 {
+    return u32::from_str_radix(&s[..4], 16).unwrap();
+}
+
+/// An iterator of tokens over `source`. It stops after the first error.
+#[derive(Debug, Clone)]
+pub struct Tokenizer<'a> {
+    source: &'a str,
+    pos: usize,
+    failed: bool,
+    comments: bool,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub fn new(source: &'a str) -> Self {
+        return Tokenizer { source, pos: 0, failed: false, comments: false };
+    }
+
+    /// Like `new`, but `//` and `/* */` comments come out as `TokenKind::Comment` tokens
+    /// rather than being errors.
+    pub fn with_comments(source: &'a str) -> Self {
+        return Tokenizer { source, pos: 0, failed: false, comments: true };
+    }
+
+    pub fn source(&self) -> &'a str {
+        return self.source;
+    }
+
+    /// The byte offset just past the last token read.
+    pub fn offset(&self) -> usize {
+        return self.pos;
+    }
+
+    fn peek(&self) -> Option<u8> {
+        return self.source.as_bytes().get(self.pos).copied();
+    }
+
+    fn number(&mut self, start: usize) -> Result<TokenKind, ErrorKind> {
+        let mut has_decimal = false;
+        let mut has_exponent = false;
+        while let Some(b) = self.peek() {
+            match b {
+                b'0'..=b'9' => {},
+                b'.' if has_decimal || has_exponent => {
+                    self.pos += 1;
+                    break;
+                },
+                b'.' => has_decimal = true,
+                b'e' | b'E' if !has_exponent => {
+                    has_exponent = true;
+                    if matches!(self.source.as_bytes().get(self.pos + 1), Some(b'+' | b'-')) {
+                        self.pos += 1;
+                    }
+                },
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        let text = &self.source[start..self.pos];
+        return match text.parse::<f64>() {
+            Ok(_) => Ok(TokenKind::Number),
+            Err(source) => Err(ErrorKind::InvalidNumber { text: text.to_string(), source }),
+        };
+    }
+
+    fn string(&mut self) -> Result<TokenKind, ErrorKind> {
+        while let Some(b) = self.peek() {
+            self.pos += 1;
+            match b {
+                b'"' => return Ok(TokenKind::String),
+                b'\\' => {
+                    let escape = self.pos - 1;
+                    match self.peek() {
+                        None => break,
+                        Some(b'u') => {
+                            self.pos += 1;
+                            for _ in 0..4 {
+                                if !self.peek().is_some_and(|b| b.is_ascii_hexdigit()) {
+                                    return Err(ErrorKind::InvalidEscape(self.source[escape..self.pos].to_string()));
+                                }
+                                self.pos += 1;
+                            }
+                        },
+                        // any other escaped character stands for itself, and the bytes of a
+                        // multi-byte one can never be mistaken for '"' or '\'
+                        Some(_) => self.pos += 1,
+                    }
+                },
+                _ => {},
+            }
+        }
+        self.pos = self.source.len();
+        return Err(ErrorKind::UnterminatedString);
+    }
+
+    fn comment(&mut self) -> Result<TokenKind, ErrorKind> {
+        let rest = &self.source[self.pos..];
+        if rest.starts_with('/') {
+            self.pos += rest.find('\n').unwrap_or(rest.len());
+            return Ok(TokenKind::Comment);
+        }
+        if let Some(body) = rest.strip_prefix('*') {
+            let Some(end) = body.find("*/") else {
+                self.pos = self.source.len();
+                return Err(ErrorKind::UnterminatedComment);
+            };
+            self.pos += end + 3;
+            return Ok(TokenKind::Comment);
+        }
+        return Err(ErrorKind::UnexpectedCharacter('/'));
+    }
+
+    fn keyword(&mut self, start: usize) -> Result<TokenKind, ErrorKind> {
+        while self.peek().is_some_and(|b| b.is_ascii_alphabetic()) {
+            self.pos += 1;
+        }
+        return match &self.source[start..self.pos] {
+            "true" => Ok(TokenKind::True),
+            "false" => Ok(TokenKind::False),
+            "null" => Ok(TokenKind::Null),
+            keyword => Err(ErrorKind::UnknownKeyword(keyword.to_string())),
+        };
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Result<Token<'a>, JsonError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        while matches!(self.peek(), Some(b' ' | b'\n' | b'\t' | b'\r')) {
+            self.pos += 1;
+        }
+        let start = self.pos;
+        let b = self.peek()?;
+        self.pos += 1;
+        let kind = match b {
+            b'{' => Ok(TokenKind::LBrace),
+            b'}' => Ok(TokenKind::RBrace),
+            b'[' => Ok(TokenKind::LSquareBracket),
+            b']' => Ok(TokenKind::RSquareBracket),
+            b':' => Ok(TokenKind::Colon),
+            b',' => Ok(TokenKind::Comma),
+            b'-' | b'0'..=b'9' => self.number(start),
+            b'"' => self.string(),
+            b'a'..=b'z' | b'A'..=b'Z' => self.keyword(start),
+            b'/' if self.comments => self.comment(),
+            _ => {
+                let c = self.source[start..].chars().next().unwrap();
+                self.pos = start + c.len_utf8();
+                Err(ErrorKind::UnexpectedCharacter(c))
+            },
+        };
+        let span = start..self.pos;
+        return Some(match kind {
+            Ok(kind) => Ok(Token { kind, text: &self.source[span.clone()], span }),
+            Err(kind) => {
+                self.failed = true;
+                Err(JsonError::new(kind, self.source, span))
+            },
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tokenize_with_spans() {
+        let source = r#"{"a": [1.5e3, true, null]}"#;
+        let tokens: Vec<Token> = Tokenizer::new(source).collect::<Result<_, _>>().unwrap();
+        let kinds: Vec<TokenKind> = tokens.iter().map(|t| t.kind).collect();
+        assert_eq!(kinds, vec![
+            TokenKind::LBrace, TokenKind::String, TokenKind::Colon, TokenKind::LSquareBracket, TokenKind::Number, TokenKind::Comma,
+            TokenKind::True, TokenKind::Comma, TokenKind::Null, TokenKind::RSquareBracket, TokenKind::RBrace,
+        ]);
+        assert_eq!(tokens[1].span, 1..4);
+        assert_eq!(tokens[1].as_str(), r#""a""#);
+        assert_eq!(tokens[4].as_str(), "1.5e3");
+        assert_eq!(tokens[4].number_value(), Some(1500.0));
+        assert_eq!(tokens[4].string_value(), None);
+    }
+
+    #[test]
+    fn token_string_values() {
+        let source = r#"["plain", "a\"b\\\/\né😀\ud800!\q"]"#;
+        let tokens: Vec<Token> = Tokenizer::new(source).collect::<Result<_, _>>().unwrap();
+        assert!(matches!(tokens[1].string_value(), Some(Cow::Borrowed("plain"))));
+        assert_eq!(tokens[3].string_value().unwrap(), "a\"b\\/\né😀\u{fffd}!q");
+        assert_eq!(tokens[3].string_value().unwrap(), crate::lexer::lex(tokens[3].as_str()).map(|t| match &t[0] {
+            crate::lexer::Token::String(s) => s.to_string(),
+            _ => panic!("expected a string"),
+        }).unwrap());
+    }
+
+    #[test]
+    fn tokenizer_stops_after_error() {
+        let mut tokenizer = Tokenizer::new("[1, @, 2]");
+        assert_eq!(tokenizer.by_ref().take(3).filter(Result::is_ok).count(), 3);
+        assert_eq!(tokenizer.next().unwrap().unwrap_err().code(), "E002");
+        assert_eq!(tokenizer.next(), None);
+    }
+
+    #[test]
+    fn tokenize_comments() {
+        let source = "[1, // one\n/* two */ 2]";
+        let tokens: Vec<Token> = Tokenizer::with_comments(source).collect::<Result<_, _>>().unwrap();
+        assert_eq!(tokens[3].kind, TokenKind::Comment);
+        assert_eq!(tokens[3].as_str(), "// one");
+        assert_eq!(tokens[4].as_str(), "/* two */");
+        assert_eq!(tokens[5].as_str(), "2");
+        assert_eq!(Tokenizer::new(source).nth(3).unwrap().unwrap_err().code(), "E002");
+        assert_eq!(Tokenizer::with_comments("[/* 1").nth(1).unwrap().unwrap_err().code(), "E016");
+    }
+}
@@ -0,0 +1,113 @@
+use ::toml::{Table, Value};
+
+use crate::j_item::JItem;
+
+/// Parses a TOML document into an object. Integers beyond 2^53 lose precision as all
+/// numbers are `f64`, and datetimes become their RFC 3339 strings.
+pub fn from_toml(input: &str) -> Result<JItem, String> {
+    let table: Table = input.parse().map_err(|e| format!("invalid toml: {}", e))?;
+    return Ok(table_to_item(table));
+}
+
+/// Writes an object as a TOML document. Fails on a non-object root and on nulls, which
+/// TOML has no way to express. Whole numbers are written as TOML integers.
+pub fn to_toml(item: &JItem) -> Result<String, String> {
+    let JItem::Object(_) = item else {
+        return Err("toml documents must be objects at the top level".to_string());
+    };
+    let Value::Table(table) = item_to_value(item, "")? else {
+        unreachable!("objects always convert to tables");
+    };
+    return Ok(table.to_string());
+}
+
+fn table_to_item(table: Table) -> JItem {
+    return JItem::Object(table.into_iter().map(|(key, value)| (key, value_to_item(value))).collect());
+}
+
+fn value_to_item(value: Value) -> JItem {
+    return match value {
+        Value::String(s) => JItem::String(s),
+        Value::Integer(i) => JItem::Number(i as f64),
+        Value::Float(x) => JItem::Number(x),
+        Value::Boolean(b) => JItem::Bool(b),
+        Value::Datetime(datetime) => JItem::String(datetime.to_string()),
+        Value::Array(elements) => JItem::Array(elements.into_iter().map(value_to_item).collect()),
+        Value::Table(table) => table_to_item(table),
+    };
+}
+
+fn item_to_value(item: &JItem, path: &str) -> Result<Value, String> {
+    return match item {
+        JItem::RawNumber(_) => item_to_value(&JItem::Number(item.number_value().unwrap()), path),
+        JItem::Object(hmap) => {
+            let mut table = Table::new();
+            for (key, value) in hmap {
+                table.insert(key.to_string(), item_to_value(value, &format!("{}/{}", path, key))?);
+            }
+            Ok(Value::Table(table))
+        },
+        JItem::Array(elements) => {
+            let converted: Result<Vec<_>, _> = elements
+                .iter()
+                .enumerate()
+                .map(|(n, element)| item_to_value(element, &format!("{}/{}", path, n)))
+                .collect();
+            Ok(Value::Array(converted?))
+        },
+        JItem::String(s) => Ok(Value::String(s.to_string())),
+        JItem::Number(x) if x.fract() == 0.0 && x.abs() < 9.2e18 => Ok(Value::Integer(*x as i64)),
+        JItem::Number(x) => Ok(Value::Float(*x)),
+        JItem::Bool(b) => Ok(Value::Boolean(*b)),
+        JItem::Null => Err(format!("toml cannot represent the null at '{}'", path)),
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn from_toml_document() {
+        let input = r#"
+title = "example"
+ratio = 0.5
+[owner]
+name = "a"
+dob = 1979-05-27T07:32:00Z
+[[servers]]
+port = 8080
+enabled = true
+"#;
+        let expected = parse(r#"{
+            "title": "example",
+            "ratio": 0.5,
+            "owner": {"name": "a", "dob": "1979-05-27T07:32:00Z"},
+            "servers": [{"port": 8080, "enabled": true}]
+        }"#).unwrap();
+        assert_eq!(from_toml(input), Ok(expected));
+    }
+
+    #[test]
+    fn from_toml_invalid() {
+        assert!(from_toml("a = ").is_err());
+    }
+
+    #[test]
+    fn to_toml_round_trip() {
+        let item = parse(r#"{"name": "x", "count": 3, "ratio": 1.5, "tags": ["a", "b"], "nested": {"ok": false}}"#).unwrap();
+        let toml = to_toml(&item).unwrap();
+        assert!(toml.contains("count = 3\n"));
+        assert_eq!(from_toml(&toml), Ok(item));
+    }
+
+    #[test]
+    fn to_toml_rejects_unrepresentable() {
+        assert_eq!(to_toml(&parse("[1]").unwrap()), Err("toml documents must be objects at the top level".to_string()));
+        assert_eq!(
+            to_toml(&parse(r#"{"a": {"b": [1, null]}}"#).unwrap()),
+            Err("toml cannot represent the null at '/a/b/1'".to_string())
+        );
+    }
+}
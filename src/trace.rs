@@ -0,0 +1,102 @@
+//! Timing of parsing and serialization. With the `tracing` feature each stage runs
+//! inside a `json` span at DEBUG level with a `stage` field, and ends with a DEBUG event
+//! carrying `stage`, `bytes`, `elapsed_us` and, for parsing, the number of tokens as
+//! `items`. Lexing happens as part of parsing, so has no stage of its own. Without the
+//! feature this compiles to nothing.
+
+pub(crate) struct Stage {
+    #[cfg(feature = "tracing")]
+    name: &'static str,
+    #[cfg(feature = "tracing")]
+    start: std::time::Instant,
+    #[cfg(feature = "tracing")]
+    _span: tracing::span::EnteredSpan,
+}
+
+impl Stage {
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub(crate) fn enter(name: &'static str) -> Self {
+        return Stage {
+            #[cfg(feature = "tracing")]
+            name,
+            #[cfg(feature = "tracing")]
+            start: std::time::Instant::now(),
+            #[cfg(feature = "tracing")]
+            _span: tracing::debug_span!("json", stage = name).entered(),
+        };
+    }
+
+    /// Ends the stage. `bytes` is the text read or written and `items` the tokens
+    /// handled, where the stage has them.
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub(crate) fn finish(self, bytes: usize, items: Option<usize>) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(stage = self.name, bytes, items, elapsed_us = self.start.elapsed().as_micros() as u64, "finished");
+    }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod test {
+    use std::{
+        fmt::Debug,
+        sync::{Arc, Mutex},
+    };
+
+    use tracing::{
+        field::{Field, Visit},
+        span, Event, Metadata, Subscriber,
+    };
+
+    use crate::{format::FormatOptions, parse};
+
+    /// Records each event's fields, apart from the timing, as `name=value` lines.
+    #[derive(Default, Clone)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    struct Fields(Vec<String>);
+
+    impl Visit for Fields {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            if field.name() != "elapsed_us" && field.name() != "message" {
+                self.0.push(format!("{}={:?}", field.name(), value));
+            }
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            return true;
+        }
+
+        fn new_span(&self, _: &span::Attributes<'_>) -> span::Id {
+            return span::Id::from_u64(1);
+        }
+
+        fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = Fields(vec![]);
+            event.record(&mut fields);
+            self.0.lock().unwrap().push(fields.0.join(" "));
+        }
+
+        fn enter(&self, _: &span::Id) {}
+
+        fn exit(&self, _: &span::Id) {}
+    }
+
+    #[test]
+    fn stages_are_reported() {
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            let item = parse("[1, true]").unwrap();
+            item.to_string_with(&FormatOptions::compact());
+        });
+        assert_eq!(*recorder.0.lock().unwrap(), [
+            "stage=\"parse\" bytes=9 items=5",
+            "stage=\"serialize\" bytes=8",
+        ]);
+    }
+}
@@ -0,0 +1,188 @@
+use crate::{j_item::JItem, path::PathSegment};
+
+impl JItem {
+    /// Rebuilds the tree bottom-up, passing every node and its path to `f` after its
+    /// children have been transformed.
+    pub fn transform<F>(self, mut f: F) -> JItem
+    where
+        F: FnMut(&[PathSegment], JItem) -> JItem,
+    {
+        let mut segments = vec![];
+        return transform_node(self, &mut segments, &mut f, false);
+    }
+
+    /// Like `transform`, but only scalar values (strings, numbers, booleans and nulls)
+    /// are passed to `f`.
+    pub fn map_values<F>(self, mut f: F) -> JItem
+    where
+        F: FnMut(&[PathSegment], JItem) -> JItem,
+    {
+        let mut segments = vec![];
+        return transform_node(self, &mut segments, &mut f, true);
+    }
+
+    /// Recursively removes object members and array elements for which `predicate`
+    /// returns false. Children are pruned before their parent is tested, so a predicate
+    /// rejecting empty objects also drops objects that were emptied by the pass.
+    /// Array elements are reported at their index before pruning.
+    pub fn prune<F>(&mut self, mut predicate: F)
+    where
+        F: FnMut(&[PathSegment], &JItem) -> bool,
+    {
+        let mut segments = vec![];
+        prune_children(self, &mut segments, &mut predicate);
+    }
+}
+
+fn transform_node<F>(item: JItem, segments: &mut Vec<PathSegment>, f: &mut F, leaves_only: bool) -> JItem
+where
+    F: FnMut(&[PathSegment], JItem) -> JItem,
+{
+    let rebuilt = match item {
+        JItem::Object(hmap) => {
+            let transformed = hmap
+                .into_iter()
+                .map(|(key, value)| {
+                    segments.push(PathSegment::Key(key));
+                    let value = transform_node(value, segments, f, leaves_only);
+                    let Some(PathSegment::Key(key)) = segments.pop() else {
+                        unreachable!("path segments are pushed and popped in pairs");
+                    };
+                    (key, value)
+                })
+                .collect();
+            JItem::Object(transformed)
+        },
+        JItem::Array(elements) => {
+            let transformed = elements
+                .into_iter()
+                .enumerate()
+                .map(|(index, value)| {
+                    segments.push(PathSegment::Index(index));
+                    let value = transform_node(value, segments, f, leaves_only);
+                    segments.pop();
+                    value
+                })
+                .collect();
+            JItem::Array(transformed)
+        },
+        scalar => return f(segments, scalar),
+    };
+
+    if leaves_only {
+        return rebuilt;
+    }
+    return f(segments, rebuilt);
+}
+
+fn prune_children<F>(item: &mut JItem, segments: &mut Vec<PathSegment>, predicate: &mut F)
+where
+    F: FnMut(&[PathSegment], &JItem) -> bool,
+{
+    match item {
+        JItem::Object(hmap) => {
+            hmap.retain(|key, value| {
+                segments.push(PathSegment::Key(key.to_string()));
+                prune_children(value, segments, predicate);
+                let keep = predicate(segments, value);
+                segments.pop();
+                keep
+            });
+        },
+        JItem::Array(elements) => {
+            let mut index = 0;
+            elements.retain_mut(|value| {
+                segments.push(PathSegment::Index(index));
+                prune_children(value, segments, predicate);
+                let keep = predicate(segments, value);
+                segments.pop();
+                index += 1;
+                keep
+            });
+        },
+        _ => {},
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn map_values_rounds_numbers() {
+        let item = parse(r#"{"a": [1.4, 2.6], "b": {"c": -0.6, "d": "x"}}"#).unwrap();
+        let rounded = item.map_values(|_, value| match value {
+            JItem::Number(x) => JItem::Number(x.round()),
+            other => other,
+        });
+        assert_eq!(rounded, parse(r#"{"a": [1, 3], "b": {"c": -1, "d": "x"}}"#).unwrap());
+    }
+
+    #[test]
+    fn map_values_receives_paths() {
+        let item = parse(r#"{"a": [null, "keep"]}"#).unwrap();
+        let mut seen = vec![];
+        item.map_values(|path, value| {
+            seen.push(path.to_vec());
+            value
+        });
+        seen.sort_by_key(|path| format!("{:?}", path));
+        assert_eq!(seen, vec![
+            vec![PathSegment::Key("a".to_string()), PathSegment::Index(0)],
+            vec![PathSegment::Key("a".to_string()), PathSegment::Index(1)],
+        ]);
+    }
+
+    #[test]
+    fn transform_visits_containers_bottom_up() {
+        let item = parse(r#"[[1, 2], [3]]"#).unwrap();
+        let lengths = item.transform(|_, value| match value {
+            JItem::Array(elements) => JItem::Number(elements.len() as f64),
+            other => other,
+        });
+        assert_eq!(lengths, JItem::Number(2.));
+    }
+
+    #[test]
+    fn transform_trims_strings() {
+        let item = parse(r#"{"name": "  foo ", "tags": [" a", "b "]}"#).unwrap();
+        let trimmed = item.transform(|_, value| match value {
+            JItem::String(s) => JItem::String(s.trim().to_string()),
+            other => other,
+        });
+        assert_eq!(trimmed, parse(r#"{"name": "foo", "tags": ["a", "b"]}"#).unwrap());
+    }
+
+    #[test]
+    fn prune_drops_nulls() {
+        let mut item = parse(r#"{"a": null, "b": [1, null, 2], "c": {"d": null, "e": true}}"#).unwrap();
+        item.prune(|_, value| *value != JItem::Null);
+        assert_eq!(item, parse(r#"{"b": [1, 2], "c": {"e": true}}"#).unwrap());
+    }
+
+    #[test]
+    fn prune_drops_objects_emptied_by_pass() {
+        let mut item = parse(r#"{"a": {"b": {"c": null}}, "d": 1}"#).unwrap();
+        item.prune(|_, value| match value {
+            JItem::Null => false,
+            JItem::Object(hmap) => !hmap.is_empty(),
+            _ => true,
+        });
+        assert_eq!(item, parse(r#"{"d": 1}"#).unwrap());
+    }
+
+    #[test]
+    fn prune_by_path() {
+        let mut item = parse(r#"[["x", "y"], ["z"]]"#).unwrap();
+        item.prune(|path, _| path != [PathSegment::Index(0), PathSegment::Index(0)]);
+        assert_eq!(item, parse(r#"[["y"], ["z"]]"#).unwrap());
+    }
+
+    #[test]
+    fn prune_scalar_root_is_untouched() {
+        let mut item = JItem::Null;
+        item.prune(|_, _| false);
+        assert_eq!(item, JItem::Null);
+    }
+}
@@ -0,0 +1,52 @@
+use ::uuid::Uuid;
+
+use crate::j_item::JItem;
+
+impl JItem {
+    /// Reads a UUID string in hyphenated, simple, braced or URN form.
+    pub fn as_uuid(&self) -> Option<Uuid> {
+        let JItem::String(s) = self else {
+            return None;
+        };
+        return Uuid::try_parse(s).ok();
+    }
+}
+
+/// Writes the lowercase hyphenated form.
+impl From<Uuid> for JItem {
+    fn from(uuid: Uuid) -> Self {
+        return JItem::String(uuid.hyphenated().to_string());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn string(s: &str) -> JItem {
+        return JItem::String(s.to_string());
+    }
+
+    #[test]
+    fn as_uuid_forms() {
+        let expected = Uuid::from_u128(0x67e55044_10b1_426f_9247_bb680e5fe0c8);
+        assert_eq!(string("67e55044-10b1-426f-9247-bb680e5fe0c8").as_uuid(), Some(expected));
+        assert_eq!(string("67E5504410B1426F9247BB680E5FE0C8").as_uuid(), Some(expected));
+        assert_eq!(string("urn:uuid:67e55044-10b1-426f-9247-bb680e5fe0c8").as_uuid(), Some(expected));
+    }
+
+    #[test]
+    fn as_uuid_rejects_other_values() {
+        assert_eq!(string("67e55044-10b1-426f-9247").as_uuid(), None);
+        assert_eq!(string("not a uuid").as_uuid(), None);
+        assert_eq!(JItem::Number(1.0).as_uuid(), None);
+    }
+
+    #[test]
+    fn uuid_round_trip() {
+        let uuid = Uuid::from_u128(0xA1A2A3A4_B1B2_C1C2_D1D2_D3D4D5D6D7D8);
+        let item = JItem::from(uuid);
+        assert_eq!(item, string("a1a2a3a4-b1b2-c1c2-d1d2-d3d4d5d6d7d8"));
+        assert_eq!(item.as_uuid(), Some(uuid));
+    }
+}
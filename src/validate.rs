@@ -0,0 +1,60 @@
+use crate::{error::JsonError, events::Events};
+
+/// Checks `s` the way `parse` would, without building tokens or a tree.
+pub(crate) fn validate(s: &str) -> Result<(), JsonError> {
+    for event in Events::new(s) {
+        event?;
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{error::ErrorKind, parse};
+
+    #[test]
+    fn validate_accepts_what_parse_accepts() {
+        for input in [
+            "null",
+            " [1, -2.5e+3, 1., 01, true, false] ",
+            r#"{"a": {"b": [[], {}, "x\"y\\z\u00e9\ud800"]}, "c": "\q"}"#,
+            "[1, 2,]",
+            "\"é😀\"",
+        ] {
+            assert_eq!(validate(input), Ok(()), "{}", input);
+            assert!(parse(input).is_ok(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn validate_errors_match_parse() {
+        for input in [
+            "",
+            "[1, 2",
+            "[1 2]",
+            "{\"a\" 1}",
+            "{\"a\": 1,",
+            "{1: 2}",
+            "]",
+            "1 2",
+            "1 2 @",
+            "[1, \"abc",
+            "[tru]",
+            "[1.2.3]",
+            "[-]",
+            "\"\\u12\"",
+            "[1, 2, é]",
+            "{\"a\":",
+        ] {
+            assert_eq!(validate(input), parse(input).map(|_| ()), "{}", input);
+        }
+    }
+
+    #[test]
+    fn validate_deep_nesting() {
+        let input = format!("{}{}", "[".repeat(100_000), "]".repeat(100_000));
+        assert_eq!(validate(&input), Ok(()));
+        assert_eq!(validate(&input[..input.len() - 1]).unwrap_err().kind(), &ErrorKind::UnterminatedArray);
+    }
+}
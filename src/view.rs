@@ -0,0 +1,149 @@
+use alloc::string::String;
+use core::fmt;
+
+use crate::{format::FormatOptions, j_item::JItem};
+
+/// A read-only view of a `JItem`, for handing a document to code that must not change
+/// it, such as a plugin or script, without cloning it. Only reading is offered, and the
+/// borrowed item itself is never given out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JItemView<'a>(&'a JItem);
+
+impl JItem {
+    pub fn view(&self) -> JItemView<'_> {
+        return JItemView(self);
+    }
+}
+
+impl<'a> JItemView<'a> {
+    /// The member named `key`, when this is an object.
+    pub fn get(self, key: &str) -> Option<JItemView<'a>> {
+        return self.0.get(key).map(JItemView);
+    }
+
+    /// The element at `index`, when this is an array.
+    pub fn at(self, index: usize) -> Option<JItemView<'a>> {
+        let JItem::Array(elements) = self.0 else {
+            return None;
+        };
+        return elements.get(index).map(JItemView);
+    }
+
+    /// Looks up the value referenced by an RFC 6901 JSON Pointer.
+    pub fn pointer(self, pointer: &str) -> Option<JItemView<'a>> {
+        return self.0.pointer(pointer).map(JItemView);
+    }
+
+    /// Looks up the value at a dotted path such as `a.b[0]`, as `JItem::path` does.
+    pub fn path(self, path: &str) -> Option<JItemView<'a>> {
+        return self.0.path(path).map(JItemView);
+    }
+
+    /// The number of elements or members, and 0 for anything else.
+    pub fn len(self) -> usize {
+        return match self.0 {
+            JItem::Array(elements) => elements.len(),
+            JItem::Object(hmap) => hmap.len(),
+            _ => 0,
+        };
+    }
+
+    pub fn is_empty(self) -> bool {
+        return self.len() == 0;
+    }
+
+    /// The elements of an array, and nothing for anything else.
+    pub fn elements(self) -> impl Iterator<Item = JItemView<'a>> {
+        let elements = match self.0 {
+            JItem::Array(elements) => elements.as_slice(),
+            _ => &[],
+        };
+        return elements.iter().map(JItemView);
+    }
+
+    /// The members of an object in map order, and nothing for anything else.
+    pub fn members(self) -> impl Iterator<Item = (&'a str, JItemView<'a>)> {
+        let hmap = match self.0 {
+            JItem::Object(hmap) => Some(hmap),
+            _ => None,
+        };
+        return hmap.into_iter().flatten().map(|(key, value)| (key.as_str(), JItemView(value)));
+    }
+
+    pub fn is_object(self) -> bool {
+        return matches!(self.0, JItem::Object(_));
+    }
+
+    pub fn is_array(self) -> bool {
+        return matches!(self.0, JItem::Array(_));
+    }
+
+    pub fn is_null(self) -> bool {
+        return matches!(self.0, JItem::Null);
+    }
+
+    pub fn as_str(self) -> Option<&'a str> {
+        return self.0.as_str();
+    }
+
+    pub fn as_i64(self) -> Option<i64> {
+        return self.0.as_i64();
+    }
+
+    pub fn as_f64(self) -> Option<f64> {
+        return self.0.as_f64();
+    }
+
+    pub fn as_bool(self) -> Option<bool> {
+        return self.0.as_bool();
+    }
+
+    /// An owned copy of the viewed value.
+    pub fn to_item(self) -> JItem {
+        return self.0.clone();
+    }
+
+    pub fn to_string_with(self, options: &FormatOptions) -> String {
+        return self.0.to_string_with(options);
+    }
+}
+
+impl PartialEq<JItem> for JItemView<'_> {
+    fn eq(&self, other: &JItem) -> bool {
+        return self.0 == other;
+    }
+}
+
+impl fmt::Display for JItemView<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return self.0.fmt(f);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn read_through_view() {
+        let item = parse(r#"{"name": "api", "ports": [80, 443], "tls": {"enabled": true}, "owner": null}"#).unwrap();
+        let view = item.view();
+        assert_eq!(view.get("name").and_then(JItemView::as_str), Some("api"));
+        assert_eq!(view.get("ports").and_then(|ports| ports.at(1)).and_then(JItemView::as_i64), Some(443));
+        assert_eq!(view.pointer("/tls/enabled").and_then(JItemView::as_bool), Some(true));
+        assert_eq!(view.path("ports[0]").and_then(JItemView::as_f64), Some(80.0));
+        assert!(view.get("owner").unwrap().is_null());
+        assert_eq!(view.len(), 4);
+        assert!(view.get("name").unwrap().is_empty());
+        let ports: Vec<i64> = view.get("ports").unwrap().elements().filter_map(JItemView::as_i64).collect();
+        assert_eq!(ports, [80, 443]);
+        let mut keys: Vec<&str> = view.members().map(|(key, _)| key).collect();
+        keys.sort();
+        assert_eq!(keys, ["name", "owner", "ports", "tls"]);
+        assert_eq!(view.get("name").unwrap().members().count(), 0);
+        assert_eq!(view.get("tls").unwrap().to_string(), r#"{"enabled":true}"#);
+        assert_eq!(view.get("tls").unwrap(), parse(r#"{"enabled": true}"#).unwrap());
+        assert_eq!(view.to_item(), item);
+    }
+}
@@ -0,0 +1,64 @@
+//! JavaScript bindings built with `wasm-bindgen`. Values cross into JavaScript as plain
+//! objects, arrays, strings, numbers, booleans and `null`, and errors are thrown as
+//! `Error`s carrying the parse message and position.
+//!
+//! Build the module with
+//! `cargo rustc --lib --release --target wasm32-unknown-unknown --features wasm --crate-type cdylib`
+//! and generate the JavaScript glue with `wasm-bindgen`.
+
+use js_sys::{Array, Object, Reflect};
+use wasm_bindgen::prelude::*;
+
+use crate::{format::FormatOptions, j_item::JItem};
+
+#[wasm_bindgen]
+pub fn parse(input: &str) -> Result<JsValue, JsValue> {
+    return to_js(&parse_item(input)?);
+}
+
+/// Rewrites JSON on a single line without whitespace.
+#[wasm_bindgen]
+pub fn minify(input: &str) -> Result<String, JsError> {
+    return Ok(parse_item(input)?.to_string());
+}
+
+/// Rewrites JSON indented by `indent` spaces, two by default, with sorted keys.
+#[wasm_bindgen]
+pub fn prettify(input: &str, indent: Option<u32>) -> Result<String, JsError> {
+    let indent = " ".repeat(indent.unwrap_or(2) as usize);
+    return Ok(parse_item(input)?.to_string_with(&FormatOptions { indent: Some(indent), ..FormatOptions::pretty() }));
+}
+
+/// The value at a JSON Pointer, or `undefined` when there is none.
+#[wasm_bindgen]
+pub fn query(input: &str, pointer: &str) -> Result<JsValue, JsValue> {
+    return parse_item(input)?.pointer(pointer).map_or(Ok(JsValue::UNDEFINED), to_js);
+}
+
+fn parse_item(input: &str) -> Result<JItem, JsError> {
+    return crate::parse(input).map_err(|e| JsError::new(&e.to_string()));
+}
+
+fn to_js(item: &JItem) -> Result<JsValue, JsValue> {
+    return Ok(match item {
+        JItem::Object(hmap) => {
+            let object = Object::new();
+            for (key, value) in hmap {
+                // defined as an own data property, as `JSON.parse` does, so that a
+                // `__proto__` key is a member rather than a call to the prototype setter
+                let descriptor = Object::new();
+                Reflect::set(&descriptor, &JsValue::from_str("value"), &to_js(value)?)?;
+                for attribute in ["writable", "enumerable", "configurable"] {
+                    Reflect::set(&descriptor, &JsValue::from_str(attribute), &JsValue::TRUE)?;
+                }
+                Reflect::define_property(&object, &JsValue::from_str(key), &descriptor)?;
+            }
+            object.into()
+        },
+        JItem::Array(elements) => elements.iter().map(to_js).collect::<Result<Array, _>>()?.into(),
+        JItem::String(s) => JsValue::from_str(s),
+        JItem::Number(_) | JItem::RawNumber(_) => JsValue::from_f64(item.number_value().unwrap()),
+        JItem::Bool(b) => JsValue::from_bool(*b),
+        JItem::Null => JsValue::NULL,
+    });
+}
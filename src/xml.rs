@@ -0,0 +1,445 @@
+//! Conversion between XML and `JItem` using the following convention:
+//!
+//! - the document `<root>...</root>` becomes the object `{"root": ...}`
+//! - an element with only text becomes a string, and an empty element becomes null
+//! - an element with attributes or child elements becomes an object, with attributes
+//!   under `@name` keys, child elements under their names, and any text under `#text`
+//! - repeated child elements with the same name become an array, in document order
+//!
+//! XML has no types, so text always reads back as strings. Text is trimmed, and
+//! comments, processing instructions and the doctype are skipped.
+
+use std::collections::HashMap;
+
+use crate::j_item::JItem;
+
+/// How deeply elements may nest in parsed input, counting the root, so that untrusted
+/// documents cannot exhaust the stack.
+const MAX_DEPTH: usize = 128;
+
+pub fn from_xml(input: &str) -> Result<JItem, String> {
+    let mut reader = XmlReader { input, pos: 0, depth: 0 };
+    reader.skip_misc()?;
+    if !reader.rest().starts_with('<') {
+        return Err(reader.error("expected a root element"));
+    }
+    let (name, value) = reader.parse_element()?;
+    reader.skip_misc()?;
+    if !reader.rest().is_empty() {
+        return Err(reader.error("unexpected content after the root element"));
+    }
+    let mut root = HashMap::new();
+    root.insert(name, value);
+    return Ok(JItem::Object(root));
+}
+
+/// Writes `item`, which must be an object with a single member naming the root element.
+/// Keys must be valid XML names; arrays nested directly in arrays are rejected.
+pub fn to_xml(item: &JItem) -> Result<String, String> {
+    let JItem::Object(hmap) = item else {
+        return Err("xml documents must be an object with one member for the root element".to_string());
+    };
+    let mut members = hmap.iter();
+    let (Some((name, value)), None) = (members.next(), members.next()) else {
+        return Err("xml documents must be an object with one member for the root element".to_string());
+    };
+    if let JItem::Array(_) = value {
+        return Err("the xml root element cannot be an array".to_string());
+    }
+    let mut out = String::new();
+    write_element(name, value, &mut out)?;
+    return Ok(out);
+}
+
+struct XmlReader<'a> {
+    input: &'a str,
+    pos: usize,
+    /// Elements open around the one being parsed.
+    depth: usize,
+}
+
+impl<'a> XmlReader<'a> {
+    fn rest(&self) -> &'a str {
+        return &self.input[self.pos..];
+    }
+
+    fn error(&self, message: &str) -> String {
+        return format!("invalid xml at byte {}: {}", self.pos, message);
+    }
+
+    fn skip_until(&mut self, terminator: &str) -> Result<(), String> {
+        let Some(end) = self.rest().find(terminator) else {
+            return Err(self.error(&format!("missing '{}'", terminator)));
+        };
+        self.pos += end + terminator.len();
+        return Ok(());
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    // skips whitespace, comments, processing instructions and doctypes between elements
+    fn skip_misc(&mut self) -> Result<(), String> {
+        loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+            if rest.starts_with("<!--") {
+                self.skip_until("-->")?;
+            }
+            else if rest.starts_with("<?") {
+                self.skip_until("?>")?;
+            }
+            else if rest.starts_with("<!DOCTYPE") {
+                self.skip_doctype()?;
+            }
+            else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn skip_doctype(&mut self) -> Result<(), String> {
+        let mut depth = 0;
+        for (n, c) in self.rest().char_indices() {
+            match c {
+                '[' => depth += 1,
+                ']' => depth -= 1,
+                '>' if depth == 0 => {
+                    self.pos += n + 1;
+                    return Ok(());
+                },
+                _ => {},
+            }
+        }
+        return Err(self.error("unterminated doctype"));
+    }
+
+    fn parse_name(&mut self) -> Result<String, String> {
+        let rest = self.rest();
+        let end = rest.find(|c: char| c.is_whitespace() || matches!(c, '>' | '/' | '=')).unwrap_or(rest.len());
+        let name = &rest[..end];
+        if !is_xml_name(name) {
+            return Err(self.error(&format!("invalid name '{}'", name)));
+        }
+        self.pos += end;
+        return Ok(name.to_string());
+    }
+
+    fn parse_element(&mut self) -> Result<(String, JItem), String> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error(&format!("elements nested more than {} deep", MAX_DEPTH)));
+        }
+        self.depth += 1;
+        self.pos += 1; // the '<'
+        let name = self.parse_name()?;
+        let mut attributes = vec![];
+        loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+            if rest.starts_with("/>") {
+                self.pos += 2;
+                self.depth -= 1;
+                return Ok((name, build_value(attributes, vec![], String::new())));
+            }
+            if rest.starts_with('>') {
+                self.pos += 1;
+                break;
+            }
+            attributes.push(self.parse_attribute()?);
+        }
+
+        let mut children = vec![];
+        let mut text = String::new();
+        loop {
+            let rest = self.rest();
+            if rest.starts_with("</") {
+                self.pos += 2;
+                let end_name = self.parse_name()?;
+                if end_name != name {
+                    return Err(self.error(&format!("expected '</{}>' but found '</{}>'", name, end_name)));
+                }
+                self.skip_whitespace();
+                if !self.rest().starts_with('>') {
+                    return Err(self.error("expected '>' to close the end tag"));
+                }
+                self.pos += 1;
+                self.depth -= 1;
+                return Ok((name, build_value(attributes, children, text)));
+            }
+            if rest.starts_with("<!--") {
+                self.skip_until("-->")?;
+            }
+            else if rest.starts_with("<?") {
+                self.skip_until("?>")?;
+            }
+            else if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+                let Some(end) = cdata.find("]]>") else {
+                    return Err(self.error("unterminated CDATA section"));
+                };
+                text.push_str(&cdata[..end]);
+                self.pos += "<![CDATA[".len() + end + "]]>".len();
+            }
+            else if rest.starts_with('<') {
+                children.push(self.parse_element()?);
+            }
+            else if rest.is_empty() {
+                return Err(self.error(&format!("unterminated element '{}'", name)));
+            }
+            else {
+                let end = rest.find('<').unwrap_or(rest.len());
+                text.push_str(&self.decode_entities(&rest[..end])?);
+                self.pos += end;
+            }
+        }
+    }
+
+    fn parse_attribute(&mut self) -> Result<(String, String), String> {
+        let name = self.parse_name()?;
+        self.skip_whitespace();
+        if !self.rest().starts_with('=') {
+            return Err(self.error(&format!("expected '=' after attribute '{}'", name)));
+        }
+        self.pos += 1;
+        self.skip_whitespace();
+        let Some(quote) = self.rest().chars().next().filter(|c| matches!(c, '"' | '\'')) else {
+            return Err(self.error(&format!("expected a quoted value for attribute '{}'", name)));
+        };
+        self.pos += 1;
+        let Some(end) = self.rest().find(quote) else {
+            return Err(self.error(&format!("unterminated value for attribute '{}'", name)));
+        };
+        let value = self.decode_entities(&self.rest()[..end])?;
+        self.pos += end + 1;
+        return Ok((name, value));
+    }
+
+    fn decode_entities(&self, raw: &str) -> Result<String, String> {
+        let mut decoded = String::with_capacity(raw.len());
+        let mut rest = raw;
+        while let Some(start) = rest.find('&') {
+            decoded.push_str(&rest[..start]);
+            let Some(end) = rest[start..].find(';') else {
+                return Err(self.error("unterminated entity reference"));
+            };
+            let entity = &rest[start + 1..start + end];
+            let c = match entity {
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "amp" => Some('&'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                _ => {
+                    let code = match entity.strip_prefix("#x") {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                        None => entity.strip_prefix('#').and_then(|dec| dec.parse().ok()),
+                    };
+                    code.and_then(char::from_u32)
+                },
+            };
+            let Some(c) = c else {
+                return Err(self.error(&format!("unknown entity '&{};'", entity)));
+            };
+            decoded.push(c);
+            rest = &rest[start + end + 1..];
+        }
+        decoded.push_str(rest);
+        return Ok(decoded);
+    }
+}
+
+fn build_value(attributes: Vec<(String, String)>, children: Vec<(String, JItem)>, text: String) -> JItem {
+    let text = text.trim();
+    if attributes.is_empty() && children.is_empty() {
+        if text.is_empty() {
+            return JItem::Null;
+        }
+        return JItem::String(text.to_string());
+    }
+
+    let mut hmap = HashMap::new();
+    for (name, value) in attributes {
+        hmap.insert(format!("@{}", name), JItem::String(value));
+    }
+    for (name, value) in children {
+        match hmap.remove(&name) {
+            None => {
+                hmap.insert(name, value);
+            },
+            Some(JItem::Array(mut repeated)) => {
+                repeated.push(value);
+                hmap.insert(name, JItem::Array(repeated));
+            },
+            Some(first) => {
+                hmap.insert(name, JItem::Array(vec![first, value]));
+            },
+        }
+    }
+    if !text.is_empty() {
+        hmap.insert("#text".to_string(), JItem::String(text.to_string()));
+    }
+    return JItem::Object(hmap);
+}
+
+fn is_xml_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    let Some(first) = chars.next() else {
+        return false;
+    };
+    return (first.is_alphabetic() || matches!(first, '_' | ':'))
+        && chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | ':' | '-' | '.'));
+}
+
+fn escape_xml(text: &str) -> String {
+    return text
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;");
+}
+
+fn scalar_text(item: &JItem) -> Option<String> {
+    return match item {
+        JItem::String(s) => Some(s.to_string()),
+        JItem::Object(_) | JItem::Array(_) => None,
+        scalar => Some(scalar.to_string()),
+    };
+}
+
+fn write_element(name: &str, value: &JItem, out: &mut String) -> Result<(), String> {
+    if !is_xml_name(name) {
+        return Err(format!("'{}' is not a valid xml element name", name));
+    }
+    match value {
+        JItem::Array(elements) => {
+            for element in elements {
+                if let JItem::Array(_) = element {
+                    return Err(format!("nested arrays under '{}' cannot be represented in xml", name));
+                }
+                write_element(name, element, out)?;
+            }
+        },
+        JItem::Object(hmap) => {
+            let mut keys: Vec<_> = hmap.keys().collect();
+            keys.sort();
+            out.push('<');
+            out.push_str(name);
+            for key in keys.iter().filter(|key| key.starts_with('@')) {
+                let attribute = &key[1..];
+                if !is_xml_name(attribute) {
+                    return Err(format!("'{}' is not a valid xml attribute name", attribute));
+                }
+                let Some(text) = scalar_text(&hmap[*key]) else {
+                    return Err(format!("attribute '{}' of '{}' must be a scalar", attribute, name));
+                };
+                out.push_str(&format!(" {}=\"{}\"", attribute, escape_xml(&text)));
+            }
+            let children: Vec<_> = keys.iter().filter(|key| !key.starts_with('@')).collect();
+            if children.is_empty() {
+                out.push_str("/>");
+                return Ok(());
+            }
+            out.push('>');
+            for key in children {
+                if *key == "#text" {
+                    let Some(text) = scalar_text(&hmap[*key]) else {
+                        return Err(format!("text of '{}' must be a scalar", name));
+                    };
+                    out.push_str(&escape_xml(&text));
+                }
+                else {
+                    write_element(key, &hmap[*key], out)?;
+                }
+            }
+            out.push_str(&format!("</{}>", name));
+        },
+        JItem::Null => out.push_str(&format!("<{}/>", name)),
+        scalar => {
+            let text = scalar_text(scalar).unwrap_or_default();
+            out.push_str(&format!("<{}>{}</{}>", name, escape_xml(&text), name));
+        },
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn from_xml_mapping() {
+        let input = r#"<?xml version="1.0"?>
+<!-- a comment -->
+<library name="city">
+  <book id="1"><title>Dune</title><year>1965</year></book>
+  <book id="2"><title>Emma</title></book>
+  <note>some &amp; more</note>
+  <empty/>
+</library>"#;
+        let expected = parse(r#"{"library": {
+            "@name": "city",
+            "book": [
+                {"@id": "1", "title": "Dune", "year": "1965"},
+                {"@id": "2", "title": "Emma"}
+            ],
+            "note": "some & more",
+            "empty": null
+        }}"#).unwrap();
+        assert_eq!(from_xml(input), Ok(expected));
+    }
+
+    #[test]
+    fn from_xml_mixed_text_and_cdata() {
+        let input = "<p lang='en'>hello <b>x</b><![CDATA[<raw>]]> &#65;&#x42;</p>";
+        let expected = parse(r##"{"p": {"@lang": "en", "b": "x", "#text": "hello <raw> AB"}}"##).unwrap();
+        assert_eq!(from_xml(input), Ok(expected));
+    }
+
+    #[test]
+    fn from_xml_errors() {
+        assert!(from_xml("<a><b></a>").is_err());
+        assert!(from_xml("<a>").is_err());
+        assert!(from_xml("<a/><b/>").is_err());
+        assert!(from_xml("<a>&bogus;</a>").is_err());
+        assert!(from_xml("plain text").is_err());
+    }
+
+    #[test]
+    fn from_xml_depth_limit() {
+        let nested = |depth: usize| format!("{}<b/>{}", "<a>".repeat(depth - 1), "</a>".repeat(depth - 1));
+        assert!(from_xml(&nested(MAX_DEPTH)).is_ok());
+        assert_eq!(from_xml(&nested(MAX_DEPTH + 1)), Err(format!("invalid xml at byte {}: elements nested more than 128 deep", 3 * MAX_DEPTH)));
+        assert!(from_xml(&nested(100_000)).is_err());
+    }
+
+    #[test]
+    fn to_xml_mapping() {
+        let item = parse(r##"{"order": {"@id": 7, "item": [{"#text": "apple", "@qty": 2}, "pear"], "note": null, "paid": true}}"##).unwrap();
+        assert_eq!(
+            to_xml(&item),
+            Ok(r#"<order id="7"><item qty="2">apple</item><item>pear</item><note/><paid>true</paid></order>"#.to_string())
+        );
+    }
+
+    #[test]
+    fn to_xml_escapes_text() {
+        let item = parse(r##"{"a": {"@t": "\"x\"", "#text": "1 < 2 & 3"}}"##).unwrap();
+        assert_eq!(to_xml(&item), Ok(r#"<a t="&quot;x&quot;">1 &lt; 2 &amp; 3</a>"#.to_string()));
+    }
+
+    #[test]
+    fn to_xml_round_trip() {
+        let item = parse(r#"{"root": {"@v": "1", "child": ["a", "b"], "other": {"deep": "c"}}}"#).unwrap();
+        assert_eq!(from_xml(&to_xml(&item).unwrap()), Ok(item));
+    }
+
+    #[test]
+    fn to_xml_rejects_unrepresentable() {
+        assert!(to_xml(&parse(r#"{"a": 1, "b": 2}"#).unwrap()).is_err());
+        assert!(to_xml(&parse(r#"{"a": [[1]]}"#).unwrap()).is_err());
+        assert!(to_xml(&parse(r#"{"bad name": 1}"#).unwrap()).is_err());
+        assert!(to_xml(&parse(r#"[1]"#).unwrap()).is_err());
+    }
+}
@@ -0,0 +1,141 @@
+use yaml_rust2::{yaml::Hash, Yaml, YamlEmitter, YamlLoader};
+
+use crate::j_item::JItem;
+
+/// Parses the first document of a YAML stream. Anchors and aliases are resolved, scalars
+/// follow the YAML core schema, and scalars with unrecognised tags are read as strings.
+/// Scalar mapping keys such as `1` or `true` become their text. An empty stream is null.
+pub fn from_yaml(input: &str) -> Result<JItem, String> {
+    let documents = from_yaml_documents(input)?;
+    return Ok(documents.into_iter().next().unwrap_or(JItem::Null));
+}
+
+/// Parses every document of a `---` separated YAML stream.
+pub fn from_yaml_documents(input: &str) -> Result<Vec<JItem>, String> {
+    let documents = YamlLoader::load_from_str(input).map_err(|e| format!("invalid yaml: {}", e))?;
+    return documents.iter().map(|document| yaml_to_item(document, "")).collect();
+}
+
+/// Writes `item` as a YAML document. Object keys are sorted and whole numbers are
+/// written as integers.
+pub fn to_yaml(item: &JItem) -> Result<String, String> {
+    let mut out = String::new();
+    YamlEmitter::new(&mut out)
+        .dump(&item_to_yaml(item))
+        .map_err(|e| format!("failed to write yaml: {:?}", e))?;
+    out.push('\n');
+    return Ok(out);
+}
+
+fn yaml_to_item(yaml: &Yaml, path: &str) -> Result<JItem, String> {
+    return match yaml {
+        Yaml::Real(text) => {
+            let Some(x) = yaml.as_f64() else {
+                return Err(format!("invalid yaml float '{}' at '{}'", text, path));
+            };
+            Ok(JItem::Number(x))
+        },
+        Yaml::Integer(i) => Ok(JItem::Number(*i as f64)),
+        Yaml::String(s) => Ok(JItem::String(s.to_string())),
+        Yaml::Boolean(b) => Ok(JItem::Bool(*b)),
+        Yaml::Null => Ok(JItem::Null),
+        Yaml::Array(elements) => {
+            let converted: Result<Vec<_>, _> = elements
+                .iter()
+                .enumerate()
+                .map(|(n, element)| yaml_to_item(element, &format!("{}/{}", path, n)))
+                .collect();
+            Ok(JItem::Array(converted?))
+        },
+        Yaml::Hash(hash) => {
+            let mut hmap = std::collections::HashMap::with_capacity(hash.len());
+            for (key, value) in hash {
+                let key = yaml_key(key, path)?;
+                let value = yaml_to_item(value, &format!("{}/{}", path, key))?;
+                hmap.insert(key, value);
+            }
+            Ok(JItem::Object(hmap))
+        },
+        Yaml::Alias(_) | Yaml::BadValue => Err(format!("invalid yaml value at '{}'", path)),
+    };
+}
+
+fn yaml_key(key: &Yaml, path: &str) -> Result<String, String> {
+    return match key {
+        Yaml::String(s) | Yaml::Real(s) => Ok(s.to_string()),
+        Yaml::Integer(i) => Ok(i.to_string()),
+        Yaml::Boolean(b) => Ok(b.to_string()),
+        Yaml::Null => Ok("null".to_string()),
+        _ => Err(format!("yaml mapping keys must be scalars, found a collection key at '{}'", path)),
+    };
+}
+
+fn item_to_yaml(item: &JItem) -> Yaml {
+    return match item {
+        JItem::RawNumber(_) => item_to_yaml(&JItem::Number(item.number_value().unwrap())),
+        JItem::Object(hmap) => {
+            let mut keys: Vec<_> = hmap.keys().collect();
+            keys.sort();
+            let mut hash = Hash::new();
+            for key in keys {
+                hash.insert(Yaml::String(key.to_string()), item_to_yaml(&hmap[key]));
+            }
+            Yaml::Hash(hash)
+        },
+        JItem::Array(elements) => Yaml::Array(elements.iter().map(item_to_yaml).collect()),
+        JItem::String(s) => Yaml::String(s.to_string()),
+        JItem::Number(x) if x.fract() == 0.0 && x.abs() < 9.2e18 => Yaml::Integer(*x as i64),
+        JItem::Number(x) if x.is_nan() => Yaml::Real(".nan".to_string()),
+        JItem::Number(x) if x.is_infinite() => Yaml::Real(if *x > 0.0 { ".inf" } else { "-.inf" }.to_string()),
+        JItem::Number(x) => Yaml::Real(format!("{:?}", x)),
+        JItem::Bool(b) => Yaml::Boolean(*b),
+        JItem::Null => Yaml::Null,
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn from_yaml_mapping() {
+        let input = "name: example\ncount: 3\nratio: 0.5\nenabled: yes\nempty: ~\ntags:\n  - a\n  - 'b'\n";
+        let expected = parse(r#"{"name": "example", "count": 3, "ratio": 0.5, "enabled": "yes", "empty": null, "tags": ["a", "b"]}"#).unwrap();
+        assert_eq!(from_yaml(input), Ok(expected));
+    }
+
+    #[test]
+    fn from_yaml_resolves_anchors() {
+        let input = "base: &base\n  host: x\n  port: 1\nother: *base\n";
+        let expected = parse(r#"{"base": {"host": "x", "port": 1}, "other": {"host": "x", "port": 1}}"#).unwrap();
+        assert_eq!(from_yaml(input), Ok(expected));
+    }
+
+    #[test]
+    fn from_yaml_tags_and_scalar_keys() {
+        let input = "1: !!str 2\ntrue: !custom value\n";
+        let expected = parse(r#"{"1": "2", "true": "value"}"#).unwrap();
+        assert_eq!(from_yaml(input), Ok(expected));
+    }
+
+    #[test]
+    fn from_yaml_multiple_documents() {
+        let documents = from_yaml_documents("--- 1\n--- [a]\n").unwrap();
+        assert_eq!(documents, vec![JItem::Number(1.), parse(r#"["a"]"#).unwrap()]);
+    }
+
+    #[test]
+    fn from_yaml_rejects_collection_keys() {
+        assert!(from_yaml("? [a, b]\n: 1\n").is_err());
+        assert!(from_yaml("a: [").is_err());
+    }
+
+    #[test]
+    fn to_yaml_round_trip() {
+        let item = parse(r#"{"b": [1, 2.5, "three", null], "a": {"ok": true, "text": "yes"}}"#).unwrap();
+        let yaml = to_yaml(&item).unwrap();
+        assert!(yaml.starts_with("---\na:\n"));
+        assert_eq!(from_yaml(&yaml), Ok(item));
+    }
+}